@@ -1,12 +1,12 @@
 #![feature(test)]
 
+extern crate ipcheck_rs;
 extern crate ipnet;
-extern crate iprange;
 extern crate rand;
 extern crate test;
 
+use ipcheck_rs::*;
 use ipnet::{Ipv4Net, Ipv6Net};
-use iprange::*;
 use rand::{Rng, SeedableRng, StdRng};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
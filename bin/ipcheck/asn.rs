@@ -0,0 +1,356 @@
+//! Attributing networks to their originating AS from a prefix-to-AS table.
+//!
+//! `--asn-source` reads a table such as CAIDA's RouteViews `pfx2as`, one
+//! prefix per line as either `1.0.0.0<TAB>24<TAB>13335` or
+//! `1.0.0.0/24 13335`. Blank lines and `#` comments are skipped, and of a
+//! multi-origin AS set such as `13335_209` or `13335,209` only the first is
+//! kept. As in routing, an address belongs to the AS of the most specific
+//! prefix covering it.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use eyre::{eyre, Result, WrapErr};
+use ipnet::{IpNet as AnyNet, Ipv4Net, Ipv6Net};
+
+use crate::input::Networks;
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct AsnArgs {
+    /// Prefix-to-AS table (e.g. a RouteViews pfx2as file) attributing the
+    /// networks to their originating AS.
+    #[arg(long, value_name = "FILE")]
+    asn_source: Option<PathBuf>,
+}
+
+impl AsnArgs {
+    /// Loads the table of `--asn-source`, if given.
+    pub fn load(&self) -> Result<Option<AsnTable>> {
+        self.asn_source.as_deref().map(AsnTable::load).transpose()
+    }
+}
+
+/// An address as an integer, so that both families share the interval code.
+trait Addr: Copy + Ord {
+    fn succ(self) -> Option<Self>;
+    fn pred(self) -> Self;
+    /// The number of addresses from `self` to `last` inclusive, saturating
+    /// at `u128::MAX` for the whole IPv6 space.
+    fn span(self, last: Self) -> u128;
+}
+
+impl Addr for u32 {
+    fn succ(self) -> Option<u32> {
+        self.checked_add(1)
+    }
+    fn pred(self) -> u32 {
+        self - 1
+    }
+    fn span(self, last: u32) -> u128 {
+        u128::from(last - self) + 1
+    }
+}
+
+impl Addr for u128 {
+    fn succ(self) -> Option<u128> {
+        self.checked_add(1)
+    }
+    fn pred(self) -> u128 {
+        self - 1
+    }
+    fn span(self, last: u128) -> u128 {
+        (last - self).saturating_add(1)
+    }
+}
+
+/// A run of addresses originated by one AS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Block<T> {
+    first: T,
+    last: T,
+    asn: u32,
+}
+
+/// Disjoint, sorted runs of addresses by originating AS.
+#[derive(Debug, Default)]
+pub struct AsnTable {
+    v4: Vec<Block<u32>>,
+    v6: Vec<Block<u128>>,
+}
+
+/// The address space of a feed that one AS originates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Group {
+    /// `None` for space no prefix of the table covers.
+    pub asn: Option<u32>,
+    pub v4_addresses: u128,
+    pub v6_addresses: u128,
+}
+
+impl AsnTable {
+    pub fn load(path: &Path) -> Result<AsnTable> {
+        let text = fs::read_to_string(path).wrap_err_with(|| path.display().to_string())?;
+        text.parse()
+            .map_err(|err| eyre!("{}: {}", path.display(), err))
+    }
+
+    /// The ASes originating any address of `network`.
+    pub fn origins(&self, network: AnyNet) -> BTreeSet<u32> {
+        match network {
+            AnyNet::V4(net) => overlapping(&self.v4, v4_interval(net))
+                .map(|(run, _)| run.asn)
+                .collect(),
+            AnyNet::V6(net) => overlapping(&self.v6, v6_interval(net))
+                .map(|(run, _)| run.asn)
+                .collect(),
+        }
+    }
+
+    /// Splits the space `networks` cover by originating AS, largest IPv4
+    /// share first, then largest IPv6 share.
+    pub fn group(&self, networks: &Networks) -> Vec<Group> {
+        let mut groups: HashMap<Option<u32>, Group> = HashMap::new();
+        for (asn, count) in tally(&self.v4, networks.v4.iter().map(v4_interval)) {
+            let group = groups.entry(asn).or_default();
+            group.asn = asn;
+            group.v4_addresses = group.v4_addresses.saturating_add(count);
+        }
+        for (asn, count) in tally(&self.v6, networks.v6.iter().map(v6_interval)) {
+            let group = groups.entry(asn).or_default();
+            group.asn = asn;
+            group.v6_addresses = group.v6_addresses.saturating_add(count);
+        }
+        let mut groups: Vec<Group> = groups.into_values().collect();
+        groups.sort_by(|a, b| {
+            (b.v4_addresses, b.v6_addresses, a.asn).cmp(&(a.v4_addresses, a.v6_addresses, b.asn))
+        });
+        groups
+    }
+}
+
+impl std::str::FromStr for AsnTable {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<AsnTable, String> {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (network, asn) = parse_line(line)
+                .ok_or_else(|| format!("line {}: {:?} is not a prefix and AS", i + 1, line))?;
+            match network {
+                AnyNet::V4(net) => {
+                    let (first, last) = v4_interval(net);
+                    v4.push(Block { first, last, asn });
+                }
+                AnyNet::V6(net) => {
+                    let (first, last) = v6_interval(net);
+                    v6.push(Block { first, last, asn });
+                }
+            }
+        }
+        Ok(AsnTable {
+            v4: most_specific(v4),
+            v6: most_specific(v6),
+        })
+    }
+}
+
+fn parse_line(line: &str) -> Option<(AnyNet, u32)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (network, asn) = match fields[..] {
+        [prefix, len, asn] => (format!("{}/{}", prefix, len), asn),
+        [network, asn] => (network.to_string(), asn),
+        _ => return None,
+    };
+    let network: AnyNet = network.parse().ok()?;
+    let asn = asn.split(['_', ',']).next()?;
+    let asn = asn.strip_prefix("AS").unwrap_or(asn).parse().ok()?;
+    Some((network.trunc(), asn))
+}
+
+fn v4_interval(net: Ipv4Net) -> (u32, u32) {
+    (net.network().into(), net.broadcast().into())
+}
+
+fn v6_interval(net: Ipv6Net) -> (u128, u128) {
+    (net.network().into(), net.broadcast().into())
+}
+
+/// Flattens nested prefixes into disjoint runs, each taking the AS of the
+/// innermost prefix covering it.
+fn most_specific<T: Addr>(mut prefixes: Vec<Block<T>>) -> Vec<Block<T>> {
+    // Outer prefixes sort before the prefixes nested in them.
+    prefixes.sort_by(|a, b| (a.first, b.last).cmp(&(b.first, a.last)));
+
+    let mut runs = Vec::new();
+    // The enclosing prefixes, innermost last, and the first address of the
+    // innermost not yet emitted (`None` once past the end of the space).
+    let mut stack: Vec<Block<T>> = Vec::new();
+    let mut next: Option<T> = None;
+    let mut emit = |first: T, last: T, asn: u32| runs.push(Block { first, last, asn });
+
+    for prefix in prefixes {
+        while let Some(&top) = stack.last() {
+            if top.last >= prefix.first {
+                break;
+            }
+            if let Some(from) = next.filter(|&from| from <= top.last) {
+                emit(from, top.last, top.asn);
+            }
+            next = top.last.succ();
+            stack.pop();
+        }
+        if let (Some(top), Some(from)) = (stack.last(), next) {
+            if from < prefix.first {
+                emit(from, prefix.first.pred(), top.asn);
+            }
+        }
+        next = Some(prefix.first);
+        stack.push(prefix);
+    }
+    while let Some(top) = stack.pop() {
+        if let Some(from) = next.filter(|&from| from <= top.last) {
+            emit(from, top.last, top.asn);
+        }
+        next = top.last.succ();
+    }
+    runs
+}
+
+/// The runs overlapping `first..=last`, each with the size of the overlap.
+fn overlapping<T: Addr>(
+    runs: &[Block<T>],
+    (first, last): (T, T),
+) -> impl Iterator<Item = (Block<T>, u128)> + '_ {
+    let start = runs.partition_point(|run| run.last < first);
+    runs[start..]
+        .iter()
+        .take_while(move |run| run.first <= last)
+        .map(move |run| (*run, run.first.max(first).span(run.last.min(last))))
+}
+
+/// Counts the addresses of `intervals` per originating AS, `None` counting
+/// those no run covers.
+fn tally<T: Addr>(
+    runs: &[Block<T>],
+    intervals: impl Iterator<Item = (T, T)>,
+) -> HashMap<Option<u32>, u128> {
+    let mut counts: HashMap<Option<u32>, u128> = HashMap::new();
+    for (first, last) in intervals {
+        let mut attributed = 0u128;
+        for (run, overlap) in overlapping(runs, (first, last)) {
+            let count = counts.entry(Some(run.asn)).or_default();
+            *count = count.saturating_add(overlap);
+            attributed = attributed.saturating_add(overlap);
+        }
+        let unknown = first.span(last) - attributed;
+        if unknown > 0 {
+            let count = counts.entry(None).or_default();
+            *count = count.saturating_add(unknown);
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> AsnTable {
+        "# prefix\tlength\tas
+        10.0.0.0\t8\t64500
+        10.1.0.0\t16\t64501
+        10.1.2.0/24 64502_64503
+        192.168.0.0/16 AS64504
+        2001:db8::/32 64505
+        "
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn nested_prefixes_take_the_most_specific_origin() {
+        let table = table();
+        let run = |first: [u8; 4], last: [u8; 4], asn| Block {
+            first: u32::from_be_bytes(first),
+            last: u32::from_be_bytes(last),
+            asn,
+        };
+        assert_eq!(
+            table.v4,
+            [
+                run([10, 0, 0, 0], [10, 0, 255, 255], 64500),
+                run([10, 1, 0, 0], [10, 1, 1, 255], 64501),
+                run([10, 1, 2, 0], [10, 1, 2, 255], 64502),
+                run([10, 1, 3, 0], [10, 1, 255, 255], 64501),
+                run([10, 2, 0, 0], [10, 255, 255, 255], 64500),
+                run([192, 168, 0, 0], [192, 168, 255, 255], 64504),
+            ]
+        );
+    }
+
+    #[test]
+    fn whole_space_prefixes_do_not_overflow() {
+        let table: AsnTable = "0.0.0.0/0 1\n255.255.255.0/24 2\n::/0 3\n".parse().unwrap();
+        assert_eq!(table.v4.len(), 2);
+        assert_eq!(table.v4[1].last, u32::MAX);
+        assert_eq!(table.v6.len(), 1);
+    }
+
+    #[test]
+    fn origins_of_a_network() {
+        let table = table();
+        let origins = |network: &str| -> Vec<u32> {
+            table
+                .origins(network.parse().unwrap())
+                .into_iter()
+                .collect()
+        };
+        assert_eq!(origins("10.1.0.0/16"), [64501, 64502]);
+        assert_eq!(origins("10.1.2.128/25"), [64502]);
+        assert_eq!(origins("8.8.8.0/24"), [] as [u32; 0]);
+        assert_eq!(origins("2001:db8:1::/48"), [64505]);
+    }
+
+    #[test]
+    fn group_covered_space() {
+        let networks = Networks {
+            v4: ["10.1.0.0/16", "8.8.8.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/127"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        };
+        let group = |asn, v4_addresses, v6_addresses| Group {
+            asn,
+            v4_addresses,
+            v6_addresses,
+        };
+        assert_eq!(
+            table().group(&networks),
+            [
+                group(Some(64501), 65536 - 256, 0),
+                group(None, 256, 0),
+                group(Some(64502), 256, 0),
+                group(Some(64505), 0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_malformed_lines() {
+        let err = "10.0.0.0/8 64500\nnonsense\n"
+            .parse::<AsnTable>()
+            .unwrap_err();
+        assert!(err.starts_with("line 2:"), "{}", err);
+    }
+}
@@ -0,0 +1,188 @@
+//! Canonical list outputs: the networks of a build written back out as the
+//! lists they were read from, simplified, deduplicated and sorted, to
+//! minimize feeds or compare them without generating any code.
+//!
+//! The `text` backend, selected by `--target text`, writes one network per
+//! line, the IPv4 networks first, with their notes as trailing comments.
+//! The `csv` backend, selected by `--target csv`, writes them under a
+//! `network` header, with a `note` column when any has a note. Either is
+//! read back as an input, holding both families, with `--auto-family`.
+
+use std::collections::HashMap;
+
+use ipcheck_rs::backend::{Error, Filters, Output, OutputBackend};
+use ipnet::IpNet;
+
+use crate::firewall::sorted;
+use crate::input::Networks;
+
+/// The networks, one per line.
+pub struct Text;
+
+impl OutputBackend for Text {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let notes = notes(filters);
+        let mut text = String::new();
+        for network in networks(filters) {
+            text.push_str(&network);
+            if let Some(note) = notes.get(network.as_str()) {
+                text.push_str("  # ");
+                text.push_str(note);
+            }
+            text.push('\n');
+        }
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: text.into_bytes(),
+        }])
+    }
+}
+
+/// The networks as CSV.
+pub struct Csv;
+
+impl OutputBackend for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let notes = notes(filters);
+        let mut csv = csv::Writer::from_writer(Vec::new());
+        match notes.is_empty() {
+            true => csv.write_record(["network"])?,
+            false => csv.write_record(["network", "note"])?,
+        }
+        for network in networks(filters) {
+            match notes.is_empty() {
+                true => csv.write_record([&network])?,
+                false => {
+                    let note = notes.get(network.as_str()).copied().unwrap_or_default();
+                    csv.write_record([network.as_str(), note])?
+                }
+            }
+        }
+        let contents = csv.into_inner().map_err(|err| err.into_error())?;
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents,
+        }])
+    }
+}
+
+/// The networks of `filters`, the IPv4 ones first, in address order.
+fn networks(filters: &Filters) -> Vec<String> {
+    let v4 = sorted(filters.v4)
+        .into_iter()
+        .map(|network| network.to_string());
+    let v6 = sorted(filters.v6)
+        .into_iter()
+        .map(|network| network.to_string());
+    v4.chain(v6).collect()
+}
+
+/// The notes of `filters`, by network.
+fn notes<'a>(filters: &Filters<'a>) -> HashMap<&'a str, &'a str> {
+    filters
+        .notes
+        .iter()
+        .map(|note| (note.network.as_str(), note.note.as_str()))
+        .collect()
+}
+
+/// The networks of the list `list`, as the `text` or `csv` backend writes
+/// it, `None` if a line holds anything else.
+pub fn list_networks(list: &str) -> Option<Networks> {
+    let mut lines = list.lines().peekable();
+    if lines
+        .peek()
+        .is_some_and(|header| header.split(',').next() == Some("network"))
+    {
+        lines.next();
+    }
+    let mut networks = Networks::default();
+    for line in lines {
+        let field = line.split(',').next().unwrap();
+        let network = field.split('#').next().unwrap().trim();
+        if network.is_empty() {
+            continue;
+        }
+        match network.parse().ok()? {
+            IpNet::V4(network) => {
+                networks.v4.add(network);
+            }
+            IpNet::V6(network) => {
+                networks.v6.add(network);
+            }
+        }
+    }
+    Some(networks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipcheck_rs::backend::Note;
+    use ipcheck_rs::IpRange;
+    use std::path::PathBuf;
+
+    #[test]
+    fn text_and_csv_lists() {
+        let v4: IpRange<_> = ["192.0.2.0/24", "10.0.0.0/8", "10.1.0.0/16"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("minimized.txt");
+        let mut filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let text = Text.render(&filters).unwrap().remove(0).contents;
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(text, "10.0.0.0/8\n192.0.2.0/24\n2001:db8::/32\n");
+        let csv = Csv.render(&filters).unwrap().remove(0).contents;
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv, "network\n10.0.0.0/8\n192.0.2.0/24\n2001:db8::/32\n");
+
+        let notes = [Note {
+            network: "10.0.0.0/8".to_string(),
+            note: "private, RFC 1918".to_string(),
+        }];
+        filters.notes = &notes;
+        let text = Text.render(&filters).unwrap().remove(0).contents;
+        let text = String::from_utf8(text).unwrap();
+        assert!(
+            text.starts_with("10.0.0.0/8  # private, RFC 1918\n"),
+            "{}",
+            text
+        );
+        let csv = Csv.render(&filters).unwrap().remove(0).contents;
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv,
+            "network,note\n\
+             10.0.0.0/8,\"private, RFC 1918\"\n\
+             192.0.2.0/24,\n\
+             2001:db8::/32,\n"
+        );
+
+        let networks = Some(Networks { v4, v6 });
+        assert_eq!(list_networks(&text), networks);
+        assert_eq!(list_networks(&csv), networks);
+        assert_eq!(list_networks("export const x = 1;\n"), None);
+    }
+}
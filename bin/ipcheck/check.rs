@@ -0,0 +1,165 @@
+//! `ipcheck check`: whether addresses are in the lists, looked up directly
+//! rather than by rendering the checker and running it under Node.
+
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::Args;
+use eyre::Result;
+
+use crate::input::{InputOptions, Lists, Networks};
+use crate::paths;
+use crate::reserved::ReservedPolicy;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    lists: Lists,
+
+    /// The IPv4 and IPv6 lists, for those not given by flag, then the
+    /// addresses to look up.
+    #[arg(value_names = ["V4", "V6", "ADDRESS"], num_args = 1..)]
+    positional: Vec<PathBuf>,
+
+    /// The addresses, once assigned from the positional arguments.
+    #[arg(skip)]
+    addresses: Vec<IpAddr>,
+
+    /// What to do with private, loopback, link-local and documentation
+    /// networks found in the inputs.
+    #[arg(long, value_enum, default_value_t)]
+    reserved_policy: ReservedPolicy,
+
+    #[command(flatten)]
+    input: InputOptions,
+}
+
+impl CheckArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        // The trailing addresses are never taken for a list left out.
+        let addresses = self
+            .positional
+            .iter()
+            .rev()
+            .take_while(|value| {
+                value
+                    .to_str()
+                    .is_some_and(|text| text.parse::<IpAddr>().is_ok())
+            })
+            .count();
+        let rest = paths::fill(&mut self.positional, self.lists.slots(), addresses.max(1))?;
+        if rest.is_empty() {
+            return Err(clap::Error::raw(
+                ErrorKind::MissingRequiredArgument,
+                "no address to check\n",
+            ));
+        }
+        for value in rest {
+            let text = value.to_string_lossy();
+            match text.parse() {
+                Ok(addr) => self.addresses.push(addr),
+                Err(_) => {
+                    return Err(clap::Error::raw(
+                        ErrorKind::ValueValidation,
+                        format!("{:?} is not an IP address\n", text),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints, for every address, the network of the lists covering it, and
+/// exits with 1 if any is not covered.
+pub fn check(args: CheckArgs) -> Result<()> {
+    let networks = crate::load_filtered(&args.lists, &args.input, args.reserved_policy)?;
+    let (report, all_covered) = render(&networks, &args.addresses);
+    print!("{}", report);
+    if !all_covered {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// One line per address, and whether every address is covered.
+fn render(networks: &Networks, addresses: &[IpAddr]) -> (String, bool) {
+    let mut report = String::new();
+    let mut all_covered = true;
+    for addr in addresses {
+        let network = match addr {
+            IpAddr::V4(addr) => networks.v4.supernet(addr).map(|n| n.to_string()),
+            IpAddr::V6(addr) => networks.v6.supernet(addr).map(|n| n.to_string()),
+        };
+        match network {
+            Some(network) => writeln!(report, "{}: contained in {}", addr, network),
+            None => {
+                all_covered = false;
+                writeln!(report, "{}: not contained", addr)
+            }
+        }
+        .unwrap();
+    }
+    (report, all_covered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command};
+
+    fn check_args(args: &[&str]) -> CheckArgs {
+        match Cli::parse_args(args.iter().copied()).command {
+            Command::Check(args) => args,
+            _ => panic!("not a check invocation"),
+        }
+    }
+
+    #[test]
+    fn addresses_follow_the_lists() {
+        let args = check_args(&[
+            "ipcheck",
+            "check",
+            "--v4",
+            "a.csv",
+            "--v6",
+            "b.csv",
+            "203.0.113.7",
+        ]);
+        assert_eq!(args.addresses, ["203.0.113.7".parse::<IpAddr>().unwrap()]);
+        let args = check_args(&["ipcheck", "check", "a.csv", "b.csv", "10.0.0.1", "::1"]);
+        assert_eq!(args.lists.v6(), [PathBuf::from("b.csv")]);
+        assert_eq!(args.addresses.len(), 2);
+    }
+
+    #[test]
+    fn render_reports_each_address() {
+        let networks = Networks {
+            v4: ["203.0.113.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        };
+        let addresses: Vec<IpAddr> = ["203.0.113.7", "2001:db8::1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            render(&networks, &addresses),
+            (
+                "203.0.113.7: contained in 203.0.113.0/24\n2001:db8::1: contained in 2001:db8::/32\n"
+                    .to_string(),
+                true
+            )
+        );
+        let (report, all_covered) = render(&networks, &["198.51.100.1".parse().unwrap()]);
+        assert_eq!(report, "198.51.100.1: not contained\n");
+        assert!(!all_covered);
+    }
+}
@@ -0,0 +1,306 @@
+//! `--format aws`, `azure`, `cloudflare` and `gcp`: the address
+//! ranges cloud providers publish, selected by service and region.
+//!
+//! - `aws`: `https://ip-ranges.amazonaws.com/ip-ranges.json`, by `service`
+//!   and `region`.
+//! - `azure`: the `ServiceTags_Public_*.json` of Microsoft's download
+//!   center, by service tag name and `region`.
+//! - `cloudflare`: `https://www.cloudflare.com/ips-v4` and `ips-v6`, which
+//!   name neither.
+//! - `gcp`: `https://www.gstatic.com/ipranges/cloud.json`, by `service` and
+//!   `scope`.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use clap::Args;
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+
+/// The parser name selecting AWS's `ip-ranges.json`.
+pub const AWS: &str = "aws";
+/// The parser name selecting Azure's service tags.
+pub const AZURE: &str = "azure";
+/// The parser name selecting Cloudflare's lists.
+pub const CLOUDFLARE: &str = "cloudflare";
+/// The parser name selecting Google Cloud's `cloud.json`.
+pub const GCP: &str = "gcp";
+
+/// Every cloud format.
+pub const FORMATS: [&str; 4] = [AWS, AZURE, CLOUDFLARE, GCP];
+
+/// The format of the file at `path`, by the name its provider publishes it
+/// under.
+pub fn sniff(path: &Path) -> Option<&'static str> {
+    match path.file_name()?.to_str()? {
+        "ip-ranges.json" => Some(AWS),
+        "ips-v4" | "ips-v6" => Some(CLOUDFLARE),
+        "cloud.json" => Some(GCP),
+        name if name.starts_with("ServiceTags_") && name.ends_with(".json") => Some(AZURE),
+        _ => None,
+    }
+}
+
+/// Which of a provider's prefixes to read.
+#[derive(Args, Clone, Debug)]
+pub struct CloudOptions {
+    /// With `--format aws`, `azure` or `gcp`, read only the prefixes
+    /// of this service, such as `CLOUDFRONT`, `AzureFrontDoor.Frontend` or
+    /// `Google Cloud`. Repeat it, or separate names with commas, to read
+    /// several.
+    #[arg(long = "service", value_name = "NAME", value_delimiter = ',')]
+    pub services: Vec<String>,
+
+    /// With `--format aws`, `azure` or `gcp`, read only the prefixes
+    /// of this region, such as `eu-west-1`, `westeurope` or
+    /// `europe-west1`. Repeat it, or separate names with commas, to read
+    /// several.
+    #[arg(long = "region", value_name = "NAME", value_delimiter = ',')]
+    pub regions: Vec<String>,
+}
+
+/// One published prefix. Its service and region are empty if its provider
+/// does not say.
+#[derive(Debug, PartialEq)]
+pub struct Prefix {
+    pub network: String,
+    pub service: String,
+    pub region: String,
+}
+
+impl CloudOptions {
+    /// Whether `prefix` is of a chosen service and region.
+    pub fn selects(&self, prefix: &Prefix) -> bool {
+        let chosen = |names: &[String], name: &str| {
+            names.is_empty() || names.iter().any(|n| n.eq_ignore_ascii_case(name))
+        };
+        chosen(&self.services, &prefix.service) && chosen(&self.regions, &prefix.region)
+    }
+
+    /// The services and regions chosen that none of `prefixes` is of.
+    pub fn unknown(&self, prefixes: &[Prefix]) -> Vec<String> {
+        let services = self.services.iter().filter(|service| {
+            !prefixes
+                .iter()
+                .any(|prefix| prefix.service.eq_ignore_ascii_case(service))
+        });
+        let regions = self.regions.iter().filter(|region| {
+            !prefixes
+                .iter()
+                .any(|prefix| prefix.region.eq_ignore_ascii_case(region))
+        });
+        services.chain(regions).cloned().collect()
+    }
+}
+
+/// The prefixes of the `format` file `input`, read from `path`, IPv4 first.
+pub fn prefixes(format: &str, path: &Path, input: impl Read) -> Result<Vec<Prefix>> {
+    let prefixes = match format {
+        AWS => aws(input),
+        AZURE => azure(input),
+        CLOUDFLARE => cloudflare(input),
+        GCP => gcp(input),
+        _ => unreachable!("not a cloud format: {}", format),
+    };
+    prefixes.wrap_err_with(|| format!("{} is not a list in the {} format", path.display(), format))
+}
+
+fn aws(input: impl Read) -> Result<Vec<Prefix>> {
+    #[derive(Deserialize)]
+    struct Ranges {
+        prefixes: Vec<AwsPrefix>,
+        #[serde(default)]
+        ipv6_prefixes: Vec<AwsPrefix>,
+    }
+
+    #[derive(Deserialize)]
+    struct AwsPrefix {
+        #[serde(alias = "ipv6_prefix")]
+        ip_prefix: String,
+        region: String,
+        service: String,
+    }
+
+    let ranges: Ranges = serde_json::from_reader(input)?;
+    let prefixes = ranges.prefixes.into_iter().chain(ranges.ipv6_prefixes);
+    Ok(prefixes
+        .map(|prefix| Prefix {
+            network: prefix.ip_prefix,
+            service: prefix.service,
+            region: prefix.region,
+        })
+        .collect())
+}
+
+fn azure(input: impl Read) -> Result<Vec<Prefix>> {
+    #[derive(Deserialize)]
+    struct ServiceTags {
+        values: Vec<Tag>,
+    }
+
+    #[derive(Deserialize)]
+    struct Tag {
+        name: String,
+        properties: Properties,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Properties {
+        #[serde(default)]
+        region: String,
+        address_prefixes: Vec<String>,
+    }
+
+    let tags: ServiceTags = serde_json::from_reader(input)?;
+    let mut prefixes = Vec::new();
+    for tag in tags.values {
+        for network in tag.properties.address_prefixes {
+            prefixes.push(Prefix {
+                network,
+                service: tag.name.clone(),
+                region: tag.properties.region.clone(),
+            });
+        }
+    }
+    Ok(prefixes)
+}
+
+fn cloudflare(input: impl Read) -> Result<Vec<Prefix>> {
+    let mut prefixes = Vec::new();
+    for line in BufReader::new(input).lines() {
+        let line = line?;
+        let network = line.trim();
+        if !network.is_empty() {
+            prefixes.push(Prefix {
+                network: network.to_string(),
+                service: String::new(),
+                region: String::new(),
+            });
+        }
+    }
+    Ok(prefixes)
+}
+
+fn gcp(input: impl Read) -> Result<Vec<Prefix>> {
+    #[derive(Deserialize)]
+    struct Ranges {
+        prefixes: Vec<GcpPrefix>,
+    }
+
+    // `goog.json`, Google's ranges beyond its cloud, has neither service
+    // nor scope.
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct GcpPrefix {
+        #[serde(alias = "ipv6Prefix")]
+        ipv4_prefix: String,
+        #[serde(default)]
+        service: String,
+        #[serde(default)]
+        scope: String,
+    }
+
+    let ranges: Ranges = serde_json::from_reader(input)?;
+    let mut prefixes: Vec<Prefix> = ranges
+        .prefixes
+        .into_iter()
+        .map(|prefix| Prefix {
+            network: prefix.ipv4_prefix,
+            service: prefix.service,
+            region: prefix.scope,
+        })
+        .collect();
+    // The families are interleaved; IPv4 first, as documented.
+    prefixes.sort_by_key(|prefix| prefix.network.contains(':'));
+    Ok(prefixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP_RANGES: &str = r#"{
+  "syncToken": "1700000000",
+  "createDate": "2023-11-14-22-13-20",
+  "prefixes": [
+    {"ip_prefix": "3.2.34.0/26", "region": "af-south-1", "service": "AMAZON", "network_border_group": "af-south-1"},
+    {"ip_prefix": "52.94.76.0/22", "region": "eu-west-1", "service": "CLOUDFRONT", "network_border_group": "eu-west-1"}
+  ],
+  "ipv6_prefixes": [
+    {"ipv6_prefix": "2600:9000:5206::/48", "region": "eu-west-1", "service": "CLOUDFRONT", "network_border_group": "eu-west-1"}
+  ]
+}"#;
+
+    #[test]
+    fn aws_prefixes_by_service_and_region() {
+        let all = prefixes(AWS, Path::new("ip-ranges.json"), IP_RANGES.as_bytes()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].network, "2600:9000:5206::/48");
+
+        let options = CloudOptions {
+            services: vec!["cloudfront".to_string()],
+            regions: vec!["eu-west-1".to_string(), "us-east-9".to_string()],
+        };
+        let selected: Vec<&str> = all
+            .iter()
+            .filter(|prefix| options.selects(prefix))
+            .map(|prefix| prefix.network.as_str())
+            .collect();
+        assert_eq!(selected, ["52.94.76.0/22", "2600:9000:5206::/48"]);
+        assert_eq!(options.unknown(&all), ["us-east-9"]);
+
+        assert_eq!(
+            sniff(Path::new("https://ip-ranges.amazonaws.com/ip-ranges.json")),
+            Some(AWS)
+        );
+        let err = prefixes(AWS, Path::new("x.json"), "[]".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "x.json is not a list in the aws format");
+    }
+
+    #[test]
+    fn azure_cloudflare_and_gcp_prefixes() {
+        let tags = r#"{"changeNumber": 1, "cloud": "Public", "values": [
+  {"name": "AzureFrontDoor.Frontend", "id": "AzureFrontDoor.Frontend", "properties":
+    {"changeNumber": 1, "region": "", "platform": "Azure", "systemService": "AzureFrontDoor",
+     "addressPrefixes": ["13.73.248.8/29", "2603:1030:21::/48"]}},
+  {"name": "AzureCloud.westeurope", "id": "AzureCloud.westeurope", "properties":
+    {"region": "westeurope", "addressPrefixes": ["13.69.0.0/17"]}}
+]}"#;
+        let all = prefixes(AZURE, Path::new("ServiceTags_Public.json"), tags.as_bytes()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            all[2],
+            Prefix {
+                network: "13.69.0.0/17".to_string(),
+                service: "AzureCloud.westeurope".to_string(),
+                region: "westeurope".to_string(),
+            }
+        );
+
+        let all = prefixes(
+            CLOUDFLARE,
+            Path::new("ips-v4"),
+            "173.245.48.0/20\n103.21.244.0/22\n".as_bytes(),
+        )
+        .unwrap();
+        let networks: Vec<&str> = all.iter().map(|prefix| prefix.network.as_str()).collect();
+        assert_eq!(networks, ["173.245.48.0/20", "103.21.244.0/22"]);
+
+        let cloud = r#"{"syncToken": "1", "creationTime": "2024-01-01T00:00:00", "prefixes": [
+  {"ipv6Prefix": "2600:1900:8000::/44", "service": "Google Cloud", "scope": "africa-south1"},
+  {"ipv4Prefix": "34.35.0.0/16", "service": "Google Cloud", "scope": "africa-south1"}
+]}"#;
+        let all = prefixes(GCP, Path::new("cloud.json"), cloud.as_bytes()).unwrap();
+        assert_eq!(all[0].network, "34.35.0.0/16");
+        assert_eq!(all[1].region, "africa-south1");
+
+        for (name, format) in [
+            ("ips-v6", CLOUDFLARE),
+            ("cloud.json", GCP),
+            ("ServiceTags_Public_20240101.json", AZURE),
+        ] {
+            assert_eq!(sniff(Path::new(name)), Some(format));
+        }
+    }
+}
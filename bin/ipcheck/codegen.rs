@@ -0,0 +1,370 @@
+//! The built-in output backends, and choosing the backends of a build.
+//!
+//! - `typescript`, the checker module, written to the output path;
+//! - `esm` and `cjs`, the checker as a JavaScript ES or CommonJS module,
+//!   written to the output path with its declarations next to it, and also
+//!   selected by `--target`;
+//! - `python`, `go`, `rust`, `c`, `java` and `lua`, the checker as a Python
+//!   module, a Go package, a Rust source file, a C header, a Java class or
+//!   a Lua module, written to the output path and also selected by
+//!   `--target`;
+//! - `node-test`, a `node:test` suite for it, written next to it and also
+//!   selected by `--emit-tests`;
+//! - `lookup`, a TypeScript module returning the name of the list holding
+//!   an address, written to the output path and also selected by
+//!   `--target`;
+//! - `artifact`, the binary artifact for the WebAssembly and C lookups,
+//!   written to the output path with the extension `.bin`;
+//! - `json`, the node arrays and their metadata as JSON, written to the
+//!   output path and also selected by `--target`;
+//! - `wasm`, the filter as a WebAssembly module, written to the output path
+//!   with the extension `.wasm` and its JavaScript glue with `.js`, and also
+//!   selected by `--target`;
+//! - `mmdb`, the networks and their notes as a MaxMind DB, written to the
+//!   output path and also selected by `--target`;
+//! - `proto`, the node arrays as the protobuf message of
+//!   [`ipcheck_rs::proto`], written to the output path and also selected by
+//!   `--target`;
+//! - `nftables`, `ipset` and `bpf`, the sets and eBPF map keys of
+//!   [`crate::firewall`], written to the output path with the extension
+//!   `.nft`, `.ipset` or `.bpf.h`;
+//! - `vcl`, the Varnish ACL of [`crate::proxy`], written to the output path
+//!   and also selected by `--target`;
+//! - `haproxy`, the HAProxy ACL files of [`crate::proxy`], written next to
+//!   the output with the extensions `.v4.acl` and `.v6.acl`, and also
+//!   selected by `--target`;
+//! - `unbound` and `rpz`, the resolver policies of [`crate::resolver`],
+//!   written next to the output with the extension `.unbound.conf` or
+//!   `.rpz.zone`;
+//! - `text` and `csv`, the lists of [`crate::canonical`], written to the
+//!   output path and also selected by `--target`;
+//! - `sql`, the range table of [`crate::sql`], in PostgreSQL's dialect
+//!   unless `--sql-dialect` picks another, written to the output path and
+//!   also selected by `--target`.
+//!
+//! Backends registered with [`ipcheck_rs::backend::register`] are selected
+//! the same way, and replace a built-in of the same name. All but the
+//! artifact, the JSON, the WebAssembly, the MaxMind DB, the protobuf, the
+//! firewall, the proxy, the resolver, the list and the SQL outputs are the
+//! targets of [`ipcheck_rs::codegen`].
+
+use std::fs;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use ipcheck_rs::backend::{self, Error, Filters, Output, OutputBackend};
+use ipcheck_rs::codegen::{self as checkers, mmdb, wasm, Target};
+use ipcheck_rs::flat::FORMAT_VERSION;
+use ipcheck_rs::{artifact, proto};
+use serde::{Deserialize, Serialize};
+
+use crate::canonical::{Csv, Text};
+use crate::cron::Civil;
+use crate::firewall::{Bpf, Ipset, Nftables};
+use crate::proxy::{Haproxy, Vcl};
+use crate::resolver::{Rpz, Unbound};
+use crate::sql::Sql;
+
+/// The language of the checker, or the binary artifact, chosen by
+/// `--target`.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// TypeScript.
+    #[default]
+    Ts,
+    /// A JavaScript ES module, with a `.d.ts` next to it, for consumers
+    /// without a TypeScript build step.
+    Esm,
+    /// A JavaScript CommonJS module, with a `.d.ts` next to it.
+    Cjs,
+    /// A Python module.
+    Python,
+    /// A Go package, named after the output's directory.
+    Go,
+    /// A `no_std` Rust source file.
+    Rust,
+    /// A C header.
+    C,
+    /// A Java class, named after the output.
+    Java,
+    /// A Lua module for OpenResty.
+    Lua,
+    /// The binary artifact, for `ipcheck_rs::artifact::load`.
+    Bin,
+    /// The node arrays as JSON, for languages without a checker.
+    Json,
+    /// A WebAssembly module with its JavaScript glue.
+    Wasm,
+    /// A MaxMind DB, for the readers of every language.
+    Mmdb,
+    /// The node arrays as an `ipcheck.v1.Filter` protobuf message, for
+    /// `ipcheck_rs::proto::decode`.
+    Proto,
+    /// A Varnish VCL ACL.
+    Vcl,
+    /// The HAProxy ACL files of the IPv4 and IPv6 networks beside the
+    /// output, as `output.v4.acl` and `output.v6.acl`.
+    Haproxy,
+    /// The simplified networks, sorted, one per line.
+    Text,
+    /// The simplified networks, sorted, as CSV.
+    Csv,
+    /// A table of the address ranges of the networks, in SQL.
+    Sql,
+    /// A TypeScript module returning the name of the list holding an
+    /// address.
+    Lookup,
+}
+
+impl Language {
+    /// The backend rendering the checker in this language.
+    pub fn backend(self) -> &'static str {
+        match self {
+            Language::Ts => "typescript",
+            Language::Esm => "esm",
+            Language::Cjs => "cjs",
+            Language::Python => "python",
+            Language::Go => "go",
+            Language::Rust => "rust",
+            Language::C => "c",
+            Language::Java => "java",
+            Language::Lua => "lua",
+            Language::Bin => "artifact",
+            Language::Json => "json",
+            Language::Wasm => "wasm",
+            Language::Mmdb => "mmdb",
+            Language::Proto => "proto",
+            Language::Vcl => "vcl",
+            Language::Haproxy => "haproxy",
+            Language::Text => "text",
+            Language::Csv => "csv",
+            Language::Sql => "sql",
+            Language::Lookup => "lookup",
+        }
+    }
+
+    /// The checker target of this language, `None` if it is not one.
+    pub fn checker(self) -> Option<Target> {
+        match self {
+            Language::Ts => Some(Target::TypeScript),
+            Language::Esm => Some(Target::Esm),
+            Language::Cjs => Some(Target::Cjs),
+            Language::Python => Some(Target::Python),
+            Language::Go => Some(Target::Go),
+            Language::Rust => Some(Target::Rust),
+            Language::C => Some(Target::C),
+            Language::Java => Some(Target::Java),
+            Language::Lua => Some(Target::Lua),
+            _ => None,
+        }
+    }
+}
+
+/// How the checkers embed their node arrays, chosen by `--encoding`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Array literals.
+    #[default]
+    Literal,
+    /// Base64 strings decoded into `Uint32Array`s when the module loads,
+    /// for the TypeScript and JavaScript checkers.
+    Base64,
+}
+
+impl From<Encoding> for checkers::Encoding {
+    fn from(encoding: Encoding) -> checkers::Encoding {
+        match encoding {
+            Encoding::Literal => checkers::Encoding::Literal,
+            Encoding::Base64 => checkers::Encoding::Base64,
+        }
+    }
+}
+
+/// The binary artifact.
+struct Artifact;
+
+impl OutputBackend for Artifact {
+    fn name(&self) -> &'static str {
+        "artifact"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        Ok(vec![Output {
+            path: filters.output.with_extension("bin"),
+            contents: artifact::write(filters.v4, filters.v6)?,
+        }])
+    }
+}
+
+/// The `ipcheck.v1.Filter` message of the node arrays.
+struct Proto;
+
+impl OutputBackend for Proto {
+    fn name(&self) -> &'static str {
+        "proto"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: proto::encode(filters.v4, filters.v6)?,
+        }])
+    }
+}
+
+/// The WebAssembly module and its glue.
+struct Wasm;
+
+impl OutputBackend for Wasm {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let path = filters.output.with_extension("wasm");
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let glue = wasm::glue(&name)?;
+        Ok(vec![
+            Output {
+                path: path.clone(),
+                contents: wasm::module(filters.v4, filters.v6)?,
+            },
+            Output {
+                path: filters.output.with_extension("js"),
+                contents: glue.into_bytes(),
+            },
+        ])
+    }
+}
+
+/// The node arrays and their metadata, as the `json` backend writes them.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonFilters {
+    /// The node-array layout version.
+    pub format_version: u32,
+    /// When these networks were first generated, to the minute: a build
+    /// of the same networks keeps the time of the output it replaces.
+    pub generated_at: String,
+    /// The checksum of the networks' binary artifact, in hex, which
+    /// changes exactly when the networks do.
+    pub source_hash: String,
+    pub filter_v4: Vec<usize>,
+    pub filter_v6: Vec<usize>,
+}
+
+/// The node arrays as JSON.
+struct Json;
+
+impl OutputBackend for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let bytes = artifact::write(filters.v4, filters.v6)?;
+        let source_hash = format!("{:08x}", artifact::checksum(&bytes).unwrap());
+        let previous = fs::read(filters.output)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<JsonFilters>(&json).ok())
+            .filter(|previous| previous.source_hash == source_hash);
+        let json = JsonFilters {
+            format_version: FORMAT_VERSION,
+            generated_at: match previous {
+                Some(previous) => previous.generated_at,
+                None => Civil::from_time(SystemTime::now()).to_string(),
+            },
+            source_hash,
+            filter_v4: filters.nodes_v4.to_vec(),
+            filter_v6: filters.nodes_v6.to_vec(),
+        };
+        let mut contents = serde_json::to_vec(&json)?;
+        contents.push(b'\n');
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents,
+        }])
+    }
+}
+
+/// The networks as a MaxMind DB.
+struct Mmdb;
+
+impl OutputBackend for Mmdb {
+    fn name(&self) -> &'static str {
+        "mmdb"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let database =
+            |build_epoch| mmdb::database(filters.v4, filters.v6, filters.notes, build_epoch);
+        // A build of the same networks and notes keeps the build time of the
+        // database it replaces, as the `json` backend keeps its own.
+        let kept = fs::read(filters.output).ok().and_then(|previous| {
+            let rebuilt = database(mmdb::build_epoch(&previous)?).ok()?;
+            (rebuilt == previous).then_some(rebuilt)
+        });
+        let contents = match kept {
+            Some(contents) => contents,
+            None => {
+                let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+                database(now.map_or(0, |now| now.as_secs()))?
+            }
+        };
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents,
+        }])
+    }
+}
+
+/// The backend named `name`: a registered one, else a built-in.
+pub fn backend(name: &str) -> Result<Arc<dyn OutputBackend>> {
+    let builtins: [Arc<dyn OutputBackend>; 26] = [
+        Arc::new(Target::TypeScript),
+        Arc::new(Target::Esm),
+        Arc::new(Target::Cjs),
+        Arc::new(Target::Python),
+        Arc::new(Target::Go),
+        Arc::new(Target::Rust),
+        Arc::new(Target::C),
+        Arc::new(Target::Java),
+        Arc::new(Target::Lua),
+        Arc::new(Target::NodeTest),
+        Arc::new(Target::Lookup),
+        Arc::new(Artifact),
+        Arc::new(Json),
+        Arc::new(Wasm),
+        Arc::new(Mmdb),
+        Arc::new(Proto),
+        Arc::new(Nftables),
+        Arc::new(Ipset),
+        Arc::new(Bpf),
+        Arc::new(Vcl),
+        Arc::new(Haproxy),
+        Arc::new(Unbound),
+        Arc::new(Rpz),
+        Arc::new(Text),
+        Arc::new(Csv),
+        Arc::new(Sql(Default::default())),
+    ];
+    let registry = backend::registry();
+    let found = registry.get(name).or_else(|| {
+        builtins
+            .iter()
+            .find(|builtin| builtin.name() == name)
+            .cloned()
+    });
+    found.ok_or_else(|| {
+        let mut names: Vec<&str> = builtins.iter().map(|builtin| builtin.name()).collect();
+        for registered in registry.names() {
+            if !names.contains(&registered) {
+                names.push(registered);
+            }
+        }
+        eyre!("unknown backend {:?} (expected {})", name, names.join(", "))
+    })
+}
@@ -0,0 +1,166 @@
+//! Reading inputs as large feeds are published: compressed with gzip, zstd
+//! or xz, such as `GeoLite2-Country-Blocks-IPv4.csv.gz` or a RIPE RIS
+//! `bview.gz` dump.
+//!
+//! A compressed input is recognized by its magic bytes, whatever it is
+//! called, and decompressed as it is read, with the `compression` feature.
+//! Its format is then told by its name without the `.gz`, `.zst` or `.xz`
+//! extension, so `list.json.gz` is read as JSON.
+
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+
+#[cfg(not(feature = "compression"))]
+use eyre::bail;
+use eyre::Result;
+
+/// A compression format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// The format `head`, the start of an input, is compressed with, if
+    /// any.
+    pub fn sniff(head: &[u8]) -> Option<Compression> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    #[cfg_attr(feature = "compression", allow(dead_code))]
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+/// `input`, named `path` in errors, decompressed if it is compressed, and
+/// the name its format is told by.
+pub fn decompress<'a>(
+    path: &Path,
+    mut input: impl BufRead + 'a,
+) -> Result<(Box<dyn Read + 'a>, PathBuf)> {
+    let Some(compression) = Compression::sniff(input.fill_buf()?) else {
+        return Ok((Box::new(input), path.to_path_buf()));
+    };
+    let name = match path.extension() {
+        Some(ext) if ext == compression.extension() => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    Ok((decoder(path, compression, input)?, name))
+}
+
+#[cfg(feature = "compression")]
+fn decoder<'a>(
+    _path: &Path,
+    compression: Compression,
+    input: impl BufRead + 'a,
+) -> Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(input)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(input)?),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(input)),
+    })
+}
+
+#[cfg(not(feature = "compression"))]
+fn decoder<'a>(
+    path: &Path,
+    compression: Compression,
+    _input: impl BufRead + 'a,
+) -> Result<Box<dyn Read + 'a>> {
+    bail!(
+        "{} is {}-compressed (decompress it, or build ipcheck with the `compression` feature to read it as it is)",
+        path.display(),
+        compression.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_compressed_inputs() {
+        assert_eq!(
+            Compression::sniff(&[0x1f, 0x8b, 8, 0]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::sniff(&[0x28, 0xb5, 0x2f, 0xfd, 0]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::sniff(b"\xfd7zXZ\0\0"), Some(Compression::Xz));
+        assert_eq!(Compression::sniff(b"network\n"), None);
+        assert_eq!(Compression::sniff(b""), None);
+
+        let (mut input, name) = decompress(Path::new("v4.csv"), &b"10.0.0.0/8\n"[..]).unwrap();
+        let mut text = String::new();
+        input.read_to_string(&mut text).unwrap();
+        assert_eq!(
+            (text.as_str(), name.as_path()),
+            ("10.0.0.0/8\n", Path::new("v4.csv"))
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompress_every_format() {
+        use std::io::Write;
+
+        let text = "network\n10.0.0.0/8\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(text.as_bytes(), 0).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(text.as_bytes()).unwrap();
+        let xz = xz.finish().unwrap();
+
+        for (path, compressed, named) in [
+            ("v4.csv.gz", gzip, "v4.csv"),
+            ("v4.csv.zst", zstd, "v4.csv"),
+            ("v4.xz.data", xz, "v4.xz.data"),
+        ] {
+            let (mut input, name) = decompress(Path::new(path), &compressed[..]).unwrap();
+            let mut decompressed = String::new();
+            input.read_to_string(&mut decompressed).unwrap();
+            assert_eq!(decompressed, text, "{}", path);
+            assert_eq!(name, Path::new(named));
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn compressed_inputs_need_the_feature() {
+        let err = decompress(Path::new("v4.csv.gz"), &[0x1f, 0x8b, 8, 0][..])
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().starts_with("v4.csv.gz is gzip-compressed"),
+            "{}",
+            err
+        );
+    }
+}
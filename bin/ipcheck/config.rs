@@ -0,0 +1,291 @@
+//! `ipcheck build --config ipcheck.toml`: the sources, exclusions and
+//! outputs of a pipeline declared in a file, for more inputs than a command
+//! line comfortably holds.
+//!
+//! ```toml
+//! [sources.drop]
+//! v4 = "https://example.com/drop-v4.csv"
+//! v6 = "https://example.com/drop-v6.csv"
+//!
+//! [sources.internal]
+//! v4 = ["blocked.csv", "abuse.csv"]
+//! v6 = "blocked-v6.csv"
+//!
+//! [exclude]
+//! v4 = "allow.csv"
+//!
+//! [[outputs]]
+//! path = "dist/ipcheck.ts"
+//! backends = ["typescript", "node-test"]
+//!
+//! [[outputs]]
+//! path = "dist/filters.json"
+//! target = "json"
+//!
+//! [[outputs]]
+//! path = "dist/internal.ts"
+//! sources = ["internal"]
+//! template = "wrapper.hbs"
+//! ```
+//!
+//! Every output is built from its `sources`, all of them by default, less
+//! the exclusions, and rendered by its `backends`, `typescript` by default,
+//! which stands for the checker of its `target` as on the command line,
+//! from its `template` if it has one. Outputs of the same sources are
+//! rendered from the same lists, loaded and flattened once, as by `--emit`.
+//! Relative paths are relative to the file. The other build options still
+//! come from the command line, and apply to every output.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use eyre::{bail, Result, WrapErr};
+use serde::Deserialize;
+
+use crate::codegen::Language;
+use crate::input::{self, Lists};
+use crate::{storage, BuildArgs, Emit};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    sources: BTreeMap<String, Families>,
+    #[serde(default)]
+    exclude: Families,
+    outputs: Vec<Output>,
+}
+
+/// The lists of each family.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Families {
+    #[serde(default)]
+    v4: Paths,
+    #[serde(default)]
+    v6: Paths,
+}
+
+/// One path, or several.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Paths {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Default for Paths {
+    fn default() -> Paths {
+        Paths::Many(Vec::new())
+    }
+}
+
+impl Paths {
+    fn resolve(&self, dir: &Path) -> Vec<PathBuf> {
+        let paths = match self {
+            Paths::One(path) => std::slice::from_ref(path),
+            Paths::Many(paths) => paths,
+        };
+        paths.iter().map(|path| resolve(dir, path)).collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Output {
+    path: PathBuf,
+    sources: Option<Vec<String>>,
+    #[serde(default = "default_backends")]
+    backends: Vec<String>,
+    target: Option<Language>,
+    template: Option<PathBuf>,
+}
+
+fn default_backends() -> Vec<String> {
+    vec!["typescript".to_string()]
+}
+
+/// `path` relative to `dir`, unless it is absolute, a URI or standard
+/// input.
+fn resolve(dir: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute()
+        || storage::is_remote(path)
+        || storage::is_url(path)
+        || path == Path::new(input::STDIN)
+    {
+        path.to_path_buf()
+    } else {
+        dir.join(path)
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`.
+    pub fn load(path: &Path) -> Result<Config> {
+        let text =
+            String::from_utf8(storage::read(path)?).wrap_err_with(|| path.display().to_string())?;
+        Config::parse(&text).wrap_err_with(|| path.display().to_string())
+    }
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        if config.outputs.is_empty() {
+            bail!("no outputs declared");
+        }
+        for output in &config.outputs {
+            for name in output.sources.iter().flatten() {
+                if !config.sources.contains_key(name) {
+                    let names: Vec<&str> = config.sources.keys().map(String::as_str).collect();
+                    bail!(
+                        "{}: unknown source {:?} (expected {})",
+                        output.path.display(),
+                        name,
+                        names.join(", ")
+                    );
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// A build of every output, with the other options of `args`, the
+    /// outputs of the same lists being emitted by the build of the first.
+    /// Relative paths are resolved against `dir`, the config file's
+    /// directory.
+    pub fn builds(&self, args: &BuildArgs, dir: &Path) -> Vec<BuildArgs> {
+        let exclude_v4 = self.exclude.v4.resolve(dir);
+        let exclude_v6 = self.exclude.v6.resolve(dir);
+        let mut builds: Vec<(Vec<PathBuf>, Vec<PathBuf>, BuildArgs)> = Vec::new();
+        for output in &self.outputs {
+            let (mut v4, mut v6) = (Vec::new(), Vec::new());
+            for (name, source) in &self.sources {
+                if output
+                    .sources
+                    .as_ref()
+                    .is_none_or(|names| names.contains(name))
+                {
+                    v4.extend(source.v4.resolve(dir));
+                    v6.extend(source.v6.resolve(dir));
+                }
+            }
+            // The target and template of the command line are those of the
+            // outputs giving neither.
+            let (target, template) = match (output.target, &output.template) {
+                (None, None) => (None, args.template.clone()),
+                (target, template) => (target, template.as_ref().map(|path| resolve(dir, path))),
+            };
+            let emit = Emit {
+                path: resolve(dir, &output.path),
+                backends: output.backends.clone(),
+                target,
+                template,
+            };
+            match builds
+                .iter_mut()
+                .find(|(lists_v4, lists_v6, _)| *lists_v4 == v4 && *lists_v6 == v6)
+            {
+                Some((_, _, build)) => build.emits.push(emit),
+                None => {
+                    let mut build = args.emitted(&emit);
+                    build.lists = Lists::new(
+                        v4.clone(),
+                        v6.clone(),
+                        exclude_v4.clone(),
+                        exclude_v6.clone(),
+                    );
+                    builds.push((v4, v6, build));
+                }
+            }
+        }
+        builds.into_iter().map(|(_, _, build)| build).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use crate::Command;
+
+    const CONFIG: &str = r#"
+[sources.drop]
+v4 = "https://example.com/drop-v4.csv"
+v6 = "/srv/drop-v6.csv"
+
+[sources.internal]
+v4 = ["blocked.csv", "abuse.csv"]
+
+[exclude]
+v4 = "allow.csv"
+
+[[outputs]]
+path = "dist/ipcheck.ts"
+backends = ["typescript", "node-test"]
+
+[[outputs]]
+path = "dist/filters.json"
+target = "json"
+
+[[outputs]]
+path = "dist/internal.ts"
+sources = ["internal"]
+template = "wrapper.hbs"
+"#;
+
+    #[test]
+    fn builds_of_every_output() {
+        let args = match Cli::parse_args(["ipcheck", "build", "--config", "ipcheck.toml"]).command {
+            Command::Build(args) => args,
+            _ => panic!("not a build"),
+        };
+        let config = Config::parse(CONFIG).unwrap();
+        let builds = config.builds(&args, Path::new("conf"));
+        let paths = |paths: &[PathBuf]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect()
+        };
+
+        assert_eq!(builds.len(), 2);
+        assert_eq!(
+            paths(builds[0].lists.v4()),
+            [
+                "https://example.com/drop-v4.csv",
+                "conf/blocked.csv",
+                "conf/abuse.csv"
+            ]
+        );
+        assert_eq!(paths(builds[0].lists.v6()), ["/srv/drop-v6.csv"]);
+        assert_eq!(paths(builds[0].lists.exclude_v4()), ["conf/allow.csv"]);
+        assert_eq!(builds[0].output(), Path::new("conf/dist/ipcheck.ts"));
+        assert_eq!(builds[0].backends, ["typescript", "node-test"]);
+        // The JSON is of the same lists, and emitted along.
+        assert_eq!(builds[0].emits.len(), 1);
+        assert_eq!(builds[0].emits[0].path, Path::new("conf/dist/filters.json"));
+        assert_eq!(builds[0].emits[0].target, Some(Language::Json));
+
+        assert_eq!(
+            paths(builds[1].lists.v4()),
+            ["conf/blocked.csv", "conf/abuse.csv"]
+        );
+        assert!(builds[1].lists.v6().is_empty());
+        assert_eq!(builds[1].backends, ["typescript"]);
+        assert_eq!(
+            builds[1].template.as_deref(),
+            Some(Path::new("conf/wrapper.hbs"))
+        );
+        assert!(builds[1].emits.is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_unknown_sources_and_fields() {
+        let err = Config::parse("[[outputs]]\npath = \"a.ts\"\nsources = [\"nope\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "a.ts: unknown source \"nope\" (expected )");
+        assert!(Config::parse("outputs = []\n").is_err());
+        assert!(Config::parse("[[outputs]]\npath = \"a.ts\"\nformat = \"x\"\n").is_err());
+        assert!(Config::parse("[[outputs]]\npath = \"a.ts\"\ntarget = \"cobol\"\n").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Cron schedules for `ipcheck daemon --refresh`.
+//!
+//! A schedule has the five fields of crontab(5), `minute hour day-of-month
+//! month day-of-week`, each `*`, a number, a range `a-b`, a step `*/n` or
+//! `a-b/n`, or a comma-separated list of those. As in cron, a day matches
+//! if either day field does when both are restricted. `@hourly`, `@daily`,
+//! `@weekly` and `@monthly` abbreviate the usual schedules. Times are UTC.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use eyre::{bail, eyre, Report, Result};
+
+/// How far ahead to look for the next match before giving up: enough to
+/// cover a leap day.
+const HORIZON_DAYS: i64 = 5 * 366;
+
+/// A parsed cron schedule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether each day field is restricted, rather than `*`.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// A UTC calendar time, to the minute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Civil {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+}
+
+impl Schedule {
+    /// The first matching minute strictly after `time`, `None` if there is
+    /// none within a few years.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        let mut minute = seconds / 60 + 1;
+        let end = minute + HORIZON_DAYS * 1440;
+        while minute < end {
+            let days = minute.div_euclid(1440);
+            let civil = Civil::from_minutes(minute);
+            if !bit(self.months, civil.month) || !self.day_matches(&civil, days) {
+                minute = (days + 1) * 1440;
+            } else if !bit(self.hours, civil.hour) {
+                minute = (minute / 60 + 1) * 60;
+            } else if !bit(self.minutes, civil.minute) {
+                minute += 1;
+            } else {
+                return Some(UNIX_EPOCH + Duration::from_secs(minute as u64 * 60));
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, civil: &Civil, days: i64) -> bool {
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as u32;
+        let day = bit(self.days, civil.day);
+        let weekday = bit(self.weekdays, weekday);
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Report;
+
+    fn from_str(text: &str) -> Result<Schedule> {
+        let expanded = match text.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!(
+                "{:?} is not a cron schedule: expected 5 fields (minute hour day month weekday)",
+                text
+            );
+        }
+        let mut weekdays = field(fields[4], 0, 7).map_err(|err| eyre!("weekday: {}", err))?;
+        // Both 0 and 7 are Sunday.
+        if bit(weekdays, 7) {
+            weekdays |= 1;
+        }
+        let schedule = Schedule {
+            minutes: field(fields[0], 0, 59).map_err(|err| eyre!("minute: {}", err))?,
+            hours: field(fields[1], 0, 23).map_err(|err| eyre!("hour: {}", err))?,
+            days: field(fields[2], 1, 31).map_err(|err| eyre!("day of month: {}", err))?,
+            months: field(fields[3], 1, 12).map_err(|err| eyre!("month: {}", err))?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        };
+        if schedule.next_after(UNIX_EPOCH).is_none() {
+            bail!("{:?} never matches", text);
+        }
+        Ok(schedule)
+    }
+}
+
+/// Parses one field into a bit set of the values in `min..=max`.
+fn field(text: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => bail!("{:?} is not a valid step", step),
+            },
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => {
+                let value = |text: &str| match text.parse::<u32>() {
+                    Ok(value) if (min..=max).contains(&value) => Ok(value),
+                    _ => Err(eyre!("{:?} is not in {}-{}", text, min, max)),
+                };
+                match range.split_once('-') {
+                    Some((first, last)) => (value(first)?, value(last)?),
+                    // `a/n` runs from `a` to the end, as in cronie.
+                    None if step > 1 => (value(range)?, max),
+                    None => (value(range)?, value(range)?),
+                }
+            }
+        };
+        if first > last {
+            bail!("{:?} is an empty range", range);
+        }
+        for value in (first..=last).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+impl Civil {
+    /// The calendar time `minutes` after the Unix epoch.
+    fn from_minutes(minutes: i64) -> Civil {
+        // Howard Hinnant's `civil_from_days`.
+        let days = minutes.div_euclid(1440);
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let minute_of_day = minutes.rem_euclid(1440) as u32;
+        Civil {
+            year: yoe + era * 400 + i64::from(month <= 2),
+            month,
+            day,
+            hour: minute_of_day / 60,
+            minute: minute_of_day % 60,
+        }
+    }
+
+    pub fn from_time(time: SystemTime) -> Civil {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Civil::from_minutes((seconds / 60) as i64)
+    }
+}
+
+impl fmt::Display for Civil {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The time `text`, as written by `Civil`'s `Display`.
+    fn at(text: &str) -> SystemTime {
+        let number = |range: std::ops::Range<usize>| text[range].parse::<i64>().unwrap();
+        let (year, month, day) = (number(0..4), number(5..7), number(8..10));
+        // Howard Hinnant's `days_from_civil`.
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        let minutes = days * 1440 + number(11..13) * 60 + number(14..16);
+        UNIX_EPOCH + Duration::from_secs(minutes as u64 * 60)
+    }
+
+    fn next(schedule: &str, after: &str) -> String {
+        let schedule: Schedule = schedule.parse().unwrap();
+        Civil::from_time(schedule.next_after(at(after)).unwrap()).to_string()
+    }
+
+    #[test]
+    fn civil_times() {
+        assert_eq!(Civil::from_minutes(0).to_string(), "1970-01-01T00:00Z");
+        // 2024-02-29T12:34Z
+        assert_eq!(
+            Civil::from_minutes(1_709_210_040 / 60).to_string(),
+            "2024-02-29T12:34Z"
+        );
+    }
+
+    #[test]
+    fn next_matches() {
+        assert_eq!(
+            next("0 */6 * * *", "2024-03-01T05:59Z"),
+            "2024-03-01T06:00Z"
+        );
+        assert_eq!(
+            next("0 */6 * * *", "2024-03-01T06:00Z"),
+            "2024-03-01T12:00Z"
+        );
+        assert_eq!(
+            next("0 */6 * * *", "2024-12-31T18:30Z"),
+            "2025-01-01T00:00Z"
+        );
+        assert_eq!(
+            next("*/15 9-17 * * 1-5", "2024-03-01T17:50Z"),
+            "2024-03-04T09:00Z"
+        );
+        assert_eq!(next("0 0 29 2 *", "2024-03-01T00:00Z"), "2028-02-29T00:00Z");
+        // Either day field matches when both are restricted.
+        assert_eq!(next("0 0 13 * 5", "2024-09-01T00:00Z"), "2024-09-06T00:00Z");
+        assert_eq!(next("@weekly", "2024-03-01T00:00Z"), "2024-03-03T00:00Z");
+        assert_eq!(next("30 4 * * 7", "2024-03-01T00:00Z"), "2024-03-03T04:30Z");
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        for text in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 30 2 *",
+        ] {
+            assert!(text.parse::<Schedule>().is_err(), "{:?}", text);
+        }
+    }
+}
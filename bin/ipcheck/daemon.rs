@@ -0,0 +1,195 @@
+//! `ipcheck daemon`: rebuilding on a schedule, in place of cron and a
+//! wrapper script.
+//!
+//! At every `--refresh` time (see [`cron`](crate::cron)), the daemon reads
+//! the inputs again, fetching remote feeds anew, renders the outputs and
+//! verifies that the rendered TypeScript filters, if any, decode back to
+//! the loaded networks, then atomically replaces the outputs that changed.
+//! A refresh that fails at any step leaves the current outputs in place and
+//! is retried at the next scheduled time; only the first build must
+//! succeed.
+//!
+//! With the `serve` feature, `--listen` also answers lookups over HTTP from
+//! the networks of the latest successful refresh, as `ipcheck serve` does.
+
+#[cfg(feature = "serve")]
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
+
+use clap::Args;
+use eyre::{bail, Result};
+
+use crate::codegen::Language;
+use crate::cron::{Civil, Schedule};
+use crate::input::Networks;
+use crate::telemetry;
+#[cfg(feature = "serve")]
+use crate::{match_log::MatchLog, metrics::Metrics, reload::Live};
+use crate::{render_outputs, rendered_networks, write_outputs, BuildArgs};
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// When to rebuild, as a cron schedule in UTC such as "0 */6 * * *".
+    #[arg(long, value_name = "SCHEDULE")]
+    refresh: Schedule,
+
+    /// Also answer lookups over HTTP on this address.
+    #[cfg(feature = "serve")]
+    #[arg(long)]
+    listen: Option<String>,
+
+    #[command(flatten)]
+    build: BuildArgs,
+}
+
+/// The networks served with `--listen`.
+#[cfg(feature = "serve")]
+struct Serving {
+    live: Arc<Live>,
+    metrics: Arc<Metrics>,
+}
+
+impl DaemonArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        self.build.assign_paths()
+    }
+}
+
+pub fn daemon(args: DaemonArgs) -> Result<()> {
+    if args.build.check {
+        bail!("--check only applies to a single build");
+    }
+    #[cfg(feature = "config")]
+    if args.build.config.is_some() {
+        bail!("--config only applies to ipcheck build");
+    }
+    if args.build.watch {
+        bail!("--watch does not apply to the daemon, which rebuilds on its schedule");
+    }
+    #[cfg_attr(not(feature = "serve"), allow(unused_variables))]
+    let networks = refresh(&args.build)?;
+    #[cfg(feature = "serve")]
+    let serving = match &args.listen {
+        Some(listen) => Some(serve(listen, networks)?),
+        None => None,
+    };
+
+    loop {
+        let now = SystemTime::now();
+        let next = args
+            .refresh
+            .next_after(now)
+            .expect("schedules are checked to match when parsed");
+        tracing::info!("next refresh at {}", Civil::from_time(next));
+        thread::sleep(next.duration_since(now).unwrap_or_default());
+
+        match refresh(&args.build) {
+            #[cfg_attr(not(feature = "serve"), allow(unused_variables))]
+            Ok(networks) => {
+                tracing::info!("refreshed outputs");
+                #[cfg(feature = "serve")]
+                if let Some(serving) = &serving {
+                    serving.metrics.loaded(&networks);
+                    serving.live.replace(networks);
+                }
+            }
+            Err(err) => {
+                #[cfg(feature = "serve")]
+                if let Some(serving) = &serving {
+                    serving.metrics.reload_failed();
+                }
+                tracing::warn!("refresh failed, keeping the current outputs: {:#}", err);
+            }
+        }
+    }
+}
+
+/// Rebuilds and verifies every output, then replaces those that changed.
+fn refresh(args: &BuildArgs) -> Result<Networks> {
+    let _span = telemetry::span("refresh");
+    let (networks, outputs) = render_outputs(args)?;
+    // The code of a template or partials of one's own has no layout to
+    // decode, nor has a minified checker or a value lookup.
+    if args.backends.iter().any(|name| name == "typescript")
+        && args.template.is_none()
+        && args.partials.is_none()
+        && !args.minify
+        && args.target != Language::Lookup
+    {
+        // Targets writing files beside the output only, such as the HAProxy
+        // ACL files, leave nothing at the output to verify.
+        let code = outputs
+            .iter()
+            .find(|rendered| rendered.path == args.output());
+        if let Some(code) = code {
+            if rendered_networks(&code.contents).as_ref() != Some(&networks) {
+                bail!("the rendered filters do not decode to the loaded networks");
+            }
+        }
+    }
+    write_outputs(args, &networks, outputs, true)?;
+    Ok(networks)
+}
+
+/// Answers HTTP lookups on `listen` from a background thread.
+#[cfg(feature = "serve")]
+fn serve(listen: &str, networks: Networks) -> Result<Serving> {
+    let server =
+        tiny_http::Server::http(listen).map_err(|err| eyre::eyre!("{}: {}", listen, err))?;
+    tracing::info!("listening on http://{}", server.server_addr());
+    let metrics = Arc::new(Metrics::default());
+    metrics.loaded(&networks);
+    let live = Arc::new(Live::new(networks));
+    let (http_live, http_metrics) = (Arc::clone(&live), Arc::clone(&metrics));
+    thread::spawn(move || {
+        crate::serve::serve_http(server, &http_live, &http_metrics, &MatchLog::default())
+    });
+    Ok(Serving { live, metrics })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command};
+    use std::fs;
+
+    #[test]
+    fn refresh_keeps_outputs_on_failure() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-daemon-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (v4, v6, out) = (dir.join("v4.csv"), dir.join("v6.csv"), dir.join("out.ts"));
+        fs::write(&v4, "network\n10.0.0.0/8\n").unwrap();
+        fs::write(&v6, "network\n2001:db8::/32\n").unwrap();
+        fs::write(&out, "stale code").unwrap();
+        let args = match Cli::parse_args([
+            "ipcheck".as_ref(),
+            "daemon".as_ref(),
+            "--refresh".as_ref(),
+            "@hourly".as_ref(),
+            "--reserved-policy=keep".as_ref(),
+            v4.as_os_str(),
+            v6.as_os_str(),
+            out.as_os_str(),
+        ])
+        .command
+        {
+            Command::Daemon(args) => args,
+            _ => panic!("not a daemon invocation"),
+        };
+
+        // The daemon owns its outputs and replaces them without --force.
+        let networks = refresh(&args.build).unwrap();
+        assert!(networks
+            .v4
+            .contains(&"10.1.2.3".parse::<std::net::Ipv4Addr>().unwrap()));
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(code.contains("IP_FILTER_V4"));
+
+        fs::write(&v4, "network\nnot a network\n").unwrap();
+        assert!(refresh(&args.build).is_err());
+        assert_eq!(fs::read_to_string(&out).unwrap(), code);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+//! `ipcheck diff`: what a change to a list adds and removes, for reviewing
+//! blocklist updates.
+//!
+//! Both lists are simplified first, so reordering rows or splitting and
+//! joining networks shows no difference; only the covered addresses count.
+
+use std::fmt::{Display, Write};
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use ipcheck_rs::{IpNet, IpRange};
+
+use crate::input::{load_input, Family, InputOptions, Networks, Report};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// The list before the change, with networks of either family.
+    old: PathBuf,
+
+    /// The list after the change.
+    new: PathBuf,
+
+    /// Print the difference as JSON, `{"added": [...], "removed": [...]}`,
+    /// rather than as `+` and `-` lines.
+    #[arg(long)]
+    json: bool,
+
+    #[command(flatten)]
+    input: InputOptions,
+}
+
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let options = InputOptions {
+        auto_family: true,
+        ..args.input.clone()
+    };
+    let mut report = Report::default();
+    let old = load_input(&args.old, Family::V4, &options, &mut report)?;
+    let new = load_input(&args.new, Family::V4, &options, &mut report)?;
+    for warning in report.warnings {
+        tracing::warn!("{}", warning);
+    }
+    let changes = Changes::between(&old, &new);
+    if args.json {
+        println!("{}", changes.json());
+    } else {
+        print!("{}", changes.lines());
+    }
+    Ok(())
+}
+
+/// The networks covering what one list has and the other lacks, IPv4
+/// first.
+#[derive(Debug, PartialEq)]
+struct Changes {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl Changes {
+    fn between(old: &Networks, new: &Networks) -> Changes {
+        Changes {
+            added: [difference(&new.v4, &old.v4), difference(&new.v6, &old.v6)].concat(),
+            removed: [difference(&old.v4, &new.v4), difference(&old.v6, &new.v6)].concat(),
+        }
+    }
+
+    fn lines(&self) -> String {
+        let mut out = String::new();
+        for network in &self.removed {
+            let _ = writeln!(out, "- {}", network);
+        }
+        for network in &self.added {
+            let _ = writeln!(out, "+ {}", network);
+        }
+        out
+    }
+
+    fn json(&self) -> String {
+        // Networks print without anything JSON would need escaped.
+        let array = |networks: &[String]| {
+            let quoted: Vec<String> = networks.iter().map(|net| format!("\"{}\"", net)).collect();
+            format!("[{}]", quoted.join(","))
+        };
+        format!(
+            "{{\"added\":{},\"removed\":{}}}",
+            array(&self.added),
+            array(&self.removed)
+        )
+    }
+}
+
+fn difference<N: IpNet + Display>(range: &IpRange<N>, other: &IpRange<N>) -> Vec<String> {
+    range
+        .exclude(other)
+        .iter()
+        .map(|net| net.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks(v4: &[&str], v6: &[&str]) -> Networks {
+        Networks {
+            v4: v4.iter().map(|s| s.parse().unwrap()).collect(),
+            v6: v6.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn changes_between_lists() {
+        let old = networks(&["10.0.0.0/8", "192.0.2.0/24"], &["2001:db8::/32"]);
+        let new = networks(
+            &["10.0.0.0/9", "10.128.0.0/9", "198.51.100.0/24"],
+            &["2001:db8::/33"],
+        );
+        let changes = Changes::between(&old, &new);
+        assert_eq!(
+            changes,
+            Changes {
+                added: vec!["198.51.100.0/24".to_string()],
+                removed: vec!["192.0.2.0/24".to_string(), "2001:db8:8000::/33".to_string()],
+            }
+        );
+        assert_eq!(
+            changes.lines(),
+            "- 192.0.2.0/24\n- 2001:db8:8000::/33\n+ 198.51.100.0/24\n"
+        );
+        assert_eq!(
+            changes.json(),
+            r#"{"added":["198.51.100.0/24"],"removed":["192.0.2.0/24","2001:db8:8000::/33"]}"#
+        );
+        assert_eq!(Changes::between(&new, &new).lines(), "");
+    }
+}
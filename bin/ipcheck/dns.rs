@@ -0,0 +1,427 @@
+//! Answering DNSBL queries for `ipcheck serve --dns-listen`, so that mail
+//! servers and other RBL clients can consume the networks over DNS.
+//!
+//! As described in RFC 5782, an address is looked up as its reversed
+//! octets (IPv4) or nibbles (IPv6) under the zone: with `--dns-zone
+//! bl.example.org`, `2.0.0.10.bl.example.org` asks about `10.0.0.2`. A
+//! listed address answers `A 127.0.0.2` and a `TXT` record naming the
+//! matching network; any other address answers `NXDOMAIN`. Queries outside
+//! the zone are refused.
+//!
+//! Only plain UDP queries are answered, which is what DNSBL clients send:
+//! the answers always fit in a 512-byte message.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+
+use clap::Args;
+use eyre::{Result, WrapErr};
+
+use crate::input::Networks;
+use crate::match_log::MatchLog;
+use crate::metrics::Metrics;
+use crate::reload::Live;
+use crate::serve::{lookup, Recorder};
+
+/// The address a listed query resolves to, by DNSBL convention.
+const LISTED: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 2);
+
+const TYPE_A: u16 = 1;
+const TYPE_TXT: u16 = 16;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+
+const NOERROR: u16 = 0;
+const FORMERR: u16 = 1;
+const NXDOMAIN: u16 = 3;
+const NOTIMP: u16 = 4;
+const REFUSED: u16 = 5;
+
+/// Options for the DNSBL listener, which shares the HTTP server's networks.
+#[derive(Args)]
+pub struct DnsArgs {
+    /// Also answer DNSBL queries over UDP on this address.
+    #[arg(long, requires = "dns_zone")]
+    dns_listen: Option<SocketAddr>,
+
+    /// Zone the reversed addresses are queried under, such as
+    /// `bl.example.org`.
+    #[arg(long, requires = "dns_listen")]
+    dns_zone: Option<String>,
+
+    /// Time to live of the answers, in seconds.
+    #[arg(long, default_value_t = 300)]
+    dns_ttl: u32,
+}
+
+impl DnsArgs {
+    pub fn enabled(&self) -> bool {
+        self.dns_listen.is_some()
+    }
+}
+
+/// The zone and TTL of the answers.
+struct Zone {
+    /// The zone's labels, lowercase.
+    labels: Vec<String>,
+    ttl: u32,
+}
+
+impl Zone {
+    fn new(name: &str, ttl: u32) -> Zone {
+        Zone {
+            labels: name
+                .trim_end_matches('.')
+                .split('.')
+                .filter(|label| !label.is_empty())
+                .map(|label| label.to_ascii_lowercase())
+                .collect(),
+            ttl,
+        }
+    }
+}
+
+/// Answers DNSBL queries on `--dns-listen` from a background thread.
+pub fn spawn(
+    args: &DnsArgs,
+    live: Arc<Live>,
+    metrics: Arc<Metrics>,
+    log: Arc<MatchLog>,
+) -> Result<()> {
+    let addr = args.dns_listen.expect("DNS serving is not enabled");
+    let zone = Zone::new(
+        args.dns_zone.as_deref().expect("clap requires --dns-zone"),
+        args.dns_ttl,
+    );
+    let socket = UdpSocket::bind(addr).wrap_err_with(|| addr.to_string())?;
+    tracing::info!("listening for DNS on {}", socket.local_addr()?);
+    thread::spawn(move || serve_udp(&socket, &zone, &live, &metrics, &log));
+    Ok(())
+}
+
+/// Answers queries arriving on `socket` until it fails.
+fn serve_udp(socket: &UdpSocket, zone: &Zone, live: &Live, metrics: &Metrics, log: &MatchLog) {
+    let mut buf = [0; 512];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) => {
+                tracing::warn!("failed to receive DNS query: {}", err);
+                continue;
+            }
+        };
+        // The peer is usually a resolver rather than the client itself.
+        let recorder = Recorder {
+            metrics,
+            log,
+            client: Some(peer),
+        };
+        if let Some(response) = answer(&buf[..len], zone, &live.get(), &recorder) {
+            if let Err(err) = socket.send_to(&response, peer) {
+                tracing::warn!("failed to send DNS response: {}", err);
+            }
+        }
+    }
+}
+
+/// The response to the message `query`, or `None` if it is not a query at
+/// all and is best ignored.
+fn answer(query: &[u8], zone: &Zone, networks: &Networks, recorder: &Recorder) -> Option<Vec<u8>> {
+    if query.len() < 12 {
+        return None;
+    }
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    if flags & 0x8000 != 0 {
+        // A response, not a query.
+        return None;
+    }
+    let mut response = Response::new(&query[..2], flags);
+    if flags & 0x7800 != 0 {
+        return Some(response.finish(NOTIMP));
+    }
+    let question_count = u16::from_be_bytes([query[4], query[5]]);
+    let question = match (question_count, parse_question(&query[12..])) {
+        (1, Some(question)) => question,
+        _ => return Some(response.finish(FORMERR)),
+    };
+    response.question(&query[12..12 + question.len]);
+    if question.class != CLASS_IN && question.class != CLASS_ANY {
+        return Some(response.finish(REFUSED));
+    }
+
+    let labels = &question.labels;
+    if labels.len() < zone.labels.len() || !labels.ends_with(&zone.labels) {
+        return Some(response.finish(REFUSED));
+    }
+    let reversed = &labels[..labels.len() - zone.labels.len()];
+    if reversed.is_empty() {
+        // The zone itself exists but holds no records of its own.
+        return Some(response.finish(NOERROR));
+    }
+    let addr = match reversed_address(reversed) {
+        Some(addr) => addr,
+        None => return Some(response.finish(NXDOMAIN)),
+    };
+    let result = lookup(&addr.to_string(), networks)?;
+    recorder.record(&result);
+    let network = match result.network {
+        Some(network) => network,
+        None => return Some(response.finish(NXDOMAIN)),
+    };
+    if matches!(question.kind, TYPE_A | TYPE_ANY) {
+        response.record(TYPE_A, zone.ttl, &LISTED.octets());
+    }
+    if matches!(question.kind, TYPE_TXT | TYPE_ANY) {
+        let text = format!("Listed in {}", network);
+        let mut data = vec![text.len() as u8];
+        data.extend_from_slice(text.as_bytes());
+        response.record(TYPE_TXT, zone.ttl, &data);
+    }
+    Some(response.finish(NOERROR))
+}
+
+/// The single question of a query.
+struct Question {
+    /// The name's labels, lowercase.
+    labels: Vec<String>,
+    kind: u16,
+    class: u16,
+    /// The length of the question in the message, in bytes.
+    len: usize,
+}
+
+fn parse_question(bytes: &[u8]) -> Option<Question> {
+    let mut labels = Vec::new();
+    let mut i = 0;
+    loop {
+        let len = *bytes.get(i)? as usize;
+        i += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers have no business in a query's question.
+        if len > 63 || i + len > 255 {
+            return None;
+        }
+        let label = bytes.get(i..i + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        i += len;
+    }
+    let fixed = bytes.get(i..i + 4)?;
+    Some(Question {
+        labels,
+        kind: u16::from_be_bytes([fixed[0], fixed[1]]),
+        class: u16::from_be_bytes([fixed[2], fixed[3]]),
+        len: i + 4,
+    })
+}
+
+/// The address queried by `labels`, reversed octets for IPv4 or reversed
+/// nibbles for IPv6.
+fn reversed_address(labels: &[String]) -> Option<IpAddr> {
+    match labels.len() {
+        4 => {
+            let mut octets = [0; 4];
+            for (octet, label) in octets.iter_mut().rev().zip(labels) {
+                if label.is_empty() || label.len() > 3 || !label.bytes().all(|b| b.is_ascii_digit())
+                {
+                    return None;
+                }
+                *octet = label.parse().ok()?;
+            }
+            Some(Ipv4Addr::from(octets).into())
+        }
+        32 => {
+            let mut bits = 0u128;
+            for label in labels.iter().rev() {
+                let mut chars = label.chars();
+                let nibble = chars.next()?.to_digit(16)?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                bits = bits << 4 | u128::from(nibble);
+            }
+            Some(Ipv6Addr::from(bits).into())
+        }
+        _ => None,
+    }
+}
+
+/// A response under construction, with at most one question.
+struct Response {
+    header: [u8; 12],
+    question: Vec<u8>,
+    answers: Vec<u8>,
+    answer_count: u16,
+}
+
+impl Response {
+    fn new(id: &[u8], query_flags: u16) -> Response {
+        let mut header = [0; 12];
+        header[..2].copy_from_slice(id);
+        // QR and AA set, opcode and RD copied from the query.
+        let flags = 0x8400 | query_flags & 0x7900;
+        header[2..4].copy_from_slice(&flags.to_be_bytes());
+        Response {
+            header,
+            question: Vec::new(),
+            answers: Vec::new(),
+            answer_count: 0,
+        }
+    }
+
+    fn question(&mut self, question: &[u8]) {
+        self.question = question.to_vec();
+        self.header[4..6].copy_from_slice(&1u16.to_be_bytes());
+    }
+
+    /// Adds a record for the question's name.
+    fn record(&mut self, kind: u16, ttl: u32, data: &[u8]) {
+        // A pointer to the name in the question, right after the header.
+        self.answers.extend_from_slice(&[0xc0, 12]);
+        self.answers.extend_from_slice(&kind.to_be_bytes());
+        self.answers.extend_from_slice(&CLASS_IN.to_be_bytes());
+        self.answers.extend_from_slice(&ttl.to_be_bytes());
+        self.answers
+            .extend_from_slice(&(data.len() as u16).to_be_bytes());
+        self.answers.extend_from_slice(data);
+        self.answer_count += 1;
+    }
+
+    fn finish(mut self, rcode: u16) -> Vec<u8> {
+        let flags = u16::from_be_bytes([self.header[2], self.header[3]]) | rcode;
+        self.header[2..4].copy_from_slice(&flags.to_be_bytes());
+        self.header[6..8].copy_from_slice(&self.answer_count.to_be_bytes());
+        let mut message = self.header.to_vec();
+        message.extend(self.question);
+        message.extend(self.answers);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn networks() -> Networks {
+        Networks {
+            v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    fn query(name: &str, kind: u16) -> Vec<u8> {
+        let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.push(0);
+        message.extend_from_slice(&kind.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+        message
+    }
+
+    /// The response code and answer count of `response`.
+    fn summary(response: &[u8]) -> (u16, u16) {
+        let flags = u16::from_be_bytes([response[2], response[3]]);
+        (flags & 0xf, u16::from_be_bytes([response[6], response[7]]))
+    }
+
+    fn ask(name: &str, kind: u16) -> Vec<u8> {
+        let zone = Zone::new("BL.example.org.", 60);
+        let (metrics, log) = (Metrics::default(), MatchLog::default());
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        answer(&query(name, kind), &zone, &networks(), &recorder).unwrap()
+    }
+
+    #[test]
+    fn listed_ipv4_answers_a_and_txt() {
+        let response = ask("4.3.2.10.bl.Example.org", TYPE_A);
+        assert_eq!(&response[..4], [0x12, 0x34, 0x85, 0x00]);
+        assert_eq!(summary(&response), (NOERROR, 1));
+        assert!(response.ends_with(&[0, 0, 0, 60, 0, 4, 127, 0, 0, 2]));
+
+        let response = ask("4.3.2.10.bl.example.org", TYPE_ANY);
+        assert_eq!(summary(&response), (NOERROR, 2));
+        assert!(response.ends_with(b"\x14Listed in 10.0.0.0/8"));
+    }
+
+    #[test]
+    fn listed_ipv6_by_nibbles() {
+        let name = "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.bl.example.org";
+        assert_eq!(summary(&ask(name, TYPE_A)), (NOERROR, 1));
+        let other = name.replace("8.b.d", "9.b.d");
+        assert_eq!(summary(&ask(&other, TYPE_A)), (NXDOMAIN, 0));
+    }
+
+    #[test]
+    fn unlisted_and_malformed_queries() {
+        assert_eq!(
+            summary(&ask("8.8.8.8.bl.example.org", TYPE_A)),
+            (NXDOMAIN, 0)
+        );
+        assert_eq!(summary(&ask("4.3.2.10.bl.example.org", 28)), (NOERROR, 0));
+        assert_eq!(summary(&ask("bl.example.org", TYPE_A)), (NOERROR, 0));
+        assert_eq!(
+            summary(&ask("x.3.2.10.bl.example.org", TYPE_A)),
+            (NXDOMAIN, 0)
+        );
+        assert_eq!(
+            summary(&ask("+4.3.2.10.bl.example.org", TYPE_A)),
+            (NXDOMAIN, 0)
+        );
+        assert_eq!(summary(&ask("4.3.2.10.example.com", TYPE_A)), (REFUSED, 0));
+        assert_eq!(summary(&ask("4.3.2.10.org", TYPE_A)), (REFUSED, 0));
+
+        let zone = Zone::new("bl.example.org", 60);
+        let (metrics, log) = (Metrics::default(), MatchLog::default());
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        let mut truncated = query("4.3.2.10.bl.example.org", TYPE_A);
+        truncated.truncate(20);
+        let response = answer(&truncated, &zone, &networks(), &recorder).unwrap();
+        assert_eq!(summary(&response), (FORMERR, 0));
+        assert!(answer(&truncated[..8], &zone, &networks(), &recorder).is_none());
+    }
+
+    #[test]
+    fn answer_over_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let live = Live::new(networks());
+        thread::spawn(move || {
+            serve_udp(
+                &server,
+                &Zone::new("bl.test", 60),
+                &live,
+                &Metrics::default(),
+                &MatchLog::default(),
+            )
+        });
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .send_to(&query("1.0.0.10.bl.test", TYPE_A), addr)
+            .unwrap();
+        let mut buf = [0; 512];
+        let len = client.recv(&mut buf).unwrap();
+        assert_eq!(summary(&buf[..len]), (NOERROR, 1));
+    }
+}
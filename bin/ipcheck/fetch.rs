@@ -0,0 +1,217 @@
+//! Reading inputs from `http://` and `https://` URLs, as most public
+//! blocklists are only published that way.
+//!
+//! Every download is cached, keyed by URL, in `IPCHECK_CACHE_DIR`, else
+//! `$XDG_CACHE_HOME/ipcheck`, else `~/.cache/ipcheck`. Later requests are
+//! conditional on the cached copy's `ETag` and `Last-Modified`, so an
+//! unchanged list is not downloaded again, and a request that fails falls
+//! back to the cached copy with a warning rather than failing the build.
+
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use eyre::{bail, eyre, Result, WrapErr};
+
+use crate::output;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `ETag` and `Last-Modified` headers a response was served with.
+type Validators = Vec<(String, String)>;
+
+/// Downloads the list at `url`, or reads the cached copy if it is
+/// unchanged or cannot be downloaded.
+pub fn get(url: &str) -> Result<Vec<u8>> {
+    fetch(url, &cache_dir())
+}
+
+fn cache_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("IPCHECK_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    let base = match (env::var_os("XDG_CACHE_HOME"), env::var_os("HOME")) {
+        (Some(cache), _) => PathBuf::from(cache),
+        (None, Some(home)) => Path::new(&home).join(".cache"),
+        (None, None) => env::temp_dir(),
+    };
+    base.join("ipcheck")
+}
+
+/// The cached copy of one URL: its body, and the validators it was served
+/// with.
+struct Cached {
+    body: PathBuf,
+    meta: PathBuf,
+}
+
+impl Cached {
+    fn of(url: &str, dir: &Path) -> Cached {
+        let key = format!("{:016x}", fnv1a(url.as_bytes()));
+        Cached {
+            body: dir.join(format!("{}.body", key)),
+            meta: dir.join(format!("{}.meta", key)),
+        }
+    }
+
+    /// The headers of the cached response, as `name: value` pairs.
+    fn validators(&self) -> Validators {
+        if !self.body.exists() {
+            return Vec::new();
+        }
+        let meta = fs::read_to_string(&self.meta).unwrap_or_default();
+        meta.lines()
+            .filter_map(|line| line.split_once(": "))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn store(&self, body: &[u8], validators: &[(String, String)]) -> Result<()> {
+        if let Some(dir) = self.body.parent() {
+            fs::create_dir_all(dir).wrap_err_with(|| dir.display().to_string())?;
+        }
+        let meta: String = validators
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect();
+        output::write_atomic(&self.body, body)?;
+        output::write_atomic(&self.meta, meta.as_bytes())
+    }
+}
+
+fn fetch(url: &str, dir: &Path) -> Result<Vec<u8>> {
+    let cached = Cached::of(url, dir);
+    let validators = cached.validators();
+    match download(url, &validators) {
+        Ok(Some((body, validators))) => {
+            if let Err(err) = cached.store(&body, &validators) {
+                tracing::warn!("failed to cache {}: {:#}", url, err);
+            }
+            Ok(body)
+        }
+        Ok(None) => fs::read(&cached.body).wrap_err_with(|| cached.body.display().to_string()),
+        Err(err) if cached.body.exists() => {
+            tracing::warn!(
+                "failed to fetch {}, using the copy cached at {}: {:#}",
+                url,
+                cached.body.display(),
+                err
+            );
+            fs::read(&cached.body).wrap_err_with(|| cached.body.display().to_string())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Requests `url`, conditional on `validators`: the body and validators of
+/// the response, or `None` if the cached copy is still current.
+fn download(url: &str, validators: &[(String, String)]) -> Result<Option<(Vec<u8>, Validators)>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let mut request = agent.get(url);
+    for (name, value) in validators {
+        match name.as_str() {
+            "ETag" => request = request.header("If-None-Match", value),
+            "Last-Modified" => request = request.header("If-Modified-Since", value),
+            _ => {}
+        }
+    }
+    let response = request
+        .call()
+        .map_err(|err| eyre!(err))
+        .wrap_err_with(|| format!("failed to fetch {}", url))?;
+    let status = response.status();
+    if status == 304 && !validators.is_empty() {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        bail!("failed to fetch {}: {}", url, status);
+    }
+    let validators = ["ETag", "Last-Modified"]
+        .iter()
+        .filter_map(|name| {
+            let value = response.headers().get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+    let mut body = Vec::new();
+    response
+        .into_body()
+        .into_reader()
+        .read_to_end(&mut body)
+        .wrap_err_with(|| format!("failed to fetch {}", url))?;
+    Ok(Some((body, validators)))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, naming cache entries.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers one request on `server` with `response`, returning the
+    /// request head.
+    fn answer(server: &TcpListener, response: &str) -> String {
+        let (conn, _) = server.accept().unwrap();
+        let mut reader = BufReader::new(conn.try_clone().unwrap());
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            head.push_str(&line);
+        }
+        (&conn).write_all(response.as_bytes()).unwrap();
+        head.to_ascii_lowercase()
+    }
+
+    #[test]
+    fn downloads_are_cached_and_revalidated() {
+        let dir = env::temp_dir().join(format!("ipcheck-fetch-{}", std::process::id()));
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v4.csv", server.local_addr().unwrap());
+
+        let client = {
+            let (url, dir) = (url.clone(), dir.clone());
+            thread::spawn(move || fetch(&url, &dir))
+        };
+        let head = answer(
+            &server,
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 19\r\nConnection: close\r\n\r\nnetwork\n10.0.0.0/8\n",
+        );
+        assert!(!head.contains("if-none-match"), "{}", head);
+        assert_eq!(client.join().unwrap().unwrap(), b"network\n10.0.0.0/8\n");
+
+        let client = {
+            let (url, dir) = (url.clone(), dir.clone());
+            thread::spawn(move || fetch(&url, &dir))
+        };
+        let head = answer(
+            &server,
+            "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n",
+        );
+        assert!(head.contains("if-none-match: \"v1\""), "{}", head);
+        assert_eq!(client.join().unwrap().unwrap(), b"network\n10.0.0.0/8\n");
+
+        // Once the server is gone, the cached copy stands in.
+        drop(server);
+        assert_eq!(fetch(&url, &dir).unwrap(), b"network\n10.0.0.0/8\n");
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(fetch(&url, &dir).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Firewall outputs: the networks of a build as sets a firewall loads, so
+//! that it filters the very networks the checkers do.
+//!
+//! The `nftables` backend writes an `nft -f` script next to the output,
+//! with the extension `.nft`. Its sets are named after the output, so
+//! `blocklist.ts` gives `blocklist_v4` and `blocklist_v6`, in the table
+//! `inet ipcheck`. Loading the script again replaces their elements, and
+//! leaves the table and any chains referring to the sets alone.
+//!
+//! The `ipset` backend writes the same sets in `ipset restore` syntax, with
+//! the extension `.ipset`, as `hash:net` sets for iptables. They are filled
+//! as temporary sets swapped in, so that rules matching them never see
+//! them empty, and restoring with `-exist` creates them the first time
+//! only. Their `maxelem` is rounded up to a power of two, so that it only
+//! changes, and the sets have to be destroyed first, when a list doubles.
+//!
+//! The `bpf` backend writes a C header of the keys of `LPM_TRIE` maps, for
+//! XDP and other eBPF filters, with the extension `.bpf.h`: a loader built
+//! on libbpf inserts each of them into the maps of the program.
+
+use std::fmt::{Display, Write};
+use std::path::{Path, PathBuf};
+
+use ipcheck_rs::backend::{Error, Filters, Output, OutputBackend};
+use ipcheck_rs::{IpNet, IpRange};
+
+/// The nftables table the sets are in.
+const NFT_TABLE: &str = "inet ipcheck";
+
+/// The `nft -f` script of the networks.
+pub struct Nftables;
+
+impl OutputBackend for Nftables {
+    fn name(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let set = set_name(filters.output);
+        let mut script = String::from("#!/usr/sbin/nft -f\n# Generated by ipcheck; do not edit.\n");
+        writeln!(script, "\nadd table {}", NFT_TABLE).unwrap();
+        nft_set(&mut script, &format!("{}_v4", set), "ipv4_addr", filters.v4);
+        nft_set(&mut script, &format!("{}_v6", set), "ipv6_addr", filters.v6);
+        Ok(vec![Output {
+            path: filters.output.with_extension("nft"),
+            contents: script.into_bytes(),
+        }])
+    }
+}
+
+/// The `ipset restore` script of the networks.
+pub struct Ipset;
+
+impl OutputBackend for Ipset {
+    fn name(&self) -> &'static str {
+        "ipset"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let mut set = set_name(filters.output);
+        // Set names are at most 31 bytes, and the temporary ones are 7
+        // longer.
+        set.truncate(IPSET_NAME_LEN - "_v4_new".len());
+        let mut script = String::from("# Generated by ipcheck; do not edit.\n");
+        ipset(&mut script, &format!("{}_v4", set), "inet", filters.v4);
+        ipset(&mut script, &format!("{}_v6", set), "inet6", filters.v6);
+        Ok(vec![Output {
+            path: filters.output.with_extension("ipset"),
+            contents: script.into_bytes(),
+        }])
+    }
+}
+
+/// The longest name of an ipset set.
+const IPSET_NAME_LEN: usize = 31;
+
+/// The smallest `maxelem` of a set, ipset's default.
+const IPSET_MAXELEM: usize = 65536;
+
+/// Creates the set `name` of `range`, filling a temporary set swapped in.
+fn ipset<N: IpNet + Display>(script: &mut String, name: &str, family: &str, range: &IpRange<N>) {
+    let networks = sorted(range);
+    let maxelem = networks.len().next_power_of_two().max(IPSET_MAXELEM);
+    let new = format!("{}_new", name);
+    for set in [name, &new] {
+        writeln!(
+            script,
+            "create {} hash:net family {} maxelem {}",
+            set, family, maxelem
+        )
+        .unwrap();
+    }
+    // A temporary set left over by a failed restore.
+    writeln!(script, "flush {}", new).unwrap();
+    for network in networks {
+        writeln!(script, "add {} {}", new, network).unwrap();
+    }
+    writeln!(script, "swap {} {}\ndestroy {}", new, name, new).unwrap();
+}
+
+/// The C header of the `LPM_TRIE` map keys of the networks.
+pub struct Bpf;
+
+/// What the `bpf` header declares, above its keys.
+const BPF_HEADER: &str = r#"#ifndef IPCHECK_LPM_H
+#define IPCHECK_LPM_H
+
+/* Generated by ipcheck; do not edit. */
+
+#include <linux/types.h>
+
+/*
+ * Keys of BPF_MAP_TYPE_LPM_TRIE maps of the networks, laid out as struct
+ * bpf_lpm_trie_key: the prefix length, then the address in network order.
+ * A BPF program declares such a map as
+ *
+ *     struct {
+ *         __uint(type, BPF_MAP_TYPE_LPM_TRIE);
+ *         __type(key, struct ipcheck_lpm_v4_key);
+ *         __type(value, __u8);
+ *         __uint(max_entries, 65536);
+ *         __uint(map_flags, BPF_F_NO_PREALLOC);
+ *     } blocklist_v4 SEC(".maps");
+ *
+ * with max_entries at least IPCHECK_LPM_V4_LEN, and its loader fills it by
+ * inserting the first IPCHECK_LPM_V4_LEN keys of IPCHECK_LPM_V4 with
+ * bpf_map_update_elem().
+ */
+struct ipcheck_lpm_v4_key {
+    __u32 prefixlen;
+    __u8 addr[4];
+};
+
+struct ipcheck_lpm_v6_key {
+    __u32 prefixlen;
+    __u8 addr[16];
+};
+"#;
+
+impl OutputBackend for Bpf {
+    fn name(&self) -> &'static str {
+        "bpf"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let mut header = BPF_HEADER.to_string();
+        let v4 = sorted(filters.v4).into_iter().map(|network| {
+            let addr = network.network().octets().map(|byte| byte.to_string());
+            (network.prefix_len(), addr.join(", "))
+        });
+        bpf_keys(&mut header, "V4", "v4", v4.collect());
+        let v6 = sorted(filters.v6).into_iter().map(|network| {
+            let addr = network
+                .network()
+                .octets()
+                .map(|byte| format!("{:#04x}", byte));
+            (network.prefix_len(), addr.join(", "))
+        });
+        bpf_keys(&mut header, "V6", "v6", v6.collect());
+        header.push_str("\n#endif  /* IPCHECK_LPM_H */\n");
+        Ok(vec![Output {
+            path: bpf_path(filters.output),
+            contents: header.into_bytes(),
+        }])
+    }
+}
+
+/// Declares the keys of one family, their prefix lengths and addresses.
+fn bpf_keys(header: &mut String, family: &str, key: &str, keys: Vec<(u8, String)>) {
+    writeln!(
+        header,
+        "\n#define IPCHECK_LPM_{family}_LEN {}\nstatic const struct ipcheck_lpm_{key}_key IPCHECK_LPM_{family}[] = {{",
+        keys.len()
+    )
+    .unwrap();
+    if keys.is_empty() {
+        // C has no empty initializers before C23.
+        header.push_str("    { 0 },\n");
+    }
+    for (prefix_len, addr) in keys {
+        writeln!(header, "    {{ {}, {{ {} }} }},", prefix_len, addr).unwrap();
+    }
+    header.push_str("};\n");
+}
+
+/// Where the `bpf` header of a build writing `output` goes: `output` with
+/// the extension `.bpf.h`.
+fn bpf_path(output: &Path) -> PathBuf {
+    match output.to_string_lossy().ends_with(".bpf.h") {
+        true => output.to_path_buf(),
+        false => output.with_extension("bpf.h"),
+    }
+}
+
+/// Declares the set `name` of `range`, replacing its elements.
+fn nft_set<N: IpNet + Display>(script: &mut String, name: &str, kind: &str, range: &IpRange<N>) {
+    writeln!(
+        script,
+        "\nadd set {table} {name} {{ type {kind}; flags interval; }}\nflush set {table} {name}",
+        table = NFT_TABLE,
+    )
+    .unwrap();
+    let networks = sorted(range);
+    if networks.is_empty() {
+        // `add element` takes at least one.
+        return;
+    }
+    let elements: Vec<String> = networks.iter().map(N::to_string).collect();
+    writeln!(
+        script,
+        "add element {} {} {{\n\t{}\n}}",
+        NFT_TABLE,
+        name,
+        elements.join(",\n\t")
+    )
+    .unwrap();
+}
+
+/// The networks of `range`, in address order.
+pub fn sorted<N: IpNet>(range: &IpRange<N>) -> Vec<N> {
+    let mut networks: Vec<N> = range.iter().collect();
+    networks.sort_unstable();
+    networks
+}
+
+/// The stem of the sets of a build writing `output`: its file stem as an
+/// nftables identifier, else `ipcheck`.
+pub fn set_name(output: &Path) -> String {
+    let stem = output.file_stem().unwrap_or_default();
+    let name: String = stem
+        .to_string_lossy()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .skip_while(|c| !c.is_ascii_alphabetic())
+        .collect();
+    match name.is_empty() {
+        true => "ipcheck".to_string(),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nftables_script() {
+        let v4: IpRange<_> = ["192.0.2.0/24", "10.0.0.0/8"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("out/drop-list.ts");
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &IpRange::new(),
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Nftables.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("out/drop-list.nft"));
+        assert_eq!(
+            String::from_utf8(outputs[0].contents.clone()).unwrap(),
+            "#!/usr/sbin/nft -f\n\
+             # Generated by ipcheck; do not edit.\n\
+             \n\
+             add table inet ipcheck\n\
+             \n\
+             add set inet ipcheck drop_list_v4 { type ipv4_addr; flags interval; }\n\
+             flush set inet ipcheck drop_list_v4\n\
+             add element inet ipcheck drop_list_v4 {\n\
+             \t10.0.0.0/8,\n\
+             \t192.0.2.0/24\n\
+             }\n\
+             \n\
+             add set inet ipcheck drop_list_v6 { type ipv6_addr; flags interval; }\n\
+             flush set inet ipcheck drop_list_v6\n"
+        );
+        assert_eq!(set_name(Path::new("2024.nft")), "ipcheck");
+    }
+
+    #[test]
+    fn bpf_header() {
+        let v4: IpRange<_> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("xdp/blocklist.ts");
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Bpf.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("xdp/blocklist.bpf.h"));
+        let header = String::from_utf8(outputs[0].contents.clone()).unwrap();
+        assert!(
+            header.contains(concat!(
+                "\n#define IPCHECK_LPM_V4_LEN 1\n",
+                "static const struct ipcheck_lpm_v4_key IPCHECK_LPM_V4[] = {\n",
+                "    { 8, { 10, 0, 0, 0 } },\n",
+                "};\n"
+            )),
+            "{}",
+            header
+        );
+        assert!(
+            header.contains("    { 32, { 0x20, 0x01, 0x0d, 0xb8, 0x00, "),
+            "{}",
+            header
+        );
+        assert_eq!(
+            bpf_path(Path::new("blocklist.bpf.h")),
+            Path::new("blocklist.bpf.h")
+        );
+    }
+
+    #[test]
+    fn ipset_script() {
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("a-rather-long-name-for-a-blocklist.ts");
+        let filters = Filters {
+            output: &output,
+            v4: &IpRange::new(),
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Ipset.render(&filters).unwrap();
+        assert_eq!(
+            outputs[0].path,
+            Path::new("a-rather-long-name-for-a-blocklist.ipset")
+        );
+        assert_eq!(
+            String::from_utf8(outputs[0].contents.clone()).unwrap(),
+            "# Generated by ipcheck; do not edit.\n\
+             create a_rather_long_name_for_a_v4 hash:net family inet maxelem 65536\n\
+             create a_rather_long_name_for_a_v4_new hash:net family inet maxelem 65536\n\
+             flush a_rather_long_name_for_a_v4_new\n\
+             swap a_rather_long_name_for_a_v4_new a_rather_long_name_for_a_v4\n\
+             destroy a_rather_long_name_for_a_v4_new\n\
+             create a_rather_long_name_for_a_v6 hash:net family inet6 maxelem 65536\n\
+             create a_rather_long_name_for_a_v6_new hash:net family inet6 maxelem 65536\n\
+             flush a_rather_long_name_for_a_v6_new\n\
+             add a_rather_long_name_for_a_v6_new 2001:db8::/32\n\
+             swap a_rather_long_name_for_a_v6_new a_rather_long_name_for_a_v6\n\
+             destroy a_rather_long_name_for_a_v6_new\n"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Annotating networks with the countries and ASNs of MaxMind databases,
+//! so that reviewers can see what a list actually covers.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::{Result, WrapErr};
+use ipnetwork::IpNetwork;
+use maxminddb::{MaxMindDBError, Reader};
+use serde::Deserialize;
+
+use crate::input::Networks;
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct GeoipArgs {
+    /// Annotate the networks with the countries and ASNs of this MaxMind
+    /// database (GeoIP2/GeoLite2 Country, City or ASN). Repeat to combine
+    /// several. With `--target lookup`, the countries are the values looked
+    /// up.
+    #[arg(long = "geoip", value_name = "MMDB")]
+    databases: Vec<PathBuf>,
+}
+
+/// What the databases know about one network.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Geo {
+    /// ISO country codes of the addresses in the network.
+    pub countries: BTreeSet<String>,
+    /// Autonomous system numbers of the addresses in the network.
+    pub asns: BTreeSet<u32>,
+}
+
+impl Geo {
+    pub fn is_empty(&self) -> bool {
+        self.countries.is_empty() && self.asns.is_empty()
+    }
+
+    /// The countries, comma-separated, or `None` if unknown.
+    pub fn country(&self) -> Option<String> {
+        join(self.countries.iter())
+    }
+
+    /// The ASNs, comma-separated, or `None` if unknown.
+    pub fn asn(&self) -> Option<String> {
+        join(self.asns.iter().map(|asn| format!("AS{}", asn)))
+    }
+}
+
+impl fmt::Display for Geo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.country().into_iter().chain(self.asn()).collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+fn join<T: ToString>(items: impl Iterator<Item = T>) -> Option<String> {
+    let items: Vec<String> = items.map(|item| item.to_string()).collect();
+    if items.is_empty() {
+        None
+    } else {
+        Some(items.join(","))
+    }
+}
+
+/// The fields read from any database; the others are ignored.
+#[derive(Deserialize)]
+struct Record<'a> {
+    #[serde(borrow)]
+    country: Option<Country<'a>>,
+    autonomous_system_number: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Country<'a> {
+    iso_code: Option<&'a str>,
+}
+
+impl GeoipArgs {
+    /// Annotates every network that the databases know something about,
+    /// keyed by its CIDR string. Without `--geoip` nothing is annotated.
+    pub fn annotate(&self, networks: &Networks) -> Result<HashMap<String, Geo>> {
+        let mut notes: HashMap<String, Geo> = HashMap::new();
+        for path in &self.databases {
+            let reader = Reader::open_readfile(path)
+                .map_err(|err| eyre::eyre!("{}: {}", path.display(), err))?;
+            let mut add = |network: String, first: IpAddr, prefix: u8| -> Result<()> {
+                let geo = lookup(&reader, first, prefix)
+                    .map_err(|err| eyre::eyre!("{}: {}", path.display(), err))
+                    .wrap_err_with(|| format!("failed to look up {}", network))?;
+                if !geo.is_empty() {
+                    let note = notes.entry(network).or_default();
+                    note.countries.extend(geo.countries);
+                    note.asns.extend(geo.asns);
+                }
+                Ok(())
+            };
+            for network in networks.v4.iter() {
+                add(
+                    network.to_string(),
+                    network.network().into(),
+                    network.prefix_len(),
+                )?;
+            }
+            // An IPv4 database has nothing to say about IPv6 networks.
+            if reader.metadata.ip_version == 6 {
+                for network in networks.v6.iter() {
+                    add(
+                        network.to_string(),
+                        network.network().into(),
+                        network.prefix_len(),
+                    )?;
+                }
+            }
+        }
+        Ok(notes)
+    }
+}
+
+/// Collects what `reader` knows about the network `first/prefix`.
+fn lookup(reader: &Reader<Vec<u8>>, first: IpAddr, prefix: u8) -> Result<Geo, MaxMindDBError> {
+    let mut geo = Geo::default();
+    let mut add = |record: Record| {
+        if let Some(code) = record.country.and_then(|country| country.iso_code) {
+            geo.countries.insert(code.to_string());
+        }
+        if let Some(asn) = record.autonomous_system_number {
+            geo.asns.insert(asn);
+        }
+    };
+
+    // `within` visits the database's blocks inside the network, and nothing
+    // when the network lies inside a single block.
+    let network = IpNetwork::new(first, prefix)
+        .map_err(|err| MaxMindDBError::InvalidNetworkError(err.to_string()))?;
+    let mut found = false;
+    for item in reader.within::<Record>(network)? {
+        add(item?.info);
+        found = true;
+    }
+    if !found {
+        match reader.lookup::<Record>(first) {
+            Ok(record) => add(record),
+            Err(MaxMindDBError::AddressNotFoundError(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(geo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Encodes an MMDB data field: `kind` is the MaxMind type number.
+    fn field(kind: u8, payload: &[u8], size: usize) -> Vec<u8> {
+        assert!(size < 29);
+        let mut out = if kind < 8 {
+            vec![kind << 5 | size as u8]
+        } else {
+            vec![size as u8, kind - 7]
+        };
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        field(2, value.as_bytes(), value.len())
+    }
+
+    fn uint(kind: u8, value: u64) -> Vec<u8> {
+        let bytes = value.to_be_bytes();
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(8);
+        field(kind, &bytes[start..], 8 - start)
+    }
+
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = field(7, &[], entries.len());
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// Writes an IPv4 MaxMind database mapping each network to a country
+    /// code and an ASN.
+    fn write_mmdb(path: &std::path::Path, blocks: &[(&str, &str, u32)]) {
+        // A binary trie of the blocks: children are node indices, and a
+        // block's last bit points at data instead.
+        #[derive(Clone, Copy)]
+        enum Child {
+            Empty,
+            Node(usize),
+            Data(usize),
+        }
+        let mut nodes = vec![[Child::Empty, Child::Empty]];
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for (i, (network, country, asn)) in blocks.iter().enumerate() {
+            let network: ipnet::Ipv4Net = network.parse().unwrap();
+            let bits = u32::from(network.network());
+            offsets.push(data.len());
+            data.extend(map(&[
+                ("autonomous_system_number", uint(6, *asn as u64)),
+                ("country", map(&[("iso_code", string(country))])),
+            ]));
+            let mut node = 0;
+            for depth in 0..network.prefix_len() {
+                let bit = (bits >> (31 - depth) & 1) as usize;
+                if depth + 1 == network.prefix_len() {
+                    nodes[node][bit] = Child::Data(i);
+                } else {
+                    node = match nodes[node][bit] {
+                        Child::Node(next) => next,
+                        _ => {
+                            nodes.push([Child::Empty, Child::Empty]);
+                            nodes[node][bit] = Child::Node(nodes.len() - 1);
+                            nodes.len() - 1
+                        }
+                    };
+                }
+            }
+        }
+
+        let node_count = nodes.len();
+        let mut out = Vec::new();
+        for node in &nodes {
+            for child in node {
+                let record = match *child {
+                    Child::Empty => node_count,
+                    Child::Node(next) => next,
+                    Child::Data(i) => node_count + 16 + offsets[i],
+                };
+                out.extend_from_slice(&(record as u32).to_be_bytes()[1..]);
+            }
+        }
+        out.extend_from_slice(&[0; 16]);
+        out.extend(data);
+        out.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        out.extend(map(&[
+            ("binary_format_major_version", uint(5, 2)),
+            ("binary_format_minor_version", uint(5, 0)),
+            ("build_epoch", uint(9, 0)),
+            ("database_type", string("ipcheck-test")),
+            ("description", map(&[])),
+            ("ip_version", uint(5, 4)),
+            ("languages", field(11, &[], 0)),
+            ("node_count", uint(6, node_count as u64)),
+            ("record_size", uint(5, 24)),
+        ]));
+        fs::write(path, out).unwrap();
+    }
+
+    #[test]
+    fn annotate_networks() {
+        let path = std::env::temp_dir().join(format!("ipcheck-geoip-{}.mmdb", std::process::id()));
+        write_mmdb(
+            &path,
+            &[
+                ("10.0.0.0/9", "US", 64500),
+                ("10.128.0.0/9", "DE", 64501),
+                ("192.168.0.0/16", "FR", 64502),
+            ],
+        );
+        let networks = Networks {
+            v4: ["10.0.0.0/8", "192.168.1.0/24", "8.8.8.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        };
+        let args = GeoipArgs {
+            databases: vec![path.clone()],
+        };
+        let notes = args.annotate(&networks).unwrap();
+
+        // A network spanning several blocks gets all of them.
+        assert_eq!(notes["10.0.0.0/8"].to_string(), "DE,US AS64500,AS64501");
+        // One inside a single block gets that block.
+        assert_eq!(notes["192.168.1.0/24"].country().as_deref(), Some("FR"));
+        assert_eq!(notes["192.168.1.0/24"].asn().as_deref(), Some("AS64502"));
+        assert!(!notes.contains_key("8.8.8.0/24"));
+        assert!(!notes.contains_key("2001:db8::/32"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_databases_no_notes() {
+        let networks = Networks::default();
+        assert!(GeoipArgs::default().annotate(&networks).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! `--format geolite2-country`: the networks of chosen countries in
+//! MaxMind's GeoLite2 Country CSV database, the most common source of
+//! country allow and deny lists.
+//!
+//! The database holds a blocks file per family,
+//! `GeoLite2-Country-Blocks-IPv4.csv` and `GeoLite2-Country-Blocks-IPv6.csv`,
+//! given as the lists, and a locations file naming the country of every
+//! `geoname_id` the blocks refer to. The locations file is looked for beside
+//! each blocks file unless `--geolite2-locations` gives it.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use csv::StringRecord;
+use eyre::{bail, eyre, Result, WrapErr};
+
+use crate::storage;
+
+/// The parser name selecting this format.
+pub const FORMAT: &str = "geolite2-country";
+
+/// The locations file MaxMind ships beside the blocks files, in English.
+const LOCATIONS: &str = "GeoLite2-Country-Locations-en.csv";
+
+/// Where the locations of a GeoLite2 Country database are.
+#[derive(Args, Clone, Debug)]
+pub struct Geolite2Options {
+    /// With `--format geolite2-country`, the locations file. By
+    /// default, `GeoLite2-Country-Locations-en.csv` beside each blocks file.
+    #[arg(long, value_name = "PATH")]
+    pub geolite2_locations: Option<PathBuf>,
+}
+
+impl Geolite2Options {
+    /// The ISO codes of `countries` by their `geoname_id`s, from the
+    /// locations file of the blocks file at `blocks`.
+    pub fn countries(
+        &self,
+        blocks: &Path,
+        countries: &[String],
+    ) -> Result<HashMap<String, String>> {
+        if countries.is_empty() {
+            bail!(
+                "{}: --format {} needs --countries, such as --countries CN,RU",
+                blocks.display(),
+                FORMAT
+            );
+        }
+        let path = match &self.geolite2_locations {
+            Some(path) => path.clone(),
+            None => blocks.with_file_name(LOCATIONS),
+        };
+        let input = storage::open(&path).wrap_err_with(|| {
+            format!(
+                "{}: failed to open the GeoLite2 locations (pass --geolite2-locations to give them)",
+                blocks.display()
+            )
+        })?;
+        geoname_ids(&path, input, countries)
+    }
+}
+
+/// The codes of `countries` by the `geoname_id`s that the locations in
+/// `input`, read from `path`, give them, failing if any country has none.
+fn geoname_ids(
+    path: &Path,
+    input: impl Read,
+    countries: &[String],
+) -> Result<HashMap<String, String>> {
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = reader.headers()?.clone();
+    let id = column(path, &headers, "geoname_id")?;
+    let code = column(path, &headers, "country_iso_code")?;
+
+    let countries = country_codes(countries);
+    let mut found = HashSet::new();
+    let mut ids = HashMap::new();
+    for record in reader.records() {
+        let record = record.wrap_err_with(|| path.display().to_string())?;
+        let country = record.get(code).unwrap_or("");
+        if let Some(country) = countries.iter().find(|c| c.as_str() == country) {
+            found.insert(country);
+            ids.insert(record.get(id).unwrap_or("").to_string(), country.clone());
+        }
+    }
+    let missing: Vec<&str> = countries
+        .iter()
+        .filter(|country| !found.contains(country))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "{}: no location is in {} (countries are given by ISO code, such as CN)",
+            path.display(),
+            missing.join(", ")
+        );
+    }
+    Ok(ids)
+}
+
+/// `countries` as the upper-case codes the databases use.
+pub fn country_codes(countries: &[String]) -> Vec<String> {
+    countries
+        .iter()
+        .map(|country| country.trim().to_ascii_uppercase())
+        .filter(|country| !country.is_empty())
+        .collect()
+}
+
+/// The index of the column named `name`, which a GeoLite2 file has.
+pub fn column(path: &Path, headers: &StringRecord, name: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == name)
+        .ok_or_else(|| {
+            eyre!(
+                "{}: no {:?} column, so not a GeoLite2 Country CSV file",
+                path.display(),
+                name
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCATIONS_CSV: &str = "\
+geoname_id,locale_code,continent_code,continent_name,country_iso_code,country_name,is_in_european_union
+1814991,en,AS,Asia,CN,China,0
+2017370,en,EU,Europe,RU,Russia,0
+2921044,en,EU,Europe,DE,Germany,1
+6255148,en,EU,Europe,,,0
+";
+
+    #[test]
+    fn geoname_ids_of_countries() {
+        let ids = geoname_ids(
+            Path::new("locations.csv"),
+            LOCATIONS_CSV.as_bytes(),
+            &["cn".to_string(), "RU".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            ids,
+            HashMap::from([
+                ("1814991".into(), "CN".into()),
+                ("2017370".into(), "RU".into())
+            ])
+        );
+
+        let err = geoname_ids(
+            Path::new("locations.csv"),
+            LOCATIONS_CSV.as_bytes(),
+            &["DE".to_string(), "XX".to_string()],
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "locations.csv: no location is in XX (countries are given by ISO code, such as CN)"
+        );
+
+        let err = geoname_ids(
+            Path::new("blocks.csv"),
+            "network,geoname_id\n".as_bytes(),
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "blocks.csv: no \"country_iso_code\" column, so not a GeoLite2 Country CSV file"
+        );
+    }
+}
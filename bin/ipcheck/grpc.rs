@@ -0,0 +1,207 @@
+//! The gRPC variant of `ipcheck serve`, defined by `proto/ipcheck.proto`.
+
+// tonic's Status is large, but it is the error type of every gRPC handler.
+#![allow(clippy::result_large_err)]
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use clap::Args;
+use eyre::{Result, WrapErr};
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::match_log::MatchLog;
+use crate::metrics::Metrics;
+use crate::reload::Live;
+use crate::serve::{lookup, Recorder};
+
+mod proto {
+    tonic::include_proto!("ipcheck.v1");
+}
+
+use proto::ip_check_server::{IpCheck, IpCheckServer};
+use proto::{IpAddress, Match};
+
+/// Options for the gRPC listener, which shares the HTTP server's networks.
+#[derive(Args)]
+pub struct GrpcArgs {
+    /// Also answer gRPC lookups on this address.
+    #[arg(long)]
+    grpc_listen: Option<SocketAddr>,
+
+    /// PEM certificate chain served to gRPC clients, enabling TLS.
+    #[arg(long, requires_all = ["grpc_listen", "tls_key"])]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key for --tls-cert.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// PEM CA certificates that gRPC clients must present a certificate
+    /// from (mutual TLS).
+    #[arg(long, requires = "tls_cert")]
+    client_ca: Option<PathBuf>,
+}
+
+impl GrpcArgs {
+    pub fn enabled(&self) -> bool {
+        self.grpc_listen.is_some()
+    }
+}
+
+struct IpCheckService {
+    live: Arc<Live>,
+    metrics: Arc<Metrics>,
+    log: Arc<MatchLog>,
+}
+
+impl IpCheckService {
+    fn check_one(&self, address: IpAddress, client: Option<SocketAddr>) -> Result<Match, Status> {
+        let result = lookup(&address.ip, &self.live.get()).ok_or_else(|| {
+            Status::invalid_argument(format!("{:?} is not an IP address", address.ip))
+        })?;
+        let recorder = Recorder {
+            metrics: &self.metrics,
+            log: &self.log,
+            client,
+        };
+        recorder.record(&result);
+        Ok(Match {
+            ip: result.ip,
+            matches: result.matches,
+            network: result.network.unwrap_or_default(),
+        })
+    }
+}
+
+type MatchStream = Pin<Box<dyn Stream<Item = Result<Match, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl IpCheck for Arc<IpCheckService> {
+    async fn check(&self, request: Request<IpAddress>) -> Result<Response<Match>, Status> {
+        let client = request.remote_addr();
+        self.check_one(request.into_inner(), client)
+            .map(Response::new)
+    }
+
+    type CheckStreamStream = MatchStream;
+
+    async fn check_stream(
+        &self,
+        request: Request<Streaming<IpAddress>>,
+    ) -> Result<Response<MatchStream>, Status> {
+        let service = Arc::clone(self);
+        let client = request.remote_addr();
+        let matches = request
+            .into_inner()
+            .map(move |address| service.check_one(address?, client));
+        Ok(Response::new(Box::pin(matches)))
+    }
+}
+
+/// Serves gRPC lookups on `--grpc-listen` until the server fails.
+pub fn serve(
+    args: &GrpcArgs,
+    live: Arc<Live>,
+    metrics: Arc<Metrics>,
+    log: Arc<MatchLog>,
+) -> Result<()> {
+    let addr = args.grpc_listen.expect("gRPC serving is not enabled");
+    let mut server = Server::builder();
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(
+            fs::read(cert).wrap_err_with(|| cert.display().to_string())?,
+            fs::read(key).wrap_err_with(|| key.display().to_string())?,
+        ));
+        if let Some(ca) = &args.client_ca {
+            let ca = fs::read(ca).wrap_err_with(|| ca.display().to_string())?;
+            tls = tls.client_ca_root(Certificate::from_pem(ca));
+        }
+        server = server.tls_config(tls)?;
+    }
+
+    let service = IpCheckServer::new(Arc::new(IpCheckService { live, metrics, log }));
+    let runtime = tokio::runtime::Runtime::new()?;
+    tracing::info!("listening for gRPC on {}", addr);
+    runtime.block_on(server.add_service(service).serve(addr))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Networks;
+    use proto::ip_check_client::IpCheckClient;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    fn service() -> Arc<IpCheckService> {
+        Arc::new(IpCheckService {
+            live: Arc::new(Live::new(Networks {
+                v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+                v6: ["2001:db8::/32"]
+                    .iter()
+                    .map(|s| s.parse().unwrap())
+                    .collect(),
+            })),
+            metrics: Arc::default(),
+            log: Arc::default(),
+        })
+    }
+
+    fn address(ip: &str) -> IpAddress {
+        IpAddress { ip: ip.to_string() }
+    }
+
+    #[test]
+    fn check_single_addresses() {
+        let service = service();
+        assert_eq!(
+            service.check_one(address("10.9.9.9"), None).unwrap(),
+            Match {
+                ip: "10.9.9.9".to_string(),
+                matches: true,
+                network: "10.0.0.0/8".to_string(),
+            }
+        );
+        assert!(
+            !service
+                .check_one(address("2001:db9::1"), None)
+                .unwrap()
+                .matches
+        );
+        let status = service.check_one(address("not an ip"), None).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn check_over_the_wire() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(IpCheckServer::new(service()))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = IpCheckClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        let single = client.check(address("10.1.1.1")).await.unwrap();
+        assert_eq!(single.into_inner().network, "10.0.0.0/8");
+
+        let requests = tokio_stream::iter(["2001:db8::1", "8.8.8.8", "10.0.0.1"].map(address));
+        let responses: Vec<Match> = client
+            .check_stream(requests)
+            .await
+            .unwrap()
+            .into_inner()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        let matches: Vec<bool> = responses.iter().map(|m| m.matches).collect();
+        assert_eq!(matches, [true, false, true]);
+    }
+}
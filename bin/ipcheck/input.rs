@@ -0,0 +1,1798 @@
+//! Loading CIDR lists from CSV files, or from feeds in the formats of the
+//! library's [`source`] registry.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::str::FromStr;
+
+use clap::Args;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use eyre::{bail, eyre, Result, WrapErr};
+use ipcheck_rs::backend::Input;
+use ipcheck_rs::{source, IpRange};
+use ipnet::{IpNet as AnyNet, Ipv4Net, Ipv6Net};
+
+use crate::cloud::{self, CloudOptions};
+use crate::compression;
+use crate::geolite2::{self, Geolite2Options};
+use crate::limits::InputLimits;
+use crate::lines;
+use crate::log;
+use crate::mrt::{self, MrtOptions};
+use crate::parse::{parse_network, LeadingZeros, ParseError, Value};
+use crate::paths::Slot;
+use crate::resolve::{self, ResolveOptions};
+use crate::rir;
+use crate::sha256::Hashing;
+use crate::storage;
+use crate::telemetry;
+
+/// Number of leading records inspected to make sure the selected column
+/// actually holds networks.
+const SAMPLE_SIZE: usize = 16;
+
+/// The address family an input file is meant to hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Family::V4 => "IPv4",
+            Family::V6 => "IPv6",
+        })
+    }
+}
+
+/// The IPv4 and IPv6 lists, given by flag or as the leading positional
+/// paths of the command.
+#[derive(Args, Clone, Debug)]
+pub struct Lists {
+    /// CSV file with IPv4 networks in the first column. Repeat it to merge
+    /// several lists.
+    #[arg(long = "v4", value_name = "PATH")]
+    v4: Vec<PathBuf>,
+    /// CSV file with IPv6 networks in the first column. Repeat it to merge
+    /// several lists.
+    #[arg(long = "v6", value_name = "PATH")]
+    v6: Vec<PathBuf>,
+    /// List of IPv4 networks to remove from the IPv4 lists, such as an
+    /// allowlist. Repeat it to exclude several lists.
+    #[arg(long = "exclude-v4", value_name = "PATH")]
+    exclude_v4: Vec<PathBuf>,
+    /// List of IPv6 networks to remove from the IPv6 lists.
+    #[arg(long = "exclude-v6", value_name = "PATH")]
+    exclude_v6: Vec<PathBuf>,
+    /// The IPv4 and IPv6 lists given by position instead.
+    #[arg(skip)]
+    positional: [Option<PathBuf>; 2],
+}
+
+impl Lists {
+    /// Lists given other than on the command line.
+    #[cfg_attr(not(feature = "config"), allow(dead_code))]
+    pub fn new(
+        v4: Vec<PathBuf>,
+        v6: Vec<PathBuf>,
+        exclude_v4: Vec<PathBuf>,
+        exclude_v6: Vec<PathBuf>,
+    ) -> Lists {
+        Lists {
+            v4,
+            v6,
+            exclude_v4,
+            exclude_v6,
+            positional: [None, None],
+        }
+    }
+
+    /// The slots the positional paths fill: those of the families without
+    /// a flag. Once one family is given by flag, the other may be left out.
+    pub fn slots(&mut self) -> Vec<Slot<'_>> {
+        let [v4, v6] = &mut self.positional;
+        let slot = |path, flag, other: &[PathBuf]| match other.is_empty() {
+            true => Slot::required(path, flag),
+            false => Slot::optional(path, flag),
+        };
+        let mut slots = Vec::new();
+        if self.v4.is_empty() {
+            slots.push(slot(v4, "--v4", &self.v6));
+        }
+        if self.v6.is_empty() {
+            slots.push(slot(v6, "--v6", &self.v4));
+        }
+        slots
+    }
+
+    pub fn v4(&self) -> &[PathBuf] {
+        match &self.positional[0] {
+            Some(path) => slice::from_ref(path),
+            None => &self.v4,
+        }
+    }
+
+    pub fn v6(&self) -> &[PathBuf] {
+        match &self.positional[1] {
+            Some(path) => slice::from_ref(path),
+            None => &self.v6,
+        }
+    }
+
+    pub fn exclude_v4(&self) -> &[PathBuf] {
+        &self.exclude_v4
+    }
+
+    pub fn exclude_v6(&self) -> &[PathBuf] {
+        &self.exclude_v6
+    }
+
+    /// Every list and exclusion list.
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        let lists = self.v4().iter().chain(self.v6());
+        lists.chain(&self.exclude_v4).chain(&self.exclude_v6)
+    }
+}
+
+/// Options controlling how input files are read.
+#[derive(Args, Clone, Debug)]
+pub struct InputOptions {
+    /// Read the inputs with this parser: `csv`, `json`, `geolite2-country`,
+    /// `rir-delegated`, `aws`, `azure`, `cloudflare`, `gcp`, `mrt`, or a
+    /// registered one such as `lines` (also `text` or `firehol`: one network
+    /// or address per line, with `#` comments, as in FireHOL's `.netset`
+    /// and `.ipset` files), `nftables`, `iptables-save`, `ipset`, `spamhaus`
+    /// or `artifact`. By default, `delegated-*` files are read as RIR
+    /// delegations, files named as the cloud providers publish them as
+    /// theirs, inputs a registered parser recognizes with it, MRT dumps as
+    /// such, other `.json` files as JSON, and the others as CSV.
+    #[arg(long = "format", alias = "input-format", value_name = "FORMAT")]
+    pub input_format: Option<String>,
+
+    /// Route networks found in the other family's input to the right filter
+    /// instead of failing.
+    #[arg(long)]
+    pub auto_family: bool,
+
+    /// How to read IPv4 octets and prefix lengths with leading zeros, such
+    /// as `010.1.1.1/8`.
+    #[arg(long, value_enum, default_value_t)]
+    pub leading_zeros: LeadingZeros,
+
+    /// Skip CSV rows and list lines that cannot be read, warning about
+    /// each, instead of failing at the first.
+    #[arg(long, overrides_with = "strict")]
+    pub lenient: bool,
+
+    /// Fail at the first row or line that cannot be read (the default). With
+    /// `validate`, exit with 1 if any row is invalid.
+    #[arg(long, overrides_with = "lenient")]
+    pub strict: bool,
+
+    #[command(flatten)]
+    pub csv: CsvOptions,
+
+    /// In lists of one network per line, what starts a comment, whole line
+    /// or trailing, such as `;` or `//`. Repeat it to allow several; by
+    /// default, `#`.
+    #[arg(long = "comment", value_name = "PREFIX")]
+    pub comments: Vec<String>,
+
+    /// Read JSON inputs as arrays of objects, each holding its network
+    /// under this key, rather than as arrays of strings.
+    #[arg(long = "key", alias = "json-key", value_name = "KEY")]
+    pub json_key: Option<String>,
+
+    /// With `--format geolite2-country` or `rir-delegated`, the ISO
+    /// codes of the countries whose networks are read, such as `CN,RU`.
+    #[arg(long, value_name = "CODES", value_delimiter = ',')]
+    pub countries: Vec<String>,
+
+    #[command(flatten)]
+    pub geolite2: Geolite2Options,
+
+    #[command(flatten)]
+    pub cloud: CloudOptions,
+
+    #[command(flatten)]
+    pub mrt: MrtOptions,
+
+    #[command(flatten)]
+    pub resolve: ResolveOptions,
+
+    #[command(flatten)]
+    pub limits: InputLimits,
+}
+
+impl InputOptions {
+    /// What starts a comment in line lists.
+    fn comment_prefixes(&self) -> Vec<&str> {
+        match self.comments.is_empty() {
+            true => vec!["#"],
+            false => self.comments.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// How CSV inputs are laid out.
+#[derive(Args, Clone, Debug)]
+pub struct CsvOptions {
+    /// The column holding the networks: its header, or its index counting
+    /// from 0. By default, the first column.
+    #[arg(long, value_name = "NAME|INDEX")]
+    pub column: Option<Column>,
+
+    /// The field delimiter, a single character, or `tab`.
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = parse_delimiter)]
+    pub delimiter: u8,
+
+    /// Read the first row as data rather than as a header.
+    #[arg(long)]
+    pub no_header: bool,
+}
+
+/// A CSV column, by header or index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        match s.parse() {
+            Ok(index) => Ok(Column::Index(index)),
+            Err(_) if s.is_empty() => Err("the column name is empty".to_string()),
+            Err(_) => Ok(Column::Name(s.to_string())),
+        }
+    }
+}
+
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        b"tab" | b"\\t" => Ok(b'\t'),
+        &[byte] if byte.is_ascii() => Ok(byte),
+        _ => Err(format!("{:?} is not a single ASCII character", s)),
+    }
+}
+
+impl CsvOptions {
+    /// The index of the column of networks among `headers`.
+    fn column(&self, path: &Path, headers: &StringRecord) -> Result<usize> {
+        match &self.column {
+            None => Ok(0),
+            Some(Column::Index(index)) => Ok(*index),
+            Some(Column::Name(name)) if self.no_header => bail!(
+                "--column {:?} names a header, but --no-header reads the first row as data (give the column's index instead)",
+                name
+            ),
+            Some(Column::Name(name)) => match headers.iter().position(|header| header == name) {
+                Some(index) => Ok(index),
+                None => {
+                    let headers: Vec<&str> = headers.iter().collect();
+                    bail!(
+                        "{}: no column named {:?} (the columns are {})",
+                        path.display(),
+                        name,
+                        headers.join(", ")
+                    )
+                }
+            },
+        }
+    }
+}
+
+/// Networks read from one or more inputs, split by family.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Networks {
+    pub v4: IpRange<Ipv4Net>,
+    pub v6: IpRange<Ipv6Net>,
+}
+
+impl Networks {
+    /// Adds everything in `other` to `self`.
+    pub fn merge(&mut self, other: &Networks) {
+        self.v4 = self.v4.merge(&other.v4);
+        self.v6 = self.v6.merge(&other.v6);
+    }
+
+    /// Removes everything in `other` from `self`.
+    pub fn exclude(&mut self, other: &Networks) {
+        self.v4 = self.v4.exclude(&other.v4);
+        self.v6 = self.v6.exclude(&other.v6);
+    }
+}
+
+/// What reading inputs found besides their networks.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Anything suspicious but accepted.
+    pub warnings: Vec<String>,
+    /// The rows skipped with `--lenient`, and why, each starting with the
+    /// file name and line.
+    pub invalid: Vec<String>,
+    /// The number of IPv4 rows read, before simplification.
+    pub rows_v4: u64,
+    /// The number of IPv6 rows read.
+    pub rows_v6: u64,
+    /// The number of blank and comment lines skipped in line lists.
+    pub skipped: u64,
+    /// The inputs read, with the digests of their contents.
+    pub inputs: Vec<Input>,
+    /// The networks of each list, after the name of its file.
+    pub lists: Vec<(String, Networks)>,
+    /// The networks of the inputs given values of their own, such as the
+    /// countries of GeoLite2 blocks, by value.
+    pub values: Vec<(String, Networks)>,
+}
+
+/// The input path that reads standard input instead of a file.
+pub const STDIN: &str = "-";
+
+/// Loads the networks of the input at `path`, or of standard input for
+/// [`STDIN`], which is expected to hold `family` networks.
+pub fn load_input(
+    path: &Path,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    if path == Path::new(STDIN) {
+        let stdin = io::stdin().lock();
+        return read_input(Path::new("<stdin>"), stdin, family, options, report);
+    }
+    read_input(path, storage::open(path)?, family, options, report)
+}
+
+/// Reads the networks of `input`, named `path` in errors and warnings, with
+/// the parser named by `--format`, else the registered parser that
+/// recognizes the input, else as CSV.
+pub fn read_input(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let mut span = telemetry::span("parse")
+        .attr("file", path.display())
+        .attr("family", family);
+    let (before, skipped) = (report.rows_v4 + report.rows_v6, report.skipped);
+    let mut input = Hashing::new(input);
+    let networks = parse_input(path, &mut input, family, options, report)?;
+    report.inputs.push(Input {
+        path: path.display().to_string(),
+        sha256: input
+            .finish()
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?,
+    });
+    span.set("rows", report.rows_v4 + report.rows_v6 - before);
+    if report.skipped > skipped {
+        span.set("skipped", report.skipped - skipped);
+    }
+    Ok(networks)
+}
+
+fn parse_input(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    // The limits apply to the decompressed input, which they guard.
+    let (input, name) = compression::decompress(path, BufReader::new(input))?;
+    let mut input = BufReader::with_capacity(source::SNIFF_LEN, options.limits.reader(input));
+    let parser = match options.input_format.as_deref() {
+        Some("csv") => None,
+        Some("json") => return load_json(path, input, family, options, report),
+        Some("text" | "firehol") => source::registry().get("lines"),
+        Some(geolite2::FORMAT) => return load_geolite2(path, input, family, options, report),
+        Some(rir::FORMAT) => return load_rir(path, input, family, options, report),
+        Some(mrt::FORMAT) => return load_mrt(path, input, family, options, report),
+        Some(name) if cloud::FORMATS.contains(&name) => {
+            return load_cloud(name, path, input, family, options, report)
+        }
+        Some(name) => {
+            let registry = source::registry();
+            let parser = registry.get(name).ok_or_else(|| {
+                eyre!(
+                    "unknown input format {:?} (expected csv, json, {}, {}, {}, {}, {})",
+                    name,
+                    geolite2::FORMAT,
+                    rir::FORMAT,
+                    cloud::FORMATS.join(", "),
+                    mrt::FORMAT,
+                    registry.names().join(", ")
+                )
+            })?;
+            Some(parser)
+        }
+        None if rir::sniff(&name) => return load_rir(path, input, family, options, report),
+        None => match cloud::sniff(&name) {
+            Some(format) => return load_cloud(format, path, input, family, options, report),
+            None => source::registry().sniff(&name, input.fill_buf()?),
+        },
+    };
+    let parser = match parser {
+        Some(parser) if parser.name() == "lines" => {
+            return load_lines(path, input, family, options, report)
+        }
+        Some(parser) => parser,
+        None if mrt::sniff(input.fill_buf()?) => {
+            return load_mrt(path, input, family, options, report)
+        }
+        None if name.extension().is_some_and(|ext| ext == "json") => {
+            return load_json(path, input, family, options, report)
+        }
+        None => return load_csv(path, input, family, options, report),
+    };
+
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let entries = parser
+        .entries(&bytes)
+        .wrap_err_with(|| format!("{} (read as {})", path.display(), parser.name()))?;
+    options
+        .limits
+        .check_records(entries.len() as u64)
+        .wrap_err_with(|| path.display().to_string())?;
+    // Checked and counted one by one, as the rows of the other formats.
+    let mut rows = Rows::new(path, family, options);
+    for (index, entry) in (0..).zip(&entries) {
+        let at = match entry.line {
+            Some(line) => Location::Line(line),
+            None => Location::Record(index),
+        };
+        rows.add_valued(&entry.network, entry.value.as_deref(), at, report)?;
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the networks in the first column of the CSV `input` read from
+/// `path`.
+///
+/// Values are trimmed of surrounding whitespace, as padded by hand-edited or
+/// Windows exports; anything suspicious but accepted, such as trimmed values
+/// or leading zeros read as decimal, is reported in `report`.
+fn load_csv(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let limits = &options.limits;
+    // The reader itself copes with CRLF and strips a leading UTF-8 BOM.
+    let (input, mut lines) = lines::track(input);
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::Headers)
+        .delimiter(options.csv.delimiter)
+        .has_headers(!options.csv.no_header)
+        .from_reader(input);
+    let headers = match options.csv.no_header {
+        true => StringRecord::new(),
+        false => reader.headers()?.clone(),
+    };
+    let column = options.csv.column(path, &headers)?;
+    let mut records = reader.records();
+
+    // Read leniently, the rows the column check would reject are each
+    // reported instead.
+    let sample: Vec<_> = if options.lenient {
+        records.by_ref().take(SAMPLE_SIZE).collect()
+    } else {
+        let sample = records
+            .by_ref()
+            .take(SAMPLE_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        check_column(
+            path,
+            &headers,
+            &sample,
+            column,
+            options.resolve.resolve_hostnames,
+        )?;
+        sample.into_iter().map(Ok).collect()
+    };
+
+    let mut rows = Rows::new(path, family, options);
+    let mut progress = log::Progress::new(path.display());
+    for (count, record) in (1..).zip(sample.into_iter().chain(records)) {
+        progress.row();
+        limits
+            .check_records(count)
+            .wrap_err_with(|| path.display().to_string())?;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                reject(format!("{}: {}", path.display(), err), options, report)?;
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |pos| lines.line_at(pos.byte()));
+        rows.add(
+            record.get(column).unwrap_or(""),
+            Location::Line(line),
+            report,
+        )?;
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the networks of the list `input` read from `path`, one per line.
+/// Blank lines and comments, whole line or trailing, are skipped and counted
+/// in `report`; everything else must be a network, unless `--lenient`.
+fn load_lines(
+    path: &Path,
+    input: impl BufRead,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let comments = options.comment_prefixes();
+    let mut rows = Rows::new(path, family, options);
+    let mut progress = log::Progress::new(path.display());
+    let mut count = 0;
+    for (line, bytes) in (1..).zip(input.split(b'\n')) {
+        let bytes = bytes.wrap_err_with(|| path.display().to_string())?;
+        progress.row();
+        let text = String::from_utf8_lossy(&bytes);
+        let end = comments.iter().filter_map(|prefix| text.find(prefix)).min();
+        let value = text[..end.unwrap_or(text.len())].trim();
+        if value.is_empty() {
+            report.skipped += 1;
+            continue;
+        }
+        count += 1;
+        options
+            .limits
+            .check_records(count)
+            .wrap_err_with(|| path.display().to_string())?;
+        rows.add(value, Location::Line(line), report)?;
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the networks of the JSON `input` read from `path`: an array of
+/// strings, or with `--key`, of objects holding them under that key.
+fn load_json(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let values: Vec<serde_json::Value> = serde_json::from_reader(input)
+        .wrap_err_with(|| format!("{} is not a JSON array", path.display()))?;
+    options
+        .limits
+        .check_records(values.len() as u64)
+        .wrap_err_with(|| path.display().to_string())?;
+    let mut rows = Rows::new(path, family, options);
+    for (index, value) in values.iter().enumerate() {
+        let at = Location::Element(index);
+        let value = match (value, &options.json_key) {
+            (serde_json::Value::String(value), _) => Some(value.as_str()),
+            (serde_json::Value::Object(object), Some(key)) => {
+                object.get(key).and_then(|value| value.as_str())
+            }
+            _ => None,
+        };
+        match value {
+            Some(value) => rows.add(value, at, report)?,
+            None => {
+                let expected = match &options.json_key {
+                    Some(key) => format!("a string or an object with a string {:?}", key),
+                    None => "a string (pass --key to read objects)".to_string(),
+                };
+                let message = format!("{}: {} is not {}", path.display(), at, expected);
+                reject(message, options, report)?;
+            }
+        }
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the networks of the chosen `--countries` in the GeoLite2 Country
+/// blocks `input` read from `path`, valued with their country codes. A
+/// block is in the country it is located in, or if that is unknown, the
+/// country it is registered in.
+fn load_geolite2(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let countries = options.geolite2.countries(path, &options.countries)?;
+    let (input, mut lines) = lines::track(input);
+    let mut reader = ReaderBuilder::new().from_reader(input);
+    let headers = reader.headers()?.clone();
+    let network = geolite2::column(path, &headers, "network")?;
+    let located = geolite2::column(path, &headers, "geoname_id")?;
+    let registered = geolite2::column(path, &headers, "registered_country_geoname_id")?;
+
+    let mut rows = Rows::new(path, family, options);
+    let mut progress = log::Progress::new(path.display());
+    for (count, record) in (1..).zip(reader.records()) {
+        progress.row();
+        options
+            .limits
+            .check_records(count)
+            .wrap_err_with(|| path.display().to_string())?;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                reject(format!("{}: {}", path.display(), err), options, report)?;
+                continue;
+            }
+        };
+        let id = match record.get(located) {
+            Some("") | None => record.get(registered).unwrap_or(""),
+            Some(id) => id,
+        };
+        let Some(country) = countries.get(id) else {
+            continue;
+        };
+        let line = record.position().map_or(0, |pos| lines.line_at(pos.byte()));
+        rows.add_valued(
+            record.get(network).unwrap_or(""),
+            Some(country),
+            Location::Line(line),
+            report,
+        )?;
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the delegations to the chosen `--countries`, or to any country, in
+/// the RIR delegation statistics `input` read from `path`, valued with the
+/// country codes.
+fn load_rir(
+    path: &Path,
+    input: impl BufRead,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let countries = geolite2::country_codes(&options.countries);
+    let mut found = vec![false; countries.len()];
+    let mut rows = Rows::new(path, family, options);
+    let mut progress = log::Progress::new(path.display());
+    let mut count = 0;
+    for (line, text) in (1..).zip(input.lines()) {
+        let text = text.wrap_err_with(|| path.display().to_string())?;
+        let delegation = match rir::parse_line(&text) {
+            None => continue,
+            Some(Ok(delegation)) => delegation,
+            Some(Err(err)) => {
+                reject(
+                    format!("{}:{}: {}", path.display(), line, err),
+                    options,
+                    report,
+                )?;
+                continue;
+            }
+        };
+        progress.row();
+        count += 1;
+        options
+            .limits
+            .check_records(count)
+            .wrap_err_with(|| path.display().to_string())?;
+        if !countries.is_empty() {
+            match countries.iter().position(|c| c == delegation.country) {
+                Some(index) => found[index] = true,
+                None => continue,
+            }
+        }
+        rows.add_valued(
+            &delegation.value,
+            Some(delegation.country),
+            Location::Line(line),
+            report,
+        )?;
+    }
+    let missing: Vec<&str> = countries
+        .iter()
+        .zip(&found)
+        .filter(|(_, found)| !**found)
+        .map(|(country, _)| country.as_str())
+        .collect();
+    if !missing.is_empty() {
+        report.warnings.push(format!(
+            "{}: nothing is delegated to {}",
+            path.display(),
+            missing.join(", ")
+        ));
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the prefixes of the chosen `--service` and `--region` in the
+/// `format` file of a cloud provider, `input`, read from `path`. The
+/// providers publish both families in one file, so each prefix is routed to
+/// its family's filter whichever list the file is given as.
+fn load_cloud(
+    format: &str,
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let prefixes = cloud::prefixes(format, path, input)?;
+    options
+        .limits
+        .check_records(prefixes.len() as u64)
+        .wrap_err_with(|| path.display().to_string())?;
+    let unknown = options.cloud.unknown(&prefixes);
+    if !unknown.is_empty() {
+        report.warnings.push(format!(
+            "{}: no prefix is of {}",
+            path.display(),
+            unknown.join(", ")
+        ));
+    }
+    let options = &InputOptions {
+        auto_family: true,
+        ..options.clone()
+    };
+    let mut rows = Rows::new(path, family, options);
+    for (index, prefix) in prefixes.iter().enumerate() {
+        if options.cloud.selects(prefix) {
+            rows.add(&prefix.network, Location::Element(index), report)?;
+        }
+    }
+    Ok(rows.finish(report))
+}
+
+/// Loads the prefixes of the routes of the chosen `--origin-asn` in the MRT
+/// dump `input` read from `path`. A dump holds both families, so each prefix
+/// is routed to its family's filter.
+fn load_mrt(
+    path: &Path,
+    input: impl Read,
+    family: Family,
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let options = &InputOptions {
+        auto_family: true,
+        ..options.clone()
+    };
+    let mut rows = Rows::new(path, family, options);
+    let mut progress = log::Progress::new(path.display());
+    for (count, route) in (1..).zip(mrt::Routes::new(input)) {
+        let (record, route) = route.wrap_err_with(|| path.display().to_string())?;
+        progress.row();
+        options
+            .limits
+            .check_records(count)
+            .wrap_err_with(|| path.display().to_string())?;
+        if options.mrt.selects(&route) {
+            rows.add(&route.network.to_string(), Location::Record(record), report)?;
+        }
+    }
+    Ok(rows.finish(report))
+}
+
+/// Where a value was found in its input.
+#[derive(Clone, Copy)]
+enum Location {
+    /// The line of a CSV row or list entry.
+    Line(u64),
+    /// The index of a JSON array element.
+    Element(usize),
+    /// The index of a binary record.
+    Record(u64),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Line(line) => write!(f, "line {}", line),
+            Location::Element(index) => write!(f, "element {}", index),
+            Location::Record(index) => write!(f, "record {}", index),
+        }
+    }
+}
+
+/// The networks of one input, as its values are read.
+struct Rows<'a> {
+    path: &'a Path,
+    family: Family,
+    options: &'a InputOptions,
+    networks: Networks,
+    /// The networks given values, by value, and where each value is.
+    values: Vec<(String, Networks)>,
+    indexes: HashMap<String, usize>,
+    trimmed: Tally,
+    leading_zeros: Tally,
+    resolved: Tally,
+}
+
+impl<'a> Rows<'a> {
+    fn new(path: &'a Path, family: Family, options: &'a InputOptions) -> Rows<'a> {
+        Rows {
+            path,
+            family,
+            options,
+            networks: Networks::default(),
+            values: Vec::new(),
+            indexes: HashMap::new(),
+            trimmed: Tally::default(),
+            leading_zeros: Tally::default(),
+            resolved: Tally::default(),
+        }
+    }
+
+    /// Adds the network `network` found `at`, or rejects it.
+    fn add(&mut self, network: &str, at: Location, report: &mut Report) -> Result<()> {
+        self.add_valued(network, None, at, report)
+    }
+
+    /// Adds the network `network` found `at`, giving it `value` if any, or
+    /// rejects it.
+    fn add_valued(
+        &mut self,
+        network: &str,
+        value: Option<&str>,
+        at: Location,
+        report: &mut Report,
+    ) -> Result<()> {
+        let (path, family, options) = (self.path, self.family, self.options);
+        // Errors name CSV rows as `path:line`.
+        let prefix = match at {
+            Location::Line(line) => format!("{}:{}", path.display(), line),
+            Location::Element(_) | Location::Record(_) => format!("{}: {}", path.display(), at),
+        };
+        let parsed = match parse_network(network, options.leading_zeros) {
+            Ok(parsed) => parsed,
+            Err(ParseError::Invalid(host))
+                if options.resolve.resolve_hostnames && resolve::is_hostname(&host) =>
+            {
+                return self.add_host(&host, value, &prefix, at, report);
+            }
+            Err(err) => return reject(format!("{}: {}", prefix, err), options, report),
+        };
+        if parsed.trimmed {
+            self.trimmed.record(at);
+        }
+        if parsed.leading_zeros {
+            self.leading_zeros.record(at);
+        }
+        let (found, described, kind) = match parsed.value {
+            Value::Network(AnyNet::V4(network)) => (Family::V4, network.to_string(), "network"),
+            Value::Network(AnyNet::V6(network)) => (Family::V6, network.to_string(), "network"),
+            Value::Range(start, end) => {
+                let found = if start.is_ipv4() {
+                    Family::V4
+                } else {
+                    Family::V6
+                };
+                (found, format!("{}-{}", start, end), "range")
+            }
+        };
+        if found != family && !options.auto_family {
+            let message = format!(
+                "{}: {} is an {} {} in the {} input (pass --auto-family to route it to the {} filter)",
+                prefix, described, found, kind, family, found,
+            );
+            return reject(message, options, report);
+        }
+        insert(&mut self.networks, parsed.value);
+        if let Some(valued) = self.valued(value) {
+            insert(valued, parsed.value);
+        }
+        match found {
+            Family::V4 => report.rows_v4 += 1,
+            Family::V6 => report.rows_v6 += 1,
+        }
+        Ok(())
+    }
+
+    /// Adds the addresses of the hostname `host` found `at`, those of the
+    /// input's family unless `--auto-family`.
+    fn add_host(
+        &mut self,
+        host: &str,
+        value: Option<&str>,
+        prefix: &str,
+        at: Location,
+        report: &mut Report,
+    ) -> Result<()> {
+        let (family, options) = (self.family, self.options);
+        let addrs = match resolve::resolve(host) {
+            Ok(addrs) if !addrs.is_empty() => addrs,
+            Ok(_) => {
+                return unresolved(
+                    format!("{}: {} has no address", prefix, host),
+                    options,
+                    report,
+                )
+            }
+            Err(err) => {
+                let message = format!("{}: failed to resolve {}: {}", prefix, host, err);
+                return unresolved(message, options, report);
+            }
+        };
+        let (wanted, other): (Vec<IpAddr>, Vec<IpAddr>) = addrs
+            .into_iter()
+            .partition(|addr| options.auto_family || addr.is_ipv4() == (family == Family::V4));
+        if wanted.is_empty() {
+            let found = match other[0] {
+                IpAddr::V4(_) => Family::V4,
+                IpAddr::V6(_) => Family::V6,
+            };
+            let message = format!(
+                "{}: {} has only {} addresses, such as {}, in the {} input (pass --auto-family to route them to the {} filter)",
+                prefix, host, found, other[0], family, found,
+            );
+            return reject(message, options, report);
+        }
+        self.resolved.record(at);
+        for addr in wanted {
+            let network = Value::Network(AnyNet::from(addr));
+            insert(&mut self.networks, network);
+            if let Some(valued) = self.valued(value) {
+                insert(valued, network);
+            }
+            match addr {
+                IpAddr::V4(_) => report.rows_v4 += 1,
+                IpAddr::V6(_) => report.rows_v6 += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// The networks given `value`, if any.
+    fn valued(&mut self, value: Option<&str>) -> Option<&mut Networks> {
+        let value = value?;
+        let index = match self.indexes.get(value) {
+            Some(&index) => index,
+            None => {
+                self.indexes.insert(value.to_string(), self.values.len());
+                self.values.push((value.to_string(), Networks::default()));
+                self.values.len() - 1
+            }
+        };
+        Some(&mut self.values[index].1)
+    }
+
+    /// The simplified networks, reporting what was accepted but suspicious.
+    fn finish(mut self, report: &mut Report) -> Networks {
+        {
+            let mut span = telemetry::span("simplify");
+            self.networks.v4.simplify();
+            self.networks.v6.simplify();
+            for (_, networks) in &mut self.values {
+                networks.v4.simplify();
+                networks.v6.simplify();
+            }
+            span.set(
+                "networks",
+                self.networks.v4.iter().count() + self.networks.v6.iter().count(),
+            );
+        }
+
+        let path = self.path.display();
+        if let Some(summary) = self.trimmed.summary() {
+            report.warnings.push(format!(
+                "{}: trimmed surrounding whitespace from {}",
+                path, summary
+            ));
+        }
+        if let Some(summary) = self.leading_zeros.summary() {
+            report.warnings.push(format!(
+                "{}: read leading zeros as decimal in {}",
+                path, summary
+            ));
+        }
+        if let Some(summary) = self.resolved.summary() {
+            report.warnings.push(format!(
+                "{}: resolved the hostnames of {}, as of now",
+                path, summary
+            ));
+        }
+        report.values.append(&mut self.values);
+        self.networks
+    }
+}
+
+/// Adds the network or range `value` to `networks`.
+fn insert(networks: &mut Networks, value: Value) {
+    match value {
+        Value::Network(AnyNet::V4(network)) => {
+            networks.v4.add(network);
+        }
+        Value::Network(AnyNet::V6(network)) => {
+            networks.v6.add(network);
+        }
+        Value::Range(IpAddr::V4(start), IpAddr::V4(end)) => {
+            networks.v4.add_range(start, end);
+        }
+        Value::Range(IpAddr::V6(start), IpAddr::V6(end)) => {
+            networks.v6.add_range(start, end);
+        }
+        Value::Range(..) => unreachable!("ranges are parsed within one family"),
+    }
+}
+
+/// Skips the row rejected with `message` if reading leniently, noting it in
+/// `report`, else fails with it.
+fn reject(message: String, options: &InputOptions, report: &mut Report) -> Result<()> {
+    if !options.lenient {
+        return Err(eyre!(message));
+    }
+    report.invalid.push(message);
+    Ok(())
+}
+
+/// Skips the hostname that failed to resolve with `message` if
+/// `--skip-unresolved`, warning about it in `report`, else rejects its row.
+fn unresolved(message: String, options: &InputOptions, report: &mut Report) -> Result<()> {
+    if options.resolve.skip_unresolved {
+        report.warnings.push(message);
+        return Ok(());
+    }
+    reject(message, options, report)
+}
+
+/// Counts the records a warning applies to, remembering the first one.
+#[derive(Default)]
+struct Tally {
+    count: usize,
+    first: Option<Location>,
+}
+
+impl Tally {
+    fn record(&mut self, at: Location) {
+        self.first.get_or_insert(at);
+        self.count += 1;
+    }
+
+    fn summary(&self) -> Option<String> {
+        let first = self.first?;
+        match self.count {
+            1 => Some(format!("1 value ({})", first)),
+            n => Some(format!("{} values (first on {})", n, first)),
+        }
+    }
+}
+
+/// Fails with a descriptive error, suggesting better candidates, if most
+/// of the sampled values in `column` are not networks.
+fn check_column(
+    path: &Path,
+    headers: &StringRecord,
+    sample: &[StringRecord],
+    column: usize,
+    hostnames: bool,
+) -> Result<()> {
+    let valid = |value: &&str| {
+        parse_network(value, LeadingZeros::Decimal).is_ok()
+            || hostnames && resolve::is_hostname(value.trim())
+    };
+    let parses = |column: usize| {
+        sample
+            .iter()
+            .filter_map(|record| record.get(column))
+            .filter(valid)
+            .count()
+    };
+    let looks_like_networks = |column: usize| parses(column) * 2 > sample.len();
+    if sample.is_empty() || looks_like_networks(column) {
+        return Ok(());
+    }
+
+    let describe = |column: usize| match headers.get(column) {
+        Some(name) if !name.is_empty() => format!("{} ({:?})", column, name),
+        _ => column.to_string(),
+    };
+
+    let mut message = format!(
+        "column {} of {} does not look like a list of networks: {} of {} sampled values failed to parse",
+        describe(column),
+        path.display(),
+        sample.len() - parses(column),
+        sample.len(),
+    );
+    let bad_value = sample
+        .iter()
+        .filter_map(|record| record.get(column))
+        .find(|value| !valid(value));
+    if let Some(value) = bad_value {
+        message.push_str(&format!(", e.g. {:?}", value));
+    }
+
+    let width = sample.iter().map(StringRecord::len).max().unwrap_or(0);
+    let candidates: Vec<String> = (0..width)
+        .filter(|&c| c != column && looks_like_networks(c))
+        .map(describe)
+        .collect();
+    if !candidates.is_empty() {
+        message.push_str(&format!(
+            "; networks were found in column {}",
+            candidates.join(", ")
+        ));
+    }
+    bail!(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limit;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const DEFAULT: InputOptions = InputOptions {
+        input_format: None,
+        auto_family: false,
+        leading_zeros: LeadingZeros::Reject,
+        lenient: false,
+        strict: false,
+        csv: CsvOptions {
+            column: None,
+            delimiter: b',',
+            no_header: false,
+        },
+        json_key: None,
+        comments: Vec::new(),
+        countries: Vec::new(),
+        geolite2: Geolite2Options {
+            geolite2_locations: None,
+        },
+        cloud: CloudOptions {
+            services: Vec::new(),
+            regions: Vec::new(),
+        },
+        mrt: MrtOptions {
+            origin_asn: Vec::new(),
+        },
+        resolve: ResolveOptions {
+            resolve_hostnames: false,
+            skip_unresolved: false,
+        },
+        limits: InputLimits {
+            max_records: Limit::UNLIMITED,
+            max_bytes: Limit::UNLIMITED,
+        },
+    };
+
+    fn write_temp_csv(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("ipcheck-{}-{}.csv", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn range<N: ipcheck_rs::IpNet + std::str::FromStr>(networks: &[&str]) -> IpRange<N>
+    where
+        <N as std::str::FromStr>::Err: fmt::Debug,
+    {
+        networks.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn load_csv_first_column() {
+        let path = write_temp_csv(
+            "first-column",
+            "network,name\n10.0.0.0/8,a\n10.1.0.0/16,b\n",
+        );
+        let networks = load_input(&path, Family::V4, &DEFAULT, &mut Report::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+        assert!(networks.v6.is_empty());
+    }
+
+    #[test]
+    fn load_csv_address_ranges() {
+        let path = write_temp_csv(
+            "ranges",
+            "network\n10.0.0.0-10.0.0.4\n10.0.1.0 - 10.0.1.255\n2001:db8::-2001:db8::1\n",
+        );
+        let options = InputOptions {
+            auto_family: true,
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &options, &mut report).unwrap();
+        assert_eq!(
+            networks.v4,
+            range(&["10.0.0.0/30", "10.0.0.4/32", "10.0.1.0/24"])
+        );
+        assert_eq!(networks.v6, range(&["2001:db8::/127"]));
+        assert_eq!((report.rows_v4, report.rows_v6), (2, 1));
+
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            err.contains(":4: 2001:db8::-2001:db8::1 is an IPv6 range in the IPv4 input"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn load_csv_resolves_hostnames() {
+        let path = write_temp_csv(
+            "hostnames",
+            "network\n10.0.0.0/8\n192.0.2.0/24\nlocalhost\n",
+        );
+        let options = InputOptions {
+            resolve: ResolveOptions {
+                resolve_hostnames: true,
+                skip_unresolved: false,
+            },
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &options, &mut report).unwrap();
+        assert_eq!(
+            networks.v4,
+            range(&["10.0.0.0/8", "192.0.2.0/24", "127.0.0.1/32"])
+        );
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "{}: resolved the hostnames of 1 value (line 4), as of now",
+                path.display()
+            )]
+        );
+
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.ends_with(":4: \"localhost\" is not a network"),
+            "{}",
+            err
+        );
+
+        fs::write(
+            &path,
+            "network\n10.0.0.0/8\n192.0.2.0/24\nnowhere.invalid\n",
+        )
+        .unwrap();
+        let err = load_input(&path, Family::V4, &options, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(":4: failed to resolve nowhere.invalid: "),
+            "{}",
+            err
+        );
+
+        let skipping = InputOptions {
+            resolve: ResolveOptions {
+                resolve_hostnames: true,
+                skip_unresolved: true,
+            },
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &skipping, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.0/24"]));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("failed to resolve nowhere.invalid"));
+    }
+
+    #[test]
+    fn load_geolite2_countries() {
+        let locations = write_temp_csv(
+            "geolite2-locations",
+            "geoname_id,locale_code,continent_code,continent_name,country_iso_code,country_name,is_in_european_union\n\
+             1814991,en,AS,Asia,CN,China,0\n\
+             2921044,en,EU,Europe,DE,Germany,1\n",
+        );
+        let options = InputOptions {
+            input_format: Some("geolite2-country".into()),
+            countries: vec!["CN".into()],
+            geolite2: Geolite2Options {
+                geolite2_locations: Some(locations.clone()),
+            },
+            ..DEFAULT
+        };
+        let blocks = "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider\n\
+            1.0.1.0/24,1814991,1814991,,0,0\n\
+            2.16.0.0/24,2921044,2921044,,0,0\n\
+            1.0.8.0/21,,1814991,,0,0\n";
+        let mut report = Report::default();
+        let networks = read_input(
+            Path::new("GeoLite2-Country-Blocks-IPv4.csv"),
+            blocks.as_bytes(),
+            Family::V4,
+            &options,
+            &mut report,
+        )
+        .unwrap();
+        fs::remove_file(&locations).unwrap();
+        assert_eq!(networks.v4, range(&["1.0.1.0/24", "1.0.8.0/21"]));
+        assert_eq!(report.rows_v4, 2);
+
+        let err = read_input(
+            Path::new("blocks.csv"),
+            blocks.as_bytes(),
+            Family::V4,
+            &InputOptions {
+                input_format: Some("geolite2-country".into()),
+                ..DEFAULT
+            },
+            &mut Report::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "blocks.csv: --format geolite2-country needs --countries, such as --countries CN,RU"
+        );
+    }
+
+    #[test]
+    fn load_rir_delegations() {
+        let delegations = "\
+2|apnic|20240101|4|19830613|20231229|+1000
+apnic|*|ipv4|*|3|summary
+apnic|CN|ipv4|1.0.1.0|768|20110414|allocated
+apnic|AU|ipv4|1.0.0.0|256|20110811|assigned
+apnic|CN|ipv4|1.0.8.0|2048|20110412|allocated
+apnic|CN|ipv6|2001:250::|35|20000426|allocated
+";
+        let read = |countries: &[&str], report: &mut Report| {
+            let options = InputOptions {
+                auto_family: true,
+                countries: countries.iter().map(|c| c.to_string()).collect(),
+                ..DEFAULT
+            };
+            read_input(
+                Path::new("delegated-apnic-latest"),
+                delegations.as_bytes(),
+                Family::V4,
+                &options,
+                report,
+            )
+            .unwrap()
+        };
+        let mut report = Report::default();
+        let networks = read(&["cn", "KP"], &mut report);
+        assert_eq!(
+            networks.v4,
+            range(&["1.0.1.0/24", "1.0.2.0/23", "1.0.8.0/21"])
+        );
+        assert_eq!(networks.v6, range(&["2001:250::/35"]));
+        assert_eq!(
+            report.warnings,
+            ["delegated-apnic-latest: nothing is delegated to KP"]
+        );
+
+        let networks = read(&[], &mut Report::default());
+        assert_eq!(
+            networks.v4,
+            range(&["1.0.0.0/23", "1.0.2.0/23", "1.0.8.0/21"])
+        );
+    }
+
+    #[test]
+    fn load_json_arrays() {
+        let read = |json: &str, options: &InputOptions, report: &mut Report| {
+            read_input(
+                Path::new("ranges.json"),
+                json.as_bytes(),
+                Family::V4,
+                options,
+                report,
+            )
+        };
+        let networks = read(
+            r#"["10.0.0.0/8", "192.0.2.7/32"]"#,
+            &DEFAULT,
+            &mut Report::default(),
+        )
+        .unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.7/32"]));
+
+        let keyed = InputOptions {
+            json_key: Some("prefix".into()),
+            lenient: true,
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = read(
+            r#"[{"prefix": "10.0.0.0/8", "owner": "ops"}, {"owner": "nobody"}, "192.0.2.0/24"]"#,
+            &keyed,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.0/24"]));
+        assert_eq!(
+            report.invalid,
+            ["ranges.json: element 1 is not a string or an object with a string \"prefix\""]
+        );
+
+        let err = read(
+            r#"[{"prefix": "10.0.0.0/8"}]"#,
+            &DEFAULT,
+            &mut Report::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.ends_with("(pass --key to read objects)"), "{}", err);
+        let err = read(
+            r#"{"prefix": "10.0.0.0/8"}"#,
+            &DEFAULT,
+            &mut Report::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(err, "ranges.json is not a JSON array");
+    }
+
+    #[test]
+    fn load_csv_with_column_delimiter_and_header_options() {
+        let path = write_temp_csv(
+            "layout",
+            "country;cidr\nNL;192.0.2.0/24\nDE;198.51.100.0/24\n",
+        );
+        let named = InputOptions {
+            csv: CsvOptions {
+                column: Some(Column::Name("cidr".to_string())),
+                delimiter: b';',
+                no_header: false,
+            },
+            ..DEFAULT
+        };
+        let networks = load_input(&path, Family::V4, &named, &mut Report::default()).unwrap();
+        assert_eq!(networks.v4, range(&["192.0.2.0/24", "198.51.100.0/24"]));
+
+        let missing = InputOptions {
+            csv: CsvOptions {
+                column: Some(Column::Name("network".to_string())),
+                ..named.csv.clone()
+            },
+            ..DEFAULT
+        };
+        let err = load_input(&path, Family::V4, &missing, &mut Report::default()).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("no column named \"network\" (the columns are country, cidr)"),
+            "{}",
+            err
+        );
+        fs::remove_file(&path).unwrap();
+
+        let path = write_temp_csv("no-header", "a\t10.0.0.0/8\nb\t10.1.0.0/16\n");
+        let headerless = InputOptions {
+            csv: CsvOptions {
+                column: Some(Column::Index(1)),
+                delimiter: parse_delimiter("tab").unwrap(),
+                no_header: true,
+            },
+            ..DEFAULT
+        };
+        let networks = load_input(&path, Family::V4, &headerless, &mut Report::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+        assert_eq!("7".parse(), Ok(Column::Index(7)));
+        assert!(parse_delimiter(";;").is_err());
+    }
+
+    #[test]
+    fn load_csv_tolerates_bom_crlf_and_whitespace() {
+        let path = write_temp_csv(
+            "bom-crlf",
+            "\u{feff}network,name\r\n 10.0.0.0/8 ,a\r\n\t10.1.0.0/16\t,b\r\n172.16.0.0/12,c\r\n",
+        );
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &DEFAULT, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "172.16.0.0/12"]));
+        assert_eq!((report.rows_v4, report.rows_v6), (3, 0));
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "{}: trimmed surrounding whitespace from 2 values (first on line 2)",
+                path.display()
+            )]
+        );
+    }
+
+    #[test]
+    fn load_csv_enforces_limits() {
+        let path = write_temp_csv("limits", "network\n10.0.0.0/8\n10.1.0.0/16\n");
+        let records = InputOptions {
+            limits: InputLimits {
+                max_records: Limit::new(1),
+                ..DEFAULT.limits
+            },
+            ..DEFAULT
+        };
+        let bytes = InputOptions {
+            limits: InputLimits {
+                max_bytes: Limit::new(16),
+                ..DEFAULT.limits
+            },
+            ..DEFAULT
+        };
+        let by_records =
+            load_input(&path, Family::V4, &records, &mut Report::default()).unwrap_err();
+        let by_bytes = load_input(&path, Family::V4, &bytes, &mut Report::default()).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(format!("{:?}", by_records).contains("--max-records"));
+        assert!(by_bytes.to_string().contains("--max-bytes"));
+    }
+
+    #[test]
+    fn load_csv_leading_zeros_policy() {
+        let path = write_temp_csv("leading-zeros", "network\n10.0.0.0/8\n010.1.1.1/8\n");
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with(&format!(
+                "{}:3: \"010.1.1.1/8\" has leading zeros",
+                path.display()
+            )),
+            "{}",
+            err
+        );
+
+        let decimal = InputOptions {
+            leading_zeros: LeadingZeros::Decimal,
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &decimal, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "{}: read leading zeros as decimal in 1 value (line 3)",
+                path.display()
+            )]
+        );
+    }
+
+    #[test]
+    fn load_csv_reports_invalid_values() {
+        let path = write_temp_csv(
+            "invalid",
+            "network\r\n10.0.0.0/8\r\n11.0.0.0/8\r\n10.0.0.0/33\r\n2001:db8::/32\r\n12.0.0.0/8\r\n",
+        );
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            format!("{}:4: \"10.0.0.0/33\" is not a network", path.display())
+        );
+
+        let lenient = InputOptions {
+            lenient: true,
+            ..DEFAULT
+        };
+        let mut report = Report::default();
+        let networks = load_input(&path, Family::V4, &lenient, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            networks.v4,
+            range(&["10.0.0.0/8", "11.0.0.0/8", "12.0.0.0/8"])
+        );
+        assert_eq!(report.invalid.len(), 2);
+        assert!(report.invalid[0].ends_with(":4: \"10.0.0.0/33\" is not a network"));
+        assert!(report.invalid[1].contains(":5: 2001:db8::/32 is an IPv6 network"));
+    }
+
+    #[test]
+    fn load_csv_suggests_network_column() {
+        let path = write_temp_csv(
+            "wrong-column",
+            "name,network\nfoo,10.0.0.0/8\nbar,192.168.0.0/16\n",
+        );
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(err.contains("column 0 (\"name\")"), "{}", err);
+        assert!(err.contains("2 of 2 sampled values"), "{}", err);
+        assert!(err.contains("e.g. \"foo\""), "{}", err);
+        assert!(err.contains("found in column 1 (\"network\")"), "{}", err);
+    }
+
+    #[test]
+    fn load_csv_rejects_wrong_family() {
+        let path = write_temp_csv(
+            "wrong-family",
+            "network\n10.0.0.0/8\n2001:db8::/32\n192.168.0.0/16\n",
+        );
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with(&format!(
+            "{}:3: 2001:db8::/32 is an IPv6 network in the IPv4 input (pass --auto-family to route it to the IPv6 filter)",
+            path.display()
+        )), "{}", err);
+
+        let auto = InputOptions {
+            auto_family: true,
+            ..DEFAULT
+        };
+        let networks = load_input(&path, Family::V4, &auto, &mut Report::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.168.0.0/16"]));
+        assert_eq!(networks.v6, range(&["2001:db8::/32"]));
+    }
+
+    #[test]
+    fn read_input_from_a_reader() {
+        let name = Path::new("<stdin>");
+        let csv = &b"network\n10.0.0.0/8\n"[..];
+        let networks = read_input(name, csv, Family::V4, &DEFAULT, &mut Report::default()).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+
+        let err = read_input(
+            name,
+            &b"network\nbogus\n"[..],
+            Family::V4,
+            &DEFAULT,
+            &mut Report::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains(" of <stdin> "), "{}", err);
+
+        let bytes = ipcheck_rs::artifact::write(&range(&["10.0.0.0/8"]), &IpRange::new()).unwrap();
+        let networks = read_input(
+            name,
+            &bytes[..],
+            Family::V4,
+            &DEFAULT,
+            &mut Report::default(),
+        )
+        .unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+    }
+
+    struct Blocks;
+
+    impl source::SourceParser for Blocks {
+        fn name(&self) -> &'static str {
+            "blocks"
+        }
+
+        fn sniff(&self, _path: &Path, _head: &[u8]) -> bool {
+            false
+        }
+
+        fn parse(
+            &self,
+            input: &[u8],
+        ) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), ipcheck_rs::IpCheckError> {
+            let text = String::from_utf8_lossy(input);
+            source::parse_networks(text.lines().filter_map(|line| line.strip_prefix("block ")))
+        }
+    }
+
+    #[test]
+    fn load_input_with_registered_parsers() {
+        let path =
+            std::env::temp_dir().join(format!("ipcheck-lines-{}.netset", std::process::id()));
+        fs::write(&path, "# FireHOL\n10.0.0.0/8\n192.0.2.7\n2001:db8::/32\n").unwrap();
+        let err = load_input(&path, Family::V4, &DEFAULT, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("2001:db8::/32 is an IPv6 network in the IPv4 input"),
+            "{}",
+            err
+        );
+        let auto = InputOptions {
+            auto_family: true,
+            ..DEFAULT
+        };
+        let networks = load_input(&path, Family::V4, &auto, &mut Report::default()).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.7/32"]));
+        assert_eq!(networks.v6, range(&["2001:db8::/32"]));
+
+        // Read as text whatever the file is called.
+        for format in ["text", "firehol"] {
+            let text = InputOptions {
+                input_format: Some(format.into()),
+                ..DEFAULT
+            };
+            let networks = read_input(
+                Path::new("feed.csv"),
+                &b"#\n# firehol_level1\n#\n  10.0.0.0/8 \n\n192.0.2.7\n"[..],
+                Family::V4,
+                &text,
+                &mut Report::default(),
+            )
+            .unwrap();
+            assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.7/32"]));
+        }
+
+        let mut report = Report::default();
+        let comments = InputOptions {
+            comments: vec![";".into(), "//".into()],
+            lenient: true,
+            ..DEFAULT
+        };
+        let networks = read_input(
+            Path::new("drop.txt"),
+            &b"; DROP list\r\n10.0.0.0/8 ; SBL1\r\n\r\n// added by hand\n192.0.2.7 // partner\nnot an address\n"[..],
+            Family::V4,
+            &comments,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8", "192.0.2.7/32"]));
+        assert_eq!(report.skipped, 3);
+        assert_eq!(
+            report.invalid,
+            ["drop.txt:6: \"not an address\" is not a network"]
+        );
+
+        // Entries of the other parsers are checked one by one too.
+        let mut report = Report::default();
+        let lenient = InputOptions {
+            lenient: true,
+            leading_zeros: LeadingZeros::Decimal,
+            ..DEFAULT
+        };
+        let networks = read_input(
+            Path::new("blocklist.save"),
+            &b"create blocklist hash:net family inet\nadd blocklist 010.0.0.0/8\nadd blocklist 10.0.0.300\n"[..],
+            Family::V4,
+            &lenient,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+        assert_eq!(report.rows_v4, 1);
+        assert_eq!(
+            report.invalid,
+            ["blocklist.save:3: \"10.0.0.300\" is not a network"]
+        );
+        assert_eq!(
+            report.warnings,
+            ["blocklist.save: read leading zeros as decimal in 1 value (line 2)"]
+        );
+
+        source::register(Blocks);
+        fs::write(&path, "block 10.0.0.0/8\n").unwrap();
+        let blocks = InputOptions {
+            input_format: Some("blocks".into()),
+            ..DEFAULT
+        };
+        let networks = load_input(&path, Family::V4, &blocks, &mut Report::default()).unwrap();
+        assert_eq!(networks.v4, range(&["10.0.0.0/8"]));
+
+        let unknown = InputOptions {
+            input_format: Some("xml".into()),
+            ..DEFAULT
+        };
+        let err = load_input(&path, Family::V4, &unknown, &mut Report::default())
+            .unwrap_err()
+            .to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            err.starts_with("unknown input format \"xml\" (expected csv, json, geolite2-country, rir-delegated, aws, azure, cloudflare, gcp, mrt, lines, nftables, iptables-save, ipset, spamhaus, artifact"),
+            "{}",
+            err
+        );
+    }
+}
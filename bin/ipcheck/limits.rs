@@ -0,0 +1,155 @@
+//! Guards against runaway or maliciously large inputs.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use clap::Args;
+use eyre::{bail, Result};
+
+/// An upper bound that can be lifted with `unlimited`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limit(Option<u64>);
+
+impl Limit {
+    pub const UNLIMITED: Limit = Limit(None);
+
+    pub const fn new(limit: u64) -> Limit {
+        Limit(Some(limit))
+    }
+
+    /// Returns `true` if `value` is beyond the limit.
+    pub fn exceeded_by(self, value: u64) -> bool {
+        self.0.is_some_and(|limit| value > limit)
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    /// Parses `unlimited` or a number with an optional binary `K`, `M` or
+    /// `G` suffix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("unlimited") {
+            return Ok(Limit::UNLIMITED);
+        }
+        let (digits, scale) = match s.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+            Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+            Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+            _ => (s, 1),
+        };
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .map(Limit::new)
+            .ok_or_else(|| format!("expected a number or `unlimited`, got {:?}", s))
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(limit) => write!(f, "{}", limit),
+            None => f.write_str("unlimited"),
+        }
+    }
+}
+
+/// Size limits applied to every input file.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct InputLimits {
+    /// Maximum number of records read from each input, or `unlimited`.
+    #[arg(long, value_name = "N", default_value = "10000000")]
+    pub max_records: Limit,
+
+    /// Maximum size of each input, e.g. `512M`, or `unlimited`.
+    #[arg(long, value_name = "SIZE", default_value = "1G")]
+    pub max_bytes: Limit,
+}
+
+impl InputLimits {
+    /// Fails once `count` records have been read past `--max-records`.
+    pub fn check_records(&self, count: u64) -> Result<()> {
+        if self.max_records.exceeded_by(count) {
+            bail!(
+                "input has more than {} records (raise --max-records to allow it)",
+                self.max_records
+            );
+        }
+        Ok(())
+    }
+
+    /// Wraps `reader` so that reading past `--max-bytes` fails.
+    pub fn reader<R: Read>(&self, reader: R) -> LimitedReader<R> {
+        LimitedReader {
+            inner: reader,
+            limit: self.max_bytes,
+            read: 0,
+        }
+    }
+}
+
+/// A reader that errors instead of yielding more than its limit.
+pub struct LimitedReader<R> {
+    inner: R,
+    limit: Limit,
+    read: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        if self.limit.exceeded_by(self.read) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "input is larger than {} bytes (raise --max-bytes to allow it)",
+                    self.limit
+                ),
+            ));
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_limit() {
+        assert_eq!("unlimited".parse(), Ok(Limit::UNLIMITED));
+        assert_eq!("1500".parse(), Ok(Limit::new(1500)));
+        assert_eq!("4k".parse(), Ok(Limit::new(4096)));
+        assert_eq!("2M".parse(), Ok(Limit::new(2 << 20)));
+        assert_eq!("1G".parse(), Ok(Limit::new(1 << 30)));
+        assert!("".parse::<Limit>().is_err());
+        assert!("-1".parse::<Limit>().is_err());
+        assert!("1T".parse::<Limit>().is_err());
+        assert!("99999999999999G".parse::<Limit>().is_err());
+    }
+
+    #[test]
+    fn limited_reader_stops_at_limit() {
+        let limits = InputLimits {
+            max_records: Limit::UNLIMITED,
+            max_bytes: Limit::new(4),
+        };
+        let mut buf = Vec::new();
+        assert!(limits.reader(&b"1234"[..]).read_to_end(&mut buf).is_ok());
+        assert!(limits.reader(&b"12345"[..]).read_to_end(&mut buf).is_err());
+    }
+
+    #[test]
+    fn record_limit() {
+        let limits = InputLimits {
+            max_records: Limit::new(2),
+            max_bytes: Limit::UNLIMITED,
+        };
+        assert!(limits.check_records(2).is_ok());
+        assert!(limits.check_records(3).is_err());
+    }
+}
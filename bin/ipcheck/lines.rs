@@ -0,0 +1,109 @@
+//! Line numbers for CSV records.
+//!
+//! `csv::Position::line` undercounts in files with CRLF terminators, so line
+//! numbers are derived from record byte offsets instead: the reader notes
+//! where every `\n` is, and a record's line is one more than the number of
+//! newlines before its first byte. csv reports the offset of a record that
+//! follows blank lines as the start of those lines, so blank lines are noted
+//! too and skipped.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+/// Wraps `reader`, returning it together with the [`LineNumbers`] of
+/// whatever is read through it.
+pub fn track<R: Read>(reader: R) -> (TrackedReader<R>, LineNumbers) {
+    let newlines = Rc::new(RefCell::new(VecDeque::new()));
+    let reader = TrackedReader {
+        inner: reader,
+        offset: 0,
+        line_has_content: false,
+        newlines: Rc::clone(&newlines),
+    };
+    (reader, LineNumbers { newlines, line: 1 })
+}
+
+/// A newline: its offset and whether it ends a blank line.
+type Newline = (u64, bool);
+
+/// A reader that records the offset of every newline it yields.
+pub struct TrackedReader<R> {
+    inner: R,
+    offset: u64,
+    line_has_content: bool,
+    newlines: Rc<RefCell<VecDeque<Newline>>>,
+}
+
+impl<R: Read> Read for TrackedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let mut newlines = self.newlines.borrow_mut();
+        for (i, &b) in buf[..n].iter().enumerate() {
+            match b {
+                b'\n' => {
+                    newlines.push_back((self.offset + i as u64, !self.line_has_content));
+                    self.line_has_content = false;
+                }
+                b'\r' => {}
+                _ => self.line_has_content = true,
+            }
+        }
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// Maps byte offsets of records to line numbers.
+///
+/// Offsets must be queried in non-decreasing order; only the newlines the
+/// reader has buffered ahead are kept.
+pub struct LineNumbers {
+    newlines: Rc<RefCell<VecDeque<Newline>>>,
+    line: u64,
+}
+
+impl LineNumbers {
+    /// Returns the line of the record starting at byte `offset`.
+    pub fn line_at(&mut self, offset: u64) -> u64 {
+        let mut newlines = self.newlines.borrow_mut();
+        // csv reports a record after a CRLF terminator as starting on the LF,
+        // so a newline at the offset itself belongs to the previous line.
+        while newlines.front().is_some_and(|&(nl, _)| nl <= offset) {
+            newlines.pop_front();
+            self.line += 1;
+        }
+        while newlines.front().is_some_and(|&(_, blank)| blank) {
+            newlines.pop_front();
+            self.line += 1;
+        }
+        self.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::ReaderBuilder;
+
+    fn lines(data: &str) -> Vec<u64> {
+        let (reader, mut lines) = track(data.as_bytes());
+        let mut reader = ReaderBuilder::new().from_reader(reader);
+        reader.headers().unwrap();
+        reader
+            .records()
+            .map(|record| lines.line_at(record.unwrap().position().unwrap().byte()))
+            .collect()
+    }
+
+    #[test]
+    fn line_numbers_for_any_terminator() {
+        assert_eq!(lines("n\n1\n2\n3\n"), [2, 3, 4]);
+        assert_eq!(lines("n\r\n1\r\n2\r\n3\r\n"), [2, 3, 4]);
+        assert_eq!(lines("\u{feff}n\r\n1\n2\r\n3"), [2, 3, 4]);
+        // Quoted newlines and blank lines.
+        assert_eq!(lines("n\n\"1\n\"\n\n2\n"), [2, 5]);
+        assert_eq!(lines("n\r\n\"1\r\n\"\r\n\r\n2\r\n"), [2, 5]);
+    }
+}
@@ -0,0 +1,333 @@
+//! What ipcheck tells the operator on stderr: warnings and progress notes,
+//! unless `--quiet`; with `--verbose`, every phase of the work as it ends,
+//! as `logfmt` lines; and with `--progress`, how far into a large list the
+//! reading is.
+//!
+//! Warnings and notes are `tracing` events, and phases are `tracing` spans
+//! (see `telemetry`), printed by the subscriber installed by `init`. The
+//! progress line is drawn apart from them.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Write as _};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// How much is said on stderr.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Errors only.
+    Quiet,
+    /// Warnings and progress notes too.
+    Normal,
+    /// Every phase, with its timing.
+    Verbose,
+}
+
+impl Level {
+    /// The most verbose `tracing` level shown.
+    fn max(self) -> tracing::Level {
+        match self {
+            Level::Quiet => tracing::Level::ERROR,
+            Level::Normal => tracing::Level::INFO,
+            Level::Verbose => tracing::Level::DEBUG,
+        }
+    }
+}
+
+static PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// How often the progress line is redrawn.
+const REDRAW: Duration = Duration::from_millis(200);
+
+/// Prints ipcheck's events and spans on stderr from now on, as much as
+/// `level` allows, and draws the progress line if `progress`.
+pub fn init(level: Level, progress: bool) {
+    PROGRESS.store(progress && level > Level::Quiet, Ordering::Relaxed);
+    let _ = tracing::subscriber::set_global_default(Stderr::new(level, std::io::stderr()));
+}
+
+/// The subscriber printing ipcheck's own events, and its spans as phases
+/// as they close; those of its dependencies are left out.
+struct Stderr {
+    max: tracing::Level,
+    out: Mutex<Box<dyn Write + Send>>,
+    spans: Mutex<HashMap<u64, Open>>,
+    next: AtomicU64,
+}
+
+/// A span not closed yet.
+struct Open {
+    phase: String,
+    start: Instant,
+    fields: Fields,
+    /// The handles to the span still around.
+    refs: usize,
+}
+
+impl Stderr {
+    fn new(level: Level, out: impl Write + Send + 'static) -> Stderr {
+        Stderr {
+            max: level.max(),
+            out: Mutex::new(Box::new(out)),
+            spans: Mutex::new(HashMap::new()),
+            next: AtomicU64::new(1),
+        }
+    }
+
+    fn print(&self, line: impl Display) {
+        let _ = writeln!(self.out.lock().unwrap(), "{}", line);
+    }
+}
+
+impl Subscriber for Stderr {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("ipcheck") && *metadata.level() <= self.max
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::from_level(self.max))
+    }
+
+    fn new_span(&self, attributes: &Attributes) -> Id {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        let phase = match fields.take("phase") {
+            Some(phase) => phase,
+            None => attributes.metadata().name().to_string(),
+        };
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.spans.lock().unwrap().insert(
+            id,
+            Open {
+                phase,
+                start: Instant::now(),
+                fields,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record) {
+        if let Some(open) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            values.record(&mut open.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut line = match *event.metadata().level() {
+            tracing::Level::ERROR => "error: ".to_string(),
+            tracing::Level::WARN => "warning: ".to_string(),
+            _ => String::new(),
+        };
+        line += &fields.take("message").unwrap_or_default();
+        for (key, value) in &fields.0 {
+            let _ = write!(line, " {}={}", key, Quoted(value));
+        }
+        self.print(line);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(open) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            open.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(open) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        open.refs -= 1;
+        if open.refs > 0 {
+            return false;
+        }
+        let open = spans.remove(&span.into_u64()).unwrap();
+        drop(spans);
+        self.print(Phase {
+            name: &open.phase,
+            elapsed: open.start.elapsed(),
+            fields: &open.fields.0,
+        });
+        true
+    }
+}
+
+/// The fields of a span or event, in the order they were recorded.
+#[derive(Default)]
+struct Fields(Vec<(&'static str, String)>);
+
+impl Fields {
+    fn take(&mut self, key: &str) -> Option<String> {
+        let at = self.0.iter().position(|&(name, _)| name == key)?;
+        Some(self.0.remove(at).1)
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        let value = format!("{:?}", value);
+        match self.0.iter_mut().find(|(name, _)| *name == field.name()) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((field.name(), value)),
+        }
+    }
+}
+
+struct Phase<'a> {
+    name: &'a str,
+    elapsed: Duration,
+    fields: &'a [(&'static str, String)],
+}
+
+impl Display for Phase<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "phase={}", self.name)?;
+        for (key, value) in self.fields {
+            write!(f, " {}={}", key, Quoted(value))?;
+        }
+        write!(f, " ms={:.1}", self.elapsed.as_secs_f64() * 1000.0)
+    }
+}
+
+/// A `logfmt` value, quoted if it needs to be.
+struct Quoted<'a>(&'a str);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() || self.0.contains([' ', '"', '=']) {
+            write!(f, "{:?}", self.0)
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+/// The rows read so far of one list, drawn over one line of stderr while
+/// `--progress` is on and stderr is a terminal. Lists read in under
+/// `REDRAW` are not drawn at all.
+pub struct Progress {
+    name: String,
+    rows: u64,
+    shown: bool,
+    drawn: Instant,
+    started: bool,
+}
+
+impl Progress {
+    pub fn new(name: impl Display) -> Progress {
+        Progress {
+            name: name.to_string(),
+            rows: 0,
+            shown: PROGRESS.load(Ordering::Relaxed) && std::io::stderr().is_terminal(),
+            drawn: Instant::now(),
+            started: false,
+        }
+    }
+
+    /// Counts one more row.
+    pub fn row(&mut self) {
+        self.rows += 1;
+        if self.shown && self.rows.is_multiple_of(1024) && self.drawn.elapsed() >= REDRAW {
+            eprint!("\r{}: {} rows", self.name, self.rows);
+            let _ = std::io::stderr().flush();
+            self.drawn = Instant::now();
+            self.started = true;
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.started {
+            eprintln!("\r{}: {} rows", self.name, self.rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_print_as_logfmt() {
+        let fields = [
+            ("file", "lists/v4.csv".to_string()),
+            ("family", "IPv4".to_string()),
+            ("output", "my out.ts".to_string()),
+        ];
+        let phase = Phase {
+            name: "parse",
+            elapsed: Duration::from_micros(12_340),
+            fields: &fields,
+        };
+        assert_eq!(
+            phase.to_string(),
+            "phase=parse file=lists/v4.csv family=IPv4 output=\"my out.ts\" ms=12.3"
+        );
+    }
+
+    /// Shared with the subscriber under test, to read what it printed.
+    #[derive(Clone, Default)]
+    struct Buffer(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn printed(level: Level) -> String {
+        let buffer = Buffer::default();
+        tracing::subscriber::with_default(Stderr::new(level, buffer.clone()), || {
+            let span = tracing::debug_span!("phase", phase = "parse", file = tracing::field::Empty);
+            span.record("file", tracing::field::display("v4.txt"));
+            tracing::warn!("skipping {}", "row 3");
+            tracing::info!("listening on {}", "[::]:8080");
+            drop(span);
+        });
+        let printed = buffer.0.lock().unwrap().clone();
+        String::from_utf8(printed).unwrap()
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(printed(Level::Quiet), "");
+        assert_eq!(
+            printed(Level::Normal),
+            "warning: skipping row 3\nlistening on [::]:8080\n"
+        );
+        let verbose = printed(Level::Verbose);
+        assert!(
+            verbose.starts_with(
+                "warning: skipping row 3\nlistening on [::]:8080\nphase=parse file=v4.txt ms="
+            ),
+            "{}",
+            verbose
+        );
+    }
+}
@@ -0,0 +1,1912 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use eyre::{bail, eyre, Result, WrapErr};
+use ipcheck_rs::artifact;
+use ipcheck_rs::backend::{self, Filters, Metadata, Note, Output, OutputBackend, Values};
+use ipcheck_rs::codegen::{mmdb, wasm, Encoded, Target, Templated};
+use ipcheck_rs::flat::{self, trie_to_nodes};
+use ipcheck_rs::{proto, IpNet, IpRange};
+use ipnet::IpNet as AnyNet;
+
+use cron::Civil;
+use input::{load_input, Family, InputOptions, Lists, Networks, Report};
+use paths::Slot;
+use reserved::ReservedPolicy;
+
+// First, so that its macros are in scope in every other module.
+mod log;
+
+mod asn;
+mod canonical;
+mod check;
+mod cloud;
+mod codegen;
+mod compression;
+#[cfg(feature = "config")]
+mod config;
+mod cron;
+mod daemon;
+mod diff;
+#[cfg(feature = "dns")]
+mod dns;
+#[cfg(feature = "http")]
+mod fetch;
+mod firewall;
+#[cfg(feature = "geoip")]
+mod geoip;
+mod geolite2;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod input;
+mod limits;
+mod lines;
+#[cfg(feature = "serve")]
+mod match_log;
+#[cfg(feature = "serve")]
+mod metrics;
+mod mrt;
+#[cfg(feature = "nats")]
+mod nats;
+mod output;
+mod parse;
+mod paths;
+mod proxy;
+#[cfg(feature = "redis")]
+mod redis_sync;
+#[cfg(feature = "serve")]
+mod reload;
+mod reserved;
+mod resolve;
+mod resolver;
+mod rir;
+#[cfg(feature = "serve")]
+mod serve;
+mod sha256;
+mod sql;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod storage;
+#[cfg(all(unix, feature = "serve"))]
+mod systemd;
+mod telemetry;
+#[cfg(all(unix, feature = "serve"))]
+mod unix_socket;
+mod validate;
+mod watch;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+/// Generates a TypeScript IP checker from CIDR lists.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Log every phase of the work as it ends, with its timing.
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Print errors only, without warnings or progress notes.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Show how many rows of each list have been read, when stderr is a
+    /// terminal.
+    #[arg(long, global = true)]
+    progress: bool,
+}
+
+impl Cli {
+    /// Parses `args`, treating an invocation without a subcommand as
+    /// `build` so that `ipcheck <v4> <v6> <out>` keeps working.
+    fn parse_args<I, T>(args: I) -> Cli
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let flags = ["-v", "--verbose", "-q", "--quiet", "--progress"];
+        let first = args
+            .iter()
+            .skip(1)
+            .find(|arg| !flags.iter().any(|flag| arg.as_os_str() == *flag));
+        if let Some(first) = first.and_then(|arg| arg.to_str()) {
+            let command = Cli::command();
+            let is_subcommand = first == "help" || command.find_subcommand(first).is_some();
+            let is_top_level_flag = ["-h", "--help", "-V", "--version"].contains(&first);
+            if !is_subcommand && !is_top_level_flag {
+                args.insert(1, "build".into());
+            }
+        }
+        let mut cli = Cli::parse_from(args);
+        if let Err(err) = cli.command.assign_paths() {
+            err.exit();
+        }
+        cli
+    }
+
+    fn level(&self) -> log::Level {
+        match (self.verbose, self.quiet) {
+            (true, _) => log::Level::Verbose,
+            (_, true) => log::Level::Quiet,
+            _ => log::Level::Normal,
+        }
+    }
+}
+
+// Parsed once per run, so the size of the largest arguments is no concern.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Command {
+    /// Render the checker from the CIDR lists (the default).
+    Build(BuildArgs),
+    /// Look addresses up in the CIDR lists, exiting with 1 if any is not
+    /// contained.
+    Check(check::CheckArgs),
+    /// Summarize what the CIDR lists cover, optionally by originating AS.
+    Stats(stats::StatsArgs),
+    /// Print the networks added and removed between two versions of a list.
+    Diff(diff::DiffArgs),
+    /// Report every row of the CIDR lists that cannot be read.
+    Validate(validate::ValidateArgs),
+    /// Rebuild the outputs on a schedule, optionally serving the networks.
+    Daemon(daemon::DaemonArgs),
+    /// Answer lookups over HTTP.
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+    /// Push the networks into Redis, optionally keeping them up to date.
+    #[cfg(feature = "redis")]
+    SyncRedis(redis_sync::SyncArgs),
+    /// Write the networks in another format.
+    #[cfg(feature = "sqlite")]
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+}
+
+impl Command {
+    /// Assigns the positional paths to the inputs and outputs not given by
+    /// flag.
+    fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        match self {
+            Command::Build(args) => args.assign_paths(),
+            Command::Check(args) => args.assign_paths(),
+            Command::Stats(args) => args.assign_paths(),
+            Command::Diff(_) => Ok(()),
+            Command::Validate(args) => args.assign_paths(),
+            Command::Daemon(args) => args.assign_paths(),
+            #[cfg(feature = "serve")]
+            Command::Serve(args) => args.assign_paths(),
+            #[cfg(feature = "redis")]
+            Command::SyncRedis(args) => args.assign_paths(),
+            #[cfg(feature = "sqlite")]
+            Command::Export {
+                format: ExportFormat::Sqlite(args),
+            } => args.assign_paths(),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Subcommand)]
+enum ExportFormat {
+    /// An SQLite database of the networks and the serialized trie.
+    Sqlite(sqlite::ExportArgs),
+}
+
+#[derive(Args, Clone)]
+struct BuildArgs {
+    /// Read the lists and outputs from this TOML file instead, building
+    /// every output it declares.
+    #[cfg(feature = "config")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["v4", "v6", "exclude_v4", "exclude_v6", "output", "paths", "backends", "emit_tests", "emits"]
+    )]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    lists: Lists,
+
+    /// Where to write the generated code, `-` for standard output.
+    #[arg(short, long = "output", value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// The IPv4 list, IPv6 list and output, for those not given by flag.
+    #[arg(value_names = ["V4", "V6", "OUTPUT"], num_args = 0..=3)]
+    paths: Vec<PathBuf>,
+
+    /// Compare the rendered code against the existing output instead of
+    /// writing it, and exit with an error if they differ.
+    #[arg(long)]
+    check: bool,
+
+    /// Render the outputs of this backend: `typescript`, `esm`, `cjs`,
+    /// `python`, `go`, `rust`, `c`, `java`, `lua`, `node-test`, `artifact`,
+    /// `json`, `wasm`, `mmdb`, `proto`, `nftables`, `ipset`, `bpf`, `vcl`,
+    /// `haproxy`, `unbound`, `rpz`, `text`, `csv`, `sql` or a registered one.
+    /// Repeat it to render several.
+    #[arg(long = "backend", value_name = "NAME", default_value = "typescript")]
+    backends: Vec<String>,
+
+    /// What to render in place of the TypeScript checker, short for
+    /// `--backend` with it in place of `typescript`.
+    #[arg(long, value_enum, default_value_t)]
+    target: codegen::Language,
+
+    /// Render the checker of `--target` from this Handlebars template
+    /// instead of the built-in one, with the same values, such as
+    /// `filterV4` and `filterV6`, the node arrays as literals of its
+    /// language. Only the checker is written, without declarations.
+    #[arg(long, value_name = "PATH")]
+    template: Option<PathBuf>,
+
+    /// Replace partials of the built-in TypeScript checker with the
+    /// `NAME.hbs` files of this directory: `header`, `parse`, `lookup`,
+    /// `exports` or `arrays`. Its other files are partials that templates
+    /// and partials can include, as `{{> NAME}}`.
+    #[arg(long, value_name = "DIR", conflicts_with = "minify")]
+    partials: Option<PathBuf>,
+
+    /// Also render this target to this path, from the same lists and tries:
+    /// `json=dist/filters.json`. The target is a `--target` one, or a
+    /// Handlebars template, `wrapper.hbs=dist/wrapper.ts`, of the checker
+    /// of `--target`. Repeat it to render several.
+    #[arg(long = "emit", value_name = "TARGET=PATH", value_parser = parse_emit)]
+    emits: Vec<Emit>,
+
+    /// Expose this variable to templates as `vars.KEY`: `--var team=netsec`
+    /// gives `{{ vars.team }}`. Repeat it to set several.
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// The SQL dialect of `--target sql`.
+    #[arg(long, value_enum, default_value_t)]
+    sql_dialect: sql::Dialect,
+
+    /// How the TypeScript and JavaScript checkers embed their node arrays:
+    /// as array literals, or as base64 strings decoded when the module
+    /// loads, smaller and quicker to parse. The other checkers keep their
+    /// literals.
+    #[arg(long, value_enum, default_value_t)]
+    encoding: codegen::Encoding,
+
+    /// Minify the TypeScript or ES module checker for edge bundles: no
+    /// whitespace, short identifiers and a single default export, the
+    /// lookup returning whether the address is listed.
+    #[arg(long, conflicts_with = "emit_tests")]
+    minify: bool,
+
+    /// Also write a `node:test` suite next to the output (`ipcheck.ts`
+    /// gets `ipcheck.test.ts`) asserting lookups sampled from each block;
+    /// short for `--backend node-test`.
+    #[arg(long)]
+    emit_tests: bool,
+
+    /// Overwrite existing output files that differ from the rendered code.
+    #[arg(long)]
+    force: bool,
+
+    /// Keep running, rebuilding whenever the lists change. Rebuilds
+    /// overwrite the outputs of the previous build.
+    #[arg(long, conflicts_with = "check")]
+    watch: bool,
+
+    /// What to do with private, loopback, link-local and documentation
+    /// networks found in the inputs.
+    #[arg(long, value_enum, default_value_t)]
+    reserved_policy: ReservedPolicy,
+
+    #[command(flatten)]
+    input: InputOptions,
+
+    #[command(flatten)]
+    asn: asn::AsnArgs,
+
+    #[cfg(feature = "geoip")]
+    #[command(flatten)]
+    geoip: geoip::GeoipArgs,
+
+    #[cfg(feature = "webhook")]
+    #[command(flatten)]
+    webhook: webhook::WebhookArgs,
+}
+
+impl BuildArgs {
+    fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        #[cfg(feature = "config")]
+        if self.config.is_some() {
+            return Ok(());
+        }
+        let mut slots = self.lists.slots();
+        slots.push(Slot::required(&mut self.output, "--output"));
+        paths::assign(&mut self.paths, slots)
+    }
+
+    fn output(&self) -> &Path {
+        self.output
+            .as_deref()
+            .expect("the output is assigned when parsed")
+    }
+
+    /// Whether the output is `-`, standard output.
+    fn to_stdout(&self) -> bool {
+        self.output() == Path::new(output::STDOUT)
+    }
+
+    /// The backends selected by `--backend`, `--target` and `--emit-tests`.
+    fn backends(&self) -> Result<Vec<Arc<dyn OutputBackend>>> {
+        let mut names: Vec<&str> = self
+            .backends
+            .iter()
+            .map(|name| match name.as_str() {
+                "typescript" => self.target.backend(),
+                name => name,
+            })
+            .collect();
+        if self.emit_tests && !names.contains(&"node-test") {
+            if !matches!(
+                self.target,
+                codegen::Language::Ts | codegen::Language::Esm | codegen::Language::Cjs
+            ) {
+                bail!(
+                    "--emit-tests writes a node:test suite, which cannot test the output of --target {}",
+                    self.target.to_possible_value().unwrap().get_name()
+                );
+            }
+            names.push("node-test");
+        }
+        if self.minify
+            && self.target.checker().is_some()
+            && !matches!(self.target, codegen::Language::Ts | codegen::Language::Esm)
+        {
+            bail!(
+                "--minify renders an ES module, which --target {} is not",
+                self.target.to_possible_value().unwrap().get_name()
+            );
+        }
+        let partials = match &self.partials {
+            Some(dir) => {
+                if self.template.is_none()
+                    && self.target.checker().is_some()
+                    && self.target != codegen::Language::Ts
+                {
+                    bail!(
+                        "--partials replaces those of the TypeScript checker, which --target {} is not",
+                        self.target.to_possible_value().unwrap().get_name()
+                    );
+                }
+                read_partials(dir)?
+            }
+            None => BTreeMap::new(),
+        };
+        let template = match &self.template {
+            Some(path) => {
+                let Some(target) = self.target.checker() else {
+                    bail!(
+                        "--template renders a checker, which --target {} is not",
+                        self.target.to_possible_value().unwrap().get_name()
+                    );
+                };
+                let template = fs::read_to_string(path)
+                    .wrap_err_with(|| format!("failed to read the template {}", path.display()))?;
+                Some(Arc::new(Templated {
+                    target,
+                    template,
+                    encoding: self.encoding.into(),
+                    partials: partials.clone(),
+                }))
+            }
+            None => None,
+        };
+        let registered = backend::registry().names();
+        names
+            .into_iter()
+            .map(|name| match name {
+                // `--template` renders the checker of `--target`.
+                name if template
+                    .as_ref()
+                    .is_some_and(|template| template.name() == name) =>
+                {
+                    Ok(template.clone().unwrap() as Arc<dyn OutputBackend>)
+                }
+                // The built-in `sql` backend renders the `--sql-dialect`.
+                "sql" if !registered.contains(&"sql") => {
+                    Ok(Arc::new(sql::Sql(self.sql_dialect)) as Arc<dyn OutputBackend>)
+                }
+                // The built-in checkers embed their arrays as `--encoding`
+                // says, and are minified with `--minify`.
+                name if !registered.contains(&name) => match checker(name) {
+                    Some(target) => Ok(Arc::new(Encoded {
+                        target,
+                        encoding: self.encoding.into(),
+                        minify: self.minify,
+                        partials: partials.clone(),
+                    }) as Arc<dyn OutputBackend>),
+                    None => codegen::backend(name),
+                },
+                name => codegen::backend(name),
+            })
+            .collect()
+    }
+}
+
+/// The partials of the `.hbs` files of `dir`, named after their stems.
+fn read_partials(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut partials = BTreeMap::new();
+    let entries = fs::read_dir(dir)
+        .wrap_err_with(|| format!("failed to read the partials {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "hbs") {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let partial = fs::read_to_string(&path)
+                .wrap_err_with(|| format!("failed to read the partial {}", path.display()))?;
+            partials.insert(name, partial);
+        }
+    }
+    Ok(partials)
+}
+
+/// The built-in checker named `name`, `None` if there is none.
+fn checker(name: &str) -> Option<Target> {
+    codegen::Language::value_variants()
+        .iter()
+        .find(|language| language.backend() == name)
+        .and_then(|language| language.checker())
+}
+
+/// An output rendered along with the one of a build, by `--emit` or from
+/// an output of the config file sharing its lists.
+#[derive(Clone, Debug)]
+struct Emit {
+    /// Where to write it.
+    path: PathBuf,
+    /// Its backends, `typescript` standing for that of its target.
+    backends: Vec<String>,
+    /// Its target, that of the build if `None`.
+    target: Option<codegen::Language>,
+    /// The template of its checker.
+    template: Option<PathBuf>,
+}
+
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{:?} is not KEY=VALUE", s)),
+    }
+}
+
+fn parse_emit(s: &str) -> Result<Emit, String> {
+    let Some((target, path)) = s.split_once('=') else {
+        return Err(format!("{:?} is not TARGET=PATH", s));
+    };
+    if path.is_empty() {
+        return Err(format!("{:?} has no path", s));
+    }
+    let mut emit = Emit {
+        path: PathBuf::from(path),
+        backends: vec!["typescript".to_string()],
+        target: None,
+        template: None,
+    };
+    match target.ends_with(".hbs") {
+        true => emit.template = Some(PathBuf::from(target)),
+        false => emit.target = Some(codegen::Language::from_str(target, false)?),
+    }
+    Ok(emit)
+}
+
+impl BuildArgs {
+    /// The build of `emit` alone, with the other options of this one.
+    fn emitted(&self, emit: &Emit) -> BuildArgs {
+        let mut build = self.clone();
+        build.output = Some(emit.path.clone());
+        build.backends = emit.backends.clone();
+        build.target = emit.target.unwrap_or(self.target);
+        build.template = emit.template.clone();
+        build.emit_tests = false;
+        build.emits = Vec::new();
+        build
+    }
+}
+
+/// Loads and merges the lists of both families, less their exclusion
+/// lists.
+fn load(lists: &Lists, options: &InputOptions) -> Result<Networks> {
+    Ok(load_reported(lists, options)?.0)
+}
+
+/// Like [`load`], also returning the report of the lists, whose rows are
+/// those of the lists without their exclusion lists.
+fn load_reported(lists: &Lists, options: &InputOptions) -> Result<(Networks, Report)> {
+    let _span = telemetry::span("load");
+    let stdin = lists
+        .paths()
+        .filter(|path| *path == Path::new(input::STDIN));
+    if stdin.count() > 1 {
+        bail!("only one of the lists can be read from standard input");
+    }
+    let mut report = Report::default();
+    let mut networks = load_paths(lists.v4(), lists.v6(), options, &mut report)?;
+    let mut excluded = Report::default();
+    networks.exclude(&load_paths(
+        lists.exclude_v4(),
+        lists.exclude_v6(),
+        options,
+        &mut excluded,
+    )?);
+    report.warnings.append(&mut excluded.warnings);
+    report.invalid.append(&mut excluded.invalid);
+    report.inputs.append(&mut excluded.inputs);
+    for warning in &report.warnings {
+        tracing::warn!("{}", warning);
+    }
+    for invalid in &report.invalid {
+        tracing::warn!("skipping {}", invalid);
+    }
+    Ok((networks, report))
+}
+
+/// Loads and merges the files at `ipv4_paths` and `ipv6_paths`, keeping
+/// the networks of each in `report`, under the values the file gives them
+/// or else its name.
+fn load_paths(
+    ipv4_paths: &[PathBuf],
+    ipv6_paths: &[PathBuf],
+    options: &InputOptions,
+    report: &mut Report,
+) -> Result<Networks> {
+    let mut networks = Networks::default();
+    let lists = ipv4_paths.iter().map(|path| (path, Family::V4));
+    for (path, family) in lists.chain(ipv6_paths.iter().map(|path| (path, Family::V6))) {
+        let mut list = load_input(path, family, options, report)?;
+        networks.merge(&list);
+        let values = std::mem::take(&mut report.values);
+        for (_, valued) in &values {
+            list.exclude(valued);
+        }
+        // A file valuing all its networks does not name any of them.
+        if values.is_empty() || !list.v4.is_empty() || !list.v6.is_empty() {
+            report.lists.push((list_name(path), list));
+        }
+        report.lists.extend(values);
+    }
+    Ok(networks)
+}
+
+/// The name of the list at `path`: its file name without any extension,
+/// `tor-exits` for `feeds/tor-exits.csv.gz`.
+fn list_name(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    match name.split('.').next() {
+        Some(stem) if !stem.is_empty() => stem.to_string(),
+        _ => name.into_owned(),
+    }
+}
+
+/// The networks of `networks` labeled with the names of the lists in
+/// `lists` holding them, the later list's name where several hold the
+/// same network.
+fn list_values(lists: &[(String, Networks)], networks: &Networks) -> Values {
+    // What the build left out of the lists, usually far fewer networks
+    // than the lists hold, so that each list drops just those.
+    let mut removed = Networks::default();
+    for (_, list) in lists {
+        removed.merge(list);
+    }
+    removed.exclude(networks);
+
+    let mut values = Values::default();
+    for (name, list) in lists {
+        let index = value_index(&mut values, name);
+        let v4 = list.v4.exclude(&removed.v4);
+        values.v4.extend(v4.iter().map(|network| (network, index)));
+        let v6 = list.v6.exclude(&removed.v6);
+        values.v6.extend(v6.iter().map(|network| (network, index)));
+    }
+    values
+}
+
+/// The index of `name` in the names of `values`, adding it if new.
+fn value_index(values: &mut Values, name: &str) -> usize {
+    match values.names.iter().position(|known| known == name) {
+        Some(index) => index,
+        None => {
+            values.names.push(name.to_string());
+            values.names.len() - 1
+        }
+    }
+}
+
+/// Like [`load`], then applies `policy` to reserved networks.
+fn load_filtered(
+    lists: &Lists,
+    options: &InputOptions,
+    policy: ReservedPolicy,
+) -> Result<Networks> {
+    let mut networks = load(lists, options)?;
+    apply_policy(&mut networks, policy);
+    Ok(networks)
+}
+
+/// Applies `policy` to the reserved networks of `networks`, warning about
+/// those found.
+fn apply_policy(networks: &mut Networks, policy: ReservedPolicy) {
+    for found in reserved::apply(networks, policy) {
+        match policy {
+            ReservedPolicy::Strip => tracing::warn!("stripping {}", found),
+            _ => tracing::warn!("{}", found),
+        }
+    }
+}
+
+/// The notes to render for `networks`: with `--geoip`, their countries and
+/// ASNs, and with `--asn-source`, the ASes originating them. With
+/// `--geoip`, the countries also become the `values` of their networks.
+fn notes(args: &BuildArgs, networks: &Networks, values: &mut Values) -> Result<Vec<Note>> {
+    #[cfg(feature = "geoip")]
+    let geo = args.geoip.annotate(networks)?;
+    #[cfg(feature = "geoip")]
+    country_values(&geo, values);
+    #[cfg(not(feature = "geoip"))]
+    let _ = values;
+    let origins = args.asn.load()?;
+    let all = networks.v4.iter().map(AnyNet::V4);
+    let all = all.chain(networks.v6.iter().map(AnyNet::V6));
+    Ok(all
+        .filter_map(|network| {
+            let network_str = network.to_string();
+            let mut parts = Vec::new();
+            #[cfg(feature = "geoip")]
+            if let Some(geo) = geo.get(&network_str) {
+                parts.push(geo.to_string());
+            }
+            if let Some(table) = &origins {
+                let asns: Vec<String> = table
+                    .origins(network)
+                    .iter()
+                    .map(|asn| format!("AS{}", asn))
+                    .collect();
+                if !asns.is_empty() {
+                    parts.push(format!("origin {}", asns.join(",")));
+                }
+            }
+            if parts.is_empty() {
+                return None;
+            }
+            Some(Note {
+                network: network_str,
+                note: parts.join("; "),
+            })
+        })
+        .collect())
+}
+
+/// Gives the networks `geo` has countries for those as their `values`, over
+/// the values of any of the networks they hold.
+#[cfg(feature = "geoip")]
+fn country_values(geo: &std::collections::HashMap<String, geoip::Geo>, values: &mut Values) {
+    let mut located = Networks::default();
+    let mut countries = Vec::new();
+    for (network, geo) in geo {
+        let (Some(country), Ok(network)) = (geo.country(), network.parse::<AnyNet>()) else {
+            continue;
+        };
+        match network {
+            AnyNet::V4(network) => {
+                located.v4.add(network);
+            }
+            AnyNet::V6(network) => {
+                located.v6.add(network);
+            }
+        }
+        countries.push((network, country));
+    }
+    located.v4.simplify();
+    located.v6.simplify();
+    values
+        .v4
+        .retain(|(network, _)| !located.v4.contains(network));
+    values
+        .v6
+        .retain(|(network, _)| !located.v6.contains(network));
+    // Sorted, for the same values from build to build.
+    countries.sort();
+    for (network, country) in countries {
+        let index = value_index(values, &country);
+        match network {
+            AnyNet::V4(network) => values.v4.push((network, index)),
+            AnyNet::V6(network) => values.v6.push((network, index)),
+        }
+    }
+}
+
+/// Builds the trie of `range` and flattens it into a node array, empty for
+/// a family without networks.
+fn flatten<N: IpNet>(range: &IpRange<N>, family: Family) -> Vec<usize> {
+    let trie = {
+        let _span = telemetry::span("trie").attr("family", family);
+        range.clone().into_trie().into_boxed_node()
+    };
+    let Some(trie) = trie else {
+        return Vec::new();
+    };
+    let mut span = telemetry::span("flatten").attr("family", family);
+    let nodes = trie_to_nodes(trie);
+    span.set("nodes", nodes.len());
+    nodes
+}
+
+/// Flattens `networks` once and renders the outputs of every backend of
+/// each of `targets` for its output path, stamped with `metadata`, with
+/// the `values` of the networks.
+fn render(
+    targets: &[(Vec<Arc<dyn OutputBackend>>, &Path)],
+    networks: &Networks,
+    notes: &[Note],
+    metadata: &Metadata,
+    values: &Values,
+) -> Result<Vec<Output>> {
+    let _span = telemetry::span("render");
+    let nodes_v4 = flatten(&networks.v4, Family::V4);
+    let nodes_v6 = flatten(&networks.v6, Family::V6);
+
+    let mut outputs: Vec<Output> = Vec::new();
+    for (backends, output) in targets {
+        let filters = Filters {
+            output,
+            v4: &networks.v4,
+            v6: &networks.v6,
+            nodes_v4: &nodes_v4,
+            nodes_v6: &nodes_v6,
+            notes,
+            metadata,
+            values,
+        };
+        for backend in backends {
+            let _span = telemetry::span("backend").attr("name", backend.name());
+            let rendered = backend
+                .render(&filters)
+                .map_err(|err| eyre!(err))
+                .wrap_err_with(|| format!("the {} backend failed", backend.name()))?;
+            for rendered in rendered {
+                if outputs.iter().any(|output| output.path == rendered.path) {
+                    bail!(
+                        "{} is rendered by more than one backend",
+                        rendered.path.display()
+                    );
+                }
+                outputs.push(rendered);
+            }
+        }
+    }
+    Ok(outputs)
+}
+
+/// The networks encoded in a checker rendered by the `typescript`, `esm`,
+/// `cjs`, `python`, `go`, `rust`, `c`, `java` or `lua` backend, or in an
+/// artifact, the JSON, the WebAssembly module, the MaxMind DB or the protobuf
+/// message, or in any of the lists, `None` for any other code.
+fn rendered_networks(code: &[u8]) -> Option<Networks> {
+    let binary = artifact::load(code)
+        .ok()
+        .or_else(|| wasm::networks(code))
+        .or_else(|| mmdb::networks(code))
+        .or_else(|| proto::decode(code).ok());
+    if let Some((v4, v6)) = binary {
+        return Some(Networks { v4, v6 });
+    }
+    if let Ok(json) = serde_json::from_slice::<codegen::JsonFilters>(code) {
+        return Some(Networks {
+            v4: flat::decode(&json.filter_v4).ok()?,
+            v6: flat::decode(&json.filter_v6).ok()?,
+        });
+    }
+    let code = std::str::from_utf8(code).ok()?;
+    let listed = proxy::vcl_networks(code)
+        .or_else(|| sql::sql_networks(code))
+        .or_else(|| canonical::list_networks(code));
+    if let Some(networks) = listed {
+        return Some(networks);
+    }
+    Some(Networks {
+        v4: rendered_filter(code, "IP_FILTER_V4")
+            .or_else(|| rendered_filter(code, "ipFilterV4"))?,
+        v6: rendered_filter(code, "IP_FILTER_V6")
+            .or_else(|| rendered_filter(code, "ipFilterV6"))?,
+    })
+}
+
+fn rendered_filter<N: IpNet>(code: &str, name: &str) -> Option<IpRange<N>> {
+    // The array is declared on a line of its own, with a type in
+    // TypeScript: `const IP_FILTER_V4: Uint32Array = new Uint32Array([...]);`,
+    // between braces in Go: `var ipFilterV4 = []uint32{...}`, and as a
+    // slice in Rust: `static IP_FILTER_V4: &[u32] = &[...];`, and in C:
+    // `static const uint32_t IP_FILTER_V4[] = {...};`. Java splits it into
+    // string chunks: `private static final int[] IP_FILTER_V4 =
+    // nodes("...", "...");`. Lua has a table: `local IP_FILTER_V4 = {...}`.
+    // With `--encoding base64`, the JavaScript arrays are decoded from a
+    // string: `const IP_FILTER_V4 = decodeNodes("...");`.
+    let line = code.lines().find(|line| {
+        let line = line.trim_start();
+        let line = line
+            .strip_prefix("private static final int[] ")
+            .unwrap_or(line);
+        let line = line.strip_prefix("const ").unwrap_or(line);
+        let line = line.strip_prefix("local ").unwrap_or(line);
+        let line = line.strip_prefix("var ").unwrap_or(line);
+        let line = line.strip_prefix("static ").unwrap_or(line);
+        let line = line.strip_prefix("const uint32_t ").unwrap_or(line);
+        line.strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with([':', ' ', '[']))
+    })?;
+    let start = line.rfind(['[', '{', '('])? + 1;
+    let len = line[start..].find([']', '}', ')'])?;
+    let array = &line[start..start + len];
+    let nodes = match line[..start].ends_with("decodeNodes(") {
+        true => base64_nodes(array.strip_prefix('"')?.strip_suffix('"')?)?,
+        false => array
+            .replace('"', "")
+            .split(',')
+            .map(|node| node.trim().parse().ok())
+            .collect::<Option<Vec<usize>>>()?,
+    };
+    flat::decode(&nodes).ok()
+}
+
+/// The node array of the base64 `encoded` of its little-endian 32-bit
+/// words, `None` if it is not one.
+fn base64_nodes(encoded: &str) -> Option<Vec<usize>> {
+    let mut bytes = Vec::new();
+    let (mut bits, mut len) = (0u32, 0);
+    for c in encoded.trim_end_matches('=').bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | u32::from(sextet);
+        len += 6;
+        if len >= 8 {
+            len -= 8;
+            bytes.push((bits >> len) as u8);
+        }
+    }
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    let words = bytes.chunks(4);
+    Some(
+        words
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()) as usize)
+            .collect(),
+    )
+}
+
+/// Returns `true` if `path` already holds exactly `code`.
+///
+/// A missing file counts as out of date.
+fn is_up_to_date(path: &Path, code: &[u8]) -> Result<bool> {
+    Ok(storage::read_if_exists(path)?.is_some_and(|existing| existing == code))
+}
+
+/// Loads the networks of `args` and renders every output from them.
+fn render_outputs(args: &BuildArgs) -> Result<(Networks, Vec<Output>)> {
+    let _span = telemetry::span("build").attr("output", args.output().display());
+    let (mut networks, report) = load_reported(&args.lists, &args.input)?;
+    apply_policy(&mut networks, args.reserved_policy);
+
+    let mut values = list_values(&report.lists, &networks);
+    let notes = notes(args, &networks, &mut values)?;
+    let metadata = Metadata {
+        timestamp: Civil::from_time(build_time()?).to_string(),
+        inputs: report.inputs,
+        vars: args.vars.iter().cloned().collect(),
+    };
+    let mut targets = vec![(args.backends()?, args.output())];
+    for emit in &args.emits {
+        targets.push((args.emitted(emit).backends()?, emit.path.as_path()));
+    }
+    let outputs = render(&targets, &networks, &notes, &metadata, &values)?;
+    Ok((networks, outputs))
+}
+
+/// When the build runs: the time of `SOURCE_DATE_EPOCH`, as reproducible
+/// builds set it, else now.
+fn build_time() -> Result<SystemTime> {
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => {
+            let seconds = epoch
+                .parse()
+                .wrap_err_with(|| format!("SOURCE_DATE_EPOCH={:?} is not a Unix time", epoch))?;
+            Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+        }
+        Err(_) => Ok(SystemTime::now()),
+    }
+}
+
+/// The builds `args` asks for: one per output of its `--config`, else
+/// itself.
+fn builds(args: &BuildArgs) -> Result<Vec<BuildArgs>> {
+    #[cfg(feature = "config")]
+    if let Some(path) = &args.config {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        return Ok(config::Config::load(path)?.builds(args, dir));
+    }
+    Ok(vec![args.clone()])
+}
+
+fn build(args: BuildArgs) -> Result<()> {
+    let builds = builds(&args)?;
+    if args.check && builds.iter().any(BuildArgs::to_stdout) {
+        bail!("--check needs an output file to compare, not standard output");
+    }
+    if args.watch {
+        return watch_builds(&builds);
+    }
+    let mut up_to_date = true;
+    for args in &builds {
+        let (networks, outputs) = render_outputs(args)?;
+        if !args.check {
+            write_outputs(args, &networks, outputs, args.force)?;
+            continue;
+        }
+        for rendered in &outputs {
+            if !is_up_to_date(&rendered.path, &rendered.contents)? {
+                eprintln!("{} is out of date", rendered.path.display());
+                up_to_date = false;
+            }
+        }
+    }
+    if !up_to_date {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Builds, then rebuilds whenever the lists change, until interrupted. A
+/// failed rebuild leaves the outputs in place.
+fn watch_builds(builds: &[BuildArgs]) -> Result<()> {
+    let mut paths: Vec<&Path> = Vec::new();
+    for args in builds {
+        for path in args.lists.paths() {
+            if !paths.contains(&path.as_path()) {
+                paths.push(path);
+            }
+        }
+    }
+    if paths.contains(&Path::new(input::STDIN)) {
+        bail!("--watch cannot watch standard input");
+    }
+    for args in builds {
+        let (networks, outputs) = render_outputs(args)?;
+        write_outputs(args, &networks, outputs, args.force)?;
+    }
+    tracing::info!("watching {} lists", paths.len());
+    watch::on_change(&paths, || {
+        for args in builds {
+            let rebuilt = render_outputs(args)
+                .and_then(|(networks, outputs)| write_outputs(args, &networks, outputs, true));
+            match rebuilt {
+                Ok(()) => tracing::info!("rebuilt {}", args.output().display()),
+                Err(err) => tracing::warn!(
+                    "rebuild of {} failed, keeping its outputs: {:#}",
+                    args.output().display(),
+                    err
+                ),
+            }
+        }
+    })
+}
+
+/// Writes the `outputs` that are out of date, then notifies the webhooks.
+/// Existing outputs are only replaced with `force`.
+#[cfg_attr(not(feature = "webhook"), allow(unused_variables))]
+fn write_outputs(
+    args: &BuildArgs,
+    networks: &Networks,
+    outputs: Vec<Output>,
+    force: bool,
+) -> Result<()> {
+    if args.to_stdout() {
+        return write_stdout(outputs);
+    }
+
+    #[cfg(feature = "webhook")]
+    let previous = if args.webhook.enabled() {
+        storage::read_if_exists(args.output())?
+    } else {
+        None
+    };
+
+    // Check every output before writing any, so a refusal leaves them all
+    // untouched.
+    let mut pending = Vec::new();
+    for rendered in outputs {
+        if is_up_to_date(&rendered.path, &rendered.contents)? {
+            continue;
+        }
+        if !force && storage::exists(&rendered.path)? {
+            bail!(
+                "{} already exists and differs from the rendered code (pass --force to overwrite it)",
+                rendered.path.display()
+            );
+        }
+        pending.push(rendered);
+    }
+    let mut written = Vec::new();
+    for rendered in pending {
+        output::write_atomic(&rendered.path, &rendered.contents)?;
+        written.push(rendered.path);
+    }
+
+    #[cfg(feature = "webhook")]
+    if args.webhook.enabled() && !written.is_empty() {
+        let summary = webhook::Summary::new(networks, previous.as_deref(), written)?;
+        args.webhook.notify(&summary)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the one output of a build to `-` to standard output. Backends
+/// writing files beside it have nowhere to write them.
+fn write_stdout(outputs: Vec<Output>) -> Result<()> {
+    if let Some(beside) = outputs
+        .iter()
+        .find(|rendered| rendered.path != Path::new(output::STDOUT))
+    {
+        bail!(
+            "{} cannot be written beside an output on standard output",
+            beside.path.display()
+        );
+    }
+    let mut stdout = io::stdout().lock();
+    for rendered in outputs {
+        stdout.write_all(&rendered.contents)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse_args(env::args_os());
+    log::init(cli.level(), cli.progress);
+    match cli.command {
+        Command::Build(args) => build(args),
+        Command::Check(args) => check::check(args),
+        Command::Stats(args) => stats::stats(args),
+        Command::Diff(args) => diff::diff(args),
+        Command::Validate(args) => validate::validate(args),
+        Command::Daemon(args) => daemon::daemon(args),
+        #[cfg(feature = "serve")]
+        Command::Serve(args) => serve::serve(args),
+        #[cfg(feature = "redis")]
+        Command::SyncRedis(args) => redis_sync::sync(args),
+        #[cfg(feature = "sqlite")]
+        Command::Export {
+            format: ExportFormat::Sqlite(args),
+        } => sqlite::export(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipcheck_rs::flat::FORMAT_VERSION;
+    use std::slice;
+
+    fn build_args<I, T>(args: I) -> BuildArgs
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        match Cli::parse_args(args).command {
+            Command::Build(args) => args,
+            #[allow(unreachable_patterns)]
+            _ => panic!("not a build invocation"),
+        }
+    }
+
+    /// The TypeScript checker of `networks`.
+    pub fn render_typescript(networks: &Networks, notes: Vec<Note>) -> Result<String> {
+        let typescript = codegen::backend("typescript")?;
+        let targets = [(vec![typescript], Path::new("ipcheck.ts"))];
+        let outputs = render(
+            &targets,
+            networks,
+            &notes,
+            &Default::default(),
+            &Default::default(),
+        )?;
+        Ok(String::from_utf8(
+            outputs.into_iter().next().unwrap().contents,
+        )?)
+    }
+
+    #[test]
+    fn up_to_date_compares_contents() {
+        let path = std::env::temp_dir().join(format!("ipcheck-check-{}.ts", std::process::id()));
+        assert!(!is_up_to_date(&path, b"code").unwrap());
+
+        fs::write(&path, "code").unwrap();
+        assert!(is_up_to_date(&path, b"code").unwrap());
+        assert!(!is_up_to_date(&path, b"other code").unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn render_ignores_row_order() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let write = |name: &str, rows: &[&str]| {
+            let path = dir.join(format!("ipcheck-order-{}-{}.csv", name, pid));
+            fs::write(&path, format!("network\n{}\n", rows.join("\n"))).unwrap();
+            path
+        };
+        let v4 = [
+            "10.0.0.0/8",
+            "192.168.1.0/24",
+            "192.168.0.0/24",
+            "10.9.0.0/16",
+        ];
+        let v6 = ["2001:db8::/33", "2001:db8:8000::/33", "fe80::/10"];
+        let mut v4_rev = v4;
+        v4_rev.reverse();
+        let mut v6_rev = v6;
+        v6_rev.reverse();
+
+        let paths = [
+            write("v4", &v4),
+            write("v6", &v6),
+            write("v4-rev", &v4_rev),
+            write("v6-rev", &v6_rev),
+        ];
+        let args = build_args(["ipcheck", "v4.csv", "v6.csv", "out.ts"]);
+        let options = args.input;
+        let forward = render_typescript(
+            &load_paths(&paths[0..1], &paths[1..2], &options, &mut Report::default()).unwrap(),
+            Vec::new(),
+        )
+        .unwrap();
+        let backward = render_typescript(
+            &load_paths(&paths[2..3], &paths[3..4], &options, &mut Report::default()).unwrap(),
+            Vec::new(),
+        )
+        .unwrap();
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn render_embeds_format_version() {
+        let path = std::env::temp_dir().join(format!("ipcheck-version-{}.csv", std::process::id()));
+        fs::write(&path, "network\n10.0.0.0/8\n").unwrap();
+        let v6 = path.with_extension("v6.csv");
+        fs::write(&v6, "network\n2001:db8::/32\n").unwrap();
+        let args = build_args(["ipcheck", "a", "b", "c"]);
+        let code = render_typescript(
+            &load_paths(
+                slice::from_ref(&path),
+                slice::from_ref(&v6),
+                &args.input,
+                &mut Report::default(),
+            )
+            .unwrap(),
+            Vec::new(),
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&v6).unwrap();
+        assert!(code.contains(&format!(
+            "export const FORMAT_VERSION = {};",
+            FORMAT_VERSION
+        )));
+        assert!(code.contains(&format!(
+            "const SUPPORTED_FORMAT_VERSION = {};",
+            FORMAT_VERSION
+        )));
+    }
+
+    #[test]
+    fn render_whole_family_as_root_leaf() {
+        let networks = Networks {
+            v4: ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: ["::/0"].iter().map(|s| s.parse().unwrap()).collect(),
+        };
+        let code = render_typescript(&networks, Vec::new()).unwrap();
+        assert!(code.contains("const IP_FILTER_V4: Uint32Array = new Uint32Array([0,0]);"));
+        assert!(code.contains("const IP_FILTER_V6: Uint32Array = new Uint32Array([0,0]);"));
+    }
+
+    #[test]
+    fn render_lists_notes_above_the_filters() {
+        let networks = Networks {
+            v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: ["::/0"].iter().map(|s| s.parse().unwrap()).collect(),
+        };
+        let notes = vec![Note {
+            network: "10.0.0.0/8".to_string(),
+            note: "US AS64500".to_string(),
+        }];
+        let code = render_typescript(&networks, notes).unwrap();
+        assert!(code.contains(
+            "// Networks covered by the filters below:\n//   10.0.0.0/8: US AS64500\n\n// Node arrays"
+        ));
+        assert!(!render_typescript(&networks, Vec::new())
+            .unwrap()
+            .contains("Networks covered"));
+    }
+
+    #[test]
+    fn build_refuses_to_clobber_without_force() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-clobber-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (v4, v6, out) = (dir.join("v4.csv"), dir.join("v6.csv"), dir.join("out.ts"));
+        fs::write(&v6, "network\n2606:4700::/32\n").unwrap();
+        let build_with = |extra: &[&str]| {
+            let mut args = vec!["ipcheck"];
+            args.extend_from_slice(extra);
+            args.extend([
+                v4.to_str().unwrap(),
+                v6.to_str().unwrap(),
+                out.to_str().unwrap(),
+            ]);
+            build(build_args(args))
+        };
+
+        fs::write(&v4, "network\n8.8.8.0/24\n").unwrap();
+        build_with(&[]).unwrap();
+        let first = fs::read_to_string(&out).unwrap();
+        // Rebuilding unchanged inputs is not clobbering.
+        build_with(&[]).unwrap();
+
+        fs::write(&v4, "network\n1.1.1.0/24\n").unwrap();
+        let err = build_with(&[]).unwrap_err().to_string();
+        assert!(err.contains("pass --force"), "{}", err);
+        assert_eq!(fs::read_to_string(&out).unwrap(), first);
+
+        build_with(&["--force"]).unwrap();
+        assert_ne!(fs::read_to_string(&out).unwrap(), first);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct Lines;
+
+    impl OutputBackend for Lines {
+        fn name(&self) -> &'static str {
+            "lines"
+        }
+
+        fn render(&self, filters: &Filters) -> Result<Vec<Output>, ipcheck_rs::backend::Error> {
+            let v4 = filters.v4.iter().map(|network| network.to_string());
+            let v6 = filters.v6.iter().map(|network| network.to_string());
+            let lines: Vec<String> = v4.chain(v6).collect();
+            Ok(vec![Output {
+                path: filters.output.with_extension("txt"),
+                contents: format!("{}\n", lines.join("\n")).into_bytes(),
+            }])
+        }
+    }
+
+    #[test]
+    fn build_routes_outputs_through_backends() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-backends-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (v4, v6, out) = (dir.join("v4.csv"), dir.join("v6.csv"), dir.join("out.ts"));
+        fs::write(&v4, "network\n8.8.8.0/24\n").unwrap();
+        fs::write(&v6, "network\n2606:4700::/32\n").unwrap();
+        let args = |extra: &[&str]| {
+            let mut args = vec!["ipcheck"];
+            args.extend_from_slice(extra);
+            args.extend([
+                v4.to_str().unwrap(),
+                v6.to_str().unwrap(),
+                out.to_str().unwrap(),
+            ]);
+            build_args(args)
+        };
+
+        ipcheck_rs::backend::register(Lines);
+        build(args(&[
+            "--backend=lines",
+            "--backend=artifact",
+            "--emit-tests",
+        ]))
+        .unwrap();
+        assert!(!out.exists());
+        assert_eq!(
+            fs::read_to_string(out.with_extension("txt")).unwrap(),
+            "8.8.8.0/24\n2606:4700::/32\n"
+        );
+        let networks = artifact::load(&fs::read(out.with_extension("bin")).unwrap());
+        assert_eq!(networks.unwrap().0.iter().count(), 1);
+        assert!(dir.join("out.test.ts").exists());
+
+        let err = build(args(&["--backend=cobol"])).unwrap_err().to_string();
+        assert!(
+            err.starts_with(
+                "unknown backend \"cobol\" (expected typescript, esm, cjs, python, go, rust, c, java, lua, node-test, lookup, artifact, json, wasm, mmdb, proto, nftables, ipset, bpf, vcl, haproxy, unbound, rpz, text, csv, sql, lines"
+            ),
+            "{}",
+            err
+        );
+        let err = build(args(&["--backend=typescript", "--backend=typescript"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("rendered by more than one backend"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn digest(data: &[u8]) -> String {
+        let mut hash = sha256::Sha256::default();
+        hash.update(data);
+        hash.hex()
+    }
+
+    #[test]
+    fn build_emits_outputs() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-emit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (v4, v6) = (dir.join("v4.csv"), dir.join("v6.csv"));
+        fs::write(&v4, "network\n8.8.8.0/24\n").unwrap();
+        fs::write(&v6, "network\n2606:4700::/32\n").unwrap();
+        let template = dir.join("wrapper.hbs");
+        fs::write(
+            &template,
+            "// {{ vars.team }}, {{ build.countV4 }}: \
+             {{#each build.inputs}}{{ sha256 }} {{/each}}\n\
+             export default {{ lenV4 }};\n",
+        )
+        .unwrap();
+        let out = dir.join("out.ts");
+        let emit =
+            |target: &str, path: &str| format!("{}={}", target, dir.join(path).to_str().unwrap());
+        let (json, bin) = (emit("json", "filters.json"), emit("bin", "filter.bin"));
+        let wrapper = emit(template.to_str().unwrap(), "wrapper.ts");
+        let args = |extra: &[&str]| {
+            let mut args = vec!["ipcheck"];
+            args.extend_from_slice(extra);
+            args.extend([
+                v4.to_str().unwrap(),
+                v6.to_str().unwrap(),
+                out.to_str().unwrap(),
+            ]);
+            build_args(args)
+        };
+
+        build(args(&[
+            "--emit",
+            &json,
+            "--emit",
+            &bin,
+            "--emit",
+            &wrapper,
+            "--var",
+            "team=net=sec",
+        ]))
+        .unwrap();
+        for path in ["out.ts", "filters.json", "filter.bin"] {
+            let networks = rendered_networks(&fs::read(dir.join(path)).unwrap()).unwrap();
+            assert_eq!(networks.v4.iter().count(), 1, "{}", path);
+        }
+        assert_eq!(
+            fs::read_to_string(dir.join("wrapper.ts")).unwrap(),
+            format!(
+                "// net=sec, 1: {} {} \nexport default 50;\n",
+                digest(b"network\n8.8.8.0/24\n"),
+                digest(b"network\n2606:4700::/32\n")
+            )
+        );
+
+        let same = emit("ts", "out.ts");
+        let err = build(args(&["--emit", &same, "--force"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("rendered by more than one backend"), "{}", err);
+        assert!(parse_emit("json").is_err());
+        assert!(parse_var("=x").is_err());
+        assert!(parse_emit("cobol=out.cob").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The value `values` give the IPv4 address `ip`, looked up in their
+    /// trie as the `lookup` checker does.
+    fn lookup_v4<'a>(values: &'a Values, ip: &str) -> Option<&'a str> {
+        let ip = u32::from(ip.parse::<std::net::Ipv4Addr>().unwrap());
+        let (nodes, indexes) = flat::valued_nodes(&values.v4);
+        let (mut node, mut found) = (0, indexes.first().copied().unwrap_or(0));
+        for bit in (0..32).rev().map(|bit| (ip >> bit) & 1) {
+            node = nodes[node * 2 + bit as usize];
+            if node == 0 {
+                break;
+            }
+            if indexes[node] != 0 {
+                found = indexes[node];
+            }
+        }
+        found
+            .checked_sub(1)
+            .map(|index| values.names[index].as_str())
+    }
+
+    #[test]
+    fn build_value_lookup() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-lookup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (corp, tor) = (dir.join("corp.csv"), dir.join("tor-exits.csv"));
+        fs::write(&corp, "network\n192.0.2.0/24\n203.0.113.0/24\n").unwrap();
+        fs::write(&tor, "network\n192.0.2.128/25\n").unwrap();
+        let out = dir.join("lookup.ts");
+        build(build_args([
+            "ipcheck",
+            "--target",
+            "lookup",
+            "--v4",
+            corp.to_str().unwrap(),
+            "--v4",
+            tor.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(
+            code.contains("\nexport const VALUES: readonly string[] = [\"corp\",\"tor-exits\"];\n"),
+            "{}",
+            code
+        );
+        assert_eq!(list_name(Path::new("feeds/tor-exits.csv.gz")), "tor-exits");
+
+        let list = |networks: &[&str]| Networks {
+            v4: networks.iter().map(|s| s.parse().unwrap()).collect(),
+            v6: IpRange::new(),
+        };
+        let lists = [
+            (
+                "corp".to_string(),
+                list(&["192.0.2.0/24", "203.0.113.0/24"]),
+            ),
+            ("tor-exits".to_string(), list(&["192.0.2.128/25"])),
+        ];
+        // Networks excluded from the build have no value.
+        let values = list_values(&lists, &list(&["192.0.2.0/24"]));
+        assert_eq!(values.names, ["corp", "tor-exits"]);
+        assert_eq!(
+            values.v4,
+            [
+                ("192.0.2.0/24".parse().unwrap(), 0),
+                ("192.0.2.128/25".parse().unwrap(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn build_spamhaus_lookup() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-drop-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let drop = dir.join("drop.txt");
+        fs::write(
+            &drop,
+            "; Spamhaus DROP List 2024/01/01 - (c) 2024 The Spamhaus Project SLU\n\
+             ; https://www.spamhaus.org/drop/drop.txt\n\
+             1.10.16.0/20 ; SBL256894\n\
+             1.19.0.0/16 ; SBL434604\n",
+        )
+        .unwrap();
+        let out = dir.join("drop.ts");
+        let args = build_args([
+            "ipcheck",
+            "--target",
+            "lookup",
+            "--v4",
+            drop.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ]);
+        build(args.clone()).unwrap();
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(
+            code.contains(
+                "\nexport const VALUES: readonly string[] = [\"SBL256894\",\"SBL434604\"];\n"
+            ),
+            "{}",
+            code
+        );
+
+        let (networks, report) = load_reported(&args.lists, &args.input).unwrap();
+        let values = list_values(&report.lists, &networks);
+        assert_eq!(lookup_v4(&values, "1.10.20.30"), Some("SBL256894"));
+        assert_eq!(lookup_v4(&values, "1.19.255.255"), Some("SBL434604"));
+        assert_eq!(lookup_v4(&values, "1.20.0.0"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_checker_targets() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-targets-{}", std::process::id()));
+        fs::create_dir_all(dir.join("ipfilter")).unwrap();
+        let (v4, v6) = (dir.join("v4.csv"), dir.join("v6.csv"));
+        fs::write(&v4, "network\n8.8.8.0/24\n").unwrap();
+        fs::write(&v6, "network\n2606:4700::/32\n").unwrap();
+        let args = |target, out: &Path, extra: &[&str]| {
+            let mut args = vec!["ipcheck", "--target", target];
+            args.extend_from_slice(extra);
+            args.extend([
+                v4.to_str().unwrap(),
+                v6.to_str().unwrap(),
+                out.to_str().unwrap(),
+            ]);
+            build_args(args)
+        };
+        for (target, out, also) in [
+            ("esm", "browser.js", "browser.d.ts"),
+            ("cjs", "node.cjs", "node.d.cts"),
+            ("python", "etl.py", "etl.py"),
+            ("go", "ipfilter/contains.go", "ipfilter/contains.go"),
+            ("rust", "ipfilter.rs", "ipfilter.rs"),
+            ("c", "ipfilter.h", "ipfilter.h"),
+            ("java", "IpFilter.java", "IpFilter.java"),
+            ("lua", "ipfilter.lua", "ipfilter.lua"),
+            ("bin", "filter.bin", "filter.bin"),
+            ("json", "filters.json", "filters.json"),
+            ("wasm", "filter.wasm", "filter.js"),
+            ("mmdb", "blocklist.mmdb", "blocklist.mmdb"),
+            ("proto", "filter.pb", "filter.pb"),
+            ("vcl", "blocklist.vcl", "blocklist.vcl"),
+            ("text", "minimized.txt", "minimized.txt"),
+            ("csv", "minimized.csv", "minimized.csv"),
+            ("sql", "ranges.sql", "ranges.sql"),
+        ] {
+            let out = dir.join(out);
+            build(args(target, &out, &[])).unwrap();
+            let code = fs::read(&out).unwrap();
+            let networks = rendered_networks(&code).unwrap();
+            assert_eq!(networks.v6.iter().count(), 1, "{}", target);
+            assert!(dir.join(also).exists(), "{}", target);
+        }
+        build(args(
+            "cjs",
+            &dir.join("node.cjs"),
+            &["--emit-tests", "--force"],
+        ))
+        .unwrap();
+        assert!(dir.join("node.test.mjs").exists());
+        let out = dir.join("encoded.js");
+        build(args("esm", &out, &["--encoding", "base64"])).unwrap();
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(code.contains("= decodeNodes(\""), "{}", code);
+        let networks = rendered_networks(code.as_bytes()).unwrap();
+        assert_eq!(networks.v4.iter().count(), 1);
+        assert_eq!(networks.v6.iter().count(), 1);
+        assert_eq!(base64_nodes("AQAAAAECAwQ="), Some(vec![1, 0x0403_0201]));
+        assert_eq!(base64_nodes("AQAA"), None);
+        build(args("haproxy", &dir.join("blocklist.acl"), &[])).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("blocklist.v6.acl"))
+                .unwrap()
+                .lines()
+                .last(),
+            Some("2606:4700::/32")
+        );
+        assert!(!dir.join("blocklist.acl").exists());
+        let ranges = dir.join("ranges.sql");
+        build(args("sql", &ranges, &["--sql-dialect", "mysql", "--force"])).unwrap();
+        assert!(fs::read_to_string(&ranges)
+            .unwrap()
+            .contains("START TRANSACTION;"));
+        let template = dir.join("wrapper.hbs");
+        fs::write(&template, "module.exports = {{ lenV6 }};\n").unwrap();
+        let wrapper = dir.join("wrapper.js");
+        let template = ["--template", template.to_str().unwrap()];
+        build(args("cjs", &wrapper, &template)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&wrapper).unwrap(),
+            "module.exports = 66;\n"
+        );
+        assert!(!dir.join("wrapper.d.ts").exists());
+        let err = build(args("sql", &ranges, &template))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "--template renders a checker, which --target sql is not"
+        );
+        let err = build(args("python", &dir.join("etl.py"), &["--emit-tests"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot test"), "{}", err);
+        let err = build(args("cjs", &dir.join("node.cjs"), &["--minify"]))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "--minify renders an ES module, which --target cjs is not"
+        );
+        let edge = dir.join("edge.mjs");
+        build(args("esm", &edge, &["--minify"])).unwrap();
+        assert!(fs::read_to_string(&edge)
+            .unwrap()
+            .contains("export default function(s){"));
+        assert!(dir.join("edge.d.mts").exists());
+        let partials = dir.join("partials");
+        fs::create_dir_all(&partials).unwrap();
+        fs::write(partials.join("header.hbs"), "// {{ vars.team }}\n").unwrap();
+        fs::write(partials.join("notes.txt"), "not a partial").unwrap();
+        let custom = dir.join("custom.ts");
+        let options = [
+            "--partials",
+            partials.to_str().unwrap(),
+            "--var",
+            "team=netsec",
+        ];
+        build(args("ts", &custom, &options)).unwrap();
+        let code = fs::read_to_string(&custom).unwrap();
+        assert!(code.starts_with("// netsec\n/**\n"), "{}", code);
+        let err = build(args("go", &dir.join("ipfilter/contains.go"), &options))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "--partials replaces those of the TypeScript checker, which --target go is not"
+        );
+        let err = build(args(
+            "go",
+            &dir.join("ipfilter/contains.go"),
+            &["--emit-tests"],
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("the output of --target go"), "{}", err);
+
+        // Rebuilding the same networks keeps the time they were generated.
+        let json = dir.join("filters.json");
+        let stale = fs::read_to_string(&json).unwrap().replacen(
+            r#""generatedAt":"2"#,
+            r#""generatedAt":"1"#,
+            1,
+        );
+        fs::write(&json, &stale).unwrap();
+        build(args("json", &json, &["--force"])).unwrap();
+        assert_eq!(fs::read_to_string(&json).unwrap(), stale);
+        let database = dir.join("blocklist.mmdb");
+        let networks = rendered_networks(&fs::read(&database).unwrap()).unwrap();
+        let stale = mmdb::database(&networks.v4, &networks.v6, &[], 1).unwrap();
+        fs::write(&database, &stale).unwrap();
+        build(args("mmdb", &database, &["--force"])).unwrap();
+        assert_eq!(fs::read(&database).unwrap(), stale);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stdout_takes_one_output() {
+        let output = |path: &str| Output {
+            path: PathBuf::from(path),
+            contents: b"code".to_vec(),
+        };
+        let err = write_stdout(vec![output("-"), output("-.test.ts")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "-.test.ts cannot be written beside an output on standard output"
+        );
+        assert!(build_args(["ipcheck", "a.csv", "b.csv", "-"]).to_stdout());
+    }
+
+    #[test]
+    fn paths_by_flag_or_position() {
+        let paths = |args: &BuildArgs| {
+            [&args.lists.v4()[0], &args.lists.v6()[0], args.output()]
+                .map(|path| path.to_str().unwrap().to_string())
+        };
+        let expected = ["a.csv", "b.csv", "out.ts"].map(String::from);
+        for args in [
+            &["ipcheck", "a.csv", "b.csv", "out.ts"][..],
+            &[
+                "ipcheck", "build", "--v4", "a.csv", "--v6", "b.csv", "-o", "out.ts",
+            ],
+            &["ipcheck", "--output", "out.ts", "a.csv", "b.csv"],
+            &["ipcheck", "build", "--v6", "b.csv", "a.csv", "out.ts"],
+        ] {
+            assert_eq!(paths(&build_args(args)), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn either_family_may_be_left_out() {
+        let args = build_args(["ipcheck", "build", "--v4", "a.csv", "out.ts"]);
+        assert_eq!(args.lists.v4(), [PathBuf::from("a.csv")]);
+        assert!(args.lists.v6().is_empty());
+        assert_eq!(args.output(), Path::new("out.ts"));
+        let args = build_args(["ipcheck", "--v6", "b.csv", "a.csv", "out.ts"]);
+        assert_eq!(args.lists.v4(), [PathBuf::from("a.csv")]);
+
+        let networks = Networks {
+            v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: IpRange::new(),
+        };
+        let code = render_typescript(&networks, Vec::new()).unwrap();
+        assert!(code.contains("IP_FILTER_V6: Uint32Array = new Uint32Array([]);"));
+    }
+
+    #[test]
+    fn repeated_lists_are_merged_less_exclusions() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-merge-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let a = write("a.csv", "network\n10.0.0.0/9\n192.168.0.0/24\n");
+        let b = write("b.csv", "network\n10.128.0.0/9\n");
+        let v6 = write("v6.csv", "network\n2001:db8::/32\n");
+        let allow = write("allow.csv", "network\n192.168.0.128/25\n");
+        let allow6 = write("allow6.csv", "network\n2001:db8::/33\n");
+        let v4 = |networks: &Networks| -> Vec<String> {
+            networks.v4.iter().map(|net| net.to_string()).collect()
+        };
+        let args = build_args([
+            "ipcheck", "build", "--v4", &a, "--v4", &b, "--v6", &v6, "-o", "out.ts",
+        ]);
+        assert_eq!(args.lists.v4().len(), 2);
+        let networks = load(&args.lists, &args.input).unwrap();
+        assert_eq!(v4(&networks), ["10.0.0.0/8", "192.168.0.0/24"]);
+
+        let args = build_args([
+            "ipcheck",
+            "--exclude-v4",
+            &allow,
+            "--exclude-v6",
+            &allow6,
+            &a,
+            &v6,
+            "out.ts",
+        ]);
+        let networks = load(&args.lists, &args.input).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(v4(&networks), ["10.0.0.0/9", "192.168.0.0/25"]);
+        let v6: Vec<String> = networks.v6.iter().map(|net| net.to_string()).collect();
+        assert_eq!(v6, ["2001:db8:8000::/33"]);
+
+        let args = build_args(["ipcheck", "--exclude-v4", "-", "-", "b.csv", "out.ts"]);
+        let err = load(&args.lists, &args.input).unwrap_err();
+        assert!(err.to_string().contains("standard input"), "{}", err);
+    }
+
+    #[test]
+    fn global_flags_come_before_or_after_the_subcommand() {
+        let levels = |args: &[&str]| Cli::parse_args(args).level();
+        assert_eq!(
+            levels(&["ipcheck", "-q", "a.csv", "b.csv", "out.ts"]),
+            log::Level::Quiet
+        );
+        assert_eq!(
+            levels(&["ipcheck", "--verbose", "stats", "a.csv", "b.csv"]),
+            log::Level::Verbose
+        );
+        assert_eq!(
+            levels(&["ipcheck", "build", "a.csv", "b.csv", "out.ts", "--progress"]),
+            log::Level::Normal
+        );
+        assert!(matches!(
+            Cli::parse_args(["ipcheck", "-q", "stats", "a.csv", "b.csv"]).command,
+            Command::Stats(_)
+        ));
+    }
+
+    #[test]
+    fn input_format_flag() {
+        for flag in ["--format", "--input-format"] {
+            let args = build_args(["ipcheck", flag, "text", "v4.txt", "v6.txt", "out.ts"]);
+            assert_eq!(args.input.input_format.as_deref(), Some("text"));
+        }
+        for flag in ["--key", "--json-key"] {
+            let args = build_args(["ipcheck", flag, "prefix", "v4.json", "v6.json", "out.ts"]);
+            assert_eq!(args.input.json_key.as_deref(), Some("prefix"));
+        }
+    }
+
+    /// Writes GeoLite2 Country blocks of a Chinese and a German network to
+    /// `dir`, returning the path of the blocks file.
+    fn geolite2_blocks(dir: &Path) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            dir.join("GeoLite2-Country-Locations-en.csv"),
+            "geoname_id,locale_code,continent_code,continent_name,country_iso_code,country_name,is_in_european_union\n\
+             1814991,en,AS,Asia,CN,China,0\n\
+             2921044,en,EU,Europe,DE,Germany,1\n",
+        )
+        .unwrap();
+        let blocks = dir.join("GeoLite2-Country-Blocks-IPv4.csv");
+        fs::write(
+            &blocks,
+            "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider\n\
+             1.0.1.0/24,1814991,1814991,,0,0\n\
+             2.16.0.0/24,2921044,2921044,,0,0\n",
+        )
+        .unwrap();
+        blocks
+    }
+
+    #[test]
+    fn build_geolite2_countries() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-geolite2-{}", std::process::id()));
+        let blocks = geolite2_blocks(&dir);
+        let out = dir.join("cn.txt");
+        build(build_args([
+            "ipcheck",
+            "--format",
+            "geolite2-country",
+            "--countries",
+            "CN",
+            "--target",
+            "text",
+            "--v4",
+            blocks.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ]))
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "1.0.1.0/24\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_geolite2_lookup() {
+        let dir =
+            std::env::temp_dir().join(format!("ipcheck-geolite2-lookup-{}", std::process::id()));
+        let blocks = geolite2_blocks(&dir);
+        let out = dir.join("countries.ts");
+        let args = build_args([
+            "ipcheck",
+            "--format",
+            "geolite2-country",
+            "--countries",
+            "CN,DE",
+            "--target",
+            "lookup",
+            "--v4",
+            blocks.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ]);
+        build(args.clone()).unwrap();
+        // The countries are the values, rather than the name of the file.
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(
+            code.contains("\nexport const VALUES: readonly string[] = [\"CN\",\"DE\"];\n"),
+            "{}",
+            code
+        );
+
+        let (networks, report) = load_reported(&args.lists, &args.input).unwrap();
+        let values = list_values(&report.lists, &networks);
+        assert_eq!(lookup_v4(&values, "1.0.1.7"), Some("CN"));
+        assert_eq!(lookup_v4(&values, "2.16.0.200"), Some("DE"));
+        assert_eq!(lookup_v4(&values, "192.0.2.1"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,246 @@
+//! Logging every match of `ipcheck serve --log-matches`, as an audit trail
+//! of what the lookup service answered.
+//!
+//! Each entry carries the client that asked, the address, the matching
+//! network and the feed it came from, as structured fields:
+//!
+//! * `--log-matches journald` sends them to the systemd journal as
+//!   `IPCHECK_CLIENT`, `IPCHECK_IP`, `IPCHECK_NETWORK` and `IPCHECK_FEED`,
+//!   for `journalctl IPCHECK_NETWORK=10.0.0.0/8`;
+//! * `--log-matches syslog` sends RFC 5424 messages to `/dev/log`, the
+//!   fields in an `ipcheck@32473` structured data element.
+//!
+//! Misses are not logged.
+
+// The log only has sinks on Unix.
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::net::SocketAddr;
+
+use clap::{Args, ValueEnum};
+use eyre::Result;
+
+use crate::reload::Source;
+use crate::serve::Lookup;
+
+#[cfg(unix)]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+#[cfg(unix)]
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Where matches are logged.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum LogTarget {
+    /// The systemd journal, with one field per detail.
+    Journald,
+    /// The local syslog daemon, as RFC 5424 structured data.
+    Syslog,
+}
+
+#[derive(Args)]
+pub struct MatchLogArgs {
+    /// Log every match, with the client, the matching network and its feed.
+    #[arg(long, value_enum, value_name = "TARGET")]
+    log_matches: Option<LogTarget>,
+}
+
+/// The match log, which does nothing without `--log-matches`.
+#[derive(Default)]
+pub struct MatchLog {
+    #[cfg(unix)]
+    sink: Option<Sink>,
+}
+
+#[cfg(unix)]
+struct Sink {
+    target: LogTarget,
+    socket: std::os::unix::net::UnixDatagram,
+    /// The feeds of IPv4 and IPv6 matches.
+    feeds: [String; 2],
+}
+
+/// One match to log.
+struct Entry<'a> {
+    client: String,
+    ip: &'a str,
+    network: &'a str,
+    feed: &'a str,
+}
+
+impl Entry<'_> {
+    fn message(&self) -> String {
+        format!(
+            "{} matches {} ({}), asked by {}",
+            self.ip, self.network, self.feed, self.client
+        )
+    }
+}
+
+impl MatchLog {
+    /// Opens the log chosen by `args`, naming the feeds of `source`.
+    #[cfg(unix)]
+    pub fn open(args: &MatchLogArgs, source: &Source) -> Result<MatchLog> {
+        use crate::input::Family;
+        use eyre::WrapErr;
+
+        let target = match args.log_matches {
+            Some(target) => target,
+            None => return Ok(MatchLog::default()),
+        };
+        let path = match target {
+            LogTarget::Journald => JOURNALD_SOCKET,
+            LogTarget::Syslog => SYSLOG_SOCKET,
+        };
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket
+            .connect(path)
+            .wrap_err_with(|| format!("failed to open the match log at {}", path))?;
+        let feed = |family| {
+            let paths: Vec<String> = source
+                .feed(family)
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            paths.join(",")
+        };
+        Ok(MatchLog {
+            sink: Some(Sink {
+                target,
+                socket,
+                feeds: [feed(Family::V4), feed(Family::V6)],
+            }),
+        })
+    }
+
+    /// Logs `lookup` if it matched; `client` is `None` when the transport
+    /// has no address for it, as on a Unix socket.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub fn record(&self, client: Option<SocketAddr>, lookup: &Lookup) {
+        #[cfg(unix)]
+        if let (Some(sink), Some(network)) = (&self.sink, &lookup.network) {
+            let entry = Entry {
+                client: client.map_or_else(|| "local".to_string(), |addr| addr.ip().to_string()),
+                ip: &lookup.ip,
+                network,
+                feed: &sink.feeds[usize::from(lookup.ip.contains(':'))],
+            };
+            let message = match sink.target {
+                LogTarget::Journald => journald_entry(&entry),
+                LogTarget::Syslog => syslog_entry(&entry),
+            };
+            if let Err(err) = sink.socket.send(&message) {
+                tracing::warn!("failed to log a match: {}", err);
+            }
+        }
+    }
+}
+
+/// An entry in the journal's native protocol: one `FIELD=value` per line.
+fn journald_entry(entry: &Entry) -> Vec<u8> {
+    let fields = [
+        ("MESSAGE", entry.message()),
+        ("PRIORITY", "6".to_string()),
+        ("SYSLOG_IDENTIFIER", "ipcheck".to_string()),
+        ("IPCHECK_CLIENT", entry.client.clone()),
+        ("IPCHECK_IP", entry.ip.to_string()),
+        ("IPCHECK_NETWORK", entry.network.to_string()),
+        ("IPCHECK_FEED", entry.feed.to_string()),
+    ];
+    let mut out = String::new();
+    for (name, value) in fields {
+        // Newlines would need the protocol's binary framing; no field
+        // legitimately contains one.
+        out.push_str(&format!("{}={}\n", name, value.replace('\n', " ")));
+    }
+    out.into_bytes()
+}
+
+/// An RFC 5424 message from the daemon facility at informational severity,
+/// leaving the timestamp and host for the syslog daemon to fill in.
+fn syslog_entry(entry: &Entry) -> Vec<u8> {
+    let param = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace(']', "\\]")
+    };
+    format!(
+        "<30>1 - - ipcheck {} match [ipcheck@32473 client=\"{}\" ip=\"{}\" network=\"{}\" feed=\"{}\"] {}",
+        std::process::id(),
+        param(&entry.client),
+        param(entry.ip),
+        param(entry.network),
+        param(entry.feed),
+        entry.message()
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry<'static> {
+        Entry {
+            client: "203.0.113.9".to_string(),
+            ip: "10.1.2.3",
+            network: "10.0.0.0/8",
+            feed: "feeds/\"v4\".csv",
+        }
+    }
+
+    #[test]
+    fn journald_fields() {
+        let text = String::from_utf8(journald_entry(&entry())).unwrap();
+        assert!(text.starts_with(
+            "MESSAGE=10.1.2.3 matches 10.0.0.0/8 (feeds/\"v4\".csv), asked by 203.0.113.9\n"
+        ));
+        assert!(text.contains("\nIPCHECK_CLIENT=203.0.113.9\n"));
+        assert!(text.contains("\nIPCHECK_NETWORK=10.0.0.0/8\n"));
+        assert!(text.ends_with("\nIPCHECK_FEED=feeds/\"v4\".csv\n"));
+    }
+
+    #[test]
+    fn syslog_structured_data() {
+        let text = String::from_utf8(syslog_entry(&entry())).unwrap();
+        assert!(text.starts_with("<30>1 - - ipcheck "));
+        assert!(text.contains(
+            " match [ipcheck@32473 client=\"203.0.113.9\" ip=\"10.1.2.3\" \
+             network=\"10.0.0.0/8\" feed=\"feeds/\\\"v4\\\".csv\"] 10.1.2.3 matches"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn record_only_matches() {
+        use std::os::unix::net::UnixDatagram;
+
+        let (socket, receiver) = UnixDatagram::pair().unwrap();
+        let log = MatchLog {
+            sink: Some(Sink {
+                target: LogTarget::Journald,
+                socket,
+                feeds: ["v4.csv".to_string(), "v6.csv".to_string()],
+            }),
+        };
+        let lookup = |ip: &str, network: Option<&str>| Lookup {
+            ip: ip.to_string(),
+            matches: network.is_some(),
+            network: network.map(str::to_string),
+        };
+        log.record(None, &lookup("8.8.8.8", None));
+        log.record(
+            Some("[::1]:4000".parse().unwrap()),
+            &lookup("2001:db8::1", Some("2001:db8::/32")),
+        );
+
+        let mut buf = [0; 512];
+        let len = receiver.recv(&mut buf).unwrap();
+        let text = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(text.contains("\nIPCHECK_IP=2001:db8::1\n"), "{}", text);
+        assert!(text.contains("\nIPCHECK_CLIENT=::1\n"));
+        assert!(text.contains("\nIPCHECK_FEED=v6.csv\n"));
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Prometheus metrics for `ipcheck serve`, exposed on `GET /metrics`.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ipcheck_rs::flat;
+use ipcheck_rs::{IpNet, IpRange};
+
+use crate::input::Networks;
+use crate::serve::Lookup;
+
+/// Counters shared by every listener, updated without locking.
+#[derive(Default)]
+pub struct Metrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    loaded_at: AtomicU64,
+    reload_failures: AtomicU64,
+    networks_v4: AtomicU64,
+    networks_v6: AtomicU64,
+    nodes_v4: AtomicU64,
+    nodes_v6: AtomicU64,
+}
+
+impl Metrics {
+    /// Counts one answered lookup.
+    pub fn record(&self, lookup: &Lookup) {
+        let counter = if lookup.matches {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the time and size of a freshly loaded set of networks.
+    pub fn loaded(&self, networks: &Networks) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.loaded_at.store(now, Ordering::Relaxed);
+        self.networks_v4
+            .store(networks.v4.iter().count() as u64, Ordering::Relaxed);
+        self.networks_v6
+            .store(networks.v6.iter().count() as u64, Ordering::Relaxed);
+        self.nodes_v4
+            .store(node_count(&networks.v4), Ordering::Relaxed);
+        self.nodes_v6
+            .store(node_count(&networks.v6), Ordering::Relaxed);
+    }
+
+    /// Counts a reload that failed, leaving the previous networks in place.
+    pub fn reload_failed(&self) {
+        self.reload_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        // NaN until the first lookup, as a ratio of nothing is undefined.
+        let ratio = hits as f64 / (hits + misses) as f64;
+
+        let mut out = String::new();
+        metric(
+            &mut out,
+            "ipcheck_lookups_total",
+            "counter",
+            "Lookups answered, by result.",
+            &[
+                ("result=\"hit\"", hits.to_string()),
+                ("result=\"miss\"", misses.to_string()),
+            ],
+        );
+        metric(
+            &mut out,
+            "ipcheck_lookup_hit_ratio",
+            "gauge",
+            "Fraction of lookups that matched a network.",
+            &[("", ratio.to_string())],
+        );
+        metric(
+            &mut out,
+            "ipcheck_last_load_timestamp_seconds",
+            "gauge",
+            "When the networks were last loaded, in seconds since the Unix epoch.",
+            &[("", self.loaded_at.load(Ordering::Relaxed).to_string())],
+        );
+        metric(
+            &mut out,
+            "ipcheck_reload_failures_total",
+            "counter",
+            "Reloads that failed and kept the previous networks.",
+            &[("", self.reload_failures.load(Ordering::Relaxed).to_string())],
+        );
+        metric(
+            &mut out,
+            "ipcheck_networks",
+            "gauge",
+            "Networks served, by family.",
+            &[
+                (
+                    "family=\"ipv4\"",
+                    self.networks_v4.load(Ordering::Relaxed).to_string(),
+                ),
+                (
+                    "family=\"ipv6\"",
+                    self.networks_v6.load(Ordering::Relaxed).to_string(),
+                ),
+            ],
+        );
+        metric(
+            &mut out,
+            "ipcheck_trie_nodes",
+            "gauge",
+            "Nodes in the lookup trie, by family.",
+            &[
+                (
+                    "family=\"ipv4\"",
+                    self.nodes_v4.load(Ordering::Relaxed).to_string(),
+                ),
+                (
+                    "family=\"ipv6\"",
+                    self.nodes_v6.load(Ordering::Relaxed).to_string(),
+                ),
+            ],
+        );
+        out
+    }
+}
+
+/// Appends one metric family, with a sample per `(labels, value)` pair.
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, String)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+fn node_count<N: IpNet>(range: &IpRange<N>) -> u64 {
+    flat::encode(range).len() as u64 / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(matches: bool) -> Lookup {
+        Lookup {
+            ip: "10.0.0.1".to_string(),
+            matches,
+            network: None,
+        }
+    }
+
+    #[test]
+    fn render_counts_and_sizes() {
+        let metrics = Metrics::default();
+        metrics.loaded(&Networks {
+            v4: ["10.0.0.0/8", "192.168.0.0/16"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: IpRange::new(),
+        });
+        metrics.record(&lookup(true));
+        metrics.record(&lookup(true));
+        metrics.record(&lookup(true));
+        metrics.record(&lookup(false));
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE ipcheck_lookups_total counter\n"));
+        assert!(text.contains("ipcheck_lookups_total{result=\"hit\"} 3\n"));
+        assert!(text.contains("ipcheck_lookups_total{result=\"miss\"} 1\n"));
+        assert!(text.contains("ipcheck_lookup_hit_ratio 0.75\n"));
+        assert!(text.contains("ipcheck_networks{family=\"ipv4\"} 2\n"));
+        assert!(text.contains("ipcheck_networks{family=\"ipv6\"} 0\n"));
+        assert!(text.contains("ipcheck_trie_nodes{family=\"ipv6\"} 0\n"));
+        assert!(!text.contains("ipcheck_last_load_timestamp_seconds 0\n"));
+    }
+
+    #[test]
+    fn hit_ratio_is_nan_before_any_lookup() {
+        assert!(Metrics::default()
+            .render()
+            .contains("ipcheck_lookup_hit_ratio NaN\n"));
+    }
+}
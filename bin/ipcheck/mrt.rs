@@ -0,0 +1,342 @@
+//! `--format mrt`: the prefixes announced in a BGP routing table dump
+//! in the MRT `TABLE_DUMP_V2` format (RFC 6396), as RouteViews and RIPE RIS
+//! publish them, optionally only those some ASes originate.
+//!
+//! The dumps are published compressed. With the `compression` feature,
+//! RIPE RIS's gzip dumps are read as they are; RouteViews' bzip2 ones must
+//! be decompressed onto standard input, e.g.
+//! `bzcat rib.bz2 | ipcheck build --v4 - --format mrt`. A prefix is
+//! originated by the last AS of the `AS_PATH` of any of its routes, or of
+//! an `AS_SET` at the end of the path, by its first.
+
+use std::io::{self, Read};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use clap::Args;
+use eyre::{bail, Result};
+use ipnet::{IpNet as AnyNet, Ipv4Net, Ipv6Net};
+
+/// The parser name selecting this format.
+pub const FORMAT: &str = "mrt";
+
+const TABLE_DUMP_V2: u16 = 13;
+const PEER_INDEX_TABLE: u16 = 1;
+const RIB_IPV4_UNICAST: u16 = 2;
+const RIB_IPV6_UNICAST: u16 = 4;
+const RIB_IPV4_UNICAST_ADDPATH: u16 = 8;
+const RIB_IPV6_UNICAST_ADDPATH: u16 = 10;
+
+const AS_PATH: u8 = 2;
+const AS_SET: u8 = 1;
+const AS_SEQUENCE: u8 = 2;
+/// The attribute flag of a two-byte length.
+const EXTENDED_LENGTH: u8 = 0x10;
+
+/// Which of a dump's prefixes to read.
+#[derive(Args, Clone, Debug)]
+pub struct MrtOptions {
+    /// With `--format mrt`, read only the prefixes this AS
+    /// originates, such as `AS13335` or `13335`. Repeat it, or separate
+    /// ASes with commas, to read those of several.
+    #[arg(long, value_name = "ASN", value_delimiter = ',')]
+    pub origin_asn: Vec<Asn>,
+}
+
+/// An AS number, written with or without `AS`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Asn(pub u32);
+
+impl FromStr for Asn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Asn, String> {
+        let digits = s
+            .strip_prefix("AS")
+            .or_else(|| s.strip_prefix("as"))
+            .unwrap_or(s);
+        digits
+            .parse()
+            .map(Asn)
+            .map_err(|_| format!("{:?} is not an AS number", s))
+    }
+}
+
+impl MrtOptions {
+    /// Whether `route` is of a chosen origin.
+    pub fn selects(&self, route: &Route) -> bool {
+        self.origin_asn.is_empty()
+            || route
+                .origins
+                .iter()
+                .any(|origin| self.origin_asn.contains(&Asn(*origin)))
+    }
+}
+
+/// Whether `head`, the start of an input, is the first record of a
+/// `TABLE_DUMP_V2` dump, its peer index table.
+pub fn sniff(head: &[u8]) -> bool {
+    head.len() >= 12 && head[4..8] == [0, TABLE_DUMP_V2 as u8, 0, PEER_INDEX_TABLE as u8]
+}
+
+/// A prefix of the table, and the ASes its routes are originated by.
+#[derive(Debug, PartialEq)]
+pub struct Route {
+    pub network: AnyNet,
+    pub origins: Vec<u32>,
+}
+
+/// The routes of the unicast RIB records of a dump, in order, each with the
+/// index of its record.
+pub struct Routes<R> {
+    input: R,
+    record: u64,
+}
+
+impl<R: Read> Routes<R> {
+    pub fn new(input: R) -> Routes<R> {
+        Routes { input, record: 0 }
+    }
+
+    /// The next unicast RIB record, skipping the others.
+    fn next_route(&mut self) -> Result<Option<(u64, Route)>> {
+        loop {
+            let mut header = [0; 12];
+            match self.input.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+            let record = self.record;
+            self.record += 1;
+            let kind = u16::from_be_bytes([header[4], header[5]]);
+            let subtype = u16::from_be_bytes([header[6], header[7]]);
+            let length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+            let mut body = vec![0; length as usize];
+            self.input
+                .read_exact(&mut body)
+                .map_err(|_| eyre::eyre!("record {} is truncated", record))?;
+            if kind != TABLE_DUMP_V2 {
+                continue;
+            }
+            let (v6, add_path) = match subtype {
+                RIB_IPV4_UNICAST => (false, false),
+                RIB_IPV6_UNICAST => (true, false),
+                RIB_IPV4_UNICAST_ADDPATH => (false, true),
+                RIB_IPV6_UNICAST_ADDPATH => (true, true),
+                _ => continue,
+            };
+            return match rib(&body, v6, add_path) {
+                Some(route) => Ok(Some((record, route))),
+                None => bail!("record {} is not a valid RIB entry", record),
+            };
+        }
+    }
+}
+
+impl<R: Read> Iterator for Routes<R> {
+    type Item = Result<(u64, Route)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_route().transpose()
+    }
+}
+
+/// The bytes of a record, read from the front.
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take(4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// The route of the RIB record `body`: its prefix, then its entries, one
+/// per peer, each with a path identifier if `add_path`.
+fn rib(body: &[u8], v6: bool, add_path: bool) -> Option<Route> {
+    let mut bytes = Bytes(body);
+    bytes.u32()?; // sequence number
+    let len = bytes.u8()?;
+    let prefix = bytes.take(usize::from(len).div_ceil(8))?;
+    let network = if v6 {
+        let mut octets = [0; 16];
+        octets.get_mut(..prefix.len())?.copy_from_slice(prefix);
+        AnyNet::V6(Ipv6Net::new(Ipv6Addr::from(octets), len).ok()?.trunc())
+    } else {
+        let mut octets = [0; 4];
+        octets.get_mut(..prefix.len())?.copy_from_slice(prefix);
+        AnyNet::V4(Ipv4Net::new(Ipv4Addr::from(octets), len).ok()?.trunc())
+    };
+
+    let mut origins = Vec::new();
+    for _ in 0..bytes.u16()? {
+        bytes.u16()?; // peer index
+        bytes.u32()?; // originated time
+        if add_path {
+            bytes.u32()?;
+        }
+        let attributes = bytes.u16()?;
+        let origin = origin(bytes.take(usize::from(attributes))?)?;
+        if let Some(origin) = origin.filter(|origin| !origins.contains(origin)) {
+            origins.push(origin);
+        }
+    }
+    Some(Route { network, origins })
+}
+
+/// The origin AS of the `AS_PATH` among `attributes`, if any; `None` if
+/// they are malformed.
+fn origin(attributes: &[u8]) -> Option<Option<u32>> {
+    let mut bytes = Bytes(attributes);
+    while !bytes.0.is_empty() {
+        let flags = bytes.u8()?;
+        let kind = bytes.u8()?;
+        let len = match flags & EXTENDED_LENGTH {
+            0 => usize::from(bytes.u8()?),
+            _ => usize::from(bytes.u16()?),
+        };
+        let value = bytes.take(len)?;
+        if kind != AS_PATH {
+            continue;
+        }
+        // The ASes of a dump are always four bytes.
+        let mut path = Bytes(value);
+        let mut origin = None;
+        while !path.0.is_empty() {
+            let segment = path.u8()?;
+            let count = usize::from(path.u8()?);
+            let asns: Vec<u32> = (0..count).map(|_| path.u32()).collect::<Option<_>>()?;
+            origin = match segment {
+                AS_SEQUENCE => asns.last().copied(),
+                AS_SET => asns.first().copied(),
+                _ => origin,
+            };
+        }
+        return Some(origin);
+    }
+    Some(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An MRT record of `kind` and `subtype` holding `body`.
+    fn record(kind: u16, subtype: u16, body: &[u8]) -> Vec<u8> {
+        let mut record = 1_700_000_000u32.to_be_bytes().to_vec();
+        record.extend(kind.to_be_bytes());
+        record.extend(subtype.to_be_bytes());
+        record.extend((body.len() as u32).to_be_bytes());
+        record.extend(body);
+        record
+    }
+
+    /// A unicast RIB record of `prefix`, with a route by each of `paths`.
+    fn rib_record(prefix: &str, paths: &[&[u32]]) -> Vec<u8> {
+        let network: AnyNet = prefix.parse().unwrap();
+        let (subtype, octets) = match network {
+            AnyNet::V4(net) => (RIB_IPV4_UNICAST, net.addr().octets().to_vec()),
+            AnyNet::V6(net) => (RIB_IPV6_UNICAST, net.addr().octets().to_vec()),
+        };
+        let len = network.prefix_len();
+        let mut body = 0u32.to_be_bytes().to_vec();
+        body.push(len);
+        body.extend(&octets[..usize::from(len).div_ceil(8)]);
+        body.extend((paths.len() as u16).to_be_bytes());
+        for path in paths {
+            let mut segment = vec![AS_SEQUENCE, path.len() as u8];
+            for asn in *path {
+                segment.extend(asn.to_be_bytes());
+            }
+            // ORIGIN IGP, then the AS_PATH.
+            let mut attributes = vec![0x40, 1, 1, 0, 0x40, AS_PATH, segment.len() as u8];
+            attributes.extend(segment);
+            body.extend(0u16.to_be_bytes());
+            body.extend(0u32.to_be_bytes());
+            body.extend((attributes.len() as u16).to_be_bytes());
+            body.extend(attributes);
+        }
+        record(TABLE_DUMP_V2, subtype, &body)
+    }
+
+    /// A dump with a peer index table and `ribs`.
+    fn dump(ribs: &[Vec<u8>]) -> Vec<u8> {
+        let mut dump = record(TABLE_DUMP_V2, PEER_INDEX_TABLE, &[0; 8]);
+        for rib in ribs {
+            dump.extend(rib);
+        }
+        dump
+    }
+
+    #[test]
+    fn routes_of_a_dump() {
+        let dump = dump(&[
+            rib_record("1.1.1.0/24", &[&[3356, 13335], &[174, 13335]]),
+            rib_record("8.8.8.0/24", &[&[15169]]),
+            rib_record("2606:4700::/32", &[&[6939, 13335], &[3356, 64500]]),
+        ]);
+        assert!(sniff(&dump));
+        let routes: Vec<(u64, Route)> = Routes::new(&dump[..]).collect::<Result<_>>().unwrap();
+        assert_eq!(
+            routes,
+            [
+                (
+                    1,
+                    Route {
+                        network: "1.1.1.0/24".parse().unwrap(),
+                        origins: vec![13335]
+                    }
+                ),
+                (
+                    2,
+                    Route {
+                        network: "8.8.8.0/24".parse().unwrap(),
+                        origins: vec![15169]
+                    }
+                ),
+                (
+                    3,
+                    Route {
+                        network: "2606:4700::/32".parse().unwrap(),
+                        origins: vec![13335, 64500]
+                    }
+                ),
+            ]
+        );
+
+        let options = MrtOptions {
+            origin_asn: vec!["AS64500".parse().unwrap()],
+        };
+        let selected: Vec<bool> = routes
+            .iter()
+            .map(|(_, route)| options.selects(route))
+            .collect();
+        assert_eq!(selected, [false, false, true]);
+
+        let err = Routes::new(&dump[..dump.len() - 1])
+            .collect::<Result<Vec<_>>>()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "record 3 is truncated");
+        assert!(!sniff(b"network\n10.0.0.0/8\n"));
+        assert!("ASX".parse::<Asn>().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Applying add/remove events from NATS to the networks of `ipcheck serve`,
+//! so that an abuse pipeline can push blocks in near real time.
+//!
+//! Every message on `--nats-subject` holds one change per line, `add
+//! <cidr>` or `remove <cidr>`. The changes of a message are applied
+//! together, and are reapplied over every reload of the inputs until the
+//! process exits. Lines that are not changes are skipped with a warning.
+//!
+//! With `--emit-artifact`, the served networks, streamed changes included,
+//! are also written as a binary artifact whenever they changed, at most
+//! every `--emit-interval` seconds, for the consumers that load artifacts.
+//!
+//! Only the core NATS protocol is spoken, over plain TCP: `CONNECT`, `SUB`
+//! and `PING`/`PONG`. A lost connection is retried with a backoff.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::Args;
+use eyre::{bail, Result};
+use ipcheck_rs::artifact;
+
+use crate::metrics::Metrics;
+use crate::output;
+use crate::parse::{parse_network, LeadingZeros, Value};
+use crate::reload::{Change, Live};
+
+/// The longest wait between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Largest accepted message payload.
+const MAX_PAYLOAD: usize = 1 << 20;
+
+#[derive(Args)]
+pub struct NatsArgs {
+    /// Apply the add/remove events published on this NATS server, as
+    /// `nats://host:port`.
+    #[arg(long, requires = "nats_subject")]
+    nats_url: Option<String>,
+
+    /// Subject the events are published on.
+    #[arg(long, requires = "nats_url")]
+    nats_subject: Option<String>,
+
+    /// Write the served networks as a binary artifact to this path
+    /// whenever they change.
+    #[arg(long)]
+    emit_artifact: Option<PathBuf>,
+
+    /// Least number of seconds between two writes of --emit-artifact.
+    #[arg(long, default_value_t = 60)]
+    emit_interval: u64,
+}
+
+/// Subscribes to `--nats-subject` and writes `--emit-artifact`, from
+/// background threads.
+pub fn spawn(args: &NatsArgs, live: Arc<Live>, metrics: Arc<Metrics>) -> Result<()> {
+    if let (Some(url), Some(subject)) = (&args.nats_url, &args.nats_subject) {
+        let addr = server_addr(url)?;
+        let subject = subject.clone();
+        let live = Arc::clone(&live);
+        thread::spawn(move || subscribe(&addr, &subject, &live, &metrics));
+    }
+    if let Some(path) = args.emit_artifact.clone() {
+        let interval = Duration::from_secs(args.emit_interval);
+        thread::spawn(move || emit(&path, interval, &live));
+    }
+    Ok(())
+}
+
+/// The `host:port` of a `nats://` URL.
+fn server_addr(url: &str) -> Result<String> {
+    let rest = url.strip_prefix("nats://").unwrap_or(url);
+    if rest.is_empty() || rest.contains(['/', '@']) {
+        bail!("{:?} is not a nats://host:port URL", url);
+    }
+    Ok(
+        if rest
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| !port.contains(']'))
+        {
+            rest.to_string()
+        } else {
+            format!("{}:4222", rest)
+        },
+    )
+}
+
+/// Applies the events of `subject` forever, reconnecting as needed.
+fn subscribe(addr: &str, subject: &str, live: &Live, metrics: &Metrics) {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match TcpStream::connect(addr) {
+            Ok(stream) => {
+                tracing::info!("subscribed to {} on nats://{}", subject, addr);
+                backoff = Duration::from_secs(1);
+                if let Err(err) = consume(stream, subject, live, metrics) {
+                    tracing::warn!("lost nats://{}: {}", addr, err);
+                }
+            }
+            Err(err) => tracing::warn!("failed to connect to nats://{}: {}", addr, err),
+        }
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Subscribes to `subject` on `stream` and applies its messages until the
+/// connection fails.
+fn consume(stream: TcpStream, subject: &str, live: &Live, metrics: &Metrics) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let connect = format!(
+        "CONNECT {{\"verbose\":false,\"pedantic\":false,\"name\":\"ipcheck\",\"lang\":\"rust\",\"version\":\"{}\"}}\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    writer.write_all(connect.as_bytes())?;
+    writer.write_all(format!("SUB {} 1\r\nPING\r\n", subject).as_bytes())?;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("PING") => writer.write_all(b"PONG\r\n")?,
+            Some("MSG") => {
+                // MSG <subject> <sid> [reply-to] <#bytes>
+                let len: usize = words
+                    .last()
+                    .and_then(|len| len.parse().ok())
+                    .filter(|&len| len <= MAX_PAYLOAD)
+                    .ok_or_else(|| invalid(&line))?;
+                let mut payload = vec![0; len + 2];
+                reader.read_exact(&mut payload)?;
+                payload.truncate(len);
+                let changes = parse_changes(&String::from_utf8_lossy(&payload));
+                if !changes.is_empty() {
+                    live.update(&changes);
+                    metrics.loaded(&live.get());
+                }
+            }
+            Some("-ERR") => return Err(io::Error::other(line.trim().to_string())),
+            Some("INFO" | "+OK" | "PONG") | None => {}
+            Some(_) => return Err(invalid(&line)),
+        }
+    }
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected {:?}", line.trim()),
+    )
+}
+
+/// The changes of one message, warning about the lines that are not.
+fn parse_changes(payload: &str) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for line in payload
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let change = line.split_once(char::is_whitespace).and_then(|(op, cidr)| {
+            let network = match parse_network(cidr, LeadingZeros::Reject).ok()?.value {
+                Value::Network(network) => network.trunc(),
+                Value::Range(..) => return None,
+            };
+            match op {
+                "add" => Some(Change::Add(network)),
+                "remove" => Some(Change::Remove(network)),
+                _ => None,
+            }
+        });
+        match change {
+            Some(change) => changes.push(change),
+            None => tracing::warn!("skipping {:?}, not `add <cidr>` or `remove <cidr>`", line),
+        }
+    }
+    changes
+}
+
+/// Writes the networks to `path` whenever they changed, forever.
+fn emit(path: &std::path::Path, interval: Duration, live: &Live) {
+    let mut written = None;
+    loop {
+        let networks = live.get();
+        if !written
+            .as_ref()
+            .is_some_and(|last| Arc::ptr_eq(last, &networks))
+        {
+            let result = artifact::write(&networks.v4, &networks.v6)
+                .map_err(eyre::Report::from)
+                .and_then(|bytes| output::write_atomic(path, &bytes));
+            match result {
+                Ok(()) => written = Some(networks),
+                Err(err) => tracing::warn!("failed to emit the artifact: {:#}", err),
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Networks;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn server_addresses() {
+        assert_eq!(
+            server_addr("nats://nats.internal").unwrap(),
+            "nats.internal:4222"
+        );
+        assert_eq!(
+            server_addr("nats://10.0.0.5:4333").unwrap(),
+            "10.0.0.5:4333"
+        );
+        assert_eq!(server_addr("[::1]").unwrap(), "[::1]:4222");
+        assert!(server_addr("nats://user@host").is_err());
+    }
+
+    #[test]
+    fn parse_change_lines() {
+        assert_eq!(
+            parse_changes("add 10.0.0.1/8\n\n  remove 2001:db8::/32\nblock 1.1.1.1/32\nadd nope\n"),
+            [
+                Change::Add("10.0.0.0/8".parse().unwrap()),
+                Change::Remove("2001:db8::/32".parse().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn consume_messages() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let live = Arc::new(Live::new(Networks::default()));
+        let client_live = Arc::clone(&live);
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            consume(stream, "blocks", &client_live, &Metrics::default())
+        });
+
+        let (mut conn, _) = server.accept().unwrap();
+        conn.write_all(b"INFO {\"server_id\":\"test\"}\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(conn.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("CONNECT {"), "{}", line);
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "SUB blocks 1\r\n");
+
+        let payload = "add 10.0.0.0/8\nremove 10.1.0.0/16";
+        let message = format!("PING\r\nMSG blocks 1 {}\r\n{}\r\n", payload.len(), payload);
+        conn.write_all(message.as_bytes()).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "PING\r\n");
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "PONG\r\n");
+
+        conn.write_all(b"-ERR 'Stale Connection'\r\n").unwrap();
+        let err = client.join().unwrap().unwrap_err();
+        assert!(err.to_string().contains("Stale Connection"), "{}", err);
+        let networks = live.get();
+        assert!(networks.v4.contains(&Ipv4Addr::new(10, 2, 0, 1)));
+        assert!(!networks.v4.contains(&Ipv4Addr::new(10, 1, 0, 1)));
+    }
+}
@@ -0,0 +1,86 @@
+//! Writing generated files, locally or to object storage.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use eyre::{Result, WrapErr};
+
+use crate::storage;
+
+/// The output path that stands for standard output.
+pub const STDOUT: &str = "-";
+
+/// Writes `contents` to `path` by way of a temporary file in the same
+/// directory and a rename, so that readers see either the old file or the
+/// complete new one, never a partial write. Objects are uploaded whole,
+/// which is atomic already.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if storage::is_remote(path) {
+        return storage::put(path, contents);
+    }
+    write_atomic_with(path, |tmp| {
+        let mut file = File::create(tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        Ok(())
+    })
+}
+
+/// Like [`write_atomic`], but `write` creates the temporary file itself, for
+/// outputs written by a library that wants a path. Objects are written to a
+/// local temporary file first, then uploaded.
+pub fn write_atomic_with<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    if storage::is_remote(path) {
+        let tmp = temp_path(&std::env::temp_dir().join(path.file_name().unwrap_or_default()));
+        let result = write(&tmp)
+            .and_then(|()| Ok(fs::read(&tmp)?))
+            .and_then(|contents| storage::put(path, &contents));
+        let _ = fs::remove_file(&tmp);
+        return result.wrap_err_with(|| format!("failed to write {}", path.display()));
+    }
+    let tmp = temp_path(path);
+    let result = write(&tmp).and_then(|()| Ok(fs::rename(&tmp, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-output-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ipcheck.ts");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        // Only the output is left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_reports_failures() {
+        let path = std::env::temp_dir()
+            .join(format!("ipcheck-missing-{}", std::process::id()))
+            .join("ipcheck.ts");
+        let err = write_atomic(&path, b"code").unwrap_err();
+        assert!(err.to_string().contains("failed to write"), "{}", err);
+        assert!(!temp_path(&path).exists());
+    }
+}
@@ -0,0 +1,250 @@
+//! Parsing network values as they appear in feeds.
+//!
+//! Upstream tools disagree about dotted-quad octets with leading zeros:
+//! `inet_aton` reads `010` as octal 8, others read it as decimal 10, and
+//! Rust's parser rejects it outright. Since any guess may silently block the
+//! wrong addresses, such values are rejected unless `--leading-zeros
+//! decimal` is passed.
+//!
+//! Besides networks, single addresses and inclusive address ranges such as
+//! `1.2.3.0-1.2.3.255` are accepted, as some feeds publish them.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::net::IpAddr;
+
+use clap::ValueEnum;
+use ipnet::IpNet as AnyNet;
+
+/// How to treat IPv4 octets and prefix lengths written with leading zeros.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LeadingZeros {
+    /// Fail on the first such value.
+    #[default]
+    Reject,
+    /// Read them as decimal, with a warning.
+    Decimal,
+}
+
+/// A successfully parsed value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parsed {
+    pub value: Value,
+    /// Whether surrounding whitespace was trimmed.
+    pub trimmed: bool,
+    /// Whether leading zeros were read as decimal.
+    pub leading_zeros: bool,
+}
+
+/// What a value describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value {
+    Network(AnyNet),
+    /// The addresses from the first to the second, inclusive, of the same
+    /// family.
+    Range(IpAddr, IpAddr),
+}
+
+/// Why a value is not a network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The value has leading zeros and the policy is to reject them.
+    LeadingZeros(String),
+    /// The value is not a network at all.
+    Invalid(String),
+    /// The value is a range that ends before it starts, or in the other
+    /// family.
+    Range(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::LeadingZeros(value) => write!(
+                f,
+                "{:?} has leading zeros, which tools read as either octal or decimal (pass --leading-zeros decimal to read them as decimal)",
+                value
+            ),
+            ParseError::Invalid(value) => write!(f, "{:?} is not a network", value),
+            ParseError::Range(value) => write!(
+                f,
+                "{:?} is not a range: it must end at or after its start, in the same family",
+                value
+            ),
+        }
+    }
+}
+
+/// `part` with its leading zeros stripped, or `None` if `policy` rejects
+/// them.
+fn normalize(part: &str, policy: LeadingZeros) -> Option<Cow<'_, str>> {
+    match strip_leading_zeros(part) {
+        Some(_) if policy == LeadingZeros::Reject => None,
+        Some(stripped) => Some(Cow::Owned(stripped)),
+        None => Some(Cow::Borrowed(part)),
+    }
+}
+
+/// Parses `raw` as a network, an address, or a `start-end` range, after
+/// trimming surrounding whitespace.
+pub fn parse_network(raw: &str, policy: LeadingZeros) -> Result<Parsed, ParseError> {
+    let value = raw.trim();
+    let trimmed = value.len() != raw.len();
+
+    let mut leading_zeros = false;
+    let mut strip = |part| -> Result<Cow<str>, ParseError> {
+        let part =
+            normalize(part, policy).ok_or_else(|| ParseError::LeadingZeros(value.to_string()))?;
+        leading_zeros |= matches!(part, Cow::Owned(_));
+        Ok(part)
+    };
+    let invalid = || ParseError::Invalid(value.to_string());
+    let parsed = match value.split_once('-') {
+        Some((start, end)) => {
+            let start: IpAddr = strip(start.trim())?.parse().map_err(|_| invalid())?;
+            let end: IpAddr = strip(end.trim())?.parse().map_err(|_| invalid())?;
+            if start.is_ipv4() != end.is_ipv4() || start > end {
+                return Err(ParseError::Range(value.to_string()));
+            }
+            Value::Range(start, end)
+        }
+        None => {
+            let value = strip(value)?;
+            let network = match value.parse::<IpAddr>() {
+                Ok(addr) => AnyNet::from(addr),
+                Err(_) => value.parse().map_err(|_| invalid())?,
+            };
+            Value::Network(network)
+        }
+    };
+    Ok(Parsed {
+        value: parsed,
+        trimmed,
+        leading_zeros,
+    })
+}
+
+/// Returns `value` with leading zeros removed from its IPv4 octets and
+/// prefix length, or `None` if it has none. IPv6 hextets are left alone:
+/// `0db8` is hexadecimal either way.
+fn strip_leading_zeros(value: &str) -> Option<String> {
+    let (addr, prefix) = match value.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (value, None),
+    };
+    let strip = |part: &str| {
+        let digits = part.trim_start_matches('0');
+        if part.len() > 1 && part.bytes().all(|b| b.is_ascii_digit()) && digits.len() < part.len() {
+            Some(if digits.is_empty() { "0" } else { digits }.to_string())
+        } else {
+            None
+        }
+    };
+
+    let mut changed = false;
+    let mut normalize = |part: &str| match strip(part) {
+        Some(stripped) => {
+            changed = true;
+            stripped
+        }
+        None => part.to_string(),
+    };
+    let addr = if addr.contains(':') {
+        addr.to_string()
+    } else {
+        addr.split('.')
+            .map(&mut normalize)
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    let prefix = prefix.map(&mut normalize);
+
+    changed.then(|| match prefix {
+        Some(prefix) => format!("{}/{}", addr, prefix),
+        None => addr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> Value {
+        Value::Network(s.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_plain_values() {
+        let parsed = parse_network("10.0.0.0/8", LeadingZeros::Reject).unwrap();
+        assert_eq!(parsed.value, net("10.0.0.0/8"));
+        assert!(!parsed.trimmed && !parsed.leading_zeros);
+        for (raw, network) in [
+            ("192.0.2.7", "192.0.2.7/32"),
+            ("2001:db8::1", "2001:db8::1/128"),
+        ] {
+            let parsed = parse_network(raw, LeadingZeros::Reject).unwrap();
+            assert_eq!(parsed.value, net(network));
+        }
+
+        let parsed = parse_network("2001:0db8::/32", LeadingZeros::Reject).unwrap();
+        assert_eq!(parsed.value, net("2001:db8::/32"));
+        assert!(!parsed.leading_zeros);
+
+        // Zero octets are not leading zeros.
+        let parsed = parse_network("0.0.0.0/0", LeadingZeros::Reject).unwrap();
+        assert_eq!(parsed.value, net("0.0.0.0/0"));
+    }
+
+    #[test]
+    fn parse_trims_whitespace() {
+        for raw in [" 10.0.0.0/8", "10.0.0.0/8\t", "\u{a0}10.0.0.0/8 "] {
+            let parsed = parse_network(raw, LeadingZeros::Reject).unwrap();
+            assert_eq!(parsed.value, net("10.0.0.0/8"));
+            assert!(parsed.trimmed, "{:?}", raw);
+        }
+        assert_eq!(
+            parse_network("10.0.0.0 /8", LeadingZeros::Reject),
+            Err(ParseError::Invalid("10.0.0.0 /8".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_leading_zeros() {
+        for raw in ["010.1.1.1/8", "10.01.0.0/16", "10.0.0.0/08", "10.0.0.00/8"] {
+            assert_eq!(
+                parse_network(raw, LeadingZeros::Reject),
+                Err(ParseError::LeadingZeros(raw.to_string()))
+            );
+        }
+
+        let parsed = parse_network("010.1.1.1/8", LeadingZeros::Decimal).unwrap();
+        assert_eq!(parsed.value, net("10.1.1.1/8"));
+        assert!(parsed.leading_zeros);
+        let parsed = parse_network("10.0.0.00/08", LeadingZeros::Decimal).unwrap();
+        assert_eq!(parsed.value, net("10.0.0.0/8"));
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let range =
+            |start: &str, end: &str| Value::Range(start.parse().unwrap(), end.parse().unwrap());
+        let parsed = parse_network("1.2.3.0-1.2.3.255", LeadingZeros::Reject).unwrap();
+        assert_eq!(parsed.value, range("1.2.3.0", "1.2.3.255"));
+        let parsed = parse_network("2001:db8:: - 2001:db8::ff", LeadingZeros::Reject).unwrap();
+        assert_eq!(parsed.value, range("2001:db8::", "2001:db8::ff"));
+        let parsed = parse_network("1.2.3.010-1.2.3.20", LeadingZeros::Decimal).unwrap();
+        assert_eq!(parsed.value, range("1.2.3.10", "1.2.3.20"));
+        assert!(parsed.leading_zeros);
+
+        for raw in ["1.2.3.9-1.2.3.1", "1.2.3.0-2001:db8::"] {
+            assert_eq!(
+                parse_network(raw, LeadingZeros::Reject),
+                Err(ParseError::Range(raw.to_string()))
+            );
+        }
+        assert_eq!(
+            parse_network("1.2.3.0/24-1.2.4.0", LeadingZeros::Reject),
+            Err(ParseError::Invalid("1.2.3.0/24-1.2.4.0".to_string()))
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Paths given either by flag or by position, so that both
+//! `ipcheck build --v4 v4.csv --v6 v6.csv -o out.ts` and the original
+//! `ipcheck v4.csv v6.csv out.ts` work, as does any mix of the two.
+//!
+//! A slot may be optional, like the list of one family once the other is
+//! given by flag: it is left empty when there are too few paths for it and
+//! the slots after it, so `ipcheck build --v4 v4.csv out.ts` builds without
+//! an IPv6 list.
+
+use std::mem;
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+
+/// A path of a command, the flag that sets it, and whether the command does
+/// without it.
+pub struct Slot<'a> {
+    path: &'a mut Option<PathBuf>,
+    flag: &'static str,
+    optional: bool,
+}
+
+impl<'a> Slot<'a> {
+    pub fn required(path: &'a mut Option<PathBuf>, flag: &'static str) -> Slot<'a> {
+        Slot {
+            path,
+            flag,
+            optional: false,
+        }
+    }
+
+    pub fn optional(path: &'a mut Option<PathBuf>, flag: &'static str) -> Slot<'a> {
+        Slot {
+            path,
+            flag,
+            optional: true,
+        }
+    }
+}
+
+/// Assigns the `positional` paths, in order, to the slots their flags left
+/// empty, failing as clap does if a required slot stays empty or a path is
+/// left over.
+pub fn assign(positional: &mut Vec<PathBuf>, slots: Vec<Slot>) -> Result<(), clap::Error> {
+    let mut positional = fill(positional, slots, 0)?.into_iter();
+    match positional.next() {
+        Some(path) => Err(clap::Error::raw(
+            ErrorKind::TooManyValues,
+            format!(
+                "unexpected path {}: every path is already given\n",
+                path.display()
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Like [`assign`], but returns the positional arguments left over for the
+/// command to make sense of, keeping at least `rest` of them from the
+/// optional slots.
+pub fn fill(
+    positional: &mut Vec<PathBuf>,
+    slots: Vec<Slot>,
+    rest: usize,
+) -> Result<Vec<PathBuf>, clap::Error> {
+    let positional = mem::take(positional);
+    let empty: Vec<Slot> = slots
+        .into_iter()
+        .filter(|slot| slot.path.is_none())
+        .collect();
+    let mut skipped = (empty.len() + rest).saturating_sub(positional.len());
+    let mut positional = positional.into_iter();
+    for slot in empty {
+        if slot.optional && skipped > 0 {
+            skipped -= 1;
+            continue;
+        }
+        match positional.next() {
+            Some(path) => *slot.path = Some(path),
+            None => {
+                return Err(clap::Error::raw(
+                    ErrorKind::MissingRequiredArgument,
+                    format!("no path given for {}\n", slot.flag),
+                ))
+            }
+        }
+    }
+    Ok(positional.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assigned(flags: [Option<&str>; 3], positional: &[&str]) -> Result<Vec<PathBuf>, String> {
+        let mut values: Vec<Option<PathBuf>> =
+            flags.iter().map(|flag| flag.map(PathBuf::from)).collect();
+        let mut positional = positional.iter().map(PathBuf::from).collect();
+        let slots = values
+            .iter_mut()
+            .zip(["--v4", "--v6", "--output"])
+            .map(|(path, flag)| Slot::required(path, flag))
+            .collect();
+        assign(&mut positional, slots).map_err(|err| err.to_string())?;
+        Ok(values.into_iter().map(Option::unwrap).collect())
+    }
+
+    #[test]
+    fn positional_paths_fill_the_gaps() {
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(
+            assigned([None; 3], &["a", "b", "c"]),
+            Ok(paths(&["a", "b", "c"]))
+        );
+        assert_eq!(
+            assigned([Some("a"), Some("b"), None], &["c"]),
+            Ok(paths(&["a", "b", "c"]))
+        );
+        assert_eq!(
+            assigned([None, Some("b"), None], &["a", "c"]),
+            Ok(paths(&["a", "b", "c"]))
+        );
+
+        let err = assigned([Some("a"), None, None], &["b"]).unwrap_err();
+        assert!(err.contains("no path given for --output"), "{}", err);
+        let err = assigned([Some("a"), Some("b"), Some("c")], &["d"]).unwrap_err();
+        assert!(err.contains("unexpected path d"), "{}", err);
+    }
+
+    #[test]
+    fn optional_slots_give_way() {
+        let filled = |positional: &[&str], rest| {
+            let (mut v4, mut v6, mut output) = (None, None, None);
+            let slots = vec![
+                Slot::optional(&mut v4, "--v4"),
+                Slot::optional(&mut v6, "--v6"),
+                Slot::required(&mut output, "--output"),
+            ];
+            let mut positional = positional.iter().map(PathBuf::from).collect();
+            let rest = fill(&mut positional, slots, rest).map_err(|err| err.to_string())?;
+            let name = |path: Option<PathBuf>| path.map(|path| path.display().to_string());
+            Ok::<_, String>((name(v4), name(v6), name(output), rest.len()))
+        };
+        let some = |name: &str| Some(name.to_string());
+        assert_eq!(
+            filled(&["a", "b", "c"], 0),
+            Ok((some("a"), some("b"), some("c"), 0))
+        );
+        assert_eq!(filled(&["b", "c"], 0), Ok((None, some("b"), some("c"), 0)));
+        assert_eq!(filled(&["c"], 0), Ok((None, None, some("c"), 0)));
+        assert_eq!(filled(&["b", "c", "d"], 2), Ok((None, None, some("b"), 2)));
+        assert!(filled(&[], 0)
+            .unwrap_err()
+            .contains("no path given for --output"));
+    }
+}
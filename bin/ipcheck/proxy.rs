@@ -0,0 +1,252 @@
+//! Proxy outputs: the networks of a build as the access lists of caches
+//! and load balancers, so that they turn away the very networks the
+//! checkers do.
+//!
+//! The `vcl` backend, selected by `--target vcl`, writes the output as a
+//! VCL file declaring an `acl` named after it, as the firewall sets are:
+//! `blocklist.vcl` declares `acl blocklist`, for Varnish and Fastly to
+//! match `client.ip` against. Notes on the networks follow their entries
+//! as comments.
+//!
+//! The `haproxy` backend, selected by `--target haproxy`, writes the
+//! pattern files of an HAProxy `src -f` ACL beside the output, one network
+//! per line, with the extensions `.v4.acl` and `.v6.acl`: `blocklist.acl`
+//! gives `blocklist.v4.acl` and `blocklist.v6.acl`. Like every output,
+//! they are replaced atomically, so that a reload never reads half a file.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::path::Path;
+
+use ipcheck_rs::backend::{Error, Filters, Output, OutputBackend};
+use ipnet::IpNet;
+
+use crate::firewall::{set_name, sorted};
+use crate::input::Networks;
+
+/// The VCL `acl` of the networks.
+pub struct Vcl;
+
+impl OutputBackend for Vcl {
+    fn name(&self) -> &'static str {
+        "vcl"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let acl = set_name(filters.output);
+        let file = filters.output.file_name().unwrap_or_default();
+        let mut vcl = String::from("# Generated by ipcheck; do not edit.\n");
+        writeln!(
+            vcl,
+            "#\n\
+             # Include it from the main VCL file and match client.ip against it:\n\
+             #\n\
+             #     include \"{}\";\n\
+             #\n\
+             #     sub vcl_recv {{\n\
+             #         if (client.ip ~ {}) {{\n\
+             #             return (synth(403));\n\
+             #         }}\n\
+             #     }}\n\
+             \n\
+             acl {} {{",
+            file.to_string_lossy(),
+            acl,
+            acl
+        )
+        .unwrap();
+        let notes: HashMap<&str, &str> = filters
+            .notes
+            .iter()
+            .map(|note| (note.network.as_str(), note.note.as_str()))
+            .collect();
+        let v4 = sorted(filters.v4).into_iter().map(IpNet::V4);
+        let v6 = sorted(filters.v6).into_iter().map(IpNet::V6);
+        for network in v4.chain(v6) {
+            write!(
+                vcl,
+                "    \"{}\"/{};",
+                network.network(),
+                network.prefix_len()
+            )
+            .unwrap();
+            match notes.get(network.to_string().as_str()) {
+                Some(note) => writeln!(vcl, "  # {}", note).unwrap(),
+                None => vcl.push('\n'),
+            }
+        }
+        vcl.push_str("}\n");
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: vcl.into_bytes(),
+        }])
+    }
+}
+
+/// The HAProxy ACL files of the networks.
+pub struct Haproxy;
+
+impl OutputBackend for Haproxy {
+    fn name(&self) -> &'static str {
+        "haproxy"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let acl = set_name(filters.output);
+        let v4 = filters.output.with_extension("v4.acl");
+        let v6 = filters.output.with_extension("v6.acl");
+        let file = |path: &Path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        // HAProxy reads every line but comments and blank ones as a pattern,
+        // so the comments are on lines of their own.
+        let header = format!(
+            "# Generated by ipcheck; do not edit.\n\
+             #\n\
+             #     acl {acl} src -f {} -f {}\n\
+             #     http-request deny if {acl}\n",
+            file(&v4),
+            file(&v6),
+        );
+        let mut contents_v4 = header.clone();
+        for network in sorted(filters.v4) {
+            writeln!(contents_v4, "{}", network).unwrap();
+        }
+        let mut contents_v6 = header;
+        for network in sorted(filters.v6) {
+            writeln!(contents_v6, "{}", network).unwrap();
+        }
+        Ok(vec![
+            Output {
+                path: v4,
+                contents: contents_v4.into_bytes(),
+            },
+            Output {
+                path: v6,
+                contents: contents_v6.into_bytes(),
+            },
+        ])
+    }
+}
+
+/// The networks of the `acl` of the VCL file `vcl`, `None` if it declares
+/// none, or entries other than networks.
+pub fn vcl_networks(vcl: &str) -> Option<Networks> {
+    let mut lines = vcl.lines().map(str::trim);
+    lines.find(|line| line.starts_with("acl ") && line.ends_with('{'))?;
+    let mut networks = Networks::default();
+    for line in lines {
+        let entry = line.split('#').next().unwrap().trim();
+        if entry.is_empty() {
+            continue;
+        }
+        if entry == "}" {
+            return Some(networks);
+        }
+        let (addr, len) = entry.strip_suffix(';')?.split_once('/')?;
+        let addr: IpAddr = addr.strip_prefix('"')?.strip_suffix('"')?.parse().ok()?;
+        match IpNet::new(addr, len.parse().ok()?).ok()? {
+            IpNet::V4(network) => {
+                networks.v4.add(network);
+            }
+            IpNet::V6(network) => {
+                networks.v6.add(network);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipcheck_rs::backend::Note;
+    use ipcheck_rs::IpRange;
+    use std::path::PathBuf;
+
+    #[test]
+    fn vcl_acl() {
+        let v4: IpRange<_> = ["192.0.2.0/24", "10.0.0.0/8"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("varnish/block-list.vcl");
+        let notes = [Note {
+            network: "2001:db8::/32".to_string(),
+            note: "documentation".to_string(),
+        }];
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &notes,
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Vcl.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("varnish/block-list.vcl"));
+        let vcl = String::from_utf8(outputs[0].contents.clone()).unwrap();
+        assert!(
+            vcl.contains("#     include \"block-list.vcl\";\n"),
+            "{}",
+            vcl
+        );
+        assert!(
+            vcl.ends_with(
+                "acl block_list {\n\
+                 \x20   \"10.0.0.0\"/8;\n\
+                 \x20   \"192.0.2.0\"/24;\n\
+                 \x20   \"2001:db8::\"/32;  # documentation\n\
+                 }\n"
+            ),
+            "{}",
+            vcl
+        );
+        assert_eq!(vcl_networks(&vcl), Some(Networks { v4, v6 }));
+        assert_eq!(vcl_networks("acl local {\n    \"localhost\";\n}\n"), None);
+    }
+
+    #[test]
+    fn haproxy_acl_files() {
+        let v4: IpRange<_> = ["192.0.2.0/24", "10.0.0.0/8"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("/etc/haproxy/blocklist.acl");
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &IpRange::new(),
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Haproxy.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("/etc/haproxy/blocklist.v4.acl"));
+        assert_eq!(outputs[1].path, Path::new("/etc/haproxy/blocklist.v6.acl"));
+        let header = "# Generated by ipcheck; do not edit.\n\
+                      #\n\
+                      #     acl blocklist src -f blocklist.v4.acl -f blocklist.v6.acl\n\
+                      #     http-request deny if blocklist\n";
+        assert_eq!(
+            String::from_utf8(outputs[0].contents.clone()).unwrap(),
+            format!("{}10.0.0.0/8\n192.0.2.0/24\n", header)
+        );
+        assert_eq!(
+            String::from_utf8(outputs[1].contents.clone()).unwrap(),
+            header
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! `ipcheck sync-redis`: pushing the networks into Redis for a fleet of
+//! application servers to share.
+//!
+//! With a key prefix (`--redis-key`) of `ipcheck`, a push replaces, in one
+//! transaction:
+//!
+//! * `ipcheck:v4` and `ipcheck:v6`, sets of the simplified networks as CIDR
+//!   strings (`--redis-format cidrs`), or
+//! * `ipcheck:artifact`, the binary artifact (`--redis-format artifact`),
+//!
+//! and increments `ipcheck:version`. The new version is then published on
+//! the `ipcheck:updates` channel, so that subscribers know to reload.
+
+use clap::{Args, ValueEnum};
+use eyre::{Result, WrapErr};
+use ipcheck_rs::{artifact, IpNet, IpRange};
+use redis::{Commands, Pipeline};
+
+use crate::input::Networks;
+use crate::reload::{on_change, Source, SourceArgs};
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Redis server to push to.
+    #[arg(long, default_value = "redis://127.0.0.1/")]
+    url: String,
+
+    /// Prefix of the keys written.
+    #[arg(long = "redis-key", default_value = "ipcheck")]
+    key: String,
+
+    /// How the networks are stored.
+    #[arg(long = "redis-format", value_enum, default_value_t = Format::Cidrs)]
+    format: Format,
+
+    /// Keep running, pushing again whenever the input files change.
+    #[arg(long)]
+    watch: bool,
+
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// A set of CIDR strings per family.
+    Cidrs,
+    /// The binary artifact, as one string value.
+    Artifact,
+}
+
+impl SyncArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        self.source.assign_paths()
+    }
+}
+
+pub fn sync(args: SyncArgs) -> Result<()> {
+    let client = redis::Client::open(args.url.as_str()).wrap_err_with(|| args.url.clone())?;
+    let source = args.source.source();
+    push(&client, &args, &source)?;
+    if args.watch {
+        on_change(&source, || {
+            if let Err(err) = push(&client, &args, &source) {
+                tracing::warn!("push failed, Redis keeps the previous networks: {:#}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Pushes the networks over a fresh connection, so that a watching sync
+/// survives the server restarting in between.
+fn push(client: &redis::Client, args: &SyncArgs, source: &Source) -> Result<()> {
+    let networks = source.load()?;
+    let mut connection = client
+        .get_connection()
+        .wrap_err_with(|| format!("failed to connect to {}", args.url))?;
+    let (version,): (u64,) = transaction(&args.key, args.format, &networks)?
+        .query(&mut connection)
+        .wrap_err("failed to update Redis")?;
+    let () = connection.publish(format!("{}:updates", args.key), version)?;
+    tracing::info!("pushed version {} to {}", version, args.url);
+    Ok(())
+}
+
+/// The transaction replacing the networks under `key`, answering the new
+/// version.
+fn transaction(key: &str, format: Format, networks: &Networks) -> Result<Pipeline> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    match format {
+        Format::Cidrs => {
+            replace_set(&mut pipe, format!("{}:v4", key), &networks.v4);
+            replace_set(&mut pipe, format!("{}:v6", key), &networks.v6);
+        }
+        Format::Artifact => {
+            let bytes = artifact::write(&networks.v4, &networks.v6)?;
+            pipe.set(format!("{}:artifact", key), bytes).ignore();
+        }
+    }
+    pipe.incr(format!("{}:version", key), 1);
+    Ok(pipe)
+}
+
+fn replace_set<N: IpNet + ToString>(pipe: &mut Pipeline, key: String, range: &IpRange<N>) {
+    pipe.del(&key).ignore();
+    let members: Vec<String> = range.iter().map(|network| network.to_string()).collect();
+    // SADD needs at least one member; an empty range is the deleted key.
+    if !members.is_empty() {
+        pipe.sadd(&key, members).ignore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(format: Format, networks: &Networks) -> String {
+        let pipe = transaction("ipcheck", format, networks).unwrap();
+        String::from_utf8_lossy(&pipe.get_packed_pipeline()).into_owned()
+    }
+
+    #[test]
+    fn cidrs_replace_each_family_set() {
+        let networks = Networks {
+            v4: ["10.0.0.0/8", "10.1.0.0/16"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: IpRange::new(),
+        };
+        let commands = packed(Format::Cidrs, &networks);
+        assert!(commands.starts_with("*1\r\n$5\r\nMULTI\r\n"));
+        assert!(commands.contains("$3\r\nDEL\r\n$10\r\nipcheck:v4\r\n"));
+        assert!(commands.contains("$4\r\nSADD\r\n$10\r\nipcheck:v4\r\n$10\r\n10.0.0.0/8\r\n"));
+        assert!(!commands.contains("10.1.0.0/16"));
+        assert!(commands.contains("$3\r\nDEL\r\n$10\r\nipcheck:v6\r\n"));
+        assert!(!commands.contains("SADD\r\n$10\r\nipcheck:v6"));
+        assert!(commands.contains("$6\r\nINCRBY\r\n$15\r\nipcheck:version\r\n"));
+        assert!(commands.ends_with("*1\r\n$4\r\nEXEC\r\n"));
+    }
+
+    #[test]
+    fn artifact_is_one_value() {
+        let networks = Networks {
+            v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: IpRange::new(),
+        };
+        let commands = packed(Format::Artifact, &networks);
+        assert!(commands.contains("$3\r\nSET\r\n$16\r\nipcheck:artifact\r\n"));
+        assert!(commands.contains("IPCK"));
+        assert!(!commands.contains("SADD"));
+    }
+}
@@ -0,0 +1,335 @@
+//! Reloading the networks of `ipcheck serve` without a restart.
+//!
+//! A reload builds the new networks off to the side and then swaps them in
+//! whole: a lookup already in progress finishes against the networks it
+//! started with, and a failed reload keeps serving the old ones. Changes
+//! applied at runtime (see [`Live::update`]) are reapplied over every
+//! reload.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use clap::Args;
+use eyre::Result;
+use ipcheck_rs::artifact;
+use ipnet::IpNet as AnyNet;
+
+use crate::input::{Family, InputOptions, Lists, Networks};
+use crate::metrics::Metrics;
+use crate::paths;
+use crate::storage;
+use crate::telemetry;
+use crate::watch;
+
+/// Options choosing where the networks are read from.
+#[derive(Args)]
+pub struct SourceArgs {
+    /// Read the networks of a binary artifact instead of CSV files.
+    #[arg(long, conflicts_with_all = ["v4", "v6", "paths"])]
+    artifact: Option<PathBuf>,
+
+    /// Read the networks of a database written by `ipcheck export sqlite`
+    /// instead of CSV files.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, conflicts_with_all = ["artifact", "v4", "v6", "paths"])]
+    sqlite: Option<PathBuf>,
+
+    #[command(flatten)]
+    lists: Lists,
+
+    /// The IPv4 and IPv6 lists, for those not given by flag.
+    #[arg(value_names = ["V4", "V6"], num_args = 0..=2)]
+    paths: Vec<PathBuf>,
+
+    #[command(flatten)]
+    input: InputOptions,
+}
+
+impl SourceArgs {
+    /// Assigns the positional paths to the lists, unless the networks are
+    /// read from elsewhere.
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        #[cfg(feature = "sqlite")]
+        if self.sqlite.is_some() {
+            return Ok(());
+        }
+        if self.artifact.is_some() {
+            return Ok(());
+        }
+        paths::assign(&mut self.paths, self.lists.slots())
+    }
+
+    pub fn source(&self) -> Source {
+        #[cfg(feature = "sqlite")]
+        if let Some(path) = &self.sqlite {
+            return Source::Sqlite(path.clone());
+        }
+        match &self.artifact {
+            Some(path) => Source::Artifact(path.clone()),
+            None => Source::Csv {
+                lists: self.lists.clone(),
+                input: self.input.clone(),
+            },
+        }
+    }
+}
+
+/// Where the networks are read from.
+// Made once per server, so the size of the CSV options is no concern.
+#[allow(clippy::large_enum_variant)]
+pub enum Source {
+    Artifact(PathBuf),
+    #[cfg(feature = "sqlite")]
+    Sqlite(PathBuf),
+    Csv {
+        lists: Lists,
+        input: InputOptions,
+    },
+}
+
+impl Source {
+    pub fn load(&self) -> Result<Networks> {
+        match self {
+            Source::Artifact(path) => {
+                let _span = telemetry::span("load").attr("file", path.display());
+                let (v4, v6) = artifact::load(&storage::read(path)?)?;
+                Ok(Networks { v4, v6 })
+            }
+            #[cfg(feature = "sqlite")]
+            Source::Sqlite(path) => crate::sqlite::load(path),
+            Source::Csv { lists, input } => crate::load(lists, input),
+        }
+    }
+
+    /// The files that networks of `family` are read from.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn feed(&self, family: Family) -> Vec<&Path> {
+        match self {
+            Source::Artifact(path) => vec![path],
+            #[cfg(feature = "sqlite")]
+            Source::Sqlite(path) => vec![path],
+            Source::Csv { lists, .. } => {
+                let paths = match family {
+                    Family::V4 => lists.v4(),
+                    Family::V6 => lists.v6(),
+                };
+                paths.iter().map(PathBuf::as_path).collect()
+            }
+        }
+    }
+
+    /// Every input file.
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Source::Artifact(path) => vec![path],
+            #[cfg(feature = "sqlite")]
+            Source::Sqlite(path) => vec![path],
+            Source::Csv { lists, .. } => lists.paths().map(PathBuf::as_path).collect(),
+        }
+    }
+}
+
+/// A network added to or removed from the served networks at runtime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(not(feature = "nats"), allow(dead_code))]
+pub enum Change {
+    Add(AnyNet),
+    Remove(AnyNet),
+}
+
+impl Change {
+    fn apply(self, networks: &mut Networks) {
+        match self {
+            Change::Add(AnyNet::V4(net)) => networks.v4.add(net).simplify(),
+            Change::Add(AnyNet::V6(net)) => networks.v6.add(net).simplify(),
+            Change::Remove(AnyNet::V4(net)) => {
+                networks.v4.remove(net);
+            }
+            Change::Remove(AnyNet::V6(net)) => {
+                networks.v6.remove(net);
+            }
+        }
+    }
+}
+
+/// The runtime changes to reapply over reloaded networks, as the addresses
+/// added and removed; the two never overlap, so the latest change to an
+/// address wins.
+#[derive(Default)]
+struct Overlay {
+    added: Networks,
+    removed: Networks,
+}
+
+impl Overlay {
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    fn record(&mut self, change: Change) {
+        match change {
+            Change::Add(net) => {
+                change.apply(&mut self.added);
+                Change::Remove(net).apply(&mut self.removed);
+            }
+            Change::Remove(net) => {
+                Change::Add(net).apply(&mut self.removed);
+                change.apply(&mut self.added);
+            }
+        }
+    }
+
+    fn apply(&self, networks: &mut Networks) {
+        networks.merge(&self.added);
+        networks.v4 = networks.v4.exclude(&self.removed.v4);
+        networks.v6 = networks.v6.exclude(&self.removed.v6);
+    }
+}
+
+/// The networks currently being served.
+pub struct Live {
+    networks: RwLock<Arc<Networks>>,
+    overlay: Mutex<Overlay>,
+}
+
+impl Live {
+    pub fn new(networks: Networks) -> Live {
+        Live {
+            networks: RwLock::new(Arc::new(networks)),
+            overlay: Mutex::default(),
+        }
+    }
+
+    /// Returns the current networks, which stay valid for as long as the
+    /// caller holds them even if a reload replaces them meanwhile.
+    pub fn get(&self) -> Arc<Networks> {
+        Arc::clone(&self.networks.read().unwrap())
+    }
+
+    /// Serves `networks` from now on, with the runtime changes applied.
+    pub fn replace(&self, mut networks: Networks) {
+        let overlay = self.overlay.lock().unwrap();
+        overlay.apply(&mut networks);
+        *self.networks.write().unwrap() = Arc::new(networks);
+    }
+
+    /// Applies `changes` to the current networks, and to every reload from
+    /// now on.
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    pub fn update(&self, changes: &[Change]) {
+        let mut overlay = self.overlay.lock().unwrap();
+        let mut networks = (*self.get()).clone();
+        for &change in changes {
+            overlay.record(change);
+            change.apply(&mut networks);
+        }
+        *self.networks.write().unwrap() = Arc::new(networks);
+    }
+}
+
+/// Reloads `source` into `live`, keeping the current networks on failure.
+pub fn reload(source: &Source, live: &Live, metrics: &Metrics) {
+    match source.load() {
+        Ok(networks) => {
+            metrics.loaded(&networks);
+            live.replace(networks);
+            tracing::info!("reloaded networks");
+        }
+        Err(err) => {
+            metrics.reload_failed();
+            tracing::warn!("reload failed, keeping the current networks: {:#}", err);
+        }
+    }
+}
+
+/// Reloads whenever the inputs change, until the process exits.
+pub fn watch(source: Arc<Source>, live: Arc<Live>, metrics: Arc<Metrics>) {
+    thread::spawn(move || on_change(&source, || reload(&source, &live, &metrics)));
+}
+
+/// Calls `changed` whenever the inputs of `source` change, forever (see
+/// [`watch::on_change`]).
+pub fn on_change(source: &Source, changed: impl FnMut()) -> ! {
+    watch::on_change(&source.paths(), changed)
+}
+
+/// Reloads on every `SIGHUP`, until the process exits.
+#[cfg(unix)]
+pub fn reload_on_sighup(source: Arc<Source>, live: Arc<Live>, metrics: Arc<Metrics>) -> Result<()> {
+    use signal_hook::consts::SIGHUP;
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            reload(&source, &live, &metrics);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::net::Ipv4Addr;
+
+    fn write_artifact(path: &Path, network: &str) {
+        let v4 = [network].iter().map(|s| s.parse().unwrap()).collect();
+        fs::write(path, artifact::write(&v4, &Default::default()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reload_swaps_networks_and_keeps_them_on_failure() {
+        let path = std::env::temp_dir().join(format!("ipcheck-reload-{}.bin", std::process::id()));
+        write_artifact(&path, "10.0.0.0/8");
+        let source = Source::Artifact(path.clone());
+        let live = Live::new(source.load().unwrap());
+        let metrics = Metrics::default();
+        let ten = Ipv4Addr::new(10, 0, 0, 1);
+        let before = live.get();
+
+        write_artifact(&path, "192.168.0.0/16");
+        reload(&source, &live, &metrics);
+        assert!(!live.get().v4.contains(&ten));
+        // A lookup holding the old networks is unaffected by the swap.
+        assert!(before.v4.contains(&ten));
+
+        fs::write(&path, b"not an artifact").unwrap();
+        reload(&source, &live, &metrics);
+        assert!(live.get().v4.contains(&Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(metrics
+            .render()
+            .contains("ipcheck_reload_failures_total 1\n"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn updates_survive_reloads() {
+        let path = std::env::temp_dir().join(format!("ipcheck-update-{}.bin", std::process::id()));
+        write_artifact(&path, "10.0.0.0/8");
+        let source = Source::Artifact(path.clone());
+        let live = Live::new(source.load().unwrap());
+        let net = |s: &str| s.parse().unwrap();
+        live.update(&[
+            Change::Add(net("192.168.0.0/16")),
+            Change::Remove(net("10.1.0.0/16")),
+            Change::Remove(net("192.168.1.0/24")),
+            Change::Add(net("10.1.2.0/24")),
+        ]);
+        let check = |live: &Live| {
+            let networks = live.get();
+            let has = |a, b, c, d| networks.v4.contains(&Ipv4Addr::new(a, b, c, d));
+            assert!(has(192, 168, 0, 1));
+            assert!(!has(192, 168, 1, 1));
+            assert!(!has(10, 1, 0, 1));
+            assert!(has(10, 1, 2, 1));
+            assert!(has(10, 2, 0, 1));
+        };
+        check(&live);
+
+        reload(&source, &live, &Metrics::default());
+        check(&live);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+//! Detecting special-purpose address space in public feeds.
+//!
+//! Private, loopback, link-local and documentation prefixes never belong in
+//! a public blocklist; when a feed contains them it is almost always a bug
+//! in the feed, and blocking them tends to break local traffic.
+
+use std::fmt;
+
+use clap::ValueEnum;
+use ipcheck_rs::{IpNet, IpRange};
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::input::Networks;
+
+const RESERVED_V4: &[(&str, Kind)] = &[
+    ("10.0.0.0/8", Kind::Private),
+    ("172.16.0.0/12", Kind::Private),
+    ("192.168.0.0/16", Kind::Private),
+    ("127.0.0.0/8", Kind::Loopback),
+    ("169.254.0.0/16", Kind::LinkLocal),
+    ("192.0.2.0/24", Kind::Documentation),
+    ("198.51.100.0/24", Kind::Documentation),
+    ("203.0.113.0/24", Kind::Documentation),
+];
+
+const RESERVED_V6: &[(&str, Kind)] = &[
+    ("fc00::/7", Kind::Private),
+    ("::1/128", Kind::Loopback),
+    ("fe80::/10", Kind::LinkLocal),
+    ("2001:db8::/32", Kind::Documentation),
+];
+
+/// What to do with special-purpose address space found in the inputs.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReservedPolicy {
+    /// Keep the networks but print a warning for each one.
+    #[default]
+    Warn,
+    /// Remove the networks, printing a warning for each one.
+    Strip,
+    /// Keep the networks silently.
+    Keep,
+}
+
+/// The kind of special-purpose address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Private,
+    Loopback,
+    LinkLocal,
+    Documentation,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Private => "private",
+            Kind::Loopback => "loopback",
+            Kind::LinkLocal => "link-local",
+            Kind::Documentation => "documentation",
+        })
+    }
+}
+
+/// A part of the inputs that falls in special-purpose address space.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reserved {
+    pub network: String,
+    pub kind: Kind,
+    pub reserved: &'static str,
+}
+
+impl fmt::Display for Reserved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is in {} address space ({})",
+            self.network, self.kind, self.reserved
+        )
+    }
+}
+
+/// Applies `policy` to `networks`, returning the special-purpose networks
+/// that were found (and, with [`ReservedPolicy::Strip`], removed).
+pub fn apply(networks: &mut Networks, policy: ReservedPolicy) -> Vec<Reserved> {
+    if policy == ReservedPolicy::Keep {
+        return Vec::new();
+    }
+    let mut found = find::<Ipv4Net>(&mut networks.v4, RESERVED_V4, policy);
+    found.extend(find::<Ipv6Net>(&mut networks.v6, RESERVED_V6, policy));
+    found
+}
+
+fn find<N>(
+    range: &mut IpRange<N>,
+    reserved: &'static [(&'static str, Kind)],
+    policy: ReservedPolicy,
+) -> Vec<Reserved>
+where
+    N: IpNet + fmt::Display + std::str::FromStr,
+    <N as std::str::FromStr>::Err: fmt::Debug,
+{
+    let mut found = Vec::new();
+    for &(prefix, kind) in reserved {
+        let mut block = IpRange::new();
+        block.add(prefix.parse::<N>().unwrap());
+
+        let overlap = range.intersect(&block);
+        found.extend(overlap.iter().map(|network| Reserved {
+            network: network.to_string(),
+            kind,
+            reserved: prefix,
+        }));
+        if policy == ReservedPolicy::Strip && !overlap.is_empty() {
+            *range = range.exclude(&block);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks() -> Networks {
+        Networks {
+            v4: ["8.8.8.0/24", "10.1.0.0/16", "192.0.0.0/8"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8:1::/48", "2606:4700::/32", "::1/128"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn warn_reports_overlaps() {
+        let mut networks = networks();
+        let found = apply(&mut networks, ReservedPolicy::Warn);
+        let messages: Vec<String> = found.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "10.1.0.0/16 is in private address space (10.0.0.0/8)",
+                "192.168.0.0/16 is in private address space (192.168.0.0/16)",
+                "192.0.2.0/24 is in documentation address space (192.0.2.0/24)",
+                "::1/128 is in loopback address space (::1/128)",
+                "2001:db8:1::/48 is in documentation address space (2001:db8::/32)",
+            ]
+        );
+        assert_eq!(networks, self::networks());
+    }
+
+    #[test]
+    fn strip_removes_overlaps() {
+        let mut networks = networks();
+        let found = apply(&mut networks, ReservedPolicy::Strip);
+        assert_eq!(found.len(), 5);
+
+        assert!(networks
+            .v4
+            .contains(&"8.8.8.0/24".parse::<Ipv4Net>().unwrap()));
+        assert!(networks
+            .v4
+            .contains(&"192.1.0.0/16".parse::<Ipv4Net>().unwrap()));
+        for reserved in ["10.1.0.0/16", "192.168.0.0/16", "192.0.2.0/24"] {
+            let reserved: Ipv4Net = reserved.parse().unwrap();
+            assert!(networks
+                .v4
+                .intersect(&[reserved].into_iter().collect())
+                .is_empty());
+        }
+        let v6: Vec<String> = networks.v6.iter().map(|n| n.to_string()).collect();
+        assert_eq!(v6, ["2606:4700::/32"]);
+    }
+
+    #[test]
+    fn keep_leaves_networks_alone() {
+        let mut networks = networks();
+        assert!(apply(&mut networks, ReservedPolicy::Keep).is_empty());
+        assert_eq!(networks, self::networks());
+    }
+}
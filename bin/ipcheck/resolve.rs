@@ -0,0 +1,87 @@
+//! `--resolve-hostnames`: list values that are hostnames, such as partner
+//! endpoints published only as DNS names, resolved when the list is read.
+//!
+//! A hostname stands for each of the addresses its `A` and `AAAA` records
+//! give, as a `/32` or `/128`. The filter holds the addresses the name had
+//! at build time, so rebuild it as they change.
+
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+
+use clap::Args;
+
+/// Whether and how hostnames are resolved.
+#[derive(Args, Clone, Debug)]
+pub struct ResolveOptions {
+    /// Read values that are hostnames by resolving their `A` and `AAAA`
+    /// records, adding each address as a `/32` or `/128`.
+    #[arg(long)]
+    pub resolve_hostnames: bool,
+
+    /// With `--resolve-hostnames`, skip hostnames that do not resolve,
+    /// warning about each, instead of failing.
+    #[arg(long, requires = "resolve_hostnames")]
+    pub skip_unresolved: bool,
+}
+
+/// Whether `value` is written as a hostname: dot-separated labels of
+/// letters, digits and hyphens, not all digits, as networks are not.
+pub fn is_hostname(value: &str) -> bool {
+    let name = value.strip_suffix('.').unwrap_or(value);
+    let label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    name.len() <= 253 && name.split('.').all(label) && name.bytes().any(|b| b.is_ascii_alphabetic())
+}
+
+/// The addresses `host` resolves to, without duplicates, in the order the
+/// resolver gives them.
+pub fn resolve(host: &str) -> io::Result<Vec<IpAddr>> {
+    let mut addrs = Vec::new();
+    for addr in (host, 0).to_socket_addrs()? {
+        if !addrs.contains(&addr.ip()) {
+            addrs.push(addr.ip());
+        }
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hostnames_are_told_from_networks() {
+        for host in [
+            "localhost",
+            "api.partner.example",
+            "edge-1.cdn.example.",
+            "1e100.net",
+        ] {
+            assert!(is_hostname(host), "{}", host);
+        }
+        for value in [
+            "10.0.0.0/8",
+            "10.0.0.1",
+            "2001:db8::1",
+            "-bad.example",
+            "bad..example",
+            "under_score.example",
+            "",
+        ] {
+            assert!(!is_hostname(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn resolve_localhost() {
+        let addrs = resolve("localhost").unwrap();
+        assert!(addrs.iter().all(|addr| addr.is_loopback()), "{:?}", addrs);
+        assert!(resolve("nowhere.invalid").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Resolver outputs: the networks of a build as the client policy of DNS
+//! resolvers, so that they refuse the very networks the checkers do.
+//!
+//! The `unbound` backend writes `access-control: <network> refuse` lines
+//! in a `server:` clause next to the output, with the extension
+//! `.unbound.conf`, for an `include:` in `unbound.conf`.
+//!
+//! The `rpz` backend writes a response policy zone next to the output,
+//! with the extension `.rpz.zone`, whose `rpz-client-ip` triggers answer
+//! `NXDOMAIN` to the clients in the networks, for BIND, Unbound and the
+//! other resolvers loading RPZ feeds. Its serial is the time of the build,
+//! kept by a build of the same networks, so that secondaries transfer the
+//! zone exactly when it changes.
+
+use std::fmt::Write;
+use std::fs;
+use std::net::Ipv6Addr;
+use std::time::SystemTime;
+
+use ipcheck_rs::backend::{Error, Filters, Output, OutputBackend};
+
+use crate::firewall::sorted;
+
+/// The `access-control:` lines of the networks.
+pub struct Unbound;
+
+impl OutputBackend for Unbound {
+    fn name(&self) -> &'static str {
+        "unbound"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let mut conf = String::from("# Generated by ipcheck; do not edit.\nserver:\n");
+        let v4 = sorted(filters.v4)
+            .into_iter()
+            .map(|network| network.to_string());
+        let v6 = sorted(filters.v6)
+            .into_iter()
+            .map(|network| network.to_string());
+        for network in v4.chain(v6) {
+            writeln!(conf, "    access-control: {} refuse", network).unwrap();
+        }
+        Ok(vec![Output {
+            path: filters.output.with_extension("unbound.conf"),
+            contents: conf.into_bytes(),
+        }])
+    }
+}
+
+/// The response policy zone of the networks.
+pub struct Rpz;
+
+impl OutputBackend for Rpz {
+    fn name(&self) -> &'static str {
+        "rpz"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let path = filters.output.with_extension("rpz.zone");
+        let kept = fs::read_to_string(&path).ok().filter(|previous| {
+            rpz_serial(previous).is_some_and(|serial| rpz_zone(filters, serial) == **previous)
+        });
+        let contents = kept.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+            rpz_zone(filters, now.map_or(0, |now| now.as_secs() as u32))
+        });
+        Ok(vec![Output {
+            path,
+            contents: contents.into_bytes(),
+        }])
+    }
+}
+
+/// The zone of the networks of `filters`, with the serial `serial`.
+fn rpz_zone(filters: &Filters, serial: u32) -> String {
+    let mut zone = String::from("; Generated by ipcheck; do not edit.\n$TTL 300\n");
+    writeln!(
+        zone,
+        "@ IN SOA localhost. hostmaster.localhost. {} 3600 600 604800 300\n  IN NS localhost.",
+        serial
+    )
+    .unwrap();
+    for network in sorted(filters.v4) {
+        let octets = network.network().octets();
+        let reversed = octets.iter().rev().map(|octet| octet.to_string());
+        let owner = reversed.collect::<Vec<_>>().join(".");
+        writeln!(
+            zone,
+            "{}.{}.rpz-client-ip CNAME .",
+            network.prefix_len(),
+            owner
+        )
+        .unwrap();
+    }
+    for network in sorted(filters.v6) {
+        let owner = rpz_v6_labels(network.network());
+        writeln!(
+            zone,
+            "{}.{}.rpz-client-ip CNAME .",
+            network.prefix_len(),
+            owner
+        )
+        .unwrap();
+    }
+    zone
+}
+
+/// The labels of an IPv6 trigger address: its groups in hex, the longest
+/// run of two or more zero groups as `zz`, as `::` in RFC 5952, reversed.
+fn rpz_v6_labels(addr: Ipv6Addr) -> String {
+    let segments = addr.segments();
+    let mut zeros = (0, 0);
+    let mut start = 0;
+    for (i, &segment) in segments.iter().enumerate() {
+        if segment != 0 {
+            start = i + 1;
+        } else if i + 1 - start > zeros.1 - zeros.0 {
+            zeros = (start, i + 1);
+        }
+    }
+    let mut labels: Vec<String> = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        if zeros.1 - zeros.0 >= 2 && (zeros.0..zeros.1).contains(&i) {
+            if i == zeros.0 {
+                labels.push("zz".to_string());
+            }
+        } else {
+            labels.push(format!("{:x}", segment));
+        }
+    }
+    labels.reverse();
+    labels.join(".")
+}
+
+/// The serial of the SOA record of the zone `zone`.
+fn rpz_serial(zone: &str) -> Option<u32> {
+    let soa = zone.lines().find(|line| line.starts_with("@ IN SOA "))?;
+    soa.split_whitespace().nth(5)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipcheck_rs::backend::{Metadata, Values};
+    use ipcheck_rs::IpRange;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    static METADATA: Metadata = Metadata {
+        timestamp: String::new(),
+        inputs: Vec::new(),
+        vars: BTreeMap::new(),
+    };
+    static VALUES: Values = Values {
+        names: Vec::new(),
+        v4: Vec::new(),
+        v6: Vec::new(),
+    };
+
+    fn filters<'a>(
+        output: &'a Path,
+        v4: &'a IpRange<ipnet::Ipv4Net>,
+        v6: &'a IpRange<ipnet::Ipv6Net>,
+    ) -> Filters<'a> {
+        Filters {
+            output,
+            v4,
+            v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &METADATA,
+            values: &VALUES,
+        }
+    }
+
+    #[test]
+    fn unbound_access_control() {
+        let v4: IpRange<_> = ["192.0.2.0/24"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("dns/blocklist.ts");
+        let outputs = Unbound.render(&filters(&output, &v4, &v6)).unwrap();
+        assert_eq!(outputs[0].path, Path::new("dns/blocklist.unbound.conf"));
+        assert_eq!(
+            String::from_utf8(outputs[0].contents.clone()).unwrap(),
+            "# Generated by ipcheck; do not edit.\n\
+             server:\n\
+             \x20   access-control: 192.0.2.0/24 refuse\n\
+             \x20   access-control: 2001:db8::/32 refuse\n"
+        );
+    }
+
+    #[test]
+    fn rpz_client_ip_triggers() {
+        let v4: IpRange<_> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("blocklist.ts");
+        let zone = rpz_zone(&filters(&output, &v4, &v6), 1_700_000_000);
+        assert!(
+            zone.ends_with(
+                "@ IN SOA localhost. hostmaster.localhost. 1700000000 3600 600 604800 300\n\
+                 \x20 IN NS localhost.\n\
+                 8.0.0.0.10.rpz-client-ip CNAME .\n\
+                 32.zz.db8.2001.rpz-client-ip CNAME .\n"
+            ),
+            "{}",
+            zone
+        );
+        assert_eq!(rpz_serial(&zone), Some(1_700_000_000));
+
+        // A rebuild of the same networks keeps the serial.
+        let dir = std::env::temp_dir().join(format!("ipcheck-rpz-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("blocklist.ts");
+        fs::write(dir.join("blocklist.rpz.zone"), &zone).unwrap();
+        let outputs = Rpz.render(&filters(&output, &v4, &v6)).unwrap();
+        assert_eq!(outputs[0].contents, zone.as_bytes());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let labels = |addr: &str| rpz_v6_labels(addr.parse().unwrap());
+        assert_eq!(labels("::"), "zz");
+        assert_eq!(labels("2001:db8:0:1::"), "zz.1.0.db8.2001");
+        assert_eq!(labels("1:0:0:2::"), "zz.2.0.0.1");
+        assert_eq!(labels("::ffff:1.2.3.4"), "304.102.ffff.zz");
+        assert_eq!(labels("1:2:3:4:5:6:7:0"), "0.7.6.5.4.3.2.1");
+    }
+}
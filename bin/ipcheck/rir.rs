@@ -0,0 +1,108 @@
+//! `--format rir-delegated`: the address delegations of a Regional
+//! Internet Registry, as published in its (extended) delegation statistics,
+//! such as `delegated-apnic-extended-latest`.
+//!
+//! Each row reads `registry|cc|type|start|value|date|status`, where an
+//! `ipv4` row's value counts addresses, not always a power of two, and an
+//! `ipv6` row's is a prefix length. Only allocated and assigned rows are
+//! read; the header, summaries, ASNs and unallocated space are skipped.
+
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+/// The parser name selecting this format.
+pub const FORMAT: &str = "rir-delegated";
+
+/// Whether the file at `path` is named as the registries name theirs.
+pub fn sniff(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("delegated-"))
+}
+
+/// The addresses delegated to one country.
+#[derive(Debug, PartialEq)]
+pub struct Delegation<'a> {
+    /// The ISO code of the country.
+    pub country: &'a str,
+    /// A network, or a `start-end` range.
+    pub value: String,
+}
+
+/// The delegation of `line`, or `None` if it is not one of addresses.
+pub fn parse_line(line: &str) -> Option<Result<Delegation<'_>, String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let fields: Vec<&str> = line.split('|').collect();
+    let kind = *fields.get(2)?;
+    if !matches!(kind, "ipv4" | "ipv6") || fields.get(5) == Some(&"summary") {
+        return None;
+    }
+    let [_, country, _, start, value, _, status, ..] = fields[..] else {
+        return Some(Err(format!("{:?} is not a delegation", line)));
+    };
+    if !matches!(status, "allocated" | "assigned") {
+        return None;
+    }
+    let value = match kind {
+        "ipv4" => match ipv4_range(start, value) {
+            Some(range) => range,
+            None => return Some(Err(format!("{:?} is not a delegation", line))),
+        },
+        _ => format!("{}/{}", start, value),
+    };
+    Some(Ok(Delegation { country, value }))
+}
+
+/// The range of the `count` addresses from `start`.
+fn ipv4_range(start: &str, count: &str) -> Option<String> {
+    let start: Ipv4Addr = start.parse().ok()?;
+    let count: u32 = count.parse().ok()?;
+    let end = u32::from(start).checked_add(count.checked_sub(1)?)?;
+    Some(format!("{}-{}", start, Ipv4Addr::from(end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_delegation_rows() {
+        let parse = |line| parse_line(line).map(|row| row.map(|row| (row.country, row.value)));
+        assert_eq!(
+            parse("apnic|CN|ipv4|1.0.1.0|768|20110414|allocated"),
+            Some(Ok(("CN", "1.0.1.0-1.0.3.255".to_string())))
+        );
+        assert_eq!(
+            parse("apnic|JP|ipv6|2001:200::|35|19990813|allocated|A91A7381"),
+            Some(Ok(("JP", "2001:200::/35".to_string())))
+        );
+        for skipped in [
+            "2|apnic|20240101|72824|19830613|20231229|+1000",
+            "apnic|*|ipv4|*|52345|summary",
+            "apnic|AU|asn|173|1|20020801|allocated",
+            "apnic||ipv4|1.0.0.0|256||available",
+            "# comment",
+            "",
+        ] {
+            assert_eq!(parse(skipped), None, "{}", skipped);
+        }
+        assert_eq!(
+            parse("apnic|CN|ipv4|1.0.1.0|0|20110414|allocated"),
+            Some(Err(
+                "\"apnic|CN|ipv4|1.0.1.0|0|20110414|allocated\" is not a delegation".to_string()
+            ))
+        );
+        assert!(parse("apnic|CN|ipv4|1.0.1.0").unwrap().is_err());
+    }
+
+    #[test]
+    fn sniff_delegation_files() {
+        assert!(sniff(Path::new(
+            "https://ftp.apnic.net/stats/apnic/delegated-apnic-extended-latest"
+        )));
+        assert!(!sniff(Path::new("lists/delegated.csv")));
+    }
+}
@@ -0,0 +1,481 @@
+//! `ipcheck serve`: answering lookups over HTTP.
+//!
+//! * `GET /check?ip=<address>` answers a single lookup.
+//! * `POST /check` with a body of `{"ips": ["<address>", ...]}` answers a
+//!   batch, in order.
+//!
+//! Lookups answer with `{"ip": ..., "matches": ..., "network": ...}`, where
+//! `network` is the matching block or `null`.
+//!
+//! `GET /metrics` reports lookup counts and the size of the networks in the
+//! Prometheus text format.
+//!
+//! Requests are answered by a few worker threads sharing the listener, so a
+//! slow client does not hold up the others.
+//!
+//! The networks are reloaded on `SIGHUP`, and with `--watch` whenever the
+//! inputs change (see [`reload`](crate::reload)). Under systemd, `serve`
+//! can be socket activated and reports readiness (see
+//! [`systemd`](crate::systemd)).
+//!
+//! With the `grpc` feature, `--grpc-listen` also answers the same lookups
+//! over gRPC from the same in-memory networks, and with the `dns` feature,
+//! `--dns-listen` answers them as DNSBL queries (see [`dns`](crate::dns)).
+//! With the `nats` feature, `--nats-url` applies changes streamed from NATS
+//! (see [`nats`](crate::nats)).
+//! On Unix, `--unix-socket` answers them on a Unix socket with a small
+//! binary protocol (see [`unix_socket`](crate::unix_socket)).
+
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use clap::Args;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::input::Networks;
+use crate::match_log::MatchLog;
+use crate::metrics::Metrics;
+use crate::reload::{self, Live, SourceArgs};
+
+/// Largest accepted `POST /check` body.
+const MAX_BODY_BYTES: u64 = 1 << 20;
+
+/// Most addresses accepted in one batch.
+const MAX_BATCH: usize = 10_000;
+
+/// Threads answering HTTP requests.
+const WORKERS: usize = 4;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    #[command(flatten)]
+    source: SourceArgs,
+
+    /// Reload the networks when the input files change. They are always
+    /// reloaded on SIGHUP.
+    #[arg(long)]
+    watch: bool,
+
+    #[cfg(feature = "grpc")]
+    #[command(flatten)]
+    grpc: crate::grpc::GrpcArgs,
+
+    #[cfg(unix)]
+    #[command(flatten)]
+    unix_socket: crate::unix_socket::UnixSocketArgs,
+
+    #[cfg(unix)]
+    #[command(flatten)]
+    match_log: crate::match_log::MatchLogArgs,
+
+    #[cfg(feature = "dns")]
+    #[command(flatten)]
+    dns: crate::dns::DnsArgs,
+
+    #[cfg(feature = "nats")]
+    #[command(flatten)]
+    nats: crate::nats::NatsArgs,
+}
+
+/// The answer to one lookup.
+#[derive(Serialize)]
+pub struct Lookup {
+    pub ip: String,
+    pub matches: bool,
+    pub network: Option<String>,
+}
+
+/// Where a lookup is recorded: the metrics and the match log.
+pub struct Recorder<'a> {
+    pub metrics: &'a Metrics,
+    pub log: &'a MatchLog,
+    /// The client that asked, if the transport has an address for it.
+    pub client: Option<SocketAddr>,
+}
+
+impl Recorder<'_> {
+    pub fn record(&self, lookup: &Lookup) {
+        self.metrics.record(lookup);
+        self.log.record(self.client, lookup);
+    }
+}
+
+#[derive(Deserialize)]
+struct Batch {
+    ips: Vec<String>,
+}
+
+impl ServeArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        self.source.assign_paths()
+    }
+}
+
+pub fn serve(args: ServeArgs) -> Result<()> {
+    #[cfg(unix)]
+    let activated = crate::systemd::listener();
+    #[cfg(not(unix))]
+    let activated: Option<tiny_http::Listener> = None;
+
+    let source = args.source.source();
+    let networks = source.load()?;
+    #[cfg(unix)]
+    let log = Arc::new(MatchLog::open(&args.match_log, &source)?);
+    #[cfg(not(unix))]
+    let log = Arc::new(MatchLog::default());
+    let metrics = Arc::new(Metrics::default());
+    metrics.loaded(&networks);
+    let live = Arc::new(Live::new(networks));
+    let source = Arc::new(source);
+    #[cfg(unix)]
+    reload::reload_on_sighup(Arc::clone(&source), Arc::clone(&live), Arc::clone(&metrics))?;
+    if args.watch {
+        reload::watch(source, Arc::clone(&live), Arc::clone(&metrics));
+    }
+
+    let server = match activated {
+        Some(listener) => Server::from_listener(listener, None)
+            .map_err(|err| eyre!("socket passed by systemd: {}", err))?,
+        None => Server::http(&args.listen).map_err(|err| eyre!("{}: {}", args.listen, err))?,
+    };
+    tracing::info!("listening on http://{}", server.server_addr());
+
+    #[cfg(feature = "nats")]
+    crate::nats::spawn(&args.nats, Arc::clone(&live), Arc::clone(&metrics))?;
+    #[cfg(unix)]
+    if args.unix_socket.enabled() {
+        crate::unix_socket::spawn(
+            &args.unix_socket,
+            Arc::clone(&live),
+            Arc::clone(&metrics),
+            Arc::clone(&log),
+        )?;
+    }
+    #[cfg(feature = "dns")]
+    if args.dns.enabled() {
+        crate::dns::spawn(
+            &args.dns,
+            Arc::clone(&live),
+            Arc::clone(&metrics),
+            Arc::clone(&log),
+        )?;
+    }
+
+    #[cfg(unix)]
+    crate::systemd::notify("READY=1");
+
+    #[cfg(feature = "grpc")]
+    if args.grpc.enabled() {
+        let http_live = Arc::clone(&live);
+        let http_metrics = Arc::clone(&metrics);
+        let http_log = Arc::clone(&log);
+        std::thread::spawn(move || serve_http(server, &http_live, &http_metrics, &http_log));
+        return crate::grpc::serve(&args.grpc, live, metrics, log);
+    }
+    serve_http(server, &live, &metrics, &log);
+    Ok(())
+}
+
+/// Answers HTTP requests on `WORKERS` threads until the server shuts down.
+pub fn serve_http(server: Server, live: &Live, metrics: &Metrics, log: &MatchLog) {
+    let server = &server;
+    std::thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    respond(request, live, metrics, log);
+                }
+            });
+        }
+    });
+}
+
+/// Answers `request`, the metrics as they are and lookups with `handle`.
+fn respond(mut request: Request, live: &Live, metrics: &Metrics, log: &MatchLog) {
+    let path = request.url().split('?').next().unwrap_or_default();
+    if path == "/metrics" && *request.method() == Method::Get {
+        let response = Response::from_string(metrics.render())
+            .with_header(Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap());
+        if let Err(err) = request.respond(response) {
+            tracing::warn!("failed to send response: {}", err);
+        }
+        return;
+    }
+
+    let mut body = Vec::new();
+    let read = request
+        .as_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut body);
+    let (status, value) = match read {
+        Ok(_) if body.len() as u64 > MAX_BODY_BYTES => error(413, "request body is too large"),
+        Ok(_) => {
+            let client = request.remote_addr().copied();
+            let recorder = Recorder {
+                metrics,
+                log,
+                client,
+            };
+            handle(
+                request.method(),
+                request.url(),
+                &body,
+                &live.get(),
+                &recorder,
+            )
+        }
+        Err(err) => error(400, &err.to_string()),
+    };
+    let response = Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    if let Err(err) = request.respond(response) {
+        tracing::warn!("failed to send response: {}", err);
+    }
+}
+
+/// Answers one request, returning the status code and JSON body.
+fn handle(
+    method: &Method,
+    url: &str,
+    body: &[u8],
+    networks: &Networks,
+    recorder: &Recorder,
+) -> (u16, Value) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    if path == "/metrics" {
+        return error(405, "use GET");
+    }
+    if path != "/check" {
+        return error(404, "not found");
+    }
+    match method {
+        Method::Get => {
+            let ip = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|&(key, _)| key == "ip")
+                .map(|(_, value)| percent_decode(value));
+            match ip.as_deref().map(|ip| lookup(ip, networks)) {
+                Some(Some(result)) => {
+                    recorder.record(&result);
+                    (200, json!(result))
+                }
+                Some(None) => error(400, "invalid IP address"),
+                None => error(400, "missing ip parameter"),
+            }
+        }
+        Method::Post => {
+            let batch: Batch = match serde_json::from_slice(body) {
+                Ok(batch) => batch,
+                Err(err) => return error(400, &format!("invalid request body: {}", err)),
+            };
+            if batch.ips.len() > MAX_BATCH {
+                return error(413, &format!("at most {} addresses per batch", MAX_BATCH));
+            }
+            let results: Option<Vec<Lookup>> =
+                batch.ips.iter().map(|ip| lookup(ip, networks)).collect();
+            match results {
+                Some(results) => {
+                    results.iter().for_each(|result| recorder.record(result));
+                    (200, json!({ "results": results }))
+                }
+                None => error(400, "invalid IP address"),
+            }
+        }
+        _ => error(405, "use GET or POST"),
+    }
+}
+
+/// Looks `ip` up, returning `None` if it is not an address.
+pub fn lookup(ip: &str, networks: &Networks) -> Option<Lookup> {
+    let network = match ip.parse::<IpAddr>().ok()? {
+        IpAddr::V4(addr) => networks.v4.supernet(&addr).map(|n| n.to_string()),
+        IpAddr::V6(addr) => networks.v6.supernet(&addr).map(|n| n.to_string()),
+    };
+    Some(Lookup {
+        ip: ip.to_string(),
+        matches: network.is_some(),
+        network,
+    })
+}
+
+fn error(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
+
+/// Decodes `%XX` escapes, as clients send `:` in IPv6 addresses.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn networks() -> Networks {
+        Networks {
+            v4: ["10.0.0.0/8", "192.168.1.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn get_check() {
+        let networks = networks();
+        let metrics = Metrics::default();
+        let log = MatchLog::default();
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        assert_eq!(
+            handle(
+                &Method::Get,
+                "/check?ip=10.1.2.3",
+                b"",
+                &networks,
+                &recorder
+            ),
+            (
+                200,
+                json!({ "ip": "10.1.2.3", "matches": true, "network": "10.0.0.0/8" })
+            )
+        );
+        assert_eq!(
+            handle(
+                &Method::Get,
+                "/check?x=1&ip=2001%3Adb8%3A%3A1",
+                b"",
+                &networks,
+                &recorder
+            ),
+            (
+                200,
+                json!({ "ip": "2001:db8::1", "matches": true, "network": "2001:db8::/32" })
+            )
+        );
+        assert_eq!(
+            handle(&Method::Get, "/check?ip=8.8.8.8", b"", &networks, &recorder),
+            (
+                200,
+                json!({ "ip": "8.8.8.8", "matches": false, "network": null })
+            )
+        );
+    }
+
+    #[test]
+    fn post_check_batch() {
+        let networks = networks();
+        let metrics = Metrics::default();
+        let log = MatchLog::default();
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        let (status, value) = handle(
+            &Method::Post,
+            "/check",
+            br#"{"ips": ["192.168.1.7", "::1"]}"#,
+            &networks,
+            &recorder,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            value,
+            json!({ "results": [
+                { "ip": "192.168.1.7", "matches": true, "network": "192.168.1.0/24" },
+                { "ip": "::1", "matches": false, "network": null },
+            ]})
+        );
+        let text = metrics.render();
+        assert!(text.contains("ipcheck_lookups_total{result=\"hit\"} 1\n"));
+        assert!(text.contains("ipcheck_lookups_total{result=\"miss\"} 1\n"));
+    }
+
+    #[test]
+    fn reject_bad_requests() {
+        let networks = networks();
+        let metrics = Metrics::default();
+        let log = MatchLog::default();
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        let status = |method, url, body: &[u8]| handle(&method, url, body, &networks, &recorder).0;
+        assert_eq!(status(Method::Get, "/check?ip=nope", b""), 400);
+        assert_eq!(status(Method::Get, "/check", b""), 400);
+        assert_eq!(status(Method::Get, "/other?ip=10.0.0.1", b""), 404);
+        assert_eq!(status(Method::Delete, "/check?ip=10.0.0.1", b""), 405);
+        assert_eq!(status(Method::Post, "/metrics", b""), 405);
+        assert_eq!(status(Method::Post, "/check", b"[1, 2]"), 400);
+        assert_eq!(status(Method::Post, "/check", br#"{"ips": ["x"]}"#), 400);
+
+        let batch = json!({ "ips": vec!["10.0.0.1"; MAX_BATCH + 1] }).to_string();
+        assert_eq!(status(Method::Post, "/check", batch.as_bytes()), 413);
+    }
+
+    #[test]
+    fn metrics_with_a_query() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || {
+            let metrics = Metrics::default();
+            metrics.loaded(&networks());
+            let live = Live::new(networks());
+            serve_http(server, &live, &metrics, &MatchLog::default());
+        });
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics?x=1 HTTP/1.1\r\nHost: ipcheck\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("ipcheck_lookups_total"), "{}", response);
+    }
+
+    #[test]
+    fn percent_decode_escapes() {
+        assert_eq!(percent_decode("fe80%3A%3a1"), "fe80::1");
+        assert_eq!(percent_decode("10.0.0.1"), "10.0.0.1");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,76 @@
+//! SHA-256 (FIPS 180-4), fingerprinting the lists of a build for the
+//! provenance stamped into templates.
+
+use std::io::{self, Read};
+
+use ring::digest::{Context, SHA256};
+
+/// A SHA-256 computation.
+#[derive(Clone)]
+pub struct Sha256(Context);
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256(Context::new(&SHA256))
+    }
+}
+
+impl Sha256 {
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// The digest of the bytes hashed, in lowercase hex.
+    pub fn hex(self) -> String {
+        self.0
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// A reader hashing the bytes read through it.
+pub struct Hashing<R> {
+    inner: R,
+    hash: Sha256,
+}
+
+impl<R: Read> Hashing<R> {
+    pub fn new(inner: R) -> Hashing<R> {
+        Hashing {
+            inner,
+            hash: Sha256::default(),
+        }
+    }
+
+    /// Reads the rest of the input, and returns the digest of all of it.
+    pub fn finish(mut self) -> io::Result<String> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hash.hex())
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hash.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashing_reads_the_rest() {
+        let mut hashing = Hashing::new(&b"abc"[..]);
+        hashing.read_exact(&mut [0; 1]).unwrap();
+        assert_eq!(
+            hashing.finish().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! `--target sql`: the networks as a table of address ranges, for lookups
+//! in a database.
+//!
+//! The script creates the table, named after the output as the firewall
+//! sets are, if it does not exist, and replaces its rows in a transaction.
+//! Each network is a row of its `family`, 4 or 6, its first and last
+//! addresses as integers, `range_start` and `range_end`, and its CIDR
+//! string. The integers are `NUMERIC(39, 0)` in PostgreSQL and
+//! `DECIMAL(39, 0)` in MySQL, as IPv6 addresses overflow a `BIGINT`, and
+//! the primary key on `(family, range_start)` serves lookups of an address
+//! `BETWEEN` the two.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use ipcheck_rs::backend::{Error, Filters, Output, OutputBackend};
+use ipnet::IpNet;
+
+use crate::firewall::{set_name, sorted};
+use crate::input::Networks;
+
+/// The rows of one `INSERT` statement.
+const BATCH: usize = 1000;
+
+/// The SQL dialect of `--target sql`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// PostgreSQL.
+    #[default]
+    Postgres,
+    /// MySQL and MariaDB.
+    Mysql,
+}
+
+impl Dialect {
+    /// The type of the addresses.
+    fn integer(self) -> &'static str {
+        match self {
+            Dialect::Postgres => "NUMERIC(39, 0)",
+            Dialect::Mysql => "DECIMAL(39, 0)",
+        }
+    }
+
+    /// The statement starting a transaction.
+    fn begin(self) -> &'static str {
+        match self {
+            Dialect::Postgres => "BEGIN",
+            Dialect::Mysql => "START TRANSACTION",
+        }
+    }
+}
+
+/// The SQL script of the range table of the networks.
+pub struct Sql(pub Dialect);
+
+impl OutputBackend for Sql {
+    fn name(&self) -> &'static str {
+        "sql"
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+        let table = set_name(filters.output);
+        let mut sql = String::from("-- Generated by ipcheck; do not edit.\n");
+        writeln!(
+            sql,
+            "--\n\
+             -- The networks, with the integers of their first and last addresses.\n\
+             -- An address is in them if a row of its family holds it:\n\
+             --\n\
+             --     SELECT network FROM {table}\n\
+             --     WHERE family = 4 AND 167772161 BETWEEN range_start AND range_end;\n\
+             \n\
+             CREATE TABLE IF NOT EXISTS {table} (\n\
+             \x20   family SMALLINT NOT NULL,\n\
+             \x20   range_start {integer} NOT NULL,\n\
+             \x20   range_end {integer} NOT NULL,\n\
+             \x20   network VARCHAR(43) NOT NULL,\n\
+             \x20   PRIMARY KEY (family, range_start)\n\
+             );\n\
+             \n\
+             {begin};\n\
+             DELETE FROM {table};",
+            integer = self.0.integer(),
+            begin = self.0.begin(),
+        )
+        .unwrap();
+        let v4 = sorted(filters.v4).into_iter().map(IpNet::V4);
+        let v6 = sorted(filters.v6).into_iter().map(IpNet::V6);
+        let rows: Vec<String> = v4.chain(v6).map(row).collect();
+        for batch in rows.chunks(BATCH) {
+            writeln!(
+                sql,
+                "INSERT INTO {} (family, range_start, range_end, network) VALUES\n{};",
+                table,
+                batch.join(",\n")
+            )
+            .unwrap();
+        }
+        sql.push_str("COMMIT;\n");
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: sql.into_bytes(),
+        }])
+    }
+}
+
+/// The row of `network`.
+fn row(network: IpNet) -> String {
+    let (family, start, end) = match network {
+        IpNet::V4(network) => (
+            4,
+            u32::from(network.network()).into(),
+            u32::from(network.broadcast()).into(),
+        ),
+        IpNet::V6(network) => (
+            6,
+            u128::from(network.network()),
+            u128::from(network.broadcast()),
+        ),
+    };
+    format!("    ({}, {}, {}, '{}')", family, start, end, network)
+}
+
+/// The networks of the rows of the script `sql`, as `--target sql` writes
+/// it, `None` if it is not one.
+pub fn sql_networks(sql: &str) -> Option<Networks> {
+    let mut lines = sql.lines();
+    lines.find(|line| line.starts_with("CREATE TABLE IF NOT EXISTS "))?;
+    let mut networks = Networks::default();
+    for line in lines.filter(|line| line.starts_with("    (")) {
+        match line.split('\'').nth(1)?.parse().ok()? {
+            IpNet::V4(network) => {
+                networks.v4.add(network);
+            }
+            IpNet::V6(network) => {
+                networks.v6.add(network);
+            }
+        }
+    }
+    Some(networks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipcheck_rs::IpRange;
+    use std::path::PathBuf;
+
+    #[test]
+    fn range_table() {
+        let v4: IpRange<_> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<_> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let output = PathBuf::from("blocked_networks.sql");
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let sql = Sql(Dialect::Postgres).render(&filters).unwrap().remove(0);
+        let sql = String::from_utf8(sql.contents).unwrap();
+        assert!(
+            sql.ends_with(
+                "CREATE TABLE IF NOT EXISTS blocked_networks (\n\
+                 \x20   family SMALLINT NOT NULL,\n\
+                 \x20   range_start NUMERIC(39, 0) NOT NULL,\n\
+                 \x20   range_end NUMERIC(39, 0) NOT NULL,\n\
+                 \x20   network VARCHAR(43) NOT NULL,\n\
+                 \x20   PRIMARY KEY (family, range_start)\n\
+                 );\n\
+                 \n\
+                 BEGIN;\n\
+                 DELETE FROM blocked_networks;\n\
+                 INSERT INTO blocked_networks (family, range_start, range_end, network) VALUES\n\
+                 \x20   (4, 167772160, 184549375, '10.0.0.0/8'),\n\
+                 \x20   (6, 42540766411282592856903984951653826560, \
+                 42540766490510755371168322545197776895, '2001:db8::/32');\n\
+                 COMMIT;\n"
+            ),
+            "{}",
+            sql
+        );
+        let networks = Networks {
+            v4: v4.clone(),
+            v6: v6.clone(),
+        };
+        assert_eq!(sql_networks(&sql), Some(networks));
+
+        let sql = Sql(Dialect::Mysql).render(&filters).unwrap().remove(0);
+        let sql = String::from_utf8(sql.contents).unwrap();
+        assert!(
+            sql.contains("range_end DECIMAL(39, 0) NOT NULL,\n"),
+            "{}",
+            sql
+        );
+        assert!(sql.contains("\nSTART TRANSACTION;\n"), "{}", sql);
+    }
+
+    #[test]
+    fn batches_inserts() {
+        let v4: IpRange<_> = (0..2500u32)
+            .map(|i| ipnet::Ipv4Net::new((i << 9).into(), 24).unwrap())
+            .collect();
+        let output = PathBuf::from("out.sql");
+        let filters = Filters {
+            output: &output,
+            v4: &v4,
+            v6: &IpRange::new(),
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let sql = Sql(Dialect::Postgres).render(&filters).unwrap().remove(0);
+        let sql = String::from_utf8(sql.contents).unwrap();
+        assert_eq!(sql.matches("INSERT INTO").count(), 3);
+        assert_eq!(sql_networks(&sql).unwrap().v4, v4);
+    }
+}
@@ -0,0 +1,279 @@
+//! `ipcheck export sqlite`: the networks as an SQLite database, for tooling
+//! in other languages to inspect and query.
+//!
+//! The database holds three tables:
+//!
+//! * `metadata`, `key`/`value` pairs such as the format version and the
+//!   number of networks of each family;
+//! * `networks`, one row per simplified network, with its `family` (4 or
+//!   6), its CIDR string, its `first` and `last` addresses as big-endian
+//!   blobs and, with `--geoip`, its comma-separated `country` codes and
+//!   `asn`s;
+//! * `trie`, a single row whose `artifact` is the binary artifact, which
+//!   [`load`] reads back.
+//!
+//! Blobs compare bytewise and the networks do not overlap, so the index on
+//! `(family, first, last)` finds the network containing the address bytes
+//! `?2` of family `?1` as the last one starting at or before it:
+//!
+//! ```sql
+//! SELECT network FROM (
+//!     SELECT network, last FROM networks
+//!     WHERE family = ?1 AND first <= ?2
+//!     ORDER BY first DESC LIMIT 1
+//! ) WHERE last >= ?2
+//! ```
+//!
+//! [`load`]: fn.load.html
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use eyre::{bail, Result, WrapErr};
+use ipcheck_rs::artifact;
+use ipcheck_rs::flat::FORMAT_VERSION;
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::input::{InputOptions, Lists, Networks};
+use crate::output;
+use crate::paths::{self, Slot};
+use crate::reserved::ReservedPolicy;
+
+const SCHEMA: &str = "
+    CREATE TABLE metadata (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE networks (
+        family INTEGER NOT NULL,
+        network TEXT NOT NULL,
+        first BLOB NOT NULL,
+        last BLOB NOT NULL,
+        country TEXT,
+        asn TEXT
+    );
+    CREATE INDEX networks_by_address ON networks (family, first, last);
+    CREATE TABLE trie (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        artifact BLOB NOT NULL
+    );
+";
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Where to write the database.
+    #[arg(short, long = "output", value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    lists: Lists,
+
+    /// The output, IPv4 list and IPv6 list, for those not given by flag.
+    #[arg(value_names = ["OUTPUT", "V4", "V6"], num_args = 0..=3)]
+    paths: Vec<PathBuf>,
+
+    /// Overwrite an existing database.
+    #[arg(long)]
+    force: bool,
+
+    /// What to do with private, loopback, link-local and documentation
+    /// networks found in the inputs.
+    #[arg(long, value_enum, default_value_t)]
+    reserved_policy: ReservedPolicy,
+
+    #[command(flatten)]
+    input: InputOptions,
+
+    #[cfg(feature = "geoip")]
+    #[command(flatten)]
+    geoip: crate::geoip::GeoipArgs,
+}
+
+/// The `country` and `asn` of each annotated network, by CIDR string.
+type Notes = HashMap<String, (Option<String>, Option<String>)>;
+
+impl ExportArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        let mut slots = vec![Slot::required(&mut self.output, "--output")];
+        slots.extend(self.lists.slots());
+        paths::assign(&mut self.paths, slots)
+    }
+}
+
+pub fn export(args: ExportArgs) -> Result<()> {
+    let networks = crate::load_filtered(&args.lists, &args.input, args.reserved_policy)?;
+    let path = args
+        .output
+        .as_deref()
+        .expect("the output is assigned when parsed");
+    if path.exists() && !args.force {
+        bail!(
+            "{} already exists (pass --force to overwrite it)",
+            path.display()
+        );
+    }
+    #[cfg(feature = "geoip")]
+    let notes: Notes = args
+        .geoip
+        .annotate(&networks)?
+        .into_iter()
+        .map(|(network, geo)| (network, (geo.country(), geo.asn())))
+        .collect();
+    #[cfg(not(feature = "geoip"))]
+    let notes = Notes::new();
+    output::write_atomic_with(path, |tmp| write(tmp, &networks, &notes))
+}
+
+/// Writes `networks` into a new database at `path`.
+fn write(path: &Path, networks: &Networks, notes: &Notes) -> Result<()> {
+    let mut db = Connection::open(path)?;
+    let tx = db.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    {
+        let mut metadata = tx.prepare("INSERT INTO metadata (key, value) VALUES (?1, ?2)")?;
+        metadata.execute(params!["format_version", FORMAT_VERSION.to_string()])?;
+        metadata.execute(params!["ipcheck_version", env!("CARGO_PKG_VERSION")])?;
+        metadata.execute(params![
+            "ipv4_networks",
+            networks.v4.iter().count().to_string()
+        ])?;
+        metadata.execute(params![
+            "ipv6_networks",
+            networks.v6.iter().count().to_string()
+        ])?;
+
+        let mut insert = tx.prepare(
+            "INSERT INTO networks (family, network, first, last, country, asn)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut add = |family: u8, network: String, first: &[u8], last: &[u8]| {
+            let (country, asn) = notes.get(&network).cloned().unwrap_or_default();
+            insert.execute(params![family, network, first, last, country, asn])
+        };
+        for network in networks.v4.iter() {
+            add(
+                4,
+                network.to_string(),
+                &network.network().octets(),
+                &network.broadcast().octets(),
+            )?;
+        }
+        for network in networks.v6.iter() {
+            add(
+                6,
+                network.to_string(),
+                &network.network().octets(),
+                &network.broadcast().octets(),
+            )?;
+        }
+    }
+    tx.execute(
+        "INSERT INTO trie (id, artifact) VALUES (1, ?1)",
+        params![artifact::write(&networks.v4, &networks.v6)?],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Reads the networks of a database written by `ipcheck export sqlite`,
+/// for `ipcheck serve --sqlite`.
+#[cfg_attr(not(feature = "serve"), allow(dead_code))]
+pub fn load(path: &Path) -> Result<Networks> {
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .wrap_err_with(|| path.display().to_string())?;
+    let bytes: Vec<u8> = db
+        .query_row("SELECT artifact FROM trie WHERE id = 1", [], |row| {
+            row.get(0)
+        })
+        .wrap_err_with(|| format!("{}: not an ipcheck database", path.display()))?;
+    let (v4, v6) =
+        artifact::load(&bytes).wrap_err_with(|| format!("{}: invalid trie", path.display()))?;
+    Ok(Networks { v4, v6 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn export_then_load_and_query() {
+        let path = std::env::temp_dir().join(format!("ipcheck-export-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let networks = Networks {
+            v4: ["10.0.0.0/8", "192.168.1.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        };
+        let notes = Notes::from([(
+            "10.0.0.0/8".to_string(),
+            (Some("US".to_string()), Some("AS64500".to_string())),
+        )]);
+        write(&path, &networks, &notes).unwrap();
+        assert_eq!(load(&path).unwrap(), networks);
+
+        let db = Connection::open(&path).unwrap();
+        let lookup = |family: u8, addr: &[u8]| -> Option<String> {
+            db.query_row(
+                "SELECT network FROM (
+                    SELECT network, last FROM networks
+                    WHERE family = ?1 AND first <= ?2
+                    ORDER BY first DESC LIMIT 1
+                ) WHERE last >= ?2",
+                params![family, addr],
+                |row| row.get(0),
+            )
+            .ok()
+        };
+        assert_eq!(
+            lookup(4, &[192, 168, 1, 77]).as_deref(),
+            Some("192.168.1.0/24")
+        );
+        assert_eq!(lookup(4, &[192, 168, 2, 1]), None);
+        assert_eq!(lookup(4, &[9, 0, 0, 1]), None);
+        let v6: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(lookup(6, &v6.octets()).as_deref(), Some("2001:db8::/32"));
+        let count: String = db
+            .query_row(
+                "SELECT value FROM metadata WHERE key = 'ipv4_networks'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, "2");
+        let geo: (Option<String>, Option<String>) = db
+            .query_row(
+                "SELECT country, asn FROM networks WHERE network = '10.0.0.0/8'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(geo, (Some("US".to_string()), Some("AS64500".to_string())));
+
+        drop(db);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_other_databases() {
+        let path = std::env::temp_dir().join(format!("ipcheck-other-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER);")
+            .unwrap();
+        let err = load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("not an ipcheck database"),
+            "{}",
+            err
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
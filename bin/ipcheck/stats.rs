@@ -0,0 +1,278 @@
+//! `ipcheck stats`: what a feed covers and what its filters cost, and with
+//! `--asn-source`, which ASes originate most of it.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use ipcheck_rs::flat::trie_to_nodes;
+use ipcheck_rs::{IpNet, IpRange};
+
+use crate::asn::{AsnArgs, AsnTable, Group};
+use crate::input::{InputOptions, Lists, Networks, Report};
+use crate::paths;
+use crate::reserved::ReservedPolicy;
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    lists: Lists,
+
+    /// The IPv4 and IPv6 lists, for those not given by flag.
+    #[arg(value_names = ["V4", "V6"], num_args = 0..=2)]
+    paths: Vec<PathBuf>,
+
+    /// How many of the largest ASes to list.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// What to do with private, loopback, link-local and documentation
+    /// networks found in the inputs.
+    #[arg(long, value_enum, default_value_t)]
+    reserved_policy: ReservedPolicy,
+
+    #[command(flatten)]
+    input: InputOptions,
+
+    #[command(flatten)]
+    asn: AsnArgs,
+}
+
+impl StatsArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        paths::assign(&mut self.paths, self.lists.slots())
+    }
+}
+
+pub fn stats(args: StatsArgs) -> Result<()> {
+    let (mut networks, report) = crate::load_reported(&args.lists, &args.input)?;
+    crate::apply_policy(&mut networks, args.reserved_policy);
+    let table = args.asn.load()?;
+    print!("{}", render(&networks, &report, table.as_ref(), args.top));
+    Ok(())
+}
+
+/// The number of addresses of `networks` by family; IPv6 saturates at
+/// `u128::MAX` for `::/0`.
+fn addresses(networks: &Networks) -> (u128, u128) {
+    let v4 = networks
+        .v4
+        .iter()
+        .map(|net| 1u128 << (32 - net.prefix_len()))
+        .sum();
+    let v6 = networks
+        .v6
+        .iter()
+        .map(|net| {
+            1u128
+                .checked_shl(128 - u32::from(net.prefix_len()))
+                .unwrap_or(u128::MAX)
+        })
+        .fold(0u128, u128::saturating_add);
+    (v4, v6)
+}
+
+/// The shape of the filter of one family.
+#[derive(Debug, PartialEq)]
+struct Trie {
+    nodes: usize,
+    depth: u8,
+    /// The length of the node array as embedded in the generated code.
+    bytes: usize,
+}
+
+impl Trie {
+    fn of<N: IpNet>(range: &IpRange<N>) -> Trie {
+        let nodes = match range.clone().into_trie().into_boxed_node() {
+            Some(trie) => trie_to_nodes(trie),
+            None => Vec::new(),
+        };
+        // `[n,n,...]`, as rendered into the checker.
+        let digits: usize = nodes.iter().map(|n| n.to_string().len()).sum();
+        Trie {
+            nodes: nodes.len() / 2,
+            // Every network is a leaf at the depth of its prefix length.
+            depth: range.iter().map(|net| net.prefix_len()).max().unwrap_or(0),
+            bytes: 2 + digits + nodes.len().saturating_sub(1),
+        }
+    }
+}
+
+/// `part` as a percentage of `whole`.
+fn share(part: u128, whole: u128) -> String {
+    if whole == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", part as f64 * 100.0 / whole as f64)
+    }
+}
+
+/// The report: rows read, networks after simplification, addresses and
+/// the filter per family, then the `top` ASes by covered space. IPv6 space
+/// is counted in /64s.
+fn render(networks: &Networks, report: &Report, table: Option<&AsnTable>, top: usize) -> String {
+    let (v4, v6) = addresses(networks);
+    let mut out = String::new();
+    let family = |out: &mut String, name, rows, count, covered: String, trie: Trie| {
+        let _ = writeln!(
+            out,
+            "{}: {} rows, {} networks, {}",
+            name, rows, count, covered
+        );
+        let _ = writeln!(
+            out,
+            "  trie: {} nodes, depth {}, filter array {} bytes",
+            trie.nodes, trie.depth, trie.bytes
+        );
+    };
+    family(
+        &mut out,
+        "IPv4",
+        report.rows_v4,
+        networks.v4.iter().count(),
+        format!("{} addresses", v4),
+        Trie::of(&networks.v4),
+    );
+    family(
+        &mut out,
+        "IPv6",
+        report.rows_v6,
+        networks.v6.iter().count(),
+        format!("{} /64s", v6 >> 64),
+        Trie::of(&networks.v6),
+    );
+
+    let table = match table {
+        Some(table) => table,
+        None => return out,
+    };
+    let groups = table.group(networks);
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "{:<12} {:>14} {:>7} {:>14} {:>7}",
+        "origin", "IPv4 addresses", "share", "IPv6 /64s", "share"
+    );
+    for Group {
+        asn,
+        v4_addresses,
+        v6_addresses,
+    } in groups.iter().take(top)
+    {
+        let origin = match asn {
+            Some(asn) => format!("AS{}", asn),
+            None => "unknown".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{:<12} {:>14} {:>7} {:>14} {:>7}",
+            origin,
+            v4_addresses,
+            share(*v4_addresses, v4),
+            v6_addresses >> 64,
+            share(*v6_addresses, v6)
+        );
+    }
+    if groups.len() > top {
+        let _ = writeln!(out, "... and {} more", groups.len() - top);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_groups_by_origin() {
+        let networks = Networks {
+            v4: ["10.0.0.0/24", "10.0.1.0/25", "192.0.2.0/24"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+            v6: ["2001:db8::/63"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        };
+        let table: AsnTable = "10.0.0.0/16 64500\n2001:db8::/32 64501\n".parse().unwrap();
+        let report = Report {
+            rows_v4: 4,
+            rows_v6: 1,
+            ..Report::default()
+        };
+        assert_eq!(
+            render(&networks, &report, Some(&table), 2),
+            "IPv4: 4 rows, 3 networks, 640 addresses
+  trie: 51 nodes, depth 25, filter array 246 bytes
+IPv6: 1 rows, 1 networks, 2 /64s
+  trie: 64 nodes, depth 63, filter array 311 bytes
+
+origin       IPv4 addresses   share      IPv6 /64s   share
+AS64500                 384   60.0%              0    0.0%
+unknown                 256   40.0%              0    0.0%
+... and 1 more
+"
+        );
+    }
+
+    #[test]
+    fn rows_of_a_registered_format() {
+        let dir = std::env::temp_dir().join(format!("ipcheck-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ipset = dir.join("blocklist.save");
+        std::fs::write(
+            &ipset,
+            "create blocklist hash:net family inet\n\
+             add blocklist 10.0.0.0/8\n\
+             add blocklist 10.1.0.0/16\n\
+             add blocklist 10.1.2.3\n\
+             add blocklist 192.0.2.1\n",
+        )
+        .unwrap();
+        let args =
+            match crate::Cli::parse_args(["ipcheck", "stats", "--v4", ipset.to_str().unwrap()])
+                .command
+            {
+                crate::Command::Stats(args) => args,
+                _ => unreachable!(),
+            };
+        let (networks, report) = crate::load_reported(&args.lists, &args.input).unwrap();
+        // The rows are the entries read, not the networks they simplify to.
+        let stats = render(&networks, &report, None, 10);
+        assert!(
+            stats.starts_with("IPv4: 4 rows, 2 networks, 16777217 addresses\n"),
+            "{}",
+            stats
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn render_without_asn_source() {
+        let networks = Networks::default();
+        assert_eq!(
+            render(&networks, &Report::default(), None, 10),
+            "IPv4: 0 rows, 0 networks, 0 addresses
+  trie: 0 nodes, depth 0, filter array 2 bytes
+IPv6: 0 rows, 0 networks, 0 /64s
+  trie: 0 nodes, depth 0, filter array 2 bytes
+"
+        );
+    }
+
+    #[test]
+    fn trie_of_a_whole_family() {
+        let all: IpRange<ipnet::Ipv4Net> =
+            ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(
+            Trie::of(&all),
+            Trie {
+                nodes: 1,
+                depth: 0,
+                bytes: "[0,0]".len(),
+            }
+        );
+    }
+}
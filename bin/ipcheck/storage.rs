@@ -0,0 +1,266 @@
+//! Reading inputs from and writing outputs to object storage, for feeds and
+//! build outputs that live in S3 or GCS rather than on local disk.
+//!
+//! Wherever a command reads a feed or an artifact, or writes its outputs, an
+//! `s3://bucket/key` or `gs://bucket/key` URI may stand in for the path.
+//! Credentials come from each provider's standard chain:
+//!
+//! * for S3, the `AWS_*` environment variables (including `AWS_REGION`),
+//!   then web identity, container or instance metadata credentials;
+//! * for GCS, `GOOGLE_SERVICE_ACCOUNT` or `GOOGLE_APPLICATION_CREDENTIALS`,
+//!   then the gcloud application default credentials, then the instance
+//!   metadata server.
+//!
+//! With the `http` feature, inputs may also be `http://` or `https://` URLs
+//! (see [`fetch`](crate::fetch)), read-only.
+//!
+//! Objects are written in a single upload, so readers see either the old
+//! object or the complete new one, like the local atomic writes.
+
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use eyre::{bail, Result, WrapErr};
+
+#[cfg(feature = "http")]
+use crate::fetch;
+
+/// The URI schemes of the supported object stores.
+const SCHEMES: [&str; 2] = ["s3://", "gs://"];
+
+/// The URL schemes read over HTTP.
+const URL_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+/// Returns `true` if `path` is an object storage URI rather than a local
+/// path.
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| SCHEMES.iter().any(|scheme| path.starts_with(scheme)))
+}
+
+/// Returns `true` if `path` is an HTTP(S) URL rather than a local path.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| URL_SCHEMES.iter().any(|scheme| path.starts_with(scheme)))
+}
+
+/// Opens the file or object at `path` for reading.
+pub fn open(path: &Path) -> Result<Box<dyn Read>> {
+    if is_remote(path) || is_url(path) {
+        Ok(Box::new(Cursor::new(read(path)?)))
+    } else {
+        Ok(Box::new(
+            File::open(path).wrap_err_with(|| path.display().to_string())?,
+        ))
+    }
+}
+
+/// Reads the whole file or object at `path`.
+pub fn read(path: &Path) -> Result<Vec<u8>> {
+    match read_if_exists(path)? {
+        Some(contents) => Ok(contents),
+        None => Err(io::Error::from(io::ErrorKind::NotFound))
+            .wrap_err_with(|| path.display().to_string()),
+    }
+}
+
+/// Reads the whole file or object at `path`, `None` if there is none.
+pub fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    if let Some(url) = path.to_str().filter(|_| is_url(path)) {
+        return fetch::get(url).map(Some);
+    }
+    if is_remote(path) {
+        return remote::get(path).wrap_err_with(|| path.display().to_string());
+    }
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).wrap_err_with(|| path.display().to_string()),
+    }
+}
+
+/// Returns `true` if there is a file or object at `path`.
+pub fn exists(path: &Path) -> Result<bool> {
+    if is_url(path) {
+        return Ok(true);
+    }
+    if is_remote(path) {
+        let modified = remote::head(path).wrap_err_with(|| path.display().to_string())?;
+        Ok(modified.is_some())
+    } else {
+        Ok(path.exists())
+    }
+}
+
+/// When the file or object at `path` was last modified, `None` if it cannot
+/// be found out, as for URLs, which are only fetched again when the inputs
+/// are reloaded.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    if is_url(path) {
+        return None;
+    }
+    if is_remote(path) {
+        remote::head(path).ok().flatten()
+    } else {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+}
+
+/// Uploads `contents` as the object at `path`.
+pub fn put(path: &Path, contents: &[u8]) -> Result<()> {
+    if is_url(path) {
+        bail!("cannot write {}: URLs are read-only", path.display());
+    }
+    remote::put(path, contents).wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(not(feature = "http"))]
+mod fetch {
+    use eyre::{bail, Result};
+
+    pub fn get(url: &str) -> Result<Vec<u8>> {
+        bail!("{} is a URL, which needs the `http` feature", url)
+    }
+}
+
+#[cfg(feature = "object-store")]
+mod remote {
+    use std::future::Future;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    use eyre::{bail, Result};
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::path::Path as Key;
+    use object_store::{ObjectStore, PutPayload};
+
+    /// The store holding the object `uri`, and the object's key in it.
+    fn object(uri: &Path) -> Result<(Box<dyn ObjectStore>, Key)> {
+        let uri = uri.to_str().unwrap_or_default();
+        let (scheme, rest) = uri.split_once("://").unwrap_or_default();
+        let (bucket, key) = match rest.split_once('/') {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => (bucket, key),
+            _ => bail!("{:?} is not a {}://bucket/key URI", uri, scheme),
+        };
+        let store: Box<dyn ObjectStore> = match scheme {
+            "s3" => Box::new(
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()?,
+            ),
+            _ => Box::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()?,
+            ),
+        };
+        Ok((store, Key::from(key)))
+    }
+
+    /// Runs `future` to completion; the commands are otherwise synchronous.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the object storage runtime")
+            .block_on(future)
+    }
+
+    pub fn get(uri: &Path) -> Result<Option<Vec<u8>>> {
+        let (store, key) = object(uri)?;
+        block_on(async {
+            match store.get(&key).await {
+                Ok(result) => Ok(Some(result.bytes().await?.to_vec())),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    pub fn head(uri: &Path) -> Result<Option<SystemTime>> {
+        let (store, key) = object(uri)?;
+        block_on(async {
+            match store.head(&key).await {
+                Ok(meta) => Ok(Some(meta.last_modified.into())),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    pub fn put(uri: &Path, contents: &[u8]) -> Result<()> {
+        let (store, key) = object(uri)?;
+        let payload = PutPayload::from(contents.to_vec());
+        block_on(store.put(&key, payload))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "object-store"))]
+mod remote {
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    use eyre::{bail, Result};
+
+    fn unsupported<T>(uri: &Path) -> Result<T> {
+        bail!(
+            "{} is in object storage, which needs the `object-store` feature",
+            uri.display()
+        )
+    }
+
+    pub fn get(uri: &Path) -> Result<Option<Vec<u8>>> {
+        unsupported(uri)
+    }
+
+    pub fn head(uri: &Path) -> Result<Option<SystemTime>> {
+        unsupported(uri)
+    }
+
+    pub fn put(uri: &Path, _contents: &[u8]) -> Result<()> {
+        unsupported(uri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_paths() {
+        assert!(is_remote(Path::new("s3://feeds/v4.csv")));
+        assert!(is_remote(Path::new("gs://feeds/v4.csv")));
+        assert!(!is_remote(Path::new("feeds/v4.csv")));
+        assert!(!is_remote(Path::new("./s3://v4.csv")));
+        assert!(is_url(Path::new("https://example.com/v4.csv")));
+        assert!(is_url(Path::new("http://example.com/v4.csv")));
+        assert!(!is_url(Path::new("s3://feeds/v4.csv")));
+        assert!(!is_remote(Path::new("https://example.com/v4.csv")));
+    }
+
+    #[test]
+    fn local_reads() {
+        let path = std::env::temp_dir().join(format!("ipcheck-storage-{}", std::process::id()));
+        assert_eq!(read_if_exists(&path).unwrap(), None);
+        assert!(!exists(&path).unwrap());
+        assert!(read(&path).is_err());
+
+        fs::write(&path, "10.0.0.0/8").unwrap();
+        assert!(exists(&path).unwrap());
+        assert_eq!(read(&path).unwrap(), b"10.0.0.0/8");
+        let mut contents = String::new();
+        open(&path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "10.0.0.0/8");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn remote_uris_need_a_key() {
+        let err = read(Path::new("s3://feeds")).unwrap_err();
+        assert!(format!("{:#}", err).contains("s3://feeds"), "{:#}", err);
+    }
+}
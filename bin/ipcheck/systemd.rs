@@ -0,0 +1,125 @@
+//! Running `ipcheck serve` under systemd: socket activation and readiness
+//! notification, without linking libsystemd.
+//!
+//! With a `.socket` unit, systemd binds the listening socket itself and
+//! passes it as file descriptor 3 (`LISTEN_FDS`, see `sd_listen_fds(3)`);
+//! `serve` then answers HTTP on it instead of binding `--listen`. Both
+//! TCP and Unix stream sockets are accepted. With `Type=notify`, `serve`
+//! reports `READY=1` on `$NOTIFY_SOCKET` once the networks are loaded and
+//! it is listening (see `sd_notify(3)`).
+
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::net::TcpListener;
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::net::{UnixDatagram, UnixListener};
+
+use tiny_http::Listener;
+
+/// The first file descriptor passed by socket activation.
+const LISTEN_FDS_START: i32 = 3;
+
+/// Takes the listening socket passed by systemd, if the process was socket
+/// activated.
+///
+/// The activation variables are removed from the environment, so that
+/// processes spawned later do not mistake the socket for theirs. Call this
+/// before starting any threads.
+pub fn listener() -> Option<Listener> {
+    let pid = env::var("LISTEN_PID").ok()?;
+    let fds = env::var("LISTEN_FDS").ok()?;
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    if pid.parse() != Ok(std::process::id()) {
+        return None;
+    }
+    match fds.parse::<i32>() {
+        Ok(0) | Err(_) => None,
+        Ok(count) => {
+            if count > 1 {
+                tracing::warn!(
+                    "systemd passed {} sockets, serving HTTP on the first",
+                    count
+                );
+            }
+            // SAFETY: LISTEN_PID names this process, so systemd opened the
+            // descriptors from 3 on for it, and nothing else has taken them.
+            let fd = unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START) };
+            Some(listener_from_fd(fd))
+        }
+    }
+}
+
+/// Wraps a listening socket of either kind: a TCP socket has an IP address,
+/// so anything else is taken to be a Unix socket.
+fn listener_from_fd(fd: OwnedFd) -> Listener {
+    let tcp = TcpListener::from(fd);
+    if tcp.local_addr().is_ok() {
+        Listener::from(tcp)
+    } else {
+        Listener::from(UnixListener::from(OwnedFd::from(tcp)))
+    }
+}
+
+/// Sends `state` (e.g. `READY=1`) to the service manager, if the process
+/// runs under one. Failures only print a warning: the service works
+/// without the notification, systemd merely waits for it.
+pub fn notify(state: &str) {
+    if let Some(socket) = env::var_os("NOTIFY_SOCKET") {
+        if let Err(err) = notify_to(&socket, state) {
+            tracing::warn!("failed to notify systemd: {}", err);
+        }
+    }
+}
+
+fn notify_to(socket: &OsStr, state: &str) -> io::Result<()> {
+    let sender = UnixDatagram::unbound()?;
+    // A leading `@` names a socket in Linux's abstract namespace.
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket.as_encoded_bytes().strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        sender.send_to_addr(state.as_bytes(), &addr)?;
+        return Ok(());
+    }
+    sender.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listener_from_either_kind_of_socket() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        match listener_from_fd(OwnedFd::from(tcp)) {
+            Listener::Tcp(listener) => assert_eq!(listener.local_addr().unwrap(), addr),
+            _ => panic!("expected a TCP listener"),
+        }
+
+        let path = std::env::temp_dir().join(format!("ipcheck-listen-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let unix = UnixListener::bind(&path).unwrap();
+        assert!(matches!(
+            listener_from_fd(OwnedFd::from(unix)),
+            Listener::Unix(_)
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn notify_sends_the_state() {
+        let path = std::env::temp_dir().join(format!("ipcheck-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        notify_to(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+//! Tracing the phases of a build as OpenTelemetry spans: loading, parsing
+//! each file, simplifying, building and flattening the tries, and rendering.
+//!
+//! With the `otel` feature, spans are exported when
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` (or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`)
+//! is set, as OTLP/HTTP with the JSON encoding, to the collector or
+//! tracing backend there. `OTEL_EXPORTER_OTLP_HEADERS` adds headers such as
+//! API keys, and `OTEL_SERVICE_NAME` overrides the service name `ipcheck`.
+//!
+//! A trace is exported, synchronously, when its root span ends: once per
+//! build, or per reload or refresh in the long-running commands. Without
+//! the endpoint, or without the feature, spans cost next to nothing.
+//!
+//! Every span is also a `tracing` span, logged as it ends with `--verbose`
+//! whether or not it is exported.
+
+// Spans are only recorded with the `otel` feature.
+#![cfg_attr(not(feature = "otel"), allow(dead_code, unused_variables))]
+
+use std::fmt::Display;
+
+use tracing::field::{self, Empty};
+use tracing::span::EnteredSpan;
+
+/// Starts a span named `name`, ending when the returned guard is dropped.
+/// Spans started meanwhile on the same thread are its children.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        #[cfg(feature = "otel")]
+        data: otel::start(name),
+        traced: tracing::debug_span!(
+            "phase",
+            phase = name,
+            file = Empty,
+            family = Empty,
+            rows = Empty,
+            skipped = Empty,
+            networks = Empty,
+            nodes = Empty,
+            name = Empty,
+            output = Empty,
+        )
+        .entered(),
+    }
+}
+
+/// A span in progress.
+#[must_use = "a span ends as soon as it is dropped"]
+pub struct Span {
+    #[cfg(feature = "otel")]
+    data: Option<otel::SpanData>,
+    traced: EnteredSpan,
+}
+
+impl Span {
+    /// Adds the attribute `key` to the span.
+    pub fn attr(mut self, key: &'static str, value: impl Display) -> Span {
+        self.set(key, value);
+        self
+    }
+
+    /// Adds the attribute `key` to the span once it is under way, e.g. to
+    /// record what it found.
+    pub fn set(&mut self, key: &'static str, value: impl Display) {
+        let value = value.to_string();
+        self.traced.record(key, field::display(&value));
+        #[cfg(feature = "otel")]
+        if let Some(data) = &mut self.data {
+            data.attributes.push((key, value));
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(data) = self.data.take() {
+            otel::end(data);
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use std::cell::RefCell;
+    use std::collections::hash_map::RandomState;
+    use std::env;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde_json::{json, Value};
+
+    /// How long the collector may take to accept a trace.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Where and how traces are exported.
+    struct Exporter {
+        url: String,
+        headers: Vec<(String, String)>,
+        service: String,
+    }
+
+    pub struct SpanData {
+        trace_id: u128,
+        span_id: u64,
+        parent_id: Option<u64>,
+        name: &'static str,
+        start: SystemTime,
+        end: SystemTime,
+        pub attributes: Vec<(&'static str, String)>,
+    }
+
+    thread_local! {
+        /// The open spans of this thread, innermost last, as their trace
+        /// and span IDs.
+        static OPEN: RefCell<Vec<(u128, u64)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// The ended spans of the traces whose root is still open.
+    static ENDED: Mutex<Vec<SpanData>> = Mutex::new(Vec::new());
+
+    fn exporter() -> Option<&'static Exporter> {
+        static EXPORTER: OnceLock<Option<Exporter>> = OnceLock::new();
+        EXPORTER
+            .get_or_init(|| {
+                let url = match env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+                    Ok(url) => url,
+                    Err(_) => {
+                        let base = env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+                        format!("{}/v1/traces", base.trim_end_matches('/'))
+                    }
+                };
+                let headers = env::var("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or_default();
+                Some(Exporter {
+                    url,
+                    headers: parse_headers(&headers),
+                    service: env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "ipcheck".into()),
+                })
+            })
+            .as_ref()
+    }
+
+    /// `key=value` pairs separated by commas, as in the OTLP exporter
+    /// specification.
+    fn parse_headers(text: &str) -> Vec<(String, String)> {
+        text.split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect()
+    }
+
+    /// A random ID, unique enough for tracing without a random number
+    /// generator dependency.
+    fn random_id() -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        hasher.finish() | 1
+    }
+
+    pub fn start(name: &'static str) -> Option<SpanData> {
+        exporter()?;
+        let parent = OPEN.with(|open| open.borrow().last().copied());
+        let trace_id = match parent {
+            Some((trace_id, _)) => trace_id,
+            None => u128::from(random_id()) << 64 | u128::from(random_id()),
+        };
+        let span_id = random_id();
+        OPEN.with(|open| open.borrow_mut().push((trace_id, span_id)));
+        Some(SpanData {
+            trace_id,
+            span_id,
+            parent_id: parent.map(|(_, span_id)| span_id),
+            name,
+            start: SystemTime::now(),
+            end: SystemTime::now(),
+            attributes: Vec::new(),
+        })
+    }
+
+    pub fn end(mut data: SpanData) {
+        data.end = SystemTime::now();
+        OPEN.with(|open| {
+            let mut open = open.borrow_mut();
+            if let Some(at) = open.iter().rposition(|&(_, id)| id == data.span_id) {
+                open.remove(at);
+            }
+        });
+        let mut ended = ENDED.lock().unwrap();
+        let is_root = data.parent_id.is_none();
+        ended.push(data);
+        if !is_root {
+            return;
+        }
+        let trace_id = ended.last().unwrap().trace_id;
+        let (trace, rest) = ended.drain(..).partition(|span| span.trace_id == trace_id);
+        *ended = rest;
+        drop(ended);
+        if let Some(exporter) = exporter() {
+            export(exporter, &trace);
+        }
+    }
+
+    fn export(exporter: &Exporter, spans: &[SpanData]) {
+        let body = encode(&exporter.service, spans).to_string();
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(TIMEOUT))
+            .build()
+            .into();
+        let mut request = agent.post(&exporter.url);
+        for (key, value) in &exporter.headers {
+            request = request.header(key, value);
+        }
+        if let Err(err) = request
+            .content_type("application/json")
+            .send(body.as_bytes())
+        {
+            tracing::warn!("failed to export a trace to {}: {}", exporter.url, err);
+        }
+    }
+
+    /// The OTLP `ExportTraceServiceRequest` of `spans`, in its JSON
+    /// encoding: IDs in hex, times as decimal strings.
+    fn encode(service: &str, spans: &[SpanData]) -> Value {
+        let nanos = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_string()
+        };
+        let attribute =
+            |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+        let spans: Vec<Value> = spans
+            .iter()
+            .map(|span| {
+                let mut value = json!({
+                    "traceId": format!("{:032x}", span.trace_id),
+                    "spanId": format!("{:016x}", span.span_id),
+                    "name": span.name,
+                    // SPAN_KIND_INTERNAL
+                    "kind": 1,
+                    "startTimeUnixNano": nanos(span.start),
+                    "endTimeUnixNano": nanos(span.end),
+                    "attributes": span
+                        .attributes
+                        .iter()
+                        .map(|(key, value)| attribute(key, value))
+                        .collect::<Vec<_>>(),
+                });
+                if let Some(parent_id) = span.parent_id {
+                    value["parentSpanId"] = format!("{:016x}", parent_id).into();
+                }
+                value
+            })
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {"attributes": [attribute("service.name", service)]},
+                "scopeSpans": [{
+                    "scope": {"name": "ipcheck", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn headers() {
+            assert_eq!(
+                parse_headers("api-key=secret, x-team = net,broken"),
+                [
+                    ("api-key".to_string(), "secret".to_string()),
+                    ("x-team".to_string(), "net".to_string()),
+                ]
+            );
+            assert!(parse_headers("").is_empty());
+        }
+
+        #[test]
+        fn encode_spans() {
+            let span = |span_id, parent_id, name| SpanData {
+                trace_id: 0xab,
+                span_id,
+                parent_id,
+                name,
+                start: UNIX_EPOCH + Duration::from_nanos(1_500),
+                end: UNIX_EPOCH + Duration::from_secs(2),
+                attributes: vec![("file", "v4.csv".to_string())],
+            };
+            let value = encode(
+                "ipcheck",
+                &[span(2, Some(1), "parse"), span(1, None, "build")],
+            );
+            let resource = &value["resourceSpans"][0];
+            assert_eq!(
+                resource["resource"]["attributes"][0]["value"]["stringValue"],
+                "ipcheck"
+            );
+            let spans = &resource["scopeSpans"][0]["spans"];
+            assert_eq!(spans[0]["traceId"], format!("{:032x}", 0xab));
+            assert_eq!(spans[0]["spanId"], "0000000000000002");
+            assert_eq!(spans[0]["parentSpanId"], "0000000000000001");
+            assert_eq!(spans[0]["startTimeUnixNano"], "1500");
+            assert_eq!(spans[0]["endTimeUnixNano"], "2000000000");
+            assert_eq!(spans[0]["attributes"][0]["key"], "file");
+            assert!(spans[1].get("parentSpanId").is_none());
+        }
+    }
+}
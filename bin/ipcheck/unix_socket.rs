@@ -0,0 +1,208 @@
+//! Answering lookups on a Unix socket for `ipcheck serve --unix-socket`, so
+//! that a local proxy or application can consult the networks without
+//! HTTP.
+//!
+//! A connection carries any number of lookups, each one a request frame
+//! answered by a response frame. A frame is a big-endian `u16` length
+//! followed by that many bytes:
+//!
+//! * a request holds the address as 4 (IPv4) or 16 (IPv6) bytes in network
+//!   order;
+//! * a response holds a status byte, `0` for no match, `1` for a match or
+//!   `2` for a request of the wrong length; a match is followed by the
+//!   matching network as a CIDR string.
+
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use clap::Args;
+use eyre::{bail, Result, WrapErr};
+
+use crate::input::Networks;
+use crate::match_log::MatchLog;
+use crate::metrics::Metrics;
+use crate::reload::Live;
+use crate::serve::{lookup, Recorder};
+
+const MISS: u8 = 0;
+const HIT: u8 = 1;
+const MALFORMED: u8 = 2;
+
+#[derive(Args)]
+pub struct UnixSocketArgs {
+    /// Also answer lookups on a Unix socket at this path, replacing any
+    /// socket left there by a previous run.
+    #[arg(long, value_name = "PATH")]
+    unix_socket: Option<PathBuf>,
+}
+
+impl UnixSocketArgs {
+    pub fn enabled(&self) -> bool {
+        self.unix_socket.is_some()
+    }
+}
+
+/// Answers lookups on `--unix-socket` from background threads, one per
+/// connection.
+pub fn spawn(
+    args: &UnixSocketArgs,
+    live: Arc<Live>,
+    metrics: Arc<Metrics>,
+    log: Arc<MatchLog>,
+) -> Result<()> {
+    let path = args
+        .unix_socket
+        .as_deref()
+        .expect("Unix socket serving is not enabled");
+    let listener = bind(path)?;
+    tracing::info!("listening on unix:{}", path.display());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let live = Arc::clone(&live);
+                    let metrics = Arc::clone(&metrics);
+                    let log = Arc::clone(&log);
+                    thread::spawn(move || {
+                        let recorder = Recorder {
+                            metrics: &metrics,
+                            log: &log,
+                            client: None,
+                        };
+                        if let Err(err) = serve_connection(stream, &live, &recorder) {
+                            tracing::warn!("Unix socket connection failed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => tracing::warn!("failed to accept connection: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Binds `path`, first removing a stale socket but never any other file.
+fn bind(path: &Path) -> Result<UnixListener> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            fs::remove_file(path).wrap_err_with(|| path.display().to_string())?
+        }
+        Ok(_) => bail!("{} exists and is not a socket", path.display()),
+        Err(_) => {}
+    }
+    UnixListener::bind(path).wrap_err_with(|| path.display().to_string())
+}
+
+/// Answers the lookups of one connection until the client closes it.
+fn serve_connection(stream: UnixStream, live: &Live, recorder: &Recorder) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut request = Vec::new();
+    loop {
+        let mut len = [0; 2];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        request.resize(u16::from_be_bytes(len) as usize, 0);
+        reader.read_exact(&mut request)?;
+        let response = answer(&request, &live.get(), recorder);
+        writer.write_all(&(response.len() as u16).to_be_bytes())?;
+        writer.write_all(&response)?;
+        // Only flush once no further pipelined request is waiting.
+        if reader.buffer().is_empty() {
+            writer.flush()?;
+        }
+    }
+}
+
+/// The response payload to the request payload `request`.
+fn answer(request: &[u8], networks: &Networks, recorder: &Recorder) -> Vec<u8> {
+    let addr = match request.len() {
+        4 => IpAddr::from(Ipv4Addr::from(<[u8; 4]>::try_from(request).unwrap())),
+        16 => IpAddr::from(Ipv6Addr::from(<[u8; 16]>::try_from(request).unwrap())),
+        _ => return vec![MALFORMED],
+    };
+    let result = lookup(&addr.to_string(), networks).expect("formatted addresses parse");
+    recorder.record(&result);
+    match result.network {
+        Some(network) => {
+            let mut response = vec![HIT];
+            response.extend_from_slice(network.as_bytes());
+            response
+        }
+        None => vec![MISS],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks() -> Networks {
+        Networks {
+            v4: ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect(),
+            v6: ["2001:db8::/32"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn answer_requests() {
+        let networks = networks();
+        let (metrics, log) = (Metrics::default(), MatchLog::default());
+        let recorder = Recorder {
+            metrics: &metrics,
+            log: &log,
+            client: None,
+        };
+        assert_eq!(
+            answer(&[10, 1, 2, 3], &networks, &recorder),
+            b"\x0110.0.0.0/8"
+        );
+        assert_eq!(answer(&[8, 8, 8, 8], &networks, &recorder), [MISS]);
+        let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(
+            answer(&v6.octets(), &networks, &recorder),
+            b"\x012001:db8::/32"
+        );
+        assert_eq!(answer(&[10, 1, 2], &networks, &recorder), [MALFORMED]);
+        assert!(metrics
+            .render()
+            .contains("ipcheck_lookups_total{result=\"hit\"} 2\n"));
+    }
+
+    #[test]
+    fn lookups_over_the_socket() {
+        let path = std::env::temp_dir().join(format!("ipcheck-unix-{}.sock", std::process::id()));
+        fs::write(&path, "not a socket").unwrap();
+        let args = UnixSocketArgs {
+            unix_socket: Some(path.clone()),
+        };
+        let live = Arc::new(Live::new(networks()));
+        assert!(spawn(&args, Arc::clone(&live), Arc::default(), Arc::default()).is_err());
+        fs::remove_file(&path).unwrap();
+        spawn(&args, live, Arc::default(), Arc::default()).unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        // Two pipelined requests, answered in order.
+        client
+            .write_all(&[0, 4, 10, 0, 0, 1, 0, 4, 9, 9, 9, 9])
+            .unwrap();
+        let mut response = [0; 2 + 11 + 2 + 1];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"\x00\x0b\x0110.0.0.0/8\x00\x01\x00");
+
+        drop(client);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+//! `ipcheck validate`: every row of the lists that cannot be read, with its
+//! file and line, rather than only the first one a build fails at.
+//!
+//! The lists are read as `--lenient` builds read them, so what `validate`
+//! reports is exactly what those builds skip. It exits with 1 for invalid
+//! rows only with `--strict`.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+
+use crate::input::{load_input, Family, InputOptions, Lists, Report};
+use crate::paths;
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    lists: Lists,
+
+    /// The IPv4 and IPv6 lists, for those not given by flag.
+    #[arg(value_names = ["V4", "V6"], num_args = 0..=2)]
+    paths: Vec<PathBuf>,
+
+    #[command(flatten)]
+    input: InputOptions,
+}
+
+impl ValidateArgs {
+    pub fn assign_paths(&mut self) -> Result<(), clap::Error> {
+        paths::assign(&mut self.paths, self.lists.slots())
+    }
+}
+
+pub fn validate(args: ValidateArgs) -> Result<()> {
+    let options = InputOptions {
+        lenient: true,
+        ..args.input.clone()
+    };
+    let lists = [
+        (args.lists.v4(), Family::V4),
+        (args.lists.v6(), Family::V6),
+        (args.lists.exclude_v4(), Family::V4),
+        (args.lists.exclude_v6(), Family::V6),
+    ];
+    let mut report = Report::default();
+    for (paths, family) in lists {
+        for path in paths {
+            load_input(path, family, &options, &mut report)?;
+        }
+    }
+    for warning in &report.warnings {
+        tracing::warn!("{}", warning);
+    }
+    print!("{}", render(&report));
+    if args.input.strict && !report.invalid.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Every invalid row, then how many rows were read, and how many blank and
+/// comment lines were skipped.
+fn render(report: &Report) -> String {
+    let mut out = String::new();
+    for invalid in &report.invalid {
+        let _ = writeln!(out, "{}", invalid);
+    }
+    let rows = report.rows_v4 + report.rows_v6 + report.invalid.len() as u64;
+    let _ = match report.invalid.len() {
+        0 => write!(out, "all {} rows are valid", rows),
+        invalid => write!(out, "{} of {} rows are invalid", invalid, rows),
+    };
+    let _ = match report.skipped {
+        0 => writeln!(out),
+        skipped => writeln!(out, " ({} blank or comment lines skipped)", skipped),
+    };
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_invalid_rows() {
+        let report = Report {
+            invalid: vec![
+                "v4.csv:3: \"10.0.0.0/33\" is not a network".to_string(),
+                "v4.csv:7: \"nonsense\" is not a network".to_string(),
+            ],
+            rows_v4: 5,
+            rows_v6: 1,
+            ..Report::default()
+        };
+        assert_eq!(
+            render(&report),
+            "v4.csv:3: \"10.0.0.0/33\" is not a network
+v4.csv:7: \"nonsense\" is not a network
+2 of 8 rows are invalid
+"
+        );
+        assert_eq!(
+            render(&Report {
+                rows_v4: 2,
+                ..Report::default()
+            }),
+            "all 2 rows are valid\n"
+        );
+        assert_eq!(
+            render(&Report {
+                rows_v4: 2,
+                skipped: 3,
+                ..Report::default()
+            }),
+            "all 2 rows are valid (3 blank or comment lines skipped)\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Noticing changed inputs, for the `--watch` flags, by polling their
+//! modification times.
+//!
+//! The inputs are polled rather than watched through the notifications of
+//! the OS, as the `notify` crate would: `s3://` and `gs://` inputs have no
+//! such notifications, only their `HEAD` requests, and polling treats both
+//! alike, including files replaced on network mounts, which notifications
+//! often miss. Local files are polled four times a second, so that a change
+//! is loaded within half a second, once it held still; remote objects
+//! every 2 seconds, as each poll is a request.
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::storage;
+
+/// How often `--watch` checks the local inputs for changes.
+const INTERVAL: Duration = Duration::from_millis(250);
+
+/// Checks of the local inputs per check of the remote ones.
+const REMOTE_EVERY: u64 = 8;
+
+/// Calls `changed` whenever the files at `paths` change, forever.
+///
+/// A change is only picked up once the modification times have held still
+/// for a whole interval, so that a file still being written is not loaded
+/// half-way. Replacing inputs by renaming a complete file over them is
+/// safer still.
+pub fn on_change(paths: &[&Path], mut changed: impl FnMut()) -> ! {
+    let mut modified: Vec<Option<SystemTime>> =
+        paths.iter().map(|path| storage::modified(path)).collect();
+    let mut debounce = Debounce::new(modified.clone());
+    let mut checks: u64 = 0;
+    loop {
+        thread::sleep(INTERVAL);
+        checks = checks.wrapping_add(1);
+        for (path, modified) in paths.iter().zip(&mut modified) {
+            if !storage::is_remote(path) || checks.is_multiple_of(REMOTE_EVERY) {
+                *modified = storage::modified(path);
+            }
+        }
+        if debounce.settled(modified.clone()) {
+            changed();
+        }
+    }
+}
+
+/// The modification times of the inputs, as last loaded and last seen.
+struct Debounce {
+    loaded: Vec<Option<SystemTime>>,
+    seen: Vec<Option<SystemTime>>,
+}
+
+impl Debounce {
+    fn new(loaded: Vec<Option<SystemTime>>) -> Debounce {
+        Debounce {
+            seen: loaded.clone(),
+            loaded,
+        }
+    }
+
+    /// Whether the inputs, now `modified`, changed since they were loaded
+    /// and held still since they were last seen.
+    fn settled(&mut self, modified: Vec<Option<SystemTime>>) -> bool {
+        let settled = modified != self.loaded && modified == self.seen;
+        if settled {
+            self.loaded = modified.clone();
+        }
+        self.seen = modified;
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_settle_before_they_count() {
+        let at = |secs| vec![Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))];
+        let mut debounce = Debounce::new(at(1));
+        assert!(!debounce.settled(at(1)));
+        // Still being written.
+        assert!(!debounce.settled(at(2)));
+        assert!(!debounce.settled(at(3)));
+        assert!(debounce.settled(at(3)));
+        assert!(!debounce.settled(at(3)));
+        // Removed.
+        assert!(!debounce.settled(vec![None]));
+        assert!(debounce.settled(vec![None]));
+    }
+}
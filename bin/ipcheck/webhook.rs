@@ -0,0 +1,195 @@
+//! Notifying deploy automation of a rebuild, by POSTing a JSON summary to
+//! the `--webhook` URLs once `ipcheck build` has written new outputs:
+//!
+//! ```json
+//! {"fingerprint":"1c291ca3","networks":{"v4":2,"v6":1},"added":1,"removed":0,
+//!  "artifacts":["ipcheck.ts","ipcheck.test.ts"]}
+//! ```
+//!
+//! The fingerprint is the checksum of the networks' binary artifact, so it
+//! only changes with the networks. `added` and `removed` count the
+//! networks covering the space gained and lost since the output being
+//! replaced; everything counts as added when there was none.
+//!
+//! Nothing is sent for `--check`, or when every output was up to date.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use eyre::{bail, Result};
+use ipcheck_rs::{artifact, IpNet, IpRange};
+use serde::Serialize;
+
+use crate::input::Networks;
+use crate::rendered_networks;
+
+/// How long a webhook may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Args, Clone)]
+pub struct WebhookArgs {
+    /// POST a JSON summary of the build to this URL after writing new
+    /// outputs; repeat it to notify several.
+    #[arg(long = "webhook", value_name = "URL")]
+    urls: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    fingerprint: String,
+    networks: Counts,
+    added: usize,
+    removed: usize,
+    artifacts: Vec<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Counts {
+    v4: usize,
+    v6: usize,
+}
+
+impl Summary {
+    /// Summarizes a build of `networks` into `artifacts`, replacing the
+    /// output `previous`.
+    pub fn new(
+        networks: &Networks,
+        previous: Option<&[u8]>,
+        artifacts: Vec<PathBuf>,
+    ) -> Result<Summary> {
+        let bytes = artifact::write(&networks.v4, &networks.v6)?;
+        let previous = previous.and_then(rendered_networks).unwrap_or_default();
+        Ok(Summary {
+            fingerprint: format!("{:08x}", artifact::checksum(&bytes).unwrap()),
+            networks: Counts {
+                v4: networks.v4.iter().count(),
+                v6: networks.v6.iter().count(),
+            },
+            added: difference(&networks.v4, &previous.v4) + difference(&networks.v6, &previous.v6),
+            removed: difference(&previous.v4, &networks.v4)
+                + difference(&previous.v6, &networks.v6),
+            artifacts,
+        })
+    }
+}
+
+impl WebhookArgs {
+    pub fn enabled(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    /// POSTs `summary` to every URL, failing if any of them did not accept
+    /// it.
+    pub fn notify(&self, summary: &Summary) -> Result<()> {
+        let body = serde_json::to_string(summary)?;
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(TIMEOUT))
+            .build()
+            .into();
+        let mut failed = 0;
+        for url in &self.urls {
+            let result = agent
+                .post(url)
+                .content_type("application/json")
+                .send(body.as_bytes());
+            if let Err(err) = result {
+                tracing::warn!("failed to notify {}: {}", url, err);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!(
+                "the outputs were written, but {} of {} webhooks failed",
+                failed,
+                self.urls.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The number of networks covering what `range` has and `other` lacks.
+fn difference<N: IpNet>(range: &IpRange<N>, other: &IpRange<N>) -> usize {
+    range.exclude(other).iter().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn networks(v4: &[&str], v6: &[&str]) -> Networks {
+        Networks {
+            v4: v4.iter().map(|s| s.parse().unwrap()).collect(),
+            v6: v6.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn summary_against_the_previous_output() {
+        let old = networks(&["10.0.0.0/8", "192.168.0.0/16"], &["2001:db8::/32"]);
+        let new = networks(&["10.0.0.0/8", "172.16.0.0/12"], &["2001:db8::/32"]);
+        let previous = crate::tests::render_typescript(&old, Vec::new()).unwrap();
+        assert_eq!(rendered_networks(previous.as_bytes()), Some(old.clone()));
+
+        let summary = Summary::new(&new, Some(previous.as_bytes()), vec!["out.ts".into()]).unwrap();
+        assert_eq!((summary.added, summary.removed), (1, 1));
+        assert_eq!(summary.networks, Counts { v4: 2, v6: 1 });
+        assert_eq!(
+            Summary::new(&old, Some(previous.as_bytes()), Vec::new())
+                .unwrap()
+                .fingerprint,
+            Summary::new(&old, None, Vec::new()).unwrap().fingerprint
+        );
+        assert_ne!(
+            summary.fingerprint,
+            Summary::new(&old, None, Vec::new()).unwrap().fingerprint
+        );
+
+        let first = Summary::new(&new, Some(b"// not ours"), Vec::new()).unwrap();
+        assert_eq!((first.added, first.removed), (3, 0));
+    }
+
+    #[test]
+    fn notify_posts_the_summary() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let args = WebhookArgs {
+            urls: vec![format!("http://{}/deploy", server.local_addr().unwrap())],
+        };
+        let summary =
+            Summary::new(&networks(&["10.0.0.0/8"], &[]), None, vec!["out.ts".into()]).unwrap();
+        let expected = serde_json::to_string(&summary).unwrap();
+        let client = std::thread::spawn(move || args.notify(&summary));
+
+        let (conn, _) = server.accept().unwrap();
+        let mut reader = BufReader::new(conn.try_clone().unwrap());
+        let mut head = String::new();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+            head.push_str(&line);
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        (&conn)
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        client.join().unwrap().unwrap();
+        assert!(head.starts_with("POST /deploy HTTP/1.1\r\n"), "{}", head);
+        assert!(head
+            .to_ascii_lowercase()
+            .contains("content-type: application/json"));
+        assert_eq!(String::from_utf8(body).unwrap(), expected);
+        assert!(expected.contains("\"added\":1,\"removed\":0,\"artifacts\":[\"out.ts\"]"));
+    }
+}
@@ -0,0 +1,23 @@
+/** The networks one set has that another lacks, and vice versa. */
+export interface Diff {
+  /** Networks only in the other set. */
+  added: Array<string>;
+  /** Networks only in this set. */
+  removed: Array<string>;
+}
+
+/** A set of IPv4 and IPv6 networks. */
+export class IpSet {
+  /** Builds a set from CIDR strings such as `"10.0.0.0/8"`. */
+  constructor(cidrs?: Array<string> | undefined | null);
+  /** Loads a binary artifact written by `ipcheck`. */
+  static load(path: string): IpSet;
+  /** Returns whether any network contains `ip`. */
+  contains(ip: string): boolean;
+  /** Returns the network containing `ip`, or `null`. */
+  lookup(ip: string): string | null;
+  /** Compares this set with `other`. */
+  diff(other: IpSet): Diff;
+  /** Returns the networks of the set, IPv4 first. */
+  toCidrs(): Array<string>;
+}
@@ -0,0 +1,112 @@
+//! Node.js bindings for `ipcheck-rs`.
+//!
+//! Exposes the in-memory ranges directly, for services whose feeds are too
+//! large or change too often for a generated lookup module.
+
+use std::net::IpAddr;
+
+use ipcheck_rs::{artifact, IpRange};
+use ipnet::{IpNet as AnyNet, Ipv4Net, Ipv6Net};
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+
+/// A set of IPv4 and IPv6 networks.
+#[napi]
+pub struct IpSet {
+    v4: IpRange<Ipv4Net>,
+    v6: IpRange<Ipv6Net>,
+}
+
+/// The networks one set has that another lacks, and vice versa.
+#[napi(object)]
+pub struct Diff {
+    /// Networks only in the other set.
+    pub added: Vec<String>,
+    /// Networks only in this set.
+    pub removed: Vec<String>,
+}
+
+#[napi]
+impl IpSet {
+    /// Builds a set from CIDR strings such as `"10.0.0.0/8"`.
+    #[napi(constructor)]
+    pub fn new(cidrs: Option<Vec<String>>) -> Result<Self> {
+        let mut set = IpSet {
+            v4: IpRange::new(),
+            v6: IpRange::new(),
+        };
+        for cidr in cidrs.unwrap_or_default() {
+            match cidr.trim().parse::<AnyNet>() {
+                Ok(AnyNet::V4(network)) => {
+                    set.v4.add(network);
+                }
+                Ok(AnyNet::V6(network)) => {
+                    set.v6.add(network);
+                }
+                Err(_) => return Err(invalid_arg(format!("{:?} is not a network", cidr))),
+            }
+        }
+        set.v4.simplify();
+        set.v6.simplify();
+        Ok(set)
+    }
+
+    /// Loads a binary artifact written by `ipcheck`.
+    #[napi(factory)]
+    pub fn load(path: String) -> Result<Self> {
+        let (v4, v6) = artifact::load_file(&path)
+            .map_err(|err| Error::new(Status::GenericFailure, format!("{}: {}", path, err)))?;
+        Ok(IpSet { v4, v6 })
+    }
+
+    /// Returns whether any network contains `ip`.
+    #[napi]
+    pub fn contains(&self, ip: String) -> Result<bool> {
+        Ok(self.lookup(ip)?.is_some())
+    }
+
+    /// Returns the network containing `ip`, or `null`.
+    #[napi]
+    pub fn lookup(&self, ip: String) -> Result<Option<String>> {
+        let network = match parse_ip(&ip)? {
+            IpAddr::V4(addr) => self.v4.supernet(&addr).map(|n| n.to_string()),
+            IpAddr::V6(addr) => self.v6.supernet(&addr).map(|n| n.to_string()),
+        };
+        Ok(network)
+    }
+
+    /// Compares this set with `other`.
+    #[napi]
+    pub fn diff(&self, other: &IpSet) -> Diff {
+        let mut added = cidrs(&other.v4.exclude(&self.v4));
+        added.extend(cidrs(&other.v6.exclude(&self.v6)));
+        let mut removed = cidrs(&self.v4.exclude(&other.v4));
+        removed.extend(cidrs(&self.v6.exclude(&other.v6)));
+        Diff { added, removed }
+    }
+
+    /// Returns the networks of the set, IPv4 first.
+    #[napi]
+    pub fn to_cidrs(&self) -> Vec<String> {
+        let mut all = cidrs(&self.v4);
+        all.extend(cidrs(&self.v6));
+        all
+    }
+}
+
+fn cidrs<N>(range: &IpRange<N>) -> Vec<String>
+where
+    N: ipcheck_rs::IpNet + ToString,
+{
+    range.iter().map(|network| network.to_string()).collect()
+}
+
+fn parse_ip(ip: &str) -> Result<IpAddr> {
+    ip.trim()
+        .parse()
+        .map_err(|_| invalid_arg(format!("{:?} is not an IP address", ip)))
+}
+
+fn invalid_arg(message: String) -> Error {
+    Error::new(Status::InvalidArg, message)
+}
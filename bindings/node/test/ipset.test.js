@@ -0,0 +1,34 @@
+'use strict';
+
+const { test } = require('node:test');
+const assert = require('node:assert/strict');
+
+const { IpSet } = require('..');
+
+test('contains and lookup', () => {
+  const set = new IpSet(['10.0.0.0/8', '192.168.1.0/24', '2001:db8::/32']);
+  assert.equal(set.contains('10.1.2.3'), true);
+  assert.equal(set.contains('11.0.0.0'), false);
+  assert.equal(set.lookup('2001:db8::ffff'), '2001:db8::/32');
+  assert.equal(set.lookup('::1'), null);
+  assert.throws(() => set.contains('not an ip'), /not an IP address/);
+});
+
+test('constructor rejects invalid networks', () => {
+  assert.throws(() => new IpSet(['10.0.0.0/33']), /not a network/);
+  assert.deepEqual(new IpSet().toCidrs(), []);
+});
+
+test('diff', () => {
+  const old = new IpSet(['10.0.0.0/8', '2001:db8::/32']);
+  const updated = new IpSet(['10.0.0.0/9', '172.16.0.0/12', '2001:db8::/32']);
+  assert.deepEqual(old.diff(updated), {
+    added: ['172.16.0.0/12'],
+    removed: ['10.128.0.0/9'],
+  });
+  assert.deepEqual(updated.diff(updated), { added: [], removed: [] });
+});
+
+test('load reports missing artifacts', () => {
+  assert.throws(() => IpSet.load('/nonexistent/filter.bin'), /nonexistent/);
+});
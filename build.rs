@@ -0,0 +1,15 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // Only the gRPC service needs generated code; other builds stay free of
+    // the protobuf toolchain.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/ipcheck.proto");
+        // protox compiles the schema in pure Rust, so no protoc is needed.
+        let descriptors =
+            protox::compile(["proto/ipcheck.proto"], ["proto"]).expect("invalid ipcheck.proto");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("failed to generate gRPC code");
+    }
+}
@@ -0,0 +1,31 @@
+// Each target uses only some of the helpers.
+#![allow(dead_code)]
+
+use ipcheck_rs::{IpNet, IpRange};
+
+/// Adds `networks` to a fresh range and checks the invariants every
+/// well-formed range must hold, returning the simplified range.
+pub fn build_and_check<N: IpNet>(networks: &[N]) -> IpRange<N> {
+    let mut range = IpRange::new();
+    for &network in networks {
+        range.add(network);
+    }
+    range.simplify();
+
+    for network in networks {
+        assert!(range.contains(network), "{:?} lost after simplify", network);
+    }
+    check(&range);
+    range
+}
+
+/// Checks the invariants every well-formed, simplified range must hold.
+pub fn check<N: IpNet>(range: &IpRange<N>) {
+    for network in range {
+        assert!(range.contains(&network));
+    }
+
+    let mut again = range.clone();
+    again.simplify();
+    assert_eq!(range, &again, "simplify is not idempotent");
+}
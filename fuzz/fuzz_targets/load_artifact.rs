@@ -0,0 +1,11 @@
+#![no_main]
+
+use ipcheck_rs::artifact;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v4, v6)) = artifact::load(data) {
+        let encoded = artifact::write(&v4, &v6).expect("loaded ranges must encode");
+        assert_eq!(artifact::load(&encoded), Ok((v4, v6)));
+    }
+});
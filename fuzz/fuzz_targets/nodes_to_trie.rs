@@ -0,0 +1,25 @@
+#![no_main]
+
+use ipcheck_rs::flat::{self, nodes_to_trie, trie_to_nodes};
+use ipcheck_rs::IpRange;
+use ipnet::{Ipv4Net, Ipv6Net};
+use libfuzzer_sys::fuzz_target;
+
+// Node arrays come from generated artifacts, so decoding must reject
+// garbage instead of panicking, down to the networks of either family.
+fuzz_target!(|nodes: Vec<u16>| {
+    let nodes: Vec<usize> = nodes.into_iter().map(usize::from).collect();
+    if let Some(trie) = nodes_to_trie(&nodes) {
+        let encoded = trie_to_nodes(trie.clone());
+        assert_eq!(nodes.len(), encoded.len());
+        assert_eq!(nodes_to_trie(&encoded), Some(trie));
+    }
+    if let Ok(range) = flat::decode::<Ipv4Net>(&nodes) {
+        let networks: IpRange<Ipv4Net> = range.iter().collect();
+        assert_eq!(networks, range);
+    }
+    if let Ok(range) = flat::decode::<Ipv6Net>(&nodes) {
+        let networks: IpRange<Ipv6Net> = range.iter().collect();
+        assert_eq!(networks, range);
+    }
+});
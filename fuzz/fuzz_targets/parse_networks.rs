@@ -0,0 +1,25 @@
+#![no_main]
+
+use ipcheck_rs::source::parse_networks;
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let Ok((v4, v6)) = parse_networks(text.lines()) else {
+        return;
+    };
+    common::check(&v4);
+    common::check(&v6);
+
+    // Whatever parses as a whole parses line by line, into parts of it.
+    for line in text.lines() {
+        let (line_v4, line_v6) = parse_networks([line]).expect("a line of a valid list is valid");
+        assert_eq!(line_v4.exclude(&v4).iter().count(), 0, "{:?} lost", line);
+        assert_eq!(line_v6.exclude(&v6).iter().count(), 0, "{:?} lost", line);
+    }
+});
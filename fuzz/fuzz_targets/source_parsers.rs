@@ -0,0 +1,27 @@
+#![no_main]
+
+use ipcheck_rs::source::{parse_entries, Registry};
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+/// The built-in parsers of feeds as text.
+const PARSERS: [&str; 5] = ["lines", "nftables", "iptables-save", "ipset", "spamhaus"];
+
+fuzz_target!(|data: &[u8]| {
+    let registry = Registry::with_builtins();
+    for name in PARSERS {
+        let parser = registry.get(name).expect("a built-in parser");
+        let entries = parser.entries(data).expect("text parsers always find entries");
+        // A parser's networks are those of its entries.
+        match (parser.parse(data), parse_entries(&entries)) {
+            (Ok(parsed), Ok(read)) => {
+                assert_eq!(parsed, read, "{}", name);
+                common::check(&parsed.0);
+                common::check(&parsed.1);
+            }
+            (Err(parsed), Err(read)) => assert_eq!(parsed.to_string(), read.to_string()),
+            (parsed, read) => panic!("{}: {:?} but {:?}", name, parsed, read),
+        }
+    }
+});
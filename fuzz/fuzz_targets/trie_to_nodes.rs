@@ -0,0 +1,40 @@
+#![no_main]
+
+use ipcheck_rs::flat::{nodes_to_trie, trie_to_nodes};
+use ipcheck_rs::IpRange;
+use ipnet::{Ipv4Net, Ipv6Net};
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+fuzz_target!(|input: (Vec<(u32, u8)>, Vec<(u128, u8)>)| {
+    let (v4, v6) = input;
+    let v4: Vec<Ipv4Net> = v4
+        .into_iter()
+        .filter_map(|(addr, len)| Ipv4Net::new(addr.into(), len % 33).ok())
+        .map(|net| net.trunc())
+        .collect();
+    let v6: Vec<Ipv6Net> = v6
+        .into_iter()
+        .filter_map(|(addr, len)| Ipv6Net::new(addr.into(), len % 129).ok())
+        .map(|net| net.trunc())
+        .collect();
+
+    let range = common::build_and_check(&v4);
+    if let Some(trie) = range.clone().into_trie().into_boxed_node() {
+        let nodes = trie_to_nodes(trie);
+        let decoded = nodes_to_trie(&nodes).expect("encoded trie must decode");
+        let mut decoded = IpRange::<Ipv4Net>::from(decoded);
+        decoded.simplify();
+        assert_eq!(range, decoded);
+    }
+
+    let range = common::build_and_check(&v6);
+    if let Some(trie) = range.clone().into_trie().into_boxed_node() {
+        let nodes = trie_to_nodes(trie);
+        let decoded = nodes_to_trie(&nodes).expect("encoded trie must decode");
+        let mut decoded = IpRange::<Ipv6Net>::from(decoded);
+        decoded.simplify();
+        assert_eq!(range, decoded);
+    }
+});
@@ -0,0 +1,59 @@
+#ifndef IPCHECK_H
+#define IPCHECK_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * The IPv4 and IPv6 networks of a loaded artifact.
+ */
+typedef struct IpCheckFilter IpCheckFilter;
+
+/**
+ * Loads the `len` bytes at `bytes` as a binary artifact.
+ *
+ * Returns `NULL` if they are not a valid artifact. Otherwise the filter
+ * must be released with `ipcheck_free`.
+ *
+ * # Safety
+ *
+ * `bytes` must point to `len` readable bytes, or be `NULL` with `len` 0.
+ */
+struct IpCheckFilter *ipcheck_load(const uint8_t *bytes, size_t len);
+
+/**
+ * Returns whether any IPv4 network of `filter` contains the address whose
+ * 4 bytes, in network order, are at `addr`.
+ *
+ * # Safety
+ *
+ * `filter` must come from `ipcheck_load` and not have been freed, and
+ * `addr` must point to 4 readable bytes.
+ */
+bool ipcheck_contains_v4(const struct IpCheckFilter *filter, const uint8_t *addr);
+
+/**
+ * Returns whether any IPv6 network of `filter` contains the address whose
+ * 16 bytes, in network order, are at `addr`.
+ *
+ * # Safety
+ *
+ * `filter` must come from `ipcheck_load` and not have been freed, and
+ * `addr` must point to 16 readable bytes.
+ */
+bool ipcheck_contains_v6(const struct IpCheckFilter *filter, const uint8_t *addr);
+
+/**
+ * Releases a filter returned by `ipcheck_load`. `NULL` is ignored.
+ *
+ * # Safety
+ *
+ * `filter` must be `NULL` or come from `ipcheck_load`, and must not be
+ * used afterwards.
+ */
+void ipcheck_free(struct IpCheckFilter *filter);
+
+#endif  /* IPCHECK_H */
@@ -0,0 +1,355 @@
+//! A compact binary artifact holding the IPv4 and IPv6 node arrays.
+//!
+//! The layout is, with every integer little-endian:
+//!
+//! | Field            | Size         |
+//! |------------------|--------------|
+//! | magic `b"IPCK"`  | 4 bytes      |
+//! | format version   | 4 bytes      |
+//! | CRC-32 of payload| 4 bytes      |
+//! | payload          | rest of file |
+//!
+//! and the payload holds, for IPv4 then IPv6, a `u32` count of node array
+//! entries followed by the entries as `u32`s (see [`flat`] for the node
+//! array layout). An empty range has no entries, while the whole address
+//! family is the two entries `[0, 0]`.
+//!
+//! The format version is [`FORMAT_VERSION`]. [`load`] rejects any other
+//! version, then verifies the checksum before decoding anything, so a file
+//! from a newer encoder, a truncated file or a corrupted one is rejected
+//! instead of answering lookups wrongly.
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! use ipcheck_rs::{artifact, IpRange};
+//! use ipnet::{Ipv4Net, Ipv6Net};
+//!
+//! let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+//! let v6: IpRange<Ipv6Net> = IpRange::new();
+//!
+//! let bytes = artifact::write(&v4, &v6).unwrap();
+//! assert_eq!(artifact::load(&bytes).unwrap(), (v4, v6));
+//! ```
+//!
+//! [`flat`]: ../flat/index.html
+//! [`load`]: fn.load.html
+//! [`FORMAT_VERSION`]: ../flat/constant.FORMAT_VERSION.html
+
+use std::fs;
+use std::path::Path;
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::flat::{self, trie_to_nodes, FORMAT_VERSION};
+use crate::{IpCheckError, IpNet, IpRange};
+
+const MAGIC: &[u8; 4] = b"IPCK";
+const HEADER_LEN: usize = 12;
+
+/// Ways in which an artifact can be malformed.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The data does not start with the artifact magic bytes.
+    #[error("not an ipcheck artifact")]
+    BadMagic,
+    /// The artifact uses a format version this library does not support.
+    #[error("artifact format version {found} is not supported (expected {supported})")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// The data ends before the header or a node array does.
+    #[error("artifact is truncated")]
+    Truncated,
+    /// The payload does not match the checksum in the header.
+    #[error("artifact checksum mismatch: header says {expected:08x}, payload is {actual:08x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// A node array is not well formed.
+    #[error("artifact contains a malformed node array")]
+    InvalidNodes,
+    /// Bytes follow the IPv6 node array.
+    #[error("artifact has trailing data")]
+    TrailingData,
+}
+
+/// Encodes `v4` and `v6` into an artifact.
+///
+/// Fails with [`IpCheckError::IndexOverflow`] if a trie has more nodes than
+/// 32-bit indices can address.
+///
+/// [`IpCheckError::IndexOverflow`]: ../enum.IpCheckError.html#variant.IndexOverflow
+pub fn write(v4: &IpRange<Ipv4Net>, v6: &IpRange<Ipv6Net>) -> Result<Vec<u8>, IpCheckError> {
+    let mut payload = Vec::new();
+    write_nodes(&mut payload, v4)?;
+    write_nodes(&mut payload, v6)?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Decodes an artifact produced by [`write`].
+///
+/// [`write`]: fn.write.html
+pub fn load(bytes: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), Error> {
+    if bytes.len() < HEADER_LEN {
+        return Err(if MAGIC.starts_with(bytes) || bytes.starts_with(MAGIC) {
+            Error::Truncated
+        } else {
+            Error::BadMagic
+        });
+    }
+    if &bytes[..4] != MAGIC {
+        return Err(Error::BadMagic);
+    }
+
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+
+    let expected = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    let mut payload = &bytes[HEADER_LEN..];
+    let actual = crc32fast::hash(payload);
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+
+    let v4 = read_nodes(&mut payload)?;
+    let v6 = read_nodes(&mut payload)?;
+    if !payload.is_empty() {
+        return Err(Error::TrailingData);
+    }
+    Ok((v4, v6))
+}
+
+/// The checksum in the header of the artifact `bytes`, which fingerprints
+/// the networks it holds, since equal networks give identical artifacts.
+///
+/// Only the header is read; see [`load`] for checking the payload.
+///
+/// [`load`]: fn.load.html
+pub fn checksum(bytes: &[u8]) -> Option<u32> {
+    match bytes.get(..HEADER_LEN) {
+        Some(header) if &header[..4] == MAGIC => {
+            Some(u32::from_le_bytes(header[8..12].try_into().unwrap()))
+        }
+        _ => None,
+    }
+}
+
+/// Reads and decodes the artifact at `path`.
+pub fn load_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+    Ok(load(&fs::read(path)?)?)
+}
+
+fn write_nodes<N: IpNet>(out: &mut Vec<u8>, range: &IpRange<N>) -> Result<(), IpCheckError> {
+    let nodes = match range.clone().into_trie().into_boxed_node() {
+        Some(trie) => trie_to_nodes(trie),
+        None => Vec::new(),
+    };
+    let len = u32::try_from(nodes.len()).map_err(|_| IpCheckError::IndexOverflow(nodes.len()))?;
+    out.extend_from_slice(&len.to_le_bytes());
+    for node in nodes {
+        let node = u32::try_from(node).map_err(|_| IpCheckError::IndexOverflow(node))?;
+        out.extend_from_slice(&node.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, Error> {
+    if input.len() < 4 {
+        return Err(Error::Truncated);
+    }
+    let (head, rest) = input.split_at(4);
+    *input = rest;
+    Ok(u32::from_le_bytes(head.try_into().unwrap()))
+}
+
+fn read_nodes<N: IpNet>(input: &mut &[u8]) -> Result<IpRange<N>, Error> {
+    let len = read_u32(input)? as usize;
+    if len == 0 {
+        return Ok(IpRange::new());
+    }
+    if input.len() / 4 < len {
+        return Err(Error::Truncated);
+    }
+    let nodes = (0..len)
+        .map(|_| read_u32(input).map(|node| node as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    // The checksum does not vouch for a crafted artifact: a trie deeper than
+    // the prefixes of its family is as malformed as any other.
+    flat::decode(&nodes).map_err(|_| Error::InvalidNodes)
+}
+
+/// An artifact of the raw node arrays `v4` and `v6`, with a valid checksum
+/// whether they are well formed or not.
+#[cfg(test)]
+pub(crate) fn with_nodes(v4: &[u32], v6: &[u32]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for nodes in [v4, v6] {
+        payload.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+        for node in nodes {
+            payload.extend_from_slice(&node.to_le_bytes());
+        }
+    }
+    let mut bytes = Vec::from(&MAGIC[..]);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// An IPv4 node array chaining 40 levels deep, deeper than any IPv4 prefix.
+#[cfg(test)]
+pub(crate) fn deep_v4_chain() -> Vec<u32> {
+    let mut chain: Vec<u32> = (1..=40).flat_map(|i| [i, 0]).collect();
+    chain.extend([0, 0]);
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges() -> (IpRange<Ipv4Net>, IpRange<Ipv6Net>) {
+        let v4 = ["10.0.0.0/8", "192.168.0.0/16", "172.16.5.0/24"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6 = ["2001:db8::/32", "fe80::/10"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        (v4, v6)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (v4, v6) = ranges();
+        let bytes = write(&v4, &v6).unwrap();
+        assert_eq!(load(&bytes), Ok((v4, v6)));
+
+        let empty = write(&IpRange::new(), &IpRange::new()).unwrap();
+        assert_eq!(empty.len(), HEADER_LEN + 8);
+        assert_eq!(load(&empty), Ok((IpRange::new(), IpRange::new())));
+    }
+
+    #[test]
+    fn checksum_fingerprints_the_networks() {
+        let (v4, v6) = ranges();
+        let bytes = write(&v4, &v6).unwrap();
+        assert_eq!(
+            checksum(&bytes),
+            Some(crc32fast::hash(&bytes[HEADER_LEN..]))
+        );
+        let mut networks: Vec<Ipv4Net> = v4.iter().collect();
+        networks.reverse();
+        let shuffled: IpRange<Ipv4Net> = networks.into_iter().collect();
+        assert_eq!(checksum(&write(&shuffled, &v6).unwrap()), checksum(&bytes));
+        assert_ne!(
+            checksum(&write(&v4, &IpRange::new()).unwrap()),
+            checksum(&bytes)
+        );
+        assert_eq!(checksum(b"IPCK"), None);
+        assert_eq!(checksum(b"not an artifact"), None);
+    }
+
+    #[test]
+    fn roundtrip_whole_family() {
+        let v4: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["::/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let bytes = write(&v4, &v6).unwrap();
+        let mut payload = Vec::new();
+        for _ in 0..2 {
+            payload.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        assert_eq!(bytes[HEADER_LEN..], payload[..]);
+        assert_eq!(load(&bytes), Ok((v4.clone(), v6.clone())));
+
+        // Whole family on one side, nothing on the other.
+        let bytes = write(&v4, &IpRange::new()).unwrap();
+        assert_eq!(load(&bytes), Ok((v4, IpRange::new())));
+    }
+
+    #[test]
+    fn load_file_reports_io_and_format_errors() {
+        let path =
+            std::env::temp_dir().join(format!("ipcheck-artifact-{}.bin", std::process::id()));
+        assert!(matches!(load_file(&path), Err(IpCheckError::Io(_))));
+
+        let (v4, v6) = ranges();
+        std::fs::write(&path, write(&v4, &v6).unwrap()).unwrap();
+        assert_eq!(load_file(&path).unwrap(), (v4, v6));
+
+        std::fs::write(&path, b"IPCK").unwrap();
+        let err = load_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, IpCheckError::Artifact(Error::Truncated)));
+    }
+
+    #[test]
+    fn reject_truncated() {
+        let (v4, v6) = ranges();
+        let bytes = write(&v4, &v6).unwrap();
+        for len in 0..bytes.len() {
+            assert!(load(&bytes[..len]).is_err(), "accepted {} bytes", len);
+        }
+        assert!(matches!(
+            load(&bytes[..bytes.len() - 4]),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert_eq!(load(&bytes[..3]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        let (v4, v6) = ranges();
+        let mut bytes = write(&v4, &v6).unwrap();
+        assert_eq!(bytes[4..8], FORMAT_VERSION.to_le_bytes());
+
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            load(&bytes),
+            Err(Error::UnsupportedVersion {
+                found: FORMAT_VERSION + 1,
+                supported: FORMAT_VERSION,
+            })
+        );
+    }
+
+    #[test]
+    fn reject_corrupted() {
+        let (v4, v6) = ranges();
+        let bytes = write(&v4, &v6).unwrap();
+        for i in 0..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 0x10;
+            let err = load(&corrupted).unwrap_err();
+            if i < 4 {
+                assert_eq!(err, Error::BadMagic);
+            } else if i < 8 {
+                assert!(matches!(err, Error::UnsupportedVersion { .. }));
+            } else {
+                assert!(matches!(err, Error::ChecksumMismatch { .. }));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_deep_tries() {
+        // A crafted artifact with a valid checksum.
+        let bytes = with_nodes(&deep_v4_chain(), &[]);
+        assert_eq!(load(&bytes), Err(Error::InvalidNodes));
+        // The same chain is a valid IPv6 trie.
+        let (v4, v6) = load(&with_nodes(&[], &deep_v4_chain())).unwrap();
+        assert!(v4.is_empty());
+        assert_eq!(v6.iter().count(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+//! Pluggable output backends for code generation.
+//!
+//! An [`OutputBackend`] receives the networks of a build, both as ranges
+//! and as the flattened node arrays of their tries (see [`flat`]), along
+//! with the metadata rendered next to them, and produces the files of one
+//! target. `ipcheck` routes all of its outputs through backends: its
+//! built-in `typescript`, `node-test` and `artifact` targets, and any
+//! backend registered with [`register`], which takes precedence over a
+//! built-in of the same name. A new language target is thus a crate
+//! implementing this trait rather than a change to the renderer:
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! use ipcheck_rs::backend::{self, Error, Filters, Output, OutputBackend};
+//!
+//! /// The IPv4 node array as a Go slice.
+//! struct Go;
+//!
+//! impl OutputBackend for Go {
+//!     fn name(&self) -> &'static str {
+//!         "go"
+//!     }
+//!
+//!     fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+//!         let nodes: Vec<String> = filters.nodes_v4.iter().map(|n| n.to_string()).collect();
+//!         Ok(vec![Output {
+//!             path: filters.output.with_extension("go"),
+//!             contents: format!("var filterV4 = []uint32{{{}}}\n", nodes.join(",")).into_bytes(),
+//!         }])
+//!     }
+//! }
+//!
+//! backend::register(Go);
+//! assert!(backend::registry().get("go").is_some());
+//! ```
+//!
+//! Backends only render; the caller writes the outputs, so every target
+//! gets the same atomic writes, `--check` comparisons and remote storage.
+//!
+//! [`flat`]: ../flat/index.html
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::IpRange;
+
+/// The error of a failed rendering.
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A code generation target.
+pub trait OutputBackend: Send + Sync {
+    /// The name the target is selected by, e.g. `typescript`.
+    fn name(&self) -> &'static str;
+
+    /// Renders the files of this target for `filters`.
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error>;
+}
+
+/// What a backend renders: the networks of a build and their metadata.
+pub struct Filters<'a> {
+    /// The output path the build was asked for. Backends writing other
+    /// files usually derive their paths from it.
+    pub output: &'a Path,
+    /// The IPv4 networks.
+    pub v4: &'a IpRange<Ipv4Net>,
+    /// The IPv6 networks.
+    pub v6: &'a IpRange<Ipv6Net>,
+    /// The flattened trie of `v4`, as produced by
+    /// [`trie_to_nodes`](../flat/fn.trie_to_nodes.html).
+    pub nodes_v4: &'a [usize],
+    /// The flattened trie of `v6`.
+    pub nodes_v6: &'a [usize],
+    /// Comments on individual networks, such as their countries.
+    pub notes: &'a [Note],
+    /// The provenance of the build and the variables of its user, which
+    /// templates stamp generated files with.
+    pub metadata: &'a Metadata,
+    /// The value of each network, such as the name of the list it was
+    /// read from, which value lookups return.
+    pub values: &'a Values,
+}
+
+/// A comment on one network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// The network, e.g. `10.0.0.0/8`.
+    pub network: String,
+    /// What to say about it.
+    pub note: String,
+}
+
+/// The provenance of a build, and variables of its user.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    /// When the build ran, e.g. `2024-05-01T12:00Z`.
+    pub timestamp: String,
+    /// The lists the networks were read from.
+    pub inputs: Vec<Input>,
+    /// Variables of the user, by name.
+    pub vars: BTreeMap<String, String>,
+}
+
+/// Networks labeled with values, the more specific network's value taking
+/// precedence where they overlap, and the later one's for the same network.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Values {
+    /// The distinct values, e.g. `tor-exits`, which networks refer to by
+    /// index.
+    pub names: Vec<String>,
+    /// The IPv4 networks, each with the index of its value.
+    pub v4: Vec<(Ipv4Net, usize)>,
+    /// The IPv6 networks.
+    pub v6: Vec<(Ipv6Net, usize)>,
+}
+
+/// A list read by a build.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    /// Where it was read from.
+    pub path: String,
+    /// The SHA-256 of its contents, in hex.
+    pub sha256: String,
+}
+
+/// A rendered file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    /// Where it goes.
+    pub path: PathBuf,
+    /// Its contents.
+    pub contents: Vec<u8>,
+}
+
+/// A set of backends, looked up by name.
+#[derive(Clone, Default)]
+pub struct Registry {
+    backends: Vec<Arc<dyn OutputBackend>>,
+}
+
+impl Registry {
+    /// A registry without any backends.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Adds `backend`, replacing any backend of the same name.
+    pub fn register<B: OutputBackend + 'static>(&mut self, backend: B) {
+        self.backends.retain(|known| known.name() != backend.name());
+        self.backends.push(Arc::new(backend));
+    }
+
+    /// The backend named `name`.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn OutputBackend>> {
+        self.backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .cloned()
+    }
+
+    /// The names of the registered backends, in registration order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+}
+
+fn global() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::new()))
+}
+
+/// Adds `backend` to the process-wide registry.
+pub fn register<B: OutputBackend + 'static>(backend: B) {
+    global().write().unwrap().register(backend);
+}
+
+/// The process-wide registry.
+pub fn registry() -> RwLockReadGuard<'static, Registry> {
+    global().read().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str, &'static str);
+
+    impl OutputBackend for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn render(&self, filters: &Filters) -> Result<Vec<Output>, Error> {
+            Ok(vec![Output {
+                path: filters.output.to_path_buf(),
+                contents: self.1.into(),
+            }])
+        }
+    }
+
+    #[test]
+    fn registration_replaces_namesakes() {
+        let mut registry = Registry::new();
+        registry.register(Named("go", "first"));
+        registry.register(Named("rust", ""));
+        registry.register(Named("go", "second"));
+        assert_eq!(registry.names(), ["rust", "go"]);
+
+        let (v4, v6) = (IpRange::new(), IpRange::new());
+        let filters = Filters {
+            output: Path::new("out"),
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &[],
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = registry.get("go").unwrap().render(&filters).unwrap();
+        assert_eq!(outputs[0].contents, b"second");
+        assert!(registry.get("python").is_none());
+    }
+}
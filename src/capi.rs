@@ -0,0 +1,145 @@
+//! A C ABI for embedding lookups, declared in `include/ipcheck.h`.
+//!
+//! ```c
+//! #include "ipcheck.h"
+//!
+//! IpCheckFilter *filter = ipcheck_load(bytes, len);
+//! if (filter == NULL) {
+//!     /* not a valid artifact */
+//! }
+//! struct in_addr addr;
+//! inet_pton(AF_INET, "10.1.2.3", &addr);
+//! bool hit = ipcheck_contains_v4(filter, (const uint8_t *)&addr);
+//! ipcheck_free(filter);
+//! ```
+//!
+//! Addresses are passed as their bytes in network order, which is the
+//! memory layout of `struct in_addr` and `struct in6_addr`. A filter is
+//! never modified after loading, so it can be shared between threads.
+//!
+//! The crate builds as an `rlib` only; build the shared library with
+//! `cargo rustc --lib --release --crate-type cdylib --features capi`.
+//!
+//! Regenerate the header with `cbindgen --config cbindgen.toml --output
+//! include/ipcheck.h` after changing this module.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::panic;
+use std::slice;
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::{artifact, IpRange};
+
+/// The IPv4 and IPv6 networks of a loaded artifact.
+pub struct IpCheckFilter {
+    v4: IpRange<Ipv4Net>,
+    v6: IpRange<Ipv6Net>,
+}
+
+/// Loads the `len` bytes at `bytes` as a binary artifact.
+///
+/// Returns `NULL` if they are not a valid artifact. Otherwise the filter
+/// must be released with `ipcheck_free`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, or be `NULL` with `len` 0.
+#[no_mangle]
+pub unsafe extern "C" fn ipcheck_load(bytes: *const u8, len: usize) -> *mut IpCheckFilter {
+    let bytes = if bytes.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(bytes, len)
+    };
+    // A panic must not unwind into the host: whatever makes the decoder
+    // panic is not a valid artifact either.
+    match panic::catch_unwind(|| artifact::load(bytes)) {
+        Ok(Ok((v4, v6))) => Box::into_raw(Box::new(IpCheckFilter { v4, v6 })),
+        Ok(Err(_)) | Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Returns whether any IPv4 network of `filter` contains the address whose
+/// 4 bytes, in network order, are at `addr`.
+///
+/// # Safety
+///
+/// `filter` must come from `ipcheck_load` and not have been freed, and
+/// `addr` must point to 4 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ipcheck_contains_v4(
+    filter: *const IpCheckFilter,
+    addr: *const u8,
+) -> bool {
+    let addr = *(addr as *const [u8; 4]);
+    (*filter).v4.contains(&Ipv4Addr::from(addr))
+}
+
+/// Returns whether any IPv6 network of `filter` contains the address whose
+/// 16 bytes, in network order, are at `addr`.
+///
+/// # Safety
+///
+/// `filter` must come from `ipcheck_load` and not have been freed, and
+/// `addr` must point to 16 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ipcheck_contains_v6(
+    filter: *const IpCheckFilter,
+    addr: *const u8,
+) -> bool {
+    let addr = *(addr as *const [u8; 16]);
+    (*filter).v6.contains(&Ipv6Addr::from(addr))
+}
+
+/// Releases a filter returned by `ipcheck_load`. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `filter` must be `NULL` or come from `ipcheck_load`, and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ipcheck_free(filter: *mut IpCheckFilter) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_and_look_up() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let bytes = artifact::write(&v4, &v6).unwrap();
+
+        unsafe {
+            let filter = ipcheck_load(bytes.as_ptr(), bytes.len());
+            assert!(!filter.is_null());
+            assert!(ipcheck_contains_v4(filter, [10, 1, 2, 3].as_ptr()));
+            assert!(!ipcheck_contains_v4(filter, [8, 8, 8, 8].as_ptr()));
+            let inside = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets();
+            let outside = "2001:db9::1".parse::<Ipv6Addr>().unwrap().octets();
+            assert!(ipcheck_contains_v6(filter, inside.as_ptr()));
+            assert!(!ipcheck_contains_v6(filter, outside.as_ptr()));
+            ipcheck_free(filter);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_artifacts() {
+        unsafe {
+            assert!(ipcheck_load(b"IPCK".as_ptr(), 4).is_null());
+            assert!(ipcheck_load(std::ptr::null(), 0).is_null());
+            // A checksum does not make a crafted trie valid.
+            let deep = artifact::with_nodes(&artifact::deep_v4_chain(), &[]);
+            assert!(ipcheck_load(deep.as_ptr(), deep.len()).is_null());
+            ipcheck_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,1106 @@
+//! Generating lookup code in-process.
+//!
+//! [`render`] produces the same files as the `ipcheck` CLI, so build
+//! scripts and other tools can generate a checker without running the
+//! binary and reading its outputs back:
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! use ipcheck_rs::codegen::{self, Options, Target};
+//! use ipcheck_rs::IpRange;
+//! use ipnet::{Ipv4Net, Ipv6Net};
+//!
+//! let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+//! let v6: IpRange<Ipv6Net> = ["2001:db8::/32"].iter().map(|s| s.parse().unwrap()).collect();
+//! let code = codegen::render(&v4, &v6, Target::TypeScript, &Options::default()).unwrap();
+//! assert!(code.contains("export function ipCheck"));
+//! ```
+//!
+//! Besides TypeScript, the checker can be rendered as plain JavaScript, an
+//! ES module or a CommonJS one, for consumers without a TypeScript build
+//! step; [`DECLARATIONS`] types either. It can also be rendered as a
+//! Python module, for ETL jobs and other Python consumers, as a Go
+//! package, for backend services sharing the filter of the frontend, or as
+//! a `no_std` Rust source file, compiled into other Rust binaries without
+//! depending on this crate. It can be rendered as a self-contained C header
+//! as well, for nginx modules and embedded firmware, as a Java class, for
+//! Android and other JVM services, or as a Lua module, for OpenResty.
+//!
+//! The checker can also be rendered from a template of one's own with the
+//! values of the built-in one, set as [`Options::template`], for wrapper
+//! code with its own exports and license headers. The built-in TypeScript
+//! template is made of partials, any of which [`Options::partials`]
+//! replaces without forking the rest.
+//!
+//! Where networks carry values, such as the names of the lists holding
+//! them or their countries, [`Target::Lookup`] renders a TypeScript module
+//! returning the value of an address instead of whether it is listed, from
+//! [`Options::values`].
+//!
+//! Each [`Target`] is also an [`OutputBackend`], as which the CLI renders
+//! it, as is a [`Templated`] one. The filter can also be compiled into a WebAssembly module, by
+//! [`wasm`], and the networks written as a MaxMind DB, by [`mmdb`].
+//!
+//! [`OutputBackend`]: ../backend/trait.OutputBackend.html
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use handlebars::{Handlebars, RenderError};
+use ipnet::{Ipv4Net, Ipv6Net};
+use serde::Serialize;
+
+use crate::backend::{self, Filters, Metadata, Note, Output, OutputBackend, Values};
+use crate::flat::{self, FORMAT_VERSION};
+use crate::{IpCheckError, IpRange};
+
+pub use self::lookup::render_lookup;
+pub use self::node_test::test_path;
+
+mod helpers;
+mod lookup;
+pub mod mmdb;
+mod node_test;
+pub mod wasm;
+
+/// A generated file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// The TypeScript checker module, exporting `ipCheck`.
+    TypeScript,
+    /// A `node:test` suite for the TypeScript checker, asserting lookups
+    /// sampled from each block.
+    NodeTest,
+    /// The checker as a JavaScript ES module, for browsers and bundlers.
+    Esm,
+    /// The checker as a CommonJS module, for `require` in Node.
+    Cjs,
+    /// The checker as a Python module without dependencies, exporting
+    /// `ip_in_list`.
+    Python,
+    /// The checker as a Go package, exporting `Contains`, named after the
+    /// directory it is written to.
+    Go,
+    /// The checker as a Rust source file needing only `core`, exporting
+    /// `contains`.
+    Rust,
+    /// The checker as a self-contained C header of `static inline`
+    /// functions, `ipcheck_filter_contains_v4` and `_v6`.
+    C,
+    /// The checker as a Java class named after the file it is written to,
+    /// exporting `contains(InetAddress)`.
+    Java,
+    /// The checker as a Lua module for LuaJIT, as in OpenResty, exporting
+    /// `contains`.
+    Lua,
+    /// A TypeScript module exporting `lookup`, which returns the value of
+    /// the most specific network covering an address, such as the name of
+    /// its list, or `null`, from the [`Values`] of the build rather than
+    /// from its networks.
+    Lookup,
+}
+
+/// How a checker embeds its node arrays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// As array literals of the checker's language.
+    #[default]
+    Literal,
+    /// As base64 strings of their little-endian 32-bit words, decoded into
+    /// `Uint32Array`s when the module loads: a fraction of the size of the
+    /// literals, and quicker to parse. Only the TypeScript and JavaScript
+    /// checkers take it; the others keep their literals.
+    Base64,
+}
+
+/// The TypeScript declarations of the JavaScript checkers, which the `esm`
+/// and `cjs` backends write next to them.
+pub const DECLARATIONS: &str = include_str!("codegen/ipcheck.d.ts");
+
+/// The TypeScript declarations of the minified ES module checker, whose
+/// only export is its lookup.
+pub const MINIFIED_DECLARATIONS: &str = include_str!("codegen/ipcheck.min.d.ts");
+
+/// Returns where the declarations of the JavaScript checker written to
+/// `output` go: `ipcheck.js` gets `ipcheck.d.ts`, and `ipcheck.mjs` and
+/// `ipcheck.cjs` get `ipcheck.d.mts` and `ipcheck.d.cts`, as TypeScript
+/// looks them up.
+pub fn declarations_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = match output.extension().and_then(|ext| ext.to_str()) {
+        Some("mjs") => "mts",
+        Some("cjs") => "cts",
+        _ => "ts",
+    };
+    output.with_file_name(format!("{}.d.{}", stem, ext))
+}
+
+/// Settings of [`render`].
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Comments on individual networks, listed above the filters.
+    pub notes: Vec<Note>,
+    /// Where the checker goes: the TypeScript checker, which the
+    /// `node:test` suite imports it from, or the Go one, whose package is
+    /// named after its directory. Defaults to `ipcheck.ts`.
+    pub checker_path: PathBuf,
+    /// A Handlebars template replacing the built-in one of the checker,
+    /// rendered with the same values: `filterV4` and `filterV6`, the node
+    /// arrays as literals of the target's language, their lengths
+    /// `lenV4` and `lenV6`, `formatVersion`, the `notes`, each with its
+    /// `network` and `note`, and `esm`, `package` and `class`, as the
+    /// JavaScript, Go and Java checkers use them. The `node:test` suite
+    /// has no such values and ignores it.
+    ///
+    /// Templates also get the `vars` of [`Options::metadata`], and the
+    /// provenance of the build as `build`: its `version` of `ipcheck`, its
+    /// `timestamp`, its `inputs`, each with its `path` and `sha256`, and the
+    /// numbers of networks of each family, `countV4` and `countV6`. Values
+    /// are written as they are, without the HTML escaping of Handlebars.
+    ///
+    /// For arrays too large for one literal, the node arrays are also
+    /// `nodesV4` and `nodesV6`, as arrays of numbers, and templates have
+    /// the helpers `chunk`, splitting an array into arrays of at most a
+    /// number of values, `join`, writing the values of an array between a
+    /// separator, `,` by default, and `hex` and `base64`, encoding a node
+    /// array as the little-endian 32-bit words of a `Uint32Array`:
+    ///
+    /// ```handlebars
+    /// {{#each (chunk nodesV4 4096)}}
+    /// const part{{@index}} = [{{join this}}];
+    /// {{/each}}
+    /// ```
+    pub template: Option<String>,
+    /// Handlebars partials by name, replacing those the built-in TypeScript
+    /// template is made of, in this order:
+    ///
+    /// - `header`, comments above the checker, none by default;
+    /// - `parse`, the parsing of addresses into bytes, `ipToBytes`;
+    /// - `lookup`, the walk of a node array, `matchBytes`, and `buildCidr`;
+    /// - `exports`, `ipCheck` and its types;
+    /// - `arrays`, the format version and the node arrays, `IP_FILTER_V4`
+    ///   and `IP_FILTER_V6`.
+    ///
+    /// Other partials are registered too, for templates and partials of
+    /// one's own to include: `{{> license}}`. The built-in ones are
+    /// available to templates as well, so that a wrapper can reuse the
+    /// parsing and the walk with exports of its own.
+    pub partials: BTreeMap<String, String>,
+    /// The provenance of the build and the variables of its user.
+    pub metadata: Metadata,
+    /// How the checker embeds its node arrays, which templates get as
+    /// `encoding`, `literal` or `base64`.
+    pub encoding: Encoding,
+    /// Whether to render the TypeScript and ES module checkers minified,
+    /// for edge bundles: without whitespace or comments, with short
+    /// identifiers, and with a single default export, the lookup of
+    /// `ipCheck` without its `includeCidr` option. The other checkers are
+    /// rendered as they are.
+    pub minify: bool,
+    /// The values of the networks, which [`Target::Lookup`] looks up.
+    pub values: Values,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            notes: Vec::new(),
+            checker_path: PathBuf::from("ipcheck.ts"),
+            template: None,
+            partials: BTreeMap::new(),
+            metadata: Metadata::default(),
+            encoding: Encoding::default(),
+            minify: false,
+            values: Values::default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IpCheckTemplate<'a> {
+    #[serde(rename = "formatVersion")]
+    format_version: u32,
+    #[serde(rename = "filterV4")]
+    filter_v4: String,
+    #[serde(rename = "filterV6")]
+    filter_v6: String,
+    /// The node arrays themselves, for the helpers.
+    #[serde(rename = "nodesV4")]
+    nodes_v4: &'a [usize],
+    #[serde(rename = "nodesV6")]
+    nodes_v6: &'a [usize],
+    /// The lengths of the node arrays, for the C checker.
+    #[serde(rename = "lenV4")]
+    len_v4: usize,
+    #[serde(rename = "lenV6")]
+    len_v6: usize,
+    notes: Vec<NoteTemplate<'a>>,
+    /// Whether the JavaScript checker is an ES module rather than CommonJS.
+    esm: bool,
+    /// Whether the minified checker is TypeScript.
+    typescript: bool,
+    /// The package of the Go or Java checker.
+    package: String,
+    /// The class of the Java checker.
+    class: String,
+    encoding: Encoding,
+    vars: &'a BTreeMap<String, String>,
+    build: BuildTemplate<'a>,
+}
+
+/// The provenance of the build.
+#[derive(Serialize)]
+struct BuildTemplate<'a> {
+    version: &'static str,
+    timestamp: &'a str,
+    inputs: Vec<InputTemplate<'a>>,
+    #[serde(rename = "countV4")]
+    count_v4: usize,
+    #[serde(rename = "countV6")]
+    count_v6: usize,
+}
+
+/// A list read by the build.
+#[derive(Serialize)]
+struct InputTemplate<'a> {
+    path: &'a str,
+    sha256: &'a str,
+}
+
+/// A comment on one network, listed above the filters.
+#[derive(Serialize)]
+struct NoteTemplate<'a> {
+    network: &'a str,
+    note: &'a str,
+}
+
+/// Renders `target` for the networks of `v4` and `v6`. Either may be
+/// empty, as for a checker of one family only: nothing of that family
+/// matches.
+pub fn render(
+    v4: &IpRange<Ipv4Net>,
+    v6: &IpRange<Ipv6Net>,
+    target: Target,
+    options: &Options,
+) -> Result<String, IpCheckError> {
+    match target {
+        Target::NodeTest => node_test::render_tests(&options.checker_path, v4, v6),
+        Target::Lookup => render_lookup(&options.values),
+        _ => {
+            let (nodes_v4, nodes_v6) = (flat::encode(v4), flat::encode(v6));
+            let filters = Filters {
+                output: &options.checker_path,
+                v4,
+                v6,
+                nodes_v4: &nodes_v4,
+                nodes_v6: &nodes_v6,
+                notes: &options.notes,
+                metadata: &options.metadata,
+                values: &options.values,
+            };
+            checker(
+                target,
+                &filters,
+                options.template.as_deref(),
+                &options.partials,
+                options.encoding,
+                options.minify,
+            )
+        }
+    }
+}
+
+/// The partials of the built-in TypeScript template, by name.
+const PARTIALS: [(&str, &str); 5] = [
+    ("header", include_str!("codegen/partials/header.ts")),
+    ("parse", include_str!("codegen/partials/parse.ts")),
+    ("lookup", include_str!("codegen/partials/lookup.ts")),
+    ("exports", include_str!("codegen/partials/exports.ts")),
+    ("arrays", include_str!("codegen/partials/arrays.ts")),
+];
+
+/// The checker of `filters` in the language of `target`, its node arrays
+/// embedded as `encoding` says, rendered from `template` if given, else
+/// from the built-in template of `target`, with `partials` replacing its
+/// built-in ones, minified if `minify` and `target` has a minified one.
+fn checker(
+    target: Target,
+    filters: &Filters,
+    template: Option<&str>,
+    partials: &BTreeMap<String, String>,
+    encoding: Encoding,
+    minify: bool,
+) -> Result<String, IpCheckError> {
+    let Filters {
+        output,
+        nodes_v4,
+        nodes_v6,
+        notes,
+        metadata,
+        ..
+    } = *filters;
+    let array = |nodes: &[usize]| {
+        let nodes: Vec<String> = nodes.iter().map(|n| n.to_string()).collect();
+        match target {
+            // As gofmt lays out a composite literal.
+            Target::Go | Target::Lua => format!("{{{}}}", nodes.join(", ")),
+            // C has no empty initializers before C23: an empty array is
+            // `{0}` of length 0.
+            Target::C if nodes.is_empty() => "{0}".to_string(),
+            Target::C => format!("{{{}}}", nodes.join(", ")),
+            Target::Rust => format!("[{}]", nodes.join(", ")),
+            Target::Java => java_chunks(&nodes),
+            _ => format!("[{}]", nodes.join(",")),
+        }
+    };
+    let mut tt = Handlebars::new();
+    // Code is not HTML: the values of a template are written as they are.
+    tt.register_escape_fn(handlebars::no_escape);
+    helpers::register(&mut tt);
+    for (name, partial) in PARTIALS {
+        tt.register_partial(name, partial)
+            .map_err(RenderError::from)?;
+    }
+    for (name, partial) in partials {
+        tt.register_partial(name, partial)
+            .map_err(RenderError::from)?;
+    }
+    let template = template.unwrap_or(match target {
+        Target::TypeScript | Target::Esm if minify => include_str!("codegen/ipcheck.min.js"),
+        Target::TypeScript => include_str!("codegen/ipcheck.ts"),
+        Target::Esm | Target::Cjs => include_str!("codegen/ipcheck.js"),
+        Target::Python => include_str!("codegen/ipcheck.py"),
+        Target::Go => include_str!("codegen/ipcheck.go"),
+        Target::Rust => include_str!("codegen/ipcheck.rs"),
+        Target::C => include_str!("codegen/ipcheck.h"),
+        Target::Java => include_str!("codegen/ipcheck.java"),
+        Target::Lua => include_str!("codegen/ipcheck.lua"),
+        Target::NodeTest | Target::Lookup => unreachable!("{:?} is not a checker", target),
+    });
+    let code = tt.render_template(
+        template,
+        &IpCheckTemplate {
+            format_version: FORMAT_VERSION,
+            filter_v4: array(nodes_v4),
+            filter_v6: array(nodes_v6),
+            nodes_v4,
+            nodes_v6,
+            len_v4: nodes_v4.len(),
+            len_v6: nodes_v6.len(),
+            notes: notes
+                .iter()
+                .map(|note| NoteTemplate {
+                    network: &note.network,
+                    note: &note.note,
+                })
+                .collect(),
+            esm: target == Target::Esm,
+            typescript: target == Target::TypeScript,
+            package: match target {
+                Target::Java => java_package(output),
+                _ => go_package(output),
+            },
+            class: java_class(output),
+            encoding,
+            vars: &metadata.vars,
+            build: BuildTemplate {
+                version: env!("CARGO_PKG_VERSION"),
+                timestamp: &metadata.timestamp,
+                inputs: metadata
+                    .inputs
+                    .iter()
+                    .map(|input| InputTemplate {
+                        path: &input.path,
+                        sha256: &input.sha256,
+                    })
+                    .collect(),
+                count_v4: networks(nodes_v4),
+                count_v6: networks(nodes_v6),
+            },
+        },
+    )?;
+    Ok(code)
+}
+
+/// The number of networks of the node array `nodes`: its leaves.
+fn networks(nodes: &[usize]) -> usize {
+    nodes
+        .chunks(2)
+        .filter(|children| children == &[0, 0])
+        .count()
+}
+
+/// Values per string chunk of a Java node array: at most 11 bytes each with
+/// its comma, well within the 65535 bytes of a class-file constant.
+const JAVA_CHUNK: usize = 4096;
+
+/// The string chunks a Java node array is decoded from, as arguments: a
+/// literal array initializer compiles to a few bytes of code per value, and
+/// a method holds at most 64 KiB of code.
+fn java_chunks(nodes: &[String]) -> String {
+    let chunks: Vec<String> = nodes
+        .chunks(JAVA_CHUNK)
+        .map(|chunk| format!("\"{}\"", chunk.join(",")))
+        .collect();
+    chunks.join(", ")
+}
+
+/// The package of the Java checker written to `output`: the directories
+/// below a `java` or `kotlin` source root, `src/main/java/com/example/
+/// IpFilter.java` being in `com.example`, else none.
+fn java_package(output: &Path) -> String {
+    let dirs: Vec<_> = output
+        .parent()
+        .into_iter()
+        .flat_map(Path::iter)
+        .map(|dir| dir.to_string_lossy())
+        .collect();
+    match dirs
+        .iter()
+        .rposition(|dir| dir == "java" || dir == "kotlin")
+    {
+        Some(root) => dirs[root + 1..].join("."),
+        None => String::new(),
+    }
+}
+
+/// The class of the Java checker written to `output`, which Java names
+/// after the file: its stem as a Java identifier, else `IpCheck`.
+fn java_class(output: &Path) -> String {
+    let stem = output.file_stem().unwrap_or_default();
+    let class: String = stem
+        .to_string_lossy()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '$')
+        .skip_while(|c| c.is_ascii_digit())
+        .collect();
+    match class.is_empty() {
+        true => "IpCheck".to_string(),
+        false => class,
+    }
+}
+
+/// The package of the Go checker written to `output`: its directory's name
+/// as a Go identifier, `tools/ip-filter/contains.go` being in `ipfilter`,
+/// else `ipcheck`.
+fn go_package(output: &Path) -> String {
+    let dir = output
+        .parent()
+        .and_then(Path::file_name)
+        .unwrap_or_default();
+    let package: String = dir
+        .to_string_lossy()
+        .to_ascii_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .skip_while(|c| c.is_ascii_digit())
+        .collect();
+    match package.is_empty() {
+        true => "ipcheck".to_string(),
+        false => package,
+    }
+}
+
+impl OutputBackend for Target {
+    fn name(&self) -> &'static str {
+        match self {
+            Target::TypeScript => "typescript",
+            Target::NodeTest => "node-test",
+            Target::Esm => "esm",
+            Target::Cjs => "cjs",
+            Target::Python => "python",
+            Target::Go => "go",
+            Target::Rust => "rust",
+            Target::C => "c",
+            Target::Java => "java",
+            Target::Lua => "lua",
+            Target::Lookup => "lookup",
+        }
+    }
+
+    /// Renders the checker to the output path, with its declarations next
+    /// to it if it is JavaScript, or its suite next to it.
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, backend::Error> {
+        Encoded {
+            target: *self,
+            encoding: Encoding::Literal,
+            minify: false,
+            partials: BTreeMap::new(),
+        }
+        .render(filters)
+    }
+}
+
+/// The checker of `target` with its node arrays embedded as `encoding` says,
+/// minified if `minify`, and rendered with `partials`: the backend of the
+/// checker, under the same name, when it is asked for another encoding than
+/// the literals, for the minified checker or for partials of one's own, as
+/// with [`Options::encoding`], [`Options::minify`] and
+/// [`Options::partials`].
+pub struct Encoded {
+    /// The target of the checker.
+    pub target: Target,
+    /// How it embeds its node arrays.
+    pub encoding: Encoding,
+    /// Whether it is minified.
+    pub minify: bool,
+    /// The partials replacing or added to those of its template.
+    pub partials: BTreeMap<String, String>,
+}
+
+impl OutputBackend for Encoded {
+    fn name(&self) -> &'static str {
+        self.target.name()
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, backend::Error> {
+        match self.target {
+            Target::NodeTest => {
+                return Ok(vec![Output {
+                    path: test_path(filters.output),
+                    contents: node_test::render_tests(filters.output, filters.v4, filters.v6)?
+                        .into_bytes(),
+                }])
+            }
+            Target::Lookup => {
+                return Ok(vec![Output {
+                    path: filters.output.to_path_buf(),
+                    contents: render_lookup(filters.values)?.into_bytes(),
+                }])
+            }
+            _ => {}
+        }
+        let code = checker(
+            self.target,
+            filters,
+            None,
+            &self.partials,
+            self.encoding,
+            self.minify,
+        )?;
+        let mut outputs = vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: code.into_bytes(),
+        }];
+        let declarations = match self.target {
+            Target::Esm if self.minify => MINIFIED_DECLARATIONS,
+            Target::Esm | Target::Cjs => DECLARATIONS,
+            _ => return Ok(outputs),
+        };
+        outputs.push(Output {
+            path: declarations_path(filters.output),
+            contents: declarations.into(),
+        });
+        Ok(outputs)
+    }
+}
+
+/// The checker of `target`, rendered from `template` instead of the
+/// built-in template: the backend of the checker, under the same name, when
+/// it is asked for a template of one's own.
+///
+/// It writes the checker to the output path alone, without the declarations
+/// of the JavaScript checkers, as they would not declare its exports. The
+/// `node:test` suite ignores the template, as with [`Options::template`].
+pub struct Templated {
+    /// The target whose values the template renders.
+    pub target: Target,
+    /// The Handlebars template.
+    pub template: String,
+    /// The `encoding` the template gets.
+    pub encoding: Encoding,
+    /// The partials the template includes, with the built-in ones.
+    pub partials: BTreeMap<String, String>,
+}
+
+impl OutputBackend for Templated {
+    fn name(&self) -> &'static str {
+        self.target.name()
+    }
+
+    fn render(&self, filters: &Filters) -> Result<Vec<Output>, backend::Error> {
+        if matches!(self.target, Target::NodeTest | Target::Lookup) {
+            return self.target.render(filters);
+        }
+        let code = checker(
+            self.target,
+            filters,
+            Some(&self.template),
+            &self.partials,
+            self.encoding,
+            false,
+        )?;
+        Ok(vec![Output {
+            path: filters.output.to_path_buf(),
+            contents: code.into_bytes(),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn targets_render_as_backends() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["::/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let options = Options {
+            checker_path: PathBuf::from("dist/checker.ts"),
+            ..Options::default()
+        };
+        let code = render(&v4, &v6, Target::TypeScript, &options).unwrap();
+        let suite = render(&v4, &v6, Target::NodeTest, &options).unwrap();
+        assert!(suite.contains("from './checker';"), "{}", suite);
+
+        let (nodes_v4, nodes_v6) = (flat::encode(&v4), flat::encode(&v6));
+        let filters = Filters {
+            output: Path::new("dist/checker.ts"),
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &nodes_v4,
+            nodes_v6: &nodes_v6,
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let outputs = Target::TypeScript.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("dist/checker.ts"));
+        assert_eq!(outputs[0].contents, code.into_bytes());
+        let outputs = Target::NodeTest.render(&filters).unwrap();
+        assert_eq!(outputs[0].path, Path::new("dist/checker.test.ts"));
+        assert_eq!(outputs[0].contents, suite.into_bytes());
+
+        let outputs = Target::Cjs.render(&filters).unwrap();
+        assert_eq!(outputs[1].path, Path::new("dist/checker.d.ts"));
+        assert_eq!(outputs[1].contents, DECLARATIONS.as_bytes());
+
+        let code = render(&IpRange::new(), &v6, Target::TypeScript, &options).unwrap();
+        assert!(
+            code.contains("const IP_FILTER_V4: Uint32Array = new Uint32Array([]);"),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn base64_encoding() {
+        let v4: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let options = Options {
+            encoding: Encoding::Base64,
+            ..Options::default()
+        };
+        let code = render(&v4, &IpRange::new(), Target::TypeScript, &options).unwrap();
+        assert!(
+            code.contains(
+                "\nconst IP_FILTER_V4: Uint32Array = decodeNodes(\"AAAAAAAAAAA=\");\n\
+                 const IP_FILTER_V6: Uint32Array = decodeNodes(\"\");\n"
+            ),
+            "{}",
+            code
+        );
+        assert!(!code.contains("new Uint32Array(["), "{}", code);
+        let esm = render(&v4, &IpRange::new(), Target::Esm, &options).unwrap();
+        assert!(
+            esm.contains("\nconst IP_FILTER_V4 = decodeNodes(\"AAAAAAAAAAA=\");\n"),
+            "{}",
+            esm
+        );
+
+        // The other checkers keep their literals.
+        let rust = render(&v4, &IpRange::new(), Target::Rust, &options).unwrap();
+        assert_eq!(
+            rust,
+            render(&v4, &IpRange::new(), Target::Rust, &Options::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn minified_checkers() {
+        let v4: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6 = IpRange::new();
+        let (nodes_v4, nodes_v6) = (flat::encode(&v4), flat::encode(&v6));
+        let filters = Filters {
+            output: Path::new("edge.mjs"),
+            v4: &v4,
+            v6: &v6,
+            nodes_v4: &nodes_v4,
+            nodes_v6: &nodes_v6,
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        let minified = |target, encoding| Encoded {
+            target,
+            encoding,
+            minify: true,
+            partials: BTreeMap::new(),
+        };
+        let outputs = minified(Target::Esm, Encoding::Literal)
+            .render(&filters)
+            .unwrap();
+        let esm = String::from_utf8(outputs[0].contents.clone()).unwrap();
+        assert!(
+            esm.starts_with("const V=new Uint32Array([0,0]),W=new Uint32Array([]);"),
+            "{}",
+            esm
+        );
+        // The supported version is kept apart from the encoded one, as in
+        // the unminified checker.
+        assert!(
+            esm.contains("const F=1,S=1;if(F!==S)throw Error("),
+            "{}",
+            esm
+        );
+        assert!(esm.contains("export default function(s){"), "{}", esm);
+        assert_eq!(esm.lines().count(), 1);
+        assert_eq!(outputs[1].path, Path::new("edge.d.mts"));
+        assert_eq!(outputs[1].contents, MINIFIED_DECLARATIONS.as_bytes());
+
+        let outputs = minified(Target::TypeScript, Encoding::Base64)
+            .render(&filters)
+            .unwrap();
+        let ts = String::from_utf8(outputs[0].contents.clone()).unwrap();
+        assert!(
+            ts.contains("const V=d(\"AAAAAAAAAAA=\"),W=d(\"\");"),
+            "{}",
+            ts
+        );
+        assert!(ts.contains("const F:number=1,S:number=1;"), "{}", ts);
+        assert!(
+            ts.contains("export default function(s:string):boolean{"),
+            "{}",
+            ts
+        );
+        assert_eq!(outputs.len(), 1);
+
+        // The other checkers are not minified.
+        let python = minified(Target::Python, Encoding::Literal)
+            .render(&filters)
+            .unwrap();
+        assert_eq!(python, Target::Python.render(&filters).unwrap());
+    }
+
+    #[test]
+    fn value_lookup() {
+        let options = Options {
+            values: Values {
+                names: vec!["eu".to_string(), "de \"office\"".to_string()],
+                v4: vec![
+                    ("128.0.0.0/1".parse().unwrap(), 0),
+                    ("192.0.0.0/2".parse().unwrap(), 1),
+                ],
+                v6: Vec::new(),
+            },
+            ..Options::default()
+        };
+        let code = render(&IpRange::new(), &IpRange::new(), Target::Lookup, &options).unwrap();
+        assert!(
+            code.contains("\nexport function lookup(ip: string): string | null {\n"),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains(
+                "\nexport const VALUES: readonly string[] = [\"eu\",\"de \\\"office\\\"\"];\n"
+            ),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains(
+                "\nconst LOOKUP_V4: Uint32Array = new Uint32Array([0,1,0,2,0,0]);\n\
+                 const VALUES_V4: Uint32Array = new Uint32Array([0,1,2]);\n\
+                 const LOOKUP_V6: Uint32Array = new Uint32Array([]);\n"
+            ),
+            "{}",
+            code
+        );
+        assert_eq!(Target::Lookup.name(), "lookup");
+    }
+
+    #[test]
+    fn checker_targets() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6 = IpRange::new();
+        let esm = render(&v4, &v6, Target::Esm, &Options::default()).unwrap();
+        assert!(
+            esm.contains("\nexport function ipCheck(ip, options"),
+            "{}",
+            esm
+        );
+        assert!(
+            esm.contains("\nconst IP_FILTER_V6 = new Uint32Array([]);"),
+            "{}",
+            esm
+        );
+        assert!(!esm.contains("module.exports"));
+        let cjs = render(&v4, &v6, Target::Cjs, &Options::default()).unwrap();
+        assert!(cjs.starts_with("'use strict';\n"), "{}", cjs);
+        assert!(
+            cjs.ends_with("\nmodule.exports = { ipCheck, FORMAT_VERSION };\n"),
+            "{}",
+            cjs
+        );
+        assert!(!cjs.contains("export "));
+
+        let python = render(&v4, &v6, Target::Python, &Options::default()).unwrap();
+        assert!(
+            python.contains("\ndef ip_in_list(addr: str) -> bool:\n"),
+            "{}",
+            python
+        );
+        assert!(
+            python.ends_with("\nIP_FILTER_V6 = array(\"L\", [])\n"),
+            "{}",
+            python
+        );
+
+        let options = Options {
+            checker_path: PathBuf::from("internal/ip-filter/contains.go"),
+            ..Options::default()
+        };
+        let go = render(&v4, &v6, Target::Go, &options).unwrap();
+        assert!(go.contains("\npackage ipfilter\n"), "{}", go);
+        assert!(go.contains("\nvar ipFilterV4 = []uint32{1, 0, "), "{}", go);
+        assert!(go.ends_with("\nvar ipFilterV6 = []uint32{}\n"), "{}", go);
+        assert_eq!(go_package(Path::new("contains.go")), "ipcheck");
+
+        let rust = render(&v4, &v6, Target::Rust, &Options::default()).unwrap();
+        assert!(
+            rust.contains("\npub fn contains(addr: IpAddr) -> bool {\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("\nstatic IP_FILTER_V4: &[u32] = &[1, 0, "),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.ends_with("\nstatic IP_FILTER_V6: &[u32] = &[];\n"),
+            "{}",
+            rust
+        );
+
+        let c = render(&v4, &v6, Target::C, &Options::default()).unwrap();
+        assert!(
+            c.contains("\nstatic const uint32_t IP_FILTER_V4[] = {1, 0, "),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains(concat!(
+                "\n#define IP_FILTER_V6_LEN 0\n",
+                "static const uint32_t IP_FILTER_V6[] = {0};\n"
+            )),
+            "{}",
+            c
+        );
+
+        let options = Options {
+            checker_path: PathBuf::from("app/src/main/java/com/example/net/IpFilter.java"),
+            ..Options::default()
+        };
+        let java = render(&v4, &v6, Target::Java, &options).unwrap();
+        assert!(java.contains("\npackage com.example.net;\n"), "{}", java);
+        assert!(
+            java.contains("\npublic final class IpFilter {\n"),
+            "{}",
+            java
+        );
+        assert!(
+            java.contains("\n    private static final int[] IP_FILTER_V4 = nodes(\"1,0,"),
+            "{}",
+            java
+        );
+        assert!(
+            java.contains("\n    private static final int[] IP_FILTER_V6 = nodes();\n"),
+            "{}",
+            java
+        );
+        let java = render(&v4, &v6, Target::Java, &Options::default()).unwrap();
+        assert!(java.starts_with("// Code generated by ipcheck. DO NOT EDIT.\n\nimport "));
+        assert!(
+            java.contains("\npublic final class ipcheck {\n"),
+            "{}",
+            java
+        );
+
+        let lua = render(&v4, &v6, Target::Lua, &Options::default()).unwrap();
+        assert!(lua.contains("\nfunction _M.contains(addr)\n"), "{}", lua);
+        assert!(lua.contains("\nlocal IP_FILTER_V4 = {1, 0, "), "{}", lua);
+        assert!(lua.contains("\nlocal IP_FILTER_V6 = {}\n"), "{}", lua);
+        assert!(lua.ends_with("\nreturn _M\n"), "{}", lua);
+
+        let nodes: Vec<String> = (0..JAVA_CHUNK + 1).map(|n| n.to_string()).collect();
+        let chunks = java_chunks(&nodes);
+        assert!(chunks.ends_with(&format!(",{}\", \"{}\"", JAVA_CHUNK - 1, JAVA_CHUNK)));
+        assert_eq!(go_package(Path::new("gen/2fa/contains.go")), "fa");
+
+        for (output, declarations) in [
+            ("ipcheck.js", "ipcheck.d.ts"),
+            ("dist/ipcheck.mjs", "dist/ipcheck.d.mts"),
+            ("ipcheck.cjs", "ipcheck.d.cts"),
+        ] {
+            assert_eq!(
+                declarations_path(Path::new(output)),
+                Path::new(declarations)
+            );
+        }
+    }
+
+    #[test]
+    fn custom_template() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let template = "// SPDX-License-Identifier: MIT\n\
+                        export const v4 = new Uint32Array({{ filterV4 }}); // {{ lenV4 }}\n\
+                        export const v6 = new Uint32Array({{ filterV6 }});\n";
+        let options = Options {
+            template: Some(template.to_string()),
+            ..Options::default()
+        };
+        let code = render(&v4, &IpRange::new(), Target::TypeScript, &options).unwrap();
+        let nodes = flat::encode(&v4);
+        assert_eq!(
+            code,
+            format!(
+                "// SPDX-License-Identifier: MIT\n\
+                 export const v4 = new Uint32Array([{}]); // {}\n\
+                 export const v6 = new Uint32Array([]);\n",
+                nodes
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                nodes.len()
+            )
+        );
+
+        // The literals are those of the target's language.
+        let templated = Templated {
+            target: Target::Go,
+            template: "var filterV6 = []uint32{{ filterV6 }}\n".to_string(),
+            encoding: Encoding::Literal,
+            partials: BTreeMap::new(),
+        };
+        let filters = Filters {
+            output: Path::new("ipfilter/contains.go"),
+            v4: &v4,
+            v6: &IpRange::new(),
+            nodes_v4: &nodes,
+            nodes_v6: &[],
+            notes: &[],
+            metadata: &Default::default(),
+            values: &Default::default(),
+        };
+        assert_eq!(templated.name(), "go");
+        let outputs = templated.render(&filters).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].contents, b"var filterV6 = []uint32{}\n");
+
+        let stamped = Options {
+            template: Some(
+                "// {{ vars.team }} {{ build.version }} {{ build.timestamp }} \
+                 {{ build.countV4 }}/{{ build.countV6 }}\n\
+                 {{#each build.inputs}}// {{ path }} {{ sha256 }}\n{{/each}}"
+                    .to_string(),
+            ),
+            metadata: Metadata {
+                timestamp: "2024-05-01T12:00Z".to_string(),
+                inputs: vec![backend::Input {
+                    path: "v4.csv".to_string(),
+                    sha256: "e3b0c442".to_string(),
+                }],
+                vars: [("team".to_string(), "netsec".to_string())].into(),
+            },
+            ..Options::default()
+        };
+        let v6: IpRange<Ipv6Net> = ["::/0"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(
+            render(&v4, &v6, Target::Rust, &stamped).unwrap(),
+            format!(
+                "// netsec {} 2024-05-01T12:00Z 1/1\n// v4.csv e3b0c442\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        // The helpers lay out the raw node arrays; `::/0` is `[0, 0]`.
+        let chunked = Options {
+            template: Some(
+                "{{#each (chunk nodesV6 1)}}[{{join this}}]{{/each}} {{base64 nodesV6}}"
+                    .to_string(),
+            ),
+            ..Options::default()
+        };
+        assert_eq!(
+            render(&v4, &v6, Target::TypeScript, &chunked).unwrap(),
+            "[0][0] AAAAAAAAAAA="
+        );
+
+        let broken = Options {
+            template: Some("{{#if notes}}".to_string()),
+            ..Options::default()
+        };
+        assert!(matches!(
+            render(&v4, &IpRange::new(), Target::TypeScript, &broken),
+            Err(IpCheckError::Render(_))
+        ));
+    }
+
+    #[test]
+    fn partial_overrides() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let builtin = render(
+            &v4,
+            &IpRange::new(),
+            Target::TypeScript,
+            &Options::default(),
+        )
+        .unwrap();
+        let options = Options {
+            partials: [
+                ("header".to_string(), "// {{> license}}\n".to_string()),
+                (
+                    "license".to_string(),
+                    "SPDX-License-Identifier: MIT".to_string(),
+                ),
+                (
+                    "exports".to_string(),
+                    "export function isListed(ip: string): boolean {\n    \
+                     return matchBytes(ipToBytes(ip), IP_FILTER_V4);\n}\n"
+                        .to_string(),
+                ),
+            ]
+            .into(),
+            ..Options::default()
+        };
+        let code = render(&v4, &IpRange::new(), Target::TypeScript, &options).unwrap();
+        assert!(
+            code.starts_with("// SPDX-License-Identifier: MIT\n"),
+            "{}",
+            code
+        );
+        assert!(code.contains("export function isListed("), "{}", code);
+        assert!(!code.contains("export function ipCheck("), "{}", code);
+        // The other partials are the built-in ones.
+        let arrays = builtin.find("/** Node-array layout").unwrap();
+        assert!(code.ends_with(&builtin[arrays..]), "{}", code);
+
+        // Templates of one's own include them too.
+        let wrapper = Options {
+            template: Some("// {{> license}}\n{{> parse}}".to_string()),
+            ..options
+        };
+        let code = render(&v4, &IpRange::new(), Target::Esm, &wrapper).unwrap();
+        assert!(
+            code.starts_with("// SPDX-License-Identifier: MIT\n"),
+            "{}",
+            code
+        );
+        assert!(code.contains("function ipToBytes("), "{}", code);
+    }
+}
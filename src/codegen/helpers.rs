@@ -0,0 +1,206 @@
+//! Handlebars helpers laying out node arrays in templates, for arrays too
+//! large for one literal of the toolchains compiling them:
+//!
+//! - `chunk`, the array split into arrays of at most a number of values:
+//!   `{{#each (chunk nodesV4 4096)}}...{{/each}}`;
+//! - `join`, the values of an array between a separator, `,` by default:
+//!   `{{join this ", "}}`;
+//! - `hex` and `base64`, an array of node indices as the little-endian
+//!   32-bit words of a `Uint32Array`, in hex or in base64:
+//!   `{{base64 nodesV4}}`.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonValue, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+
+/// Registers the helpers with `tt`.
+pub(super) fn register(tt: &mut Handlebars) {
+    tt.register_helper("chunk", Box::new(Chunk));
+    tt.register_helper("join", Box::new(Join));
+    tt.register_helper("hex", Box::new(Encode("hex", hex)));
+    tt.register_helper("base64", Box::new(Encode("base64", base64)));
+}
+
+struct Chunk;
+
+impl HelperDef for Chunk {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let values = array(h, "chunk")?;
+        let size = h
+            .param(1)
+            .and_then(|size| size.value().as_u64())
+            .filter(|&size| size > 0)
+            .ok_or_else(|| {
+                RenderErrorReason::ParamTypeMismatchForName(
+                    "chunk",
+                    "size".to_string(),
+                    "a positive integer".to_string(),
+                )
+            })?;
+        let chunks = values
+            .chunks(size as usize)
+            .map(|chunk| JsonValue::Array(chunk.to_vec()))
+            .collect();
+        Ok(ScopedJson::Derived(JsonValue::Array(chunks)))
+    }
+}
+
+struct Join;
+
+impl HelperDef for Join {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let values = array(h, "join")?;
+        let separator = match h.param(1) {
+            Some(separator) => separator.value().as_str().ok_or_else(|| {
+                RenderErrorReason::ParamTypeMismatchForName(
+                    "join",
+                    "separator".to_string(),
+                    "a string".to_string(),
+                )
+            })?,
+            None => ",",
+        };
+        let values: Vec<String> = values
+            .iter()
+            .map(|value| match value {
+                JsonValue::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect();
+        Ok(ScopedJson::Derived(JsonValue::String(
+            values.join(separator),
+        )))
+    }
+}
+
+/// A helper encoding the words of a node array, named by its first field.
+struct Encode(&'static str, fn(&[u8]) -> String);
+
+impl HelperDef for Encode {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let mismatch = || {
+            RenderErrorReason::ParamTypeMismatchForName(
+                self.0,
+                "nodes".to_string(),
+                "an array of 32-bit integers".to_string(),
+            )
+        };
+        let mut bytes = Vec::new();
+        for value in array(h, self.0)? {
+            let word = value.as_u64().and_then(|word| u32::try_from(word).ok());
+            bytes.extend_from_slice(&word.ok_or_else(mismatch)?.to_le_bytes());
+        }
+        Ok(ScopedJson::Derived(JsonValue::String((self.1)(&bytes))))
+    }
+}
+
+/// The array of the first parameter of the helper `name`.
+fn array<'h>(h: &'h Helper, name: &'static str) -> Result<&'h Vec<JsonValue>, RenderError> {
+    match h.param(0).map(|param| param.value()) {
+        Some(JsonValue::Array(values)) => Ok(values),
+        Some(_) => Err(RenderErrorReason::ParamTypeMismatchForName(
+            name,
+            "array".to_string(),
+            "an array".to_string(),
+        )
+        .into()),
+        None => Err(RenderErrorReason::ParamNotFoundForName(name, "array".to_string()).into()),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `bytes` in the standard base64 alphabet, padded.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Values {
+        nodes: Vec<usize>,
+    }
+
+    fn render(template: &str, nodes: Vec<usize>) -> Result<String, RenderError> {
+        let mut tt = Handlebars::new();
+        tt.register_escape_fn(handlebars::no_escape);
+        register(&mut tt);
+        tt.render_template(template, &Values { nodes })
+    }
+
+    #[test]
+    fn chunk_and_join() {
+        let nodes = (1..=5).collect::<Vec<_>>();
+        assert_eq!(
+            render(
+                "{{#each (chunk nodes 2)}}[{{join this}}]{{/each}}",
+                nodes.clone()
+            )
+            .unwrap(),
+            "[1,2][3,4][5]"
+        );
+        assert_eq!(
+            render("{{join nodes \", \"}}", nodes.clone()).unwrap(),
+            "1, 2, 3, 4, 5"
+        );
+        assert_eq!(render("{{join nodes}}", Vec::new()).unwrap(), "");
+        assert!(render("{{#each (chunk nodes 0)}}{{/each}}", nodes.clone()).is_err());
+        assert!(render("{{join 7}}", nodes).is_err());
+    }
+
+    #[test]
+    fn hex_and_base64() {
+        let nodes = vec![1, 0x0403_0201];
+        assert_eq!(
+            render("{{hex nodes}}", nodes.clone()).unwrap(),
+            "0100000001020304"
+        );
+        assert_eq!(render("{{base64 nodes}}", nodes).unwrap(), "AQAAAAECAwQ=");
+        assert!(render("{{base64 nodes}}", vec![1 << 32]).is_err());
+
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+}
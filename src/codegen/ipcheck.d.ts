@@ -0,0 +1,15 @@
+export interface IpCheckOptions {
+    includeCidr?: boolean;
+}
+
+export interface IpCheckResult {
+    matches: boolean;
+    cidr: string;
+}
+
+export declare function ipCheck(ip: string, options?: { includeCidr: true }): IpCheckResult;
+export declare function ipCheck(ip: string, options?: { includeCidr: false }): boolean;
+export declare function ipCheck(ip: string, options?: IpCheckOptions): boolean | IpCheckResult;
+
+/** Node-array layout version the filters were encoded with. */
+export declare const FORMAT_VERSION: number;
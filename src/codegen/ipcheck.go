@@ -0,0 +1,63 @@
+// Code generated by ipcheck. DO NOT EDIT.
+
+// Package {{ package }} tells whether an address is covered by the networks
+// it was generated from.
+package {{ package }}
+
+import "net/netip"
+
+// FormatVersion is the node-array layout version the filters were encoded
+// with.
+const FormatVersion = {{ formatVersion }}
+
+// Contains reports whether addr, an IPv4 or IPv6 address, is covered by the
+// filters. IPv4-mapped IPv6 addresses are looked up as IPv6; pass
+// addr.Unmap() to look them up as IPv4.
+func Contains(addr netip.Addr) bool {
+	var nodes []uint32
+	var bytes []byte
+	switch {
+	case addr.Is4():
+		b := addr.As4()
+		nodes, bytes = ipFilterV4, b[:]
+	case addr.Is6():
+		b := addr.As16()
+		nodes, bytes = ipFilterV6, b[:]
+	default:
+		return false
+	}
+	if len(nodes) == 0 {
+		return false
+	}
+
+	var index uint32
+	for _, b := range bytes {
+		for shift := 7; shift >= 0; shift-- {
+			if isLeaf(nodes, index) {
+				return true
+			}
+			index = nodes[index*2+uint32(b>>shift&1)]
+			if index == 0 {
+				return false
+			}
+		}
+	}
+	return isLeaf(nodes, index)
+}
+
+func isLeaf(nodes []uint32, index uint32) bool {
+	return nodes[index*2] == 0 && nodes[index*2+1] == 0
+}
+{{#if notes}}
+
+// Networks covered by the filters below:
+{{#each notes}}
+//   {{{ network }}}: {{{ note }}}
+{{/each}}
+{{/if}}
+
+// Node arrays: two child indices per node, 0 meaning "no child", and a node
+// with no children a matching network. A root-only array ({0, 0}) therefore
+// matches every address of its family, and an empty array none.
+var ipFilterV4 = []uint32{{ filterV4 }}
+var ipFilterV6 = []uint32{{ filterV6 }}
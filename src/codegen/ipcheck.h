@@ -0,0 +1,79 @@
+#ifndef IPCHECK_FILTER_H
+#define IPCHECK_FILTER_H
+
+/* Generated by ipcheck; do not edit. Needs C99 or C++. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* Node-array layout version the filters were encoded with. */
+#define IPCHECK_FILTER_FORMAT_VERSION {{ formatVersion }}
+{{#if notes}}
+
+/*
+ * Networks covered by the filters below:
+{{#each notes}}
+ *   {{{ network }}}: {{{ note }}}
+{{/each}}
+ */
+{{/if}}
+
+/*
+ * Node arrays: two child indices per node, 0 meaning "no child", and a node
+ * with no children a matching network. A root-only array ({0, 0}) therefore
+ * matches every address of its family, and an empty one, of length 0, none.
+ */
+#define IP_FILTER_V4_LEN {{ lenV4 }}
+static const uint32_t IP_FILTER_V4[] = {{ filterV4 }};
+#define IP_FILTER_V6_LEN {{ lenV6 }}
+static const uint32_t IP_FILTER_V6[] = {{ filterV6 }};
+
+static inline bool ipcheck_filter_is_leaf(const uint32_t *nodes, size_t index)
+{
+    return nodes[index * 2] == 0 && nodes[index * 2 + 1] == 0;
+}
+
+static inline bool ipcheck_filter_lookup(const uint32_t *nodes, size_t len,
+                                         const uint8_t *addr, size_t addr_len)
+{
+    size_t index = 0;
+    size_t i;
+    int shift;
+
+    if (len == 0) {
+        return false;
+    }
+    for (i = 0; i < addr_len; i++) {
+        for (shift = 7; shift >= 0; shift--) {
+            if (ipcheck_filter_is_leaf(nodes, index)) {
+                return true;
+            }
+            index = nodes[index * 2 + ((addr[i] >> shift) & 1)];
+            if (index == 0) {
+                return false;
+            }
+        }
+    }
+    return ipcheck_filter_is_leaf(nodes, index);
+}
+
+/*
+ * Returns whether the IPv4 address whose 4 bytes, in network order, are at
+ * `addr` is covered by the filters.
+ */
+static inline bool ipcheck_filter_contains_v4(const uint8_t *addr)
+{
+    return ipcheck_filter_lookup(IP_FILTER_V4, IP_FILTER_V4_LEN, addr, 4);
+}
+
+/*
+ * Returns whether the IPv6 address whose 16 bytes, in network order, are at
+ * `addr` is covered by the filters.
+ */
+static inline bool ipcheck_filter_contains_v6(const uint8_t *addr)
+{
+    return ipcheck_filter_lookup(IP_FILTER_V6, IP_FILTER_V6_LEN, addr, 16);
+}
+
+#endif  /* IPCHECK_FILTER_H */
@@ -0,0 +1,78 @@
+// Code generated by ipcheck. DO NOT EDIT.
+{{#if package}}
+
+package {{ package }};
+{{/if}}
+
+import java.net.InetAddress;
+
+/**
+ * IP filter: {@link #contains(InetAddress)} tells whether an address is
+ * covered by the networks it was generated from.
+ */
+public final class {{ class }} {
+    /** Node-array layout version the filters were encoded with. */
+    public static final int FORMAT_VERSION = {{ formatVersion }};
+{{#if notes}}
+
+    // Networks covered by the filters below:
+{{#each notes}}
+    //   {{{ network }}}: {{{ note }}}
+{{/each}}
+{{/if}}
+
+    // Node arrays: two child indices per node, 0 meaning "no child", and a
+    // node with no children a matching network. A root-only array (0,0)
+    // therefore matches every address of its family, and an empty array
+    // none. They are decoded from string chunks when the class is loaded, as
+    // array initializers run into the size limit of a method.
+    private static final int[] IP_FILTER_V4 = nodes({{{ filterV4 }}});
+    private static final int[] IP_FILTER_V6 = nodes({{{ filterV6 }}});
+
+    private {{ class }}() {}
+
+    /**
+     * Whether {@code addr}, an IPv4 or IPv6 address, is covered by the
+     * filters.
+     */
+    public static boolean contains(InetAddress addr) {
+        byte[] bytes = addr.getAddress();
+        int[] nodes = bytes.length == 4 ? IP_FILTER_V4 : IP_FILTER_V6;
+        if (nodes.length == 0) {
+            return false;
+        }
+
+        int index = 0;
+        for (byte b : bytes) {
+            for (int shift = 7; shift >= 0; shift--) {
+                if (isLeaf(nodes, index)) {
+                    return true;
+                }
+                index = nodes[index * 2 + ((b >> shift) & 1)];
+                if (index == 0) {
+                    return false;
+                }
+            }
+        }
+        return isLeaf(nodes, index);
+    }
+
+    private static boolean isLeaf(int[] nodes, int index) {
+        return nodes[index * 2] == 0 && nodes[index * 2 + 1] == 0;
+    }
+
+    private static int[] nodes(String... chunks) {
+        int count = 0;
+        for (String chunk : chunks) {
+            count += chunk.split(",").length;
+        }
+        int[] nodes = new int[count];
+        int i = 0;
+        for (String chunk : chunks) {
+            for (String node : chunk.split(",")) {
+                nodes[i++] = Integer.parseInt(node);
+            }
+        }
+        return nodes;
+    }
+}
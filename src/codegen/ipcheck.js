@@ -1,10 +1,13 @@
+{{#unless esm}}'use strict';
+
+{{/unless}}
 /**
  * Converts an IPv4 address string to a byte array
- * @param ip IPv4 address string (e.g., "192.168.1.1")
- * @returns Uint8Array of 4 bytes
+ * @param {string} ip IPv4 address string (e.g., "192.168.1.1")
+ * @returns {Uint8Array} 4 bytes
  * @throws Error if invalid IPv4 format
  */
-function ipv4ToBytes(ip: string): Uint8Array {
+function ipv4ToBytes(ip) {
     const parts = ip.split('.');
     if (parts.length !== 4) {
         throw new Error('Invalid IPv4 address format');
@@ -23,11 +26,15 @@ function ipv4ToBytes(ip: string): Uint8Array {
 
 /**
  * Converts an IPv6 address string to a byte array
- * @param ip IPv6 address string (e.g., "2001:0db8:85a3:0000:0000:8a2e:0370:7334")
- * @returns Uint8Array of 16 bytes
+ *
+ * The address is kept as 16 separate bytes and the lookup walks it bit by
+ * bit, so no 128-bit value is ever formed and nothing is lost to the 53-bit
+ * precision of a JavaScript number.
+ * @param {string} ip IPv6 address string (e.g., "2001:0db8:85a3:0000:0000:8a2e:0370:7334")
+ * @returns {Uint8Array} 16 bytes
  * @throws Error if invalid IPv6 format
  */
-function ipv6ToBytes(ip: string): Uint8Array {
+function ipv6ToBytes(ip) {
     // Remove IPv6 zone index if present
     const zoneIndex = ip.indexOf('%');
     if (zoneIndex !== -1) {
@@ -37,8 +44,10 @@ function ipv6ToBytes(ip: string): Uint8Array {
     // Expand :: notation
     const doubleColonIndex = ip.indexOf('::');
     if (doubleColonIndex !== -1) {
-        const before = ip.substring(0, doubleColonIndex).split(':');
-        const after = ip.substring(doubleColonIndex + 2).split(':');
+        const head = ip.substring(0, doubleColonIndex);
+        const tail = ip.substring(doubleColonIndex + 2);
+        const before = head ? head.split(':') : [];
+        const after = tail ? tail.split(':') : [];
         const missing = 8 - (before.length + after.length);
         const middle = Array(missing).fill('0');
         ip = [...before, ...middle, ...after].join(':');
@@ -63,28 +72,19 @@ function ipv6ToBytes(ip: string): Uint8Array {
 
 /**
  * Converts an IP address string to a byte array, automatically detecting IPv4 or IPv6
- * @param ip IP address string
- * @returns Uint8Array of either 4 bytes (IPv4) or 16 bytes (IPv6)
+ * @param {string} ip IP address string
+ * @returns {Uint8Array} either 4 bytes (IPv4) or 16 bytes (IPv6)
  * @throws Error if invalid IP format
  */
-function ipToBytes(ip: string): Uint8Array {
+function ipToBytes(ip) {
     return ip.includes(':') ? ipv6ToBytes(ip) : ipv4ToBytes(ip);
 }
 
-function isLeaf(index: number, filter: Uint32Array): boolean {
+function isLeaf(index, filter) {
     return filter[index * 2] === 0 && filter[index * 2 + 1] === 0;
 }
 
-interface IpCheckOptions {
-    includeCidr?: boolean;
-}
-
-interface IpCheckResult {
-    matches: boolean;
-    cidr: string;
-}
-
-function buildCidr(path: number[], isIpv6: boolean): string {
+function buildCidr(path, isIpv6) {
     // Convert path of bits to IP and prefix length
     const bytes = new Uint8Array(isIpv6 ? 16 : 4);
     const prefixLength = path.length;
@@ -108,12 +108,25 @@ function buildCidr(path: number[], isIpv6: boolean): string {
     return `${parts}/${prefixLength}`;
 }
 
-export function ipCheck(ip: string, options?: { includeCidr: true }): IpCheckResult;
-export function ipCheck(ip: string, options?: { includeCidr: false }): boolean;
-export function ipCheck(ip: string, options: IpCheckOptions = { includeCidr: false }): boolean | IpCheckResult {
+/**
+ * Whether `ip` is covered by the filters, or with `includeCidr`, also the
+ * covering network.
+ * @param {string} ip IP address string
+ * @param {Object} [options]
+ * @param {boolean} [options.includeCidr] Whether to return the covering network too
+ * @returns {boolean | { matches: boolean, cidr: string | null }}
+ */
+{{#if esm}}export {{/if}}function ipCheck(ip, options = { includeCidr: false }) {
     const bytes = ipToBytes(ip);
     const IP_FILTER = bytes.length === 4 ? IP_FILTER_V4 : IP_FILTER_V6;
-    const path: number[] | undefined = options.includeCidr ? [] : undefined;
+    const path = options.includeCidr ? [] : undefined;
+
+    if (IP_FILTER.length === 0) {
+        return options.includeCidr ? {
+            matches: false,
+            cidr: null
+        } : false;
+    }
 
     let nodeIndex = 0;
     for (let byteIndex = 0; byteIndex < bytes.length; byteIndex++) {
@@ -123,7 +136,7 @@ export function ipCheck(ip: string, options: IpCheckOptions = { includeCidr: fal
             if (isLeaf(nodeIndex, IP_FILTER)) {
                 return options.includeCidr ? {
                     matches: true,
-                    cidr: buildCidr(path as number[], bytes.length === 16)
+                    cidr: buildCidr(path, bytes.length === 16)
                 } : true;
             }
 
@@ -142,9 +155,56 @@ export function ipCheck(ip: string, options: IpCheckOptions = { includeCidr: fal
     const matches = isLeaf(nodeIndex, IP_FILTER);
     return options.includeCidr ? {
         matches,
-        cidr: matches ? buildCidr(path as number[], bytes.length === 16) : null
+        cidr: matches ? buildCidr(path, bytes.length === 16) : null
     } : matches;
 }
 
-const IP_FILTER_V4: Uint32Array = new Uint32Array({{ filterV4 }});
-const IP_FILTER_V6: Uint32Array = new Uint32Array({{ filterV6 }});
+/** Node-array layout version the lookup code above understands. */
+const SUPPORTED_FORMAT_VERSION = 1;
+
+/** Node-array layout version the filters below were encoded with. */
+{{#if esm}}export {{/if}}const FORMAT_VERSION = {{ formatVersion }};
+
+if (FORMAT_VERSION !== SUPPORTED_FORMAT_VERSION) {
+    throw new Error(`ipcheck: filter format version ${FORMAT_VERSION} is not supported (expected ${SUPPORTED_FORMAT_VERSION})`);
+}
+
+{{#if notes}}
+// Networks covered by the filters below:
+{{#each notes}}
+//   {{{ network }}}: {{{ note }}}
+{{/each}}
+
+{{/if}}
+// Node arrays: two child indices per node, 0 meaning "no child", and a node
+// with no children a matching network. A root-only array ([0,0]) therefore
+// matches every address of its family, and an empty array none.
+{{#if (eq encoding "base64")}}
+/**
+ * Decodes a node array from the base64 of its little-endian 32-bit words,
+ * whatever the byte order of the platform.
+ * @param {string} base64 The encoded words
+ * @returns {Uint32Array} The node array
+ */
+function decodeNodes(base64) {
+    const bytes = atob(base64);
+    const nodes = new Uint32Array(bytes.length / 4);
+    for (let i = 0; i < nodes.length; i++) {
+        nodes[i] = (bytes.charCodeAt(i * 4)
+            | bytes.charCodeAt(i * 4 + 1) << 8
+            | bytes.charCodeAt(i * 4 + 2) << 16
+            | bytes.charCodeAt(i * 4 + 3) << 24) >>> 0;
+    }
+    return nodes;
+}
+
+const IP_FILTER_V4 = decodeNodes("{{ base64 nodesV4 }}");
+const IP_FILTER_V6 = decodeNodes("{{ base64 nodesV6 }}");
+{{else}}
+const IP_FILTER_V4 = new Uint32Array({{ filterV4 }});
+const IP_FILTER_V6 = new Uint32Array({{ filterV6 }});
+{{/if}}
+{{#unless esm}}
+
+module.exports = { ipCheck, FORMAT_VERSION };
+{{/unless}}
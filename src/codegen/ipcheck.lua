@@ -0,0 +1,75 @@
+-- Code generated by ipcheck. DO NOT EDIT.
+--
+-- IP filter: `contains` tells whether an address is covered by the networks
+-- it was generated from. It takes the address in binary form, as
+-- `ngx.var.binary_remote_addr` has it in OpenResty:
+--
+--     local ipfilter = require("ipfilter")
+--     if ipfilter.contains(ngx.var.binary_remote_addr) then
+--         return ngx.exit(ngx.HTTP_FORBIDDEN)
+--     end
+--
+-- The lookup uses LuaJIT's `bit` module, as OpenResty bundles it.
+
+local bit = require("bit")
+
+local band, rshift = bit.band, bit.rshift
+local byte = string.byte
+
+local _M = {
+    -- Node-array layout version the filters were encoded with.
+    FORMAT_VERSION = {{ formatVersion }},
+}
+{{#if notes}}
+
+-- Networks covered by the filters below:
+{{#each notes}}
+--   {{{ network }}}: {{{ note }}}
+{{/each}}
+{{/if}}
+
+-- Node arrays: two child indices per node, 0 meaning "no child", and a node
+-- with no children a matching network. A root-only array ({0, 0}) therefore
+-- matches every address of its family, and an empty array none. Indices
+-- count from 0, so node `i` is at `nodes[i * 2 + 1]` and `nodes[i * 2 + 2]`.
+local IP_FILTER_V4 = {{ filterV4 }}
+local IP_FILTER_V6 = {{ filterV6 }}
+
+local function is_leaf(nodes, index)
+    return nodes[index * 2 + 1] == 0 and nodes[index * 2 + 2] == 0
+end
+
+local function lookup(nodes, addr)
+    if #nodes == 0 then
+        return false
+    end
+
+    local index = 0
+    for i = 1, #addr do
+        local b = byte(addr, i)
+        for shift = 7, 0, -1 do
+            if is_leaf(nodes, index) then
+                return true
+            end
+            index = nodes[index * 2 + band(rshift(b, shift), 1) + 1]
+            if index == 0 then
+                return false
+            end
+        end
+    end
+    return is_leaf(nodes, index)
+end
+
+-- Whether `addr`, the 4 bytes of an IPv4 address or the 16 of an IPv6 one,
+-- is covered by the filters. Any other string is not.
+function _M.contains(addr)
+    local len = #addr
+    if len == 4 then
+        return lookup(IP_FILTER_V4, addr)
+    elseif len == 16 then
+        return lookup(IP_FILTER_V6, addr)
+    end
+    return false
+end
+
+return _M
@@ -0,0 +1,3 @@
+/** Whether `ip` is covered by the filters. */
+declare function ipCheck(ip: string): boolean;
+export default ipCheck;
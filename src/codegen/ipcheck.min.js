@@ -0,0 +1 @@
+{{#if (eq encoding "base64")}}function d(s{{#if typescript}}:string{{/if}}){{#if typescript}}:Uint32Array{{/if}}{const b=atob(s),a=new Uint32Array(b.length/4);for(let i=0;i<a.length;i++)a[i]=(b.charCodeAt(i*4)|b.charCodeAt(i*4+1)<<8|b.charCodeAt(i*4+2)<<16|b.charCodeAt(i*4+3)<<24)>>>0;return a}const V=d("{{ base64 nodesV4 }}"),W=d("{{ base64 nodesV6 }}");{{else}}const V=new Uint32Array({{ filterV4 }}),W=new Uint32Array({{ filterV6 }});{{/if}}const F{{#if typescript}}:number{{/if}}={{ formatVersion }},S{{#if typescript}}:number{{/if}}=1;if(F!==S)throw Error(`ipcheck: filter format version ${F} is not supported (expected ${S})`);function o(p{{#if typescript}}:string[]{{/if}},r{{#if typescript}}:number{{/if}},m{{#if typescript}}:number{{/if}},l{{#if typescript}}:number{{/if}}){{#if typescript}}:number[]{{/if}}{if(p.length!==l)throw Error("Invalid IP address");return p.map(x=>{const n=parseInt(x,r);if(isNaN(n)||n<0||n>m)throw Error(`Invalid IP address part: ${x}`);return n})}export default function(s{{#if typescript}}:string{{/if}}){{#if typescript}}:boolean{{/if}}{let f{{#if typescript}}:Uint32Array{{/if}},p{{#if typescript}}:number[]{{/if}},w{{#if typescript}}:number{{/if}};if(s.includes(":")){const z=s.indexOf("%");if(z>=0)s=s.slice(0,z);const c=s.indexOf("::");if(c>=0){const h=s.slice(0,c),t=s.slice(c+2),a=h?h.split(":"):[],b=t?t.split(":"):[];s=[...a,...Array(8-a.length-b.length).fill("0"),...b].join(":")}f=W;p=o(s.split(":"),16,65535,8);w=16}else{f=V;p=o(s.split("."),10,255,4);w=8}if(!f.length)return false;let n=0;for(const x of p)for(let i=w-1;i>=0;i--){if(!f[n*2]&&!f[n*2+1])return true;n=f[n*2+(x>>i&1)];if(!n)return false}return!f[n*2]&&!f[n*2+1]}
@@ -0,0 +1,63 @@
+"""IP filter generated by ipcheck: `ip_in_list` tells whether an address is
+covered by the networks it was built from.
+
+The module needs nothing beyond the standard library.
+"""
+
+import ipaddress
+from array import array
+
+__all__ = ["ip_in_list", "FORMAT_VERSION"]
+
+
+def _is_leaf(nodes, index):
+    return nodes[index * 2] == 0 and nodes[index * 2 + 1] == 0
+
+
+def ip_in_list(addr: str) -> bool:
+    """Whether `addr`, an IPv4 or IPv6 address such as "192.168.1.1" or
+    "2001:db8::1", is covered by the filters.
+
+    Raises ValueError if `addr` is not an address.
+    """
+    # Remove IPv6 zone index if present
+    packed = ipaddress.ip_address(addr.split("%", 1)[0]).packed
+    nodes = IP_FILTER_V4 if len(packed) == 4 else IP_FILTER_V6
+    if not nodes:
+        return False
+
+    index = 0
+    for byte in packed:
+        for shift in range(7, -1, -1):
+            if _is_leaf(nodes, index):
+                return True
+            index = nodes[index * 2 + ((byte >> shift) & 1)]
+            if index == 0:
+                return False
+    return _is_leaf(nodes, index)
+
+
+# Node-array layout version the lookup code above understands.
+_SUPPORTED_FORMAT_VERSION = 1
+
+# Node-array layout version the filters below were encoded with.
+FORMAT_VERSION = {{ formatVersion }}
+
+if FORMAT_VERSION != _SUPPORTED_FORMAT_VERSION:
+    raise ImportError(
+        f"ipcheck: filter format version {FORMAT_VERSION} is not supported "
+        f"(expected {_SUPPORTED_FORMAT_VERSION})"
+    )
+
+{{#if notes}}
+# Networks covered by the filters below:
+{{#each notes}}
+#   {{{ network }}}: {{{ note }}}
+{{/each}}
+
+{{/if}}
+# Node arrays: two child indices per node, 0 meaning "no child", and a node
+# with no children a matching network. A root-only array ([0,0]) therefore
+# matches every address of its family, and an empty array none.
+IP_FILTER_V4 = array("L", {{ filterV4 }})
+IP_FILTER_V6 = array("L", {{ filterV6 }})
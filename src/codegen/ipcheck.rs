@@ -0,0 +1,60 @@
+// Code generated by ipcheck. DO NOT EDIT.
+//
+// IP filter: `contains` tells whether an address is covered by the networks
+// it was generated from. It needs nothing beyond `core`, so it builds in
+// `no_std` crates too.
+
+use core::net::IpAddr;
+
+/// Node-array layout version the filters were encoded with.
+#[allow(dead_code)]
+pub const FORMAT_VERSION: u32 = {{ formatVersion }};
+
+/// Whether `addr` is covered by the filters. IPv4-mapped IPv6 addresses are
+/// looked up as IPv6; pass `addr.to_canonical()` to look them up as IPv4.
+#[must_use]
+pub fn contains(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => lookup(IP_FILTER_V4, &addr.octets()),
+        IpAddr::V6(addr) => lookup(IP_FILTER_V6, &addr.octets()),
+    }
+}
+
+fn lookup(nodes: &[u32], bytes: &[u8]) -> bool {
+    if nodes.is_empty() {
+        return false;
+    }
+
+    let mut index = 0;
+    for byte in bytes {
+        for shift in (0..8).rev() {
+            if is_leaf(nodes, index) {
+                return true;
+            }
+            index = nodes[index * 2 + usize::from((byte >> shift) & 1)] as usize;
+            if index == 0 {
+                return false;
+            }
+        }
+    }
+    is_leaf(nodes, index)
+}
+
+fn is_leaf(nodes: &[u32], index: usize) -> bool {
+    nodes[index * 2] == 0 && nodes[index * 2 + 1] == 0
+}
+{{#if notes}}
+
+// Networks covered by the filters below:
+{{#each notes}}
+//   {{{ network }}}: {{{ note }}}
+{{/each}}
+{{/if}}
+
+// Node arrays: two child indices per node, 0 meaning "no child", and a node
+// with no children a matching network. A root-only array ([0, 0]) therefore
+// matches every address of its family, and an empty array none.
+#[rustfmt::skip]
+static IP_FILTER_V4: &[u32] = &{{ filterV4 }};
+#[rustfmt::skip]
+static IP_FILTER_V6: &[u32] = &{{ filterV6 }};
@@ -0,0 +1,13 @@
+import { test } from 'node:test';
+import assert from 'node:assert/strict';
+
+import { ipCheck } from '{{{ importPath }}}';
+
+{{#each blocks}}
+test('{{{ network }}}', () => {
+{{#each cases}}
+    assert.equal(ipCheck('{{{ ip }}}'), {{ expected }});
+{{/each}}
+});
+
+{{/each}}
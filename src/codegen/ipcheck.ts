@@ -0,0 +1,8 @@
+{{> header}}
+{{> parse}}
+
+{{> lookup}}
+
+{{> exports}}
+
+{{> arrays}}
@@ -0,0 +1,56 @@
+//! Generating a TypeScript module whose lookup returns the value of the
+//! network covering an address, rather than whether there is one.
+
+use handlebars::{Handlebars, JsonValue, RenderError};
+use serde::Serialize;
+
+use super::PARTIALS;
+use crate::backend::Values;
+use crate::flat::{valued_nodes, FORMAT_VERSION};
+use crate::IpCheckError;
+
+#[derive(Serialize)]
+struct LookupTemplate {
+    #[serde(rename = "formatVersion")]
+    format_version: u32,
+    /// The values, as a JSON array of strings, which is a TypeScript one.
+    names: String,
+    #[serde(rename = "lookupV4")]
+    lookup_v4: String,
+    #[serde(rename = "valuesV4")]
+    values_v4: String,
+    #[serde(rename = "lookupV6")]
+    lookup_v6: String,
+    #[serde(rename = "valuesV6")]
+    values_v6: String,
+}
+
+/// Renders the module exporting `lookup`, which returns the value of the
+/// most specific network of `values` covering an address, or `null`.
+pub fn render_lookup(values: &Values) -> Result<String, IpCheckError> {
+    let (lookup_v4, values_v4) = valued_nodes(&values.v4);
+    let (lookup_v6, values_v6) = valued_nodes(&values.v6);
+    let array = |nodes: &[usize]| {
+        let nodes: Vec<String> = nodes.iter().map(|n| n.to_string()).collect();
+        format!("[{}]", nodes.join(","))
+    };
+    let mut tt = Handlebars::new();
+    tt.register_escape_fn(handlebars::no_escape);
+    // The addresses are parsed as by the checker.
+    for (name, partial) in PARTIALS {
+        tt.register_partial(name, partial)
+            .map_err(RenderError::from)?;
+    }
+    let code = tt.render_template(
+        include_str!("lookup.ts"),
+        &LookupTemplate {
+            format_version: FORMAT_VERSION,
+            names: JsonValue::from(values.names.clone()).to_string(),
+            lookup_v4: array(&lookup_v4),
+            values_v4: array(&values_v4),
+            lookup_v6: array(&lookup_v6),
+            values_v6: array(&values_v6),
+        },
+    )?;
+    Ok(code)
+}
@@ -0,0 +1,53 @@
+{{> parse}}
+
+/**
+ * Returns the value of the most specific network covering `ip`, or `null`
+ * if none does.
+ * @param ip IPv4 or IPv6 address string
+ * @throws Error if invalid IP format
+ */
+export function lookup(ip: string): string | null {
+    const bytes = ipToBytes(ip);
+    const [filter, values] = bytes.length === 4 ?
+        [LOOKUP_V4, VALUES_V4] :
+        [LOOKUP_V6, VALUES_V6];
+    if (filter.length === 0) {
+        return null;
+    }
+
+    let nodeIndex = 0;
+    let found = values[0];
+    for (let byteIndex = 0; byteIndex < bytes.length; byteIndex++) {
+        for (let bitIndex = 7; bitIndex >= 0; bitIndex--) {
+            const bit = (bytes[byteIndex] >> bitIndex) & 1;
+            nodeIndex = filter[nodeIndex * 2 + bit];
+            if (nodeIndex === 0) {
+                return found === 0 ? null : VALUES[found - 1];
+            }
+            found = values[nodeIndex] || found;
+        }
+    }
+    return found === 0 ? null : VALUES[found - 1];
+}
+
+/** Node-array layout version the lookup code above understands. */
+const SUPPORTED_FORMAT_VERSION = 1;
+
+/** Node-array layout version the tries below were encoded with. */
+export const FORMAT_VERSION = {{ formatVersion }};
+
+if (FORMAT_VERSION !== SUPPORTED_FORMAT_VERSION) {
+    throw new Error(`ipcheck: filter format version ${FORMAT_VERSION} is not supported (expected ${SUPPORTED_FORMAT_VERSION})`);
+}
+
+/** The values the lookup returns. */
+export const VALUES: readonly string[] = {{ names }};
+
+// Tries of the networks, in the layout of the node arrays of `ipCheck`: two
+// child indices per node, 0 meaning "no child". The networks are the nodes
+// with a value, one plus the index of their value in VALUES, and 0 for the
+// nodes without one.
+const LOOKUP_V4: Uint32Array = new Uint32Array({{ lookupV4 }});
+const VALUES_V4: Uint32Array = new Uint32Array({{ valuesV4 }});
+const LOOKUP_V6: Uint32Array = new Uint32Array({{ lookupV6 }});
+const VALUES_V6: Uint32Array = new Uint32Array({{ valuesV6 }});
@@ -0,0 +1,492 @@
+//! Writing the networks as a MaxMind DB, the format of GeoIP2 databases,
+//! for which readers exist in every language.
+//!
+//! The database is an IPv6 one, as MaxMind's own databases are: the IPv4
+//! networks lie under `::/96`, where readers look IPv4 addresses up, and
+//! IPv6 networks within `::/96` are left out, as readers could not tell
+//! them from IPv4 ones. Each network's record is a map holding `listed`,
+//! always `true`, and the network's note, if it has one, as `note`:
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! use ipcheck_rs::codegen::mmdb;
+//! use ipcheck_rs::IpRange;
+//! use ipnet::{Ipv4Net, Ipv6Net};
+//!
+//! let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+//! let v6: IpRange<Ipv6Net> = IpRange::new();
+//!
+//! let bytes = mmdb::database(&v4, &v6, &[], 0).unwrap();
+//! assert_eq!(mmdb::networks(&bytes), Some((v4, v6)));
+//! ```
+
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::backend::Note;
+use crate::{IpCheckError, IpRange};
+
+/// What separates the data section from the metadata.
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+
+/// The zeros between the search tree and the data section.
+const DATA_SEPARATOR: usize = 16;
+
+/// The `database_type` of the metadata.
+const DATABASE_TYPE: &str = "ipcheck";
+
+const POINTER: u8 = 1;
+const STRING: u8 = 2;
+const UINT16: u8 = 5;
+const UINT32: u8 = 6;
+const MAP: u8 = 7;
+const UINT64: u8 = 9;
+const ARRAY: u8 = 11;
+const BOOLEAN: u8 = 14;
+
+/// A record of the search tree.
+#[derive(Clone, Copy)]
+enum Record {
+    /// No network covers the addresses below.
+    Empty,
+    /// The node at this index.
+    Node(usize),
+    /// The data at this offset of the data section.
+    Data(usize),
+}
+
+/// The MaxMind DB of the networks of `v4` and `v6`, with the notes in
+/// `notes` on their records and `build_epoch`, in seconds since the Unix
+/// epoch, in its metadata.
+///
+/// Fails with [`IpCheckError::IndexOverflow`] if the search tree and the
+/// data need more than 32-bit records.
+///
+/// [`IpCheckError::IndexOverflow`]: ../../enum.IpCheckError.html#variant.IndexOverflow
+pub fn database(
+    v4: &IpRange<Ipv4Net>,
+    v6: &IpRange<Ipv6Net>,
+    notes: &[Note],
+    build_epoch: u64,
+) -> Result<Vec<u8>, IpCheckError> {
+    let notes: HashMap<&str, &str> = notes
+        .iter()
+        .map(|note| (note.network.as_str(), note.note.as_str()))
+        .collect();
+    let mut data = Vec::new();
+    let mut records: HashMap<Option<&str>, usize> = HashMap::new();
+    let mut record = |network: String| {
+        let note = notes.get(network.as_str()).copied();
+        *records.entry(note).or_insert_with(|| {
+            let offset = data.len();
+            header(&mut data, MAP, 1 + usize::from(note.is_some()));
+            string(&mut data, "listed");
+            header(&mut data, BOOLEAN, 1);
+            if let Some(note) = note {
+                string(&mut data, "note");
+                string(&mut data, note);
+            }
+            offset
+        })
+    };
+
+    let mut tree = vec![[Record::Empty; 2]];
+    let mut v6 = v6.clone();
+    v6.remove(ipv4_subtree());
+    for network in &v6 {
+        let bits = u128::from(network.network());
+        let offset = record(network.to_string());
+        insert(&mut tree, bits, network.prefix_len(), offset);
+    }
+    for network in v4 {
+        let bits = u128::from(u32::from(network.network()));
+        let offset = record(network.to_string());
+        insert(&mut tree, bits, 96 + network.prefix_len(), offset);
+    }
+
+    let node_count = tree.len();
+    let max = node_count + DATA_SEPARATOR + data.len();
+    let record_size = match max {
+        _ if max < 1 << 24 => 24,
+        _ if max < 1 << 28 => 28,
+        _ if max < 1 << 32 => 32,
+        _ => return Err(IpCheckError::IndexOverflow(max)),
+    };
+    let value = |record: Record| match record {
+        Record::Empty => node_count as u32,
+        Record::Node(index) => index as u32,
+        Record::Data(offset) => (node_count + DATA_SEPARATOR + offset) as u32,
+    };
+    let mut bytes = Vec::with_capacity(node_count * record_size / 4 + max);
+    for [left, right] in tree {
+        let (left, right) = (value(left), value(right));
+        match record_size {
+            24 => {
+                bytes.extend(&left.to_be_bytes()[1..]);
+                bytes.extend(&right.to_be_bytes()[1..]);
+            }
+            28 => {
+                bytes.extend(&left.to_be_bytes()[1..]);
+                bytes.push((left >> 24 << 4) as u8 | (right >> 24) as u8);
+                bytes.extend(&right.to_be_bytes()[1..]);
+            }
+            _ => {
+                bytes.extend(left.to_be_bytes());
+                bytes.extend(right.to_be_bytes());
+            }
+        }
+    }
+    bytes.extend([0; DATA_SEPARATOR]);
+    bytes.extend(data);
+
+    bytes.extend(METADATA_MARKER);
+    header(&mut bytes, MAP, 9);
+    string(&mut bytes, "binary_format_major_version");
+    uint(&mut bytes, UINT16, 2);
+    string(&mut bytes, "binary_format_minor_version");
+    uint(&mut bytes, UINT16, 0);
+    string(&mut bytes, "build_epoch");
+    uint(&mut bytes, UINT64, build_epoch);
+    string(&mut bytes, "database_type");
+    string(&mut bytes, DATABASE_TYPE);
+    string(&mut bytes, "description");
+    header(&mut bytes, MAP, 1);
+    string(&mut bytes, "en");
+    string(&mut bytes, "Networks generated by ipcheck");
+    string(&mut bytes, "ip_version");
+    uint(&mut bytes, UINT16, 6);
+    string(&mut bytes, "languages");
+    header(&mut bytes, ARRAY, 1);
+    string(&mut bytes, "en");
+    string(&mut bytes, "node_count");
+    uint(&mut bytes, UINT32, node_count as u64);
+    string(&mut bytes, "record_size");
+    uint(&mut bytes, UINT16, record_size as u64);
+    Ok(bytes)
+}
+
+/// `::/96`, where the IPv4 networks lie.
+fn ipv4_subtree() -> Ipv6Net {
+    Ipv6Net::new(Ipv6Addr::UNSPECIFIED, 96).unwrap()
+}
+
+/// Points the record of the network of the top `len` bits of the address
+/// `bits` at the data at `offset`, adding the nodes above it.
+fn insert(tree: &mut Vec<[Record; 2]>, bits: u128, len: u8, offset: usize) {
+    let mut node = 0;
+    for depth in 0..len {
+        let bit = (bits >> (127 - depth) & 1) as usize;
+        if depth + 1 == len {
+            tree[node][bit] = Record::Data(offset);
+            return;
+        }
+        node = match tree[node][bit] {
+            Record::Node(child) => child,
+            _ => {
+                tree.push([Record::Empty; 2]);
+                tree[node][bit] = Record::Node(tree.len() - 1);
+                tree.len() - 1
+            }
+        };
+    }
+}
+
+/// Writes the control byte of a value of `kind` and `size`.
+fn header(out: &mut Vec<u8>, kind: u8, size: usize) {
+    let (extended, kind) = match kind {
+        0..=7 => (None, kind),
+        _ => (Some(kind - 7), 0),
+    };
+    let size_bits = match size {
+        0..=28 => size as u8,
+        29..=284 => 29,
+        285..=65820 => 30,
+        _ => 31,
+    };
+    out.push(kind << 5 | size_bits);
+    out.extend(extended);
+    match size_bits {
+        29 => out.push((size - 29) as u8),
+        30 => out.extend(&((size - 285) as u16).to_be_bytes()),
+        31 => out.extend(&((size - 65821) as u32).to_be_bytes()[1..]),
+        _ => {}
+    }
+}
+
+fn string(out: &mut Vec<u8>, value: &str) {
+    header(out, STRING, value.len());
+    out.extend(value.as_bytes());
+}
+
+/// Writes `value` as an unsigned integer of `kind`, in as few bytes as it
+/// takes.
+fn uint(out: &mut Vec<u8>, kind: u8, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+    header(out, kind, 8 - skip);
+    out.extend(&bytes[skip..]);
+}
+
+/// The parts of the metadata [`networks`] and [`build_epoch`] read.
+struct Metadata {
+    node_count: usize,
+    record_size: usize,
+    ip_version: u64,
+    build_epoch: u64,
+    /// Where the search tree ends, and the metadata starts.
+    end: usize,
+}
+
+/// A value of the data section, as far as the metadata needs them.
+enum Value {
+    Uint(u64),
+    Other,
+}
+
+/// The metadata of the MaxMind DB `bytes`, `None` if it has none.
+fn metadata(bytes: &[u8]) -> Option<Metadata> {
+    let end = bytes
+        .windows(METADATA_MARKER.len())
+        .rposition(|window| window == METADATA_MARKER)?;
+    let mut input = &bytes[end + METADATA_MARKER.len()..];
+    let (kind, size) = read_header(&mut input)?;
+    if kind != MAP {
+        return None;
+    }
+    let mut fields = HashMap::new();
+    for _ in 0..size {
+        let (STRING, len) = read_header(&mut input)? else {
+            return None;
+        };
+        let key = input.get(..len)?;
+        input = &input[len..];
+        if let Value::Uint(value) = read_value(&mut input)? {
+            fields.insert(key, value);
+        }
+    }
+    let field = |key: &str| fields.get(key.as_bytes()).copied();
+    Some(Metadata {
+        node_count: usize::try_from(field("node_count")?).ok()?,
+        record_size: usize::try_from(field("record_size")?).ok()?,
+        ip_version: field("ip_version")?,
+        build_epoch: field("build_epoch").unwrap_or(0),
+        end,
+    })
+}
+
+fn read_header(input: &mut &[u8]) -> Option<(u8, usize)> {
+    let (&control, rest) = input.split_first()?;
+    *input = rest;
+    let mut kind = control >> 5;
+    if kind == 0 {
+        let (&extended, rest) = input.split_first()?;
+        *input = rest;
+        kind = extended.checked_add(7)?;
+    }
+    if kind == POINTER {
+        return None;
+    }
+    let mut extra = |len: usize| {
+        let bytes = input.get(..len)?;
+        *input = &input[len..];
+        Some(
+            bytes
+                .iter()
+                .fold(0, |size, &byte| size << 8 | byte as usize),
+        )
+    };
+    let size = match control & 0x1f {
+        29 => 29 + extra(1)?,
+        30 => 285 + extra(2)?,
+        31 => 65821 + extra(3)?,
+        size => size as usize,
+    };
+    Some((kind, size))
+}
+
+fn read_value(input: &mut &[u8]) -> Option<Value> {
+    let (kind, size) = read_header(input)?;
+    match kind {
+        MAP | ARRAY => {
+            let values = if kind == MAP { size * 2 } else { size };
+            for _ in 0..values {
+                read_value(input)?;
+            }
+            Some(Value::Other)
+        }
+        BOOLEAN => Some(Value::Other),
+        _ => {
+            let bytes = input.get(..size)?;
+            *input = &input[size..];
+            match kind {
+                UINT16 | UINT32 | UINT64 if size <= 8 => Some(Value::Uint(
+                    bytes
+                        .iter()
+                        .fold(0, |value, &byte| value << 8 | byte as u64),
+                )),
+                _ => Some(Value::Other),
+            }
+        }
+    }
+}
+
+/// The networks of the MaxMind DB `bytes`, those whose addresses have
+/// records, `None` if it is not a MaxMind DB.
+///
+/// The networks with IPv4 addresses are those under `::/96` in an IPv6
+/// database. Other aliases of them, such as `::ffff:0:0/96`, are read as
+/// IPv6 networks.
+pub fn networks(bytes: &[u8]) -> Option<(IpRange<Ipv4Net>, IpRange<Ipv6Net>)> {
+    let metadata = metadata(bytes)?;
+    let node_len = metadata.record_size / 4;
+    if !matches!(metadata.record_size, 24 | 28 | 32)
+        || metadata.node_count.checked_mul(node_len)? > metadata.end
+    {
+        return None;
+    }
+    let record = |node: usize, bit: usize| {
+        let bytes = &bytes[node * node_len..(node + 1) * node_len];
+        let be = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as usize)
+        };
+        match (metadata.record_size, bit) {
+            (24, 0) => be(&bytes[..3]),
+            (24, _) => be(&bytes[3..]),
+            (28, 0) => (bytes[3] as usize >> 4) << 24 | be(&bytes[..3]),
+            (28, _) => (bytes[3] as usize & 0x0f) << 24 | be(&bytes[4..]),
+            (_, 0) => be(&bytes[..4]),
+            (_, _) => be(&bytes[4..]),
+        }
+    };
+
+    let bits = if metadata.ip_version == 4 { 32 } else { 128 };
+    let mut v4 = IpRange::new();
+    let mut v6 = IpRange::new();
+    // The nodes to walk, with the bits of the path to them, top-aligned.
+    let mut stack = vec![(0, 0u128, 0u8)];
+    while let Some((node, path, depth)) = stack.pop() {
+        if depth == bits || node >= metadata.node_count {
+            return None;
+        }
+        for bit in 0..2 {
+            let path = path | (bit as u128) << (127 - depth);
+            let value = record(node, bit);
+            if value < metadata.node_count {
+                stack.push((value, path, depth + 1));
+            } else if value > metadata.node_count {
+                if bits == 32 {
+                    v4.add(Ipv4Net::new(((path >> 96) as u32).into(), depth + 1).ok()?);
+                } else if depth + 1 >= 96 && path >> 32 == 0 {
+                    v4.add(Ipv4Net::new((path as u32).into(), depth + 1 - 96).ok()?);
+                } else {
+                    v6.add(Ipv6Net::new(path.into(), depth + 1).ok()?);
+                }
+            }
+        }
+    }
+    v4.simplify();
+    v6.simplify();
+    Some((v4, v6))
+}
+
+/// The `build_epoch` of the MaxMind DB `bytes`, `None` if it is not one.
+pub fn build_epoch(bytes: &[u8]) -> Option<u64> {
+    metadata(bytes).map(|metadata| metadata.build_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges() -> (IpRange<Ipv4Net>, IpRange<Ipv6Net>) {
+        let v4 = ["10.0.0.0/8", "192.168.1.0/24", "8.8.8.8/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6 = ["2001:db8::/32", "::/8"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        (v4, v6)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (v4, v6) = ranges();
+        let notes = [Note {
+            network: "10.0.0.0/8".to_string(),
+            note: "private".to_string(),
+        }];
+        let bytes = database(&v4, &v6, &notes, 1_700_000_000).unwrap();
+        let mut expected = v6.clone();
+        expected.remove(ipv4_subtree());
+        assert_eq!(networks(&bytes), Some((v4, expected)));
+        assert_eq!(build_epoch(&bytes), Some(1_700_000_000));
+        // The note is the only one, so it is written once.
+        assert_eq!(bytes.windows(7).filter(|w| w == b"private").count(), 1);
+
+        let empty = database(&IpRange::new(), &IpRange::new(), &[], 0).unwrap();
+        assert_eq!(networks(&empty), Some((IpRange::new(), IpRange::new())));
+        assert_eq!(networks(b"IPCK"), None);
+    }
+
+    #[test]
+    fn roundtrip_whole_families() {
+        let v4: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["::/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let bytes = database(&v4, &v6, &[], 0).unwrap();
+        let (read_v4, read_v6) = networks(&bytes).unwrap();
+        assert_eq!(read_v4, v4);
+        assert!(read_v6.contains(&"2001:db8::1".parse::<Ipv6Addr>().unwrap()));
+        assert!(!read_v6.contains(&"::1".parse::<Ipv6Addr>().unwrap()));
+    }
+
+    #[test]
+    fn data_headers() {
+        let encoded = |kind: u8, size: usize| {
+            let mut out = Vec::new();
+            header(&mut out, kind, size);
+            out
+        };
+        assert_eq!(encoded(STRING, 3), [0x43]);
+        assert_eq!(encoded(STRING, 29), [0x5d, 0]);
+        assert_eq!(encoded(STRING, 300), [0x5e, 0, 15]);
+        assert_eq!(encoded(BOOLEAN, 1), [0x01, 0x07]);
+        assert_eq!(encoded(UINT64, 8), [0x08, 0x02]);
+        let mut out = Vec::new();
+        uint(&mut out, UINT16, 0);
+        assert_eq!(out, [0xa0]);
+    }
+
+    #[cfg(feature = "geoip")]
+    #[test]
+    fn maxminddb_reads_it() {
+        #[derive(serde::Deserialize)]
+        struct Listed {
+            listed: bool,
+            note: Option<String>,
+        }
+
+        let (v4, v6) = ranges();
+        let notes = [Note {
+            network: "192.168.1.0/24".to_string(),
+            note: "office".to_string(),
+        }];
+        let bytes = database(&v4, &v6, &notes, 0).unwrap();
+        let reader = maxminddb::Reader::from_source(bytes).unwrap();
+        assert_eq!(reader.metadata.database_type, DATABASE_TYPE);
+        let lookup = |ip: &str| reader.lookup::<Listed>(ip.parse().unwrap()).ok();
+        let office = lookup("192.168.1.7").unwrap();
+        assert!(office.listed);
+        assert_eq!(office.note.as_deref(), Some("office"));
+        assert!(lookup("10.1.2.3").unwrap().note.is_none());
+        assert!(lookup("2001:db8::1").is_some());
+        assert!(lookup("192.168.2.1").is_none());
+        assert!(lookup("2001:db9::1").is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//! Generating a companion test file for the rendered checker.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+use handlebars::Handlebars;
+use ipnet::{Ipv4Net, Ipv6Net};
+use serde::Serialize;
+
+use crate::{IpCheckError, IpRange};
+
+/// Upper bound on the number of blocks sampled per family, so that feeds
+/// with millions of networks still produce a test file of reasonable size.
+const MAX_BLOCKS: usize = 1000;
+
+/// The first integer a JavaScript `number` cannot tell from its successor.
+const PRECISION_LIMIT: u128 = 1 << 53;
+
+#[derive(Serialize)]
+struct TestTemplate {
+    #[serde(rename = "importPath")]
+    import_path: String,
+    blocks: Vec<Block>,
+}
+
+#[derive(Serialize)]
+struct Block {
+    network: String,
+    cases: Vec<Case>,
+}
+
+#[derive(Serialize)]
+struct Case {
+    ip: String,
+    expected: bool,
+}
+
+/// Returns where the test for the checker written to `output` goes:
+/// `ipcheck.ts` becomes `ipcheck.test.ts`. The test of a JavaScript checker
+/// is an ES module whatever the checker is, so `ipcheck.cjs` gets
+/// `ipcheck.test.mjs`.
+pub fn test_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(_) if is_javascript(output) => format!("{}.test.mjs", stem),
+        Some(ext) => format!("{}.test.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.test", stem),
+    };
+    output.with_file_name(name)
+}
+
+/// Whether `output` is named as a JavaScript module, which is imported by
+/// its full name.
+fn is_javascript(output: &Path) -> bool {
+    output
+        .extension()
+        .is_some_and(|ext| ext == "js" || ext == "mjs" || ext == "cjs")
+}
+
+/// Renders a test asserting that the checker written to `output` matches
+/// the first, middle and last address of each block of `v4` and `v6`, and
+/// rejects the addresses just outside it.
+pub fn render_tests(
+    output: &Path,
+    v4: &IpRange<Ipv4Net>,
+    v6: &IpRange<Ipv6Net>,
+) -> Result<String, IpCheckError> {
+    let name = match is_javascript(output) {
+        true => output.file_name(),
+        false => output.file_stem(),
+    };
+    let import_path = format!("./{}", name.unwrap_or_default().to_string_lossy());
+
+    let mut blocks = Vec::new();
+    let v4_networks: Vec<Ipv4Net> = v4.iter().collect();
+    for network in sample(&v4_networks) {
+        let (first, last) = (u32::from(network.network()), u32::from(network.broadcast()));
+        blocks.push(block(
+            network.to_string(),
+            vec![first, first + (last - first) / 2, last],
+            [first.checked_sub(1), last.checked_add(1)],
+            |addr| IpAddr::V4(Ipv4Addr::from(addr)),
+            |addr| v4.contains(&Ipv4Net::from(Ipv4Addr::from(addr))),
+        ));
+    }
+    let v6_networks: Vec<Ipv6Net> = v6.iter().collect();
+    for network in sample(&v6_networks) {
+        let (first, last) = (
+            u128::from(network.network()),
+            u128::from(network.broadcast()),
+        );
+        let mut inside = vec![first, first + (last - first) / 2, last];
+        // Addresses straddling 2^53 within the block catch lookups that
+        // round IPv6 addresses through a JavaScript `number`.
+        if last - first >= PRECISION_LIMIT {
+            inside.extend([first + PRECISION_LIMIT - 1, first + PRECISION_LIMIT]);
+        }
+        blocks.push(block(
+            network.to_string(),
+            inside,
+            [first.checked_sub(1), last.checked_add(1)],
+            |addr| IpAddr::V6(Ipv6Addr::from(addr)),
+            |addr| v6.contains(&Ipv6Net::from(Ipv6Addr::from(addr))),
+        ));
+    }
+
+    let tt = Handlebars::new();
+    let code = tt.render_template(
+        include_str!("ipcheck.test.ts"),
+        &TestTemplate {
+            import_path,
+            blocks,
+        },
+    )?;
+    Ok(code)
+}
+
+/// Picks at most `MAX_BLOCKS` networks spread evenly over `networks`.
+fn sample<N>(networks: &[N]) -> impl Iterator<Item = &N> {
+    let step = networks.len().div_ceil(MAX_BLOCKS).max(1);
+    networks.iter().step_by(step)
+}
+
+fn block<A: Copy + Ord>(
+    network: String,
+    mut inside: Vec<A>,
+    outside: [Option<A>; 2],
+    to_ip: impl Fn(A) -> IpAddr,
+    covered: impl Fn(A) -> bool,
+) -> Block {
+    inside.sort();
+    inside.dedup();
+    let mut cases: Vec<Case> = inside
+        .iter()
+        .map(|&addr| Case {
+            ip: to_ip(addr).to_string(),
+            expected: true,
+        })
+        .collect();
+    // A neighbouring address may belong to another block of the feed.
+    cases.extend(
+        outside
+            .iter()
+            .flatten()
+            .filter(|&&addr| !covered(addr))
+            .map(|&addr| Case {
+                ip: to_ip(addr).to_string(),
+                expected: false,
+            }),
+    );
+    Block { network, cases }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Networks {
+        v4: IpRange<Ipv4Net>,
+        v6: IpRange<Ipv6Net>,
+    }
+
+    fn networks(v4: &[&str], v6: &[&str]) -> Networks {
+        Networks {
+            v4: v4.iter().map(|s| s.parse().unwrap()).collect(),
+            v6: v6.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_path_inserts_test_suffix() {
+        assert_eq!(
+            test_path(Path::new("out/ipcheck.ts")),
+            Path::new("out/ipcheck.test.ts")
+        );
+        assert_eq!(test_path(Path::new("checker")), Path::new("checker.test"));
+        assert_eq!(
+            test_path(Path::new("out/ipcheck.cjs")),
+            Path::new("out/ipcheck.test.mjs")
+        );
+    }
+
+    #[test]
+    fn render_tests_samples_each_block() {
+        let networks = networks(
+            &["10.0.0.0/8", "11.0.0.0/24", "192.168.1.1/32"],
+            &["2001:db8::/32"],
+        );
+        let code = render_tests(Path::new("dist/ipcheck.ts"), &networks.v4, &networks.v6).unwrap();
+
+        assert!(code.contains("from './ipcheck';"), "{}", code);
+        let code = render_tests(Path::new("dist/ipcheck.cjs"), &networks.v4, &networks.v6).unwrap();
+        assert!(code.contains("from './ipcheck.cjs';"), "{}", code);
+        for inside in [
+            "10.0.0.0",
+            "10.127.255.255",
+            "10.255.255.255",
+            "192.168.1.1",
+        ] {
+            assert!(
+                code.contains(&format!("assert.equal(ipCheck('{}'), true);", inside)),
+                "{}",
+                code
+            );
+        }
+        for outside in [
+            "9.255.255.255",
+            "11.0.1.0",
+            "192.168.1.0",
+            "192.168.1.2",
+            "2001:db7:ffff:ffff:ffff:ffff:ffff:ffff",
+            "2001:db9::",
+        ] {
+            assert!(
+                code.contains(&format!("assert.equal(ipCheck('{}'), false);", outside)),
+                "{}",
+                code
+            );
+        }
+        // 10.255.255.255 + 1 is the start of the adjacent 11.0.0.0/24.
+        assert!(!code.contains("ipCheck('11.0.0.0'), false"), "{}", code);
+    }
+
+    #[test]
+    fn render_tests_probes_full_ipv6_precision() {
+        let networks = networks(
+            &[],
+            &[
+                "2001:db8::/64",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/128",
+            ],
+        );
+        let code = render_tests(Path::new("ipcheck.ts"), &networks.v4, &networks.v6).unwrap();
+        for (ip, expected) in [
+            ("2001:db8::1f:ffff:ffff:ffff", true),
+            ("2001:db8:0:0:20::", true),
+            ("2001:db8:0:1::", false),
+            ("ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe", true),
+            ("ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffd", false),
+            ("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff", false),
+        ] {
+            assert!(
+                code.contains(&format!("assert.equal(ipCheck('{}'), {});", ip, expected)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn render_tests_handles_whole_family_edges() {
+        let networks = networks(&["0.0.0.0/1", "255.255.255.255/32"], &[]);
+        let code = render_tests(Path::new("ipcheck.ts"), &networks.v4, &networks.v6).unwrap();
+        assert!(code.contains("ipCheck('0.0.0.0'), true"), "{}", code);
+        assert!(code.contains("ipCheck('128.0.0.0'), false"), "{}", code);
+        assert!(
+            code.contains("ipCheck('255.255.255.254'), false"),
+            "{}",
+            code
+        );
+    }
+}
@@ -0,0 +1,43 @@
+/** Node-array layout version the lookup code above understands. */
+const SUPPORTED_FORMAT_VERSION = 1;
+
+/** Node-array layout version the filters below were encoded with. */
+export const FORMAT_VERSION = {{ formatVersion }};
+
+if (FORMAT_VERSION !== SUPPORTED_FORMAT_VERSION) {
+    throw new Error(`ipcheck: filter format version ${FORMAT_VERSION} is not supported (expected ${SUPPORTED_FORMAT_VERSION})`);
+}
+
+{{#if notes}}
+// Networks covered by the filters below:
+{{#each notes}}
+//   {{{ network }}}: {{{ note }}}
+{{/each}}
+
+{{/if}}
+// Node arrays: two child indices per node, 0 meaning "no child", and a node
+// with no children a matching network. A root-only array ([0,0]) therefore
+// matches every address of its family, and an empty array none.
+{{#if (eq encoding "base64")}}
+/**
+ * Decodes a node array from the base64 of its little-endian 32-bit words,
+ * whatever the byte order of the platform.
+ */
+function decodeNodes(base64: string): Uint32Array {
+    const bytes = atob(base64);
+    const nodes = new Uint32Array(bytes.length / 4);
+    for (let i = 0; i < nodes.length; i++) {
+        nodes[i] = (bytes.charCodeAt(i * 4)
+            | bytes.charCodeAt(i * 4 + 1) << 8
+            | bytes.charCodeAt(i * 4 + 2) << 16
+            | bytes.charCodeAt(i * 4 + 3) << 24) >>> 0;
+    }
+    return nodes;
+}
+
+const IP_FILTER_V4: Uint32Array = decodeNodes("{{ base64 nodesV4 }}");
+const IP_FILTER_V6: Uint32Array = decodeNodes("{{ base64 nodesV6 }}");
+{{else}}
+const IP_FILTER_V4: Uint32Array = new Uint32Array({{ filterV4 }});
+const IP_FILTER_V6: Uint32Array = new Uint32Array({{ filterV6 }});
+{{/if}}
@@ -0,0 +1,21 @@
+interface IpCheckOptions {
+    includeCidr?: boolean;
+}
+
+interface IpCheckResult {
+    matches: boolean;
+    cidr: string;
+}
+
+export function ipCheck(ip: string, options?: { includeCidr: true }): IpCheckResult;
+export function ipCheck(ip: string, options?: { includeCidr: false }): boolean;
+export function ipCheck(ip: string, options: IpCheckOptions = { includeCidr: false }): boolean | IpCheckResult {
+    const bytes = ipToBytes(ip);
+    const IP_FILTER = bytes.length === 4 ? IP_FILTER_V4 : IP_FILTER_V6;
+    const path: number[] | undefined = options.includeCidr ? [] : undefined;
+    const matches = matchBytes(bytes, IP_FILTER, path);
+    return options.includeCidr ? {
+        matches,
+        cidr: matches ? buildCidr(path as number[], bytes.length === 16) : null
+    } : matches;
+}
@@ -0,0 +1 @@
+{{!-- Comments above the checker, such as a license header: none by default. --}}
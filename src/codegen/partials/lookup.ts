@@ -0,0 +1,57 @@
+function isLeaf(index: number, filter: Uint32Array): boolean {
+    return filter[index * 2] === 0 && filter[index * 2 + 1] === 0;
+}
+
+/**
+ * Whether the node array `filter` matches the address `bytes`, walking it
+ * bit by bit from the root, with the bits of the matching network pushed
+ * to `path` if given.
+ */
+function matchBytes(bytes: Uint8Array, filter: Uint32Array, path?: number[]): boolean {
+    if (filter.length === 0) {
+        return false;
+    }
+
+    let nodeIndex = 0;
+    for (let byteIndex = 0; byteIndex < bytes.length; byteIndex++) {
+        const byte = bytes[byteIndex];
+        for (let bitIndex = 7; bitIndex >= 0; bitIndex--) {
+            const bit = (byte >> bitIndex) & 1;
+            if (isLeaf(nodeIndex, filter)) {
+                return true;
+            }
+
+            path?.push(bit);
+            const nextIndex = filter[nodeIndex * 2 + bit];
+            if (nextIndex === 0) {
+                return false;
+            }
+            nodeIndex = nextIndex;
+        }
+    }
+    return isLeaf(nodeIndex, filter);
+}
+
+function buildCidr(path: number[], isIpv6: boolean): string {
+    // Convert path of bits to IP and prefix length
+    const bytes = new Uint8Array(isIpv6 ? 16 : 4);
+    const prefixLength = path.length;
+
+    // Fill in the known bits from the path
+    for (let i = 0; i < path.length; i++) {
+        const byteIndex = Math.floor(i / 8);
+        const bitPosition = 7 - (i % 8);
+        bytes[byteIndex] |= (path[i] << bitPosition);
+    }
+
+    // Convert bytes to IP string
+    const parts = isIpv6 ?
+        // IPv6: Convert each pair of bytes to hex
+        Array.from({ length: 8 }, (_, i) =>
+            ((bytes[i * 2] << 8) | bytes[i * 2 + 1]).toString(16)
+        ).join(':') :
+        // IPv4: Convert each byte to decimal
+        Array.from(bytes).join('.');
+
+    return `${parts}/${prefixLength}`;
+}
@@ -0,0 +1,331 @@
+//! Compiling the filter into a standalone WebAssembly module, for lists too
+//! large to ship as a JavaScript array literal.
+//!
+//! The module exports `check_v4(u32) -> i32` and `check_v6(hi: u64, lo:
+//! u64) -> i32`, 1 for an address the filters cover and 0 otherwise, and
+//! its memory, whose only data is the binary [`artifact`] of the networks:
+//! the lookups walk its node arrays where they lie. [`glue`] renders an ES
+//! module instantiating it and taking address strings.
+//!
+//! The artifact is the last section of the module, so that the module can
+//! be decoded back with [`artifact::load`] from the artifact's magic on.
+//!
+//! [`artifact`]: ../../artifact/index.html
+//! [`artifact::load`]: ../../artifact/fn.load.html
+
+use handlebars::Handlebars;
+use ipnet::{Ipv4Net, Ipv6Net};
+use serde::Serialize;
+
+use crate::artifact;
+use crate::flat::FORMAT_VERSION;
+use crate::{IpCheckError, IpRange};
+
+/// The bytes of the artifact header, before the node arrays.
+const ARTIFACT_HEADER: usize = 12;
+
+/// A WebAssembly page, the unit of memory sizes.
+const PAGE: usize = 65536;
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+
+/// The WebAssembly module of the networks of `v4` and `v6`.
+pub fn module(v4: &IpRange<Ipv4Net>, v6: &IpRange<Ipv6Net>) -> Result<Vec<u8>, IpCheckError> {
+    let data = artifact::write(v4, v6)?;
+    let count = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let len_v4 = count(ARTIFACT_HEADER);
+    let base_v4 = ARTIFACT_HEADER + 4;
+    let base_v6 = base_v4 + len_v4 as usize * 4 + 4;
+    let len_v6 = count(base_v6 - 4);
+    let pages = data.len().div_ceil(PAGE).max(1);
+    let overflow = || IpCheckError::IndexOverflow(data.len());
+    let base_v6 = i32::try_from(base_v6).map_err(|_| overflow())?;
+    let pages = u32::try_from(pages).map_err(|_| overflow())?;
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    section(
+        &mut module,
+        1,
+        vector(&[
+            func_type(&[I32, I32, I64, I64, I32]),
+            func_type(&[I32]),
+            func_type(&[I64, I64]),
+        ]),
+    );
+    section(&mut module, 3, vector(&[vec![0], vec![1], vec![2]]));
+    let mut memory = vec![0];
+    uleb(&mut memory, pages);
+    section(&mut module, 5, vector(&[memory]));
+    section(
+        &mut module,
+        7,
+        vector(&[
+            export("check_v4", 0, 1),
+            export("check_v6", 0, 2),
+            export("memory", 2, 0),
+        ]),
+    );
+    section(
+        &mut module,
+        10,
+        vector(&[
+            body(&[(3, I32)], &walk()),
+            body(&[], &check_v4(base_v4 as i32, len_v4 as i32)),
+            body(&[], &check_v6(base_v6, len_v6 as i32)),
+        ]),
+    );
+    // One active segment at address 0.
+    let mut segment = vec![0, 0x41, 0, 0x0b];
+    uleb(&mut segment, data.len() as u32);
+    segment.extend_from_slice(&data);
+    section(&mut module, 11, vector(&[segment]));
+    Ok(module)
+}
+
+/// `walk(base, len, hi, lo, bits) -> i32`: whether the node array of `len`
+/// entries at `base` covers the address whose `bits` bits are the top bits
+/// of `hi` then `lo`.
+fn walk() -> Vec<u8> {
+    // Locals: 5 is the node index, 6 the bits walked, 7 the node address.
+    let (base, len, hi, lo, bits, index, walked, node) = (0, 1, 2, 3, 4, 5, 6, 7);
+    let mut code = Vec::new();
+    get(&mut code, len);
+    code.extend([0x45, 0x04, 0x40]); // i32.eqz, if
+    return_i32(&mut code, 0);
+    code.extend([0x0b, 0x03, 0x40]); // end, loop
+
+    // A node without children is a matching network.
+    get(&mut code, base);
+    get(&mut code, index);
+    i32_const(&mut code, 3);
+    code.extend([0x74, 0x6a, 0x22, node]); // i32.shl, i32.add, local.tee
+    code.extend([0x28, 2, 0]); // i32.load
+    get(&mut code, node);
+    code.extend([0x28, 2, 4]); // i32.load offset=4
+    code.extend([0x72, 0x45, 0x04, 0x40]); // i32.or, i32.eqz, if
+    return_i32(&mut code, 1);
+    code.push(0x0b);
+
+    get(&mut code, walked);
+    get(&mut code, bits);
+    code.extend([0x46, 0x04, 0x40]); // i32.eq, if
+    return_i32(&mut code, 0);
+    code.push(0x0b);
+
+    // The child of the top bit, 0 meaning none.
+    get(&mut code, node);
+    get(&mut code, hi);
+    i64_const(&mut code, 63);
+    code.extend([0x88, 0xa7]); // i64.shr_u, i32.wrap_i64
+    i32_const(&mut code, 2);
+    code.extend([0x74, 0x6a]); // i32.shl, i32.add
+    code.extend([0x28, 2, 0, 0x22, index, 0x45, 0x04, 0x40]); // i32.load, local.tee, i32.eqz, if
+    return_i32(&mut code, 0);
+    code.push(0x0b);
+
+    // Shift the next bit to the top.
+    get(&mut code, hi);
+    i64_const(&mut code, 1);
+    code.push(0x86); // i64.shl
+    get(&mut code, lo);
+    i64_const(&mut code, 63);
+    code.extend([0x88, 0x84, 0x21, hi]); // i64.shr_u, i64.or, local.set
+    get(&mut code, lo);
+    i64_const(&mut code, 1);
+    code.extend([0x86, 0x21, lo]); // i64.shl, local.set
+    get(&mut code, walked);
+    i32_const(&mut code, 1);
+    code.extend([0x6a, 0x21, walked]); // i32.add, local.set
+    code.extend([0x0c, 0, 0x0b, 0x00]); // br 0, end, unreachable
+    code
+}
+
+/// `check_v4(addr) -> i32`.
+fn check_v4(base: i32, len: i32) -> Vec<u8> {
+    let mut code = Vec::new();
+    i32_const(&mut code, base);
+    i32_const(&mut code, len);
+    get(&mut code, 0);
+    code.push(0xad); // i64.extend_i32_u
+    i64_const(&mut code, 32);
+    code.push(0x86); // i64.shl
+    i64_const(&mut code, 0);
+    i32_const(&mut code, 32);
+    code.extend([0x10, 0]); // call walk
+    code
+}
+
+/// `check_v6(hi, lo) -> i32`.
+fn check_v6(base: i32, len: i32) -> Vec<u8> {
+    let mut code = Vec::new();
+    i32_const(&mut code, base);
+    i32_const(&mut code, len);
+    get(&mut code, 0);
+    get(&mut code, 1);
+    i32_const(&mut code, 128);
+    code.extend([0x10, 0]); // call walk
+    code
+}
+
+fn get(code: &mut Vec<u8>, local: u8) {
+    code.extend([0x20, local]);
+}
+
+fn return_i32(code: &mut Vec<u8>, value: i32) {
+    i32_const(code, value);
+    code.push(0x0f);
+}
+
+fn i32_const(code: &mut Vec<u8>, value: i32) {
+    code.push(0x41);
+    sleb(code, value.into());
+}
+
+fn i64_const(code: &mut Vec<u8>, value: i64) {
+    code.push(0x42);
+    sleb(code, value);
+}
+
+/// A function type taking `params` and returning an `i32`.
+fn func_type(params: &[u8]) -> Vec<u8> {
+    let mut ty = vec![0x60];
+    ty.extend(vector(
+        &params.iter().map(|&param| vec![param]).collect::<Vec<_>>(),
+    ));
+    ty.extend([1, I32]);
+    ty
+}
+
+fn export(name: &str, kind: u8, index: u8) -> Vec<u8> {
+    let mut export = Vec::new();
+    uleb(&mut export, name.len() as u32);
+    export.extend(name.as_bytes());
+    export.extend([kind, index]);
+    export
+}
+
+/// A function body of `locals`, counts of each type, and `code`.
+fn body(locals: &[(u32, u8)], code: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    uleb(&mut body, locals.len() as u32);
+    for &(count, ty) in locals {
+        uleb(&mut body, count);
+        body.push(ty);
+    }
+    body.extend(code);
+    body.push(0x0b);
+    let mut sized = Vec::new();
+    uleb(&mut sized, body.len() as u32);
+    sized.extend(body);
+    sized
+}
+
+fn vector(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut vector = Vec::new();
+    uleb(&mut vector, items.len() as u32);
+    for item in items {
+        vector.extend(item);
+    }
+    vector
+}
+
+fn section(module: &mut Vec<u8>, id: u8, contents: Vec<u8>) {
+    module.push(id);
+    uleb(module, contents.len() as u32);
+    module.extend(contents);
+}
+
+fn uleb(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[derive(Serialize)]
+struct GlueTemplate<'a> {
+    module: &'a str,
+    #[serde(rename = "formatVersion")]
+    format_version: u32,
+}
+
+/// The ES module glue of the WebAssembly module named `module`, exporting
+/// `load`, which instantiates it into an `ipCheck(ip)` function.
+pub fn glue(module: &str) -> Result<String, IpCheckError> {
+    let code = Handlebars::new().render_template(
+        include_str!("wasm_glue.js"),
+        &GlueTemplate {
+            module,
+            format_version: FORMAT_VERSION,
+        },
+    )?;
+    Ok(code)
+}
+
+/// The networks of the WebAssembly module `bytes`, `None` if it is not one
+/// from [`module`].
+pub fn networks(bytes: &[u8]) -> Option<(IpRange<Ipv4Net>, IpRange<Ipv6Net>)> {
+    if !bytes.starts_with(b"\0asm") {
+        return None;
+    }
+    (0..bytes.len())
+        .filter(|&at| bytes[at..].starts_with(b"IPCK"))
+        .find_map(|at| artifact::load(&bytes[at..]).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let encoded = |value: i64| {
+            let mut out = Vec::new();
+            sleb(&mut out, value);
+            out
+        };
+        assert_eq!(encoded(0), [0]);
+        assert_eq!(encoded(63), [0x3f]);
+        assert_eq!(encoded(64), [0xc0, 0]);
+        assert_eq!(encoded(128), [0x80, 1]);
+        assert_eq!(encoded(-1), [0x7f]);
+        let mut out = Vec::new();
+        uleb(&mut out, 624_485);
+        assert_eq!(out, [0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn module_holds_the_artifact() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let bytes = module(&v4, &v6).unwrap();
+        assert!(bytes.starts_with(b"\0asm\x01\0\0\0"));
+        assert!(bytes.ends_with(&artifact::write(&v4, &v6).unwrap()));
+        assert_eq!(networks(&bytes), Some((v4, v6)));
+        assert_eq!(networks(b"IPCK"), None);
+
+        let glue = glue("filter.wasm").unwrap();
+        assert!(glue.contains("readFileSync('filter.wasm')"), "{}", glue);
+    }
+}
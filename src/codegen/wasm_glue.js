@@ -0,0 +1,82 @@
+// Generated by ipcheck; do not edit.
+//
+// Glue for the filter module {{ module }}, exporting `check_v4(u32) -> i32`
+// and `check_v6(hi: u64, lo: u64) -> i32`:
+//
+//     const ipCheck = await load(fetch(new URL('./{{ module }}', import.meta.url)));
+//     ipCheck('192.168.1.1'); // false
+//
+// In Node, pass the bytes instead: `await load(readFileSync('{{ module }}'))`.
+
+/** Node-array layout version the filters were encoded with. */
+export const FORMAT_VERSION = {{ formatVersion }};
+
+/**
+ * Instantiates the filter module `source`, its bytes or the `Response` to a
+ * request for it, or a promise of either.
+ * @returns {Promise<(ip: string) => boolean>} whether an IPv4 or IPv6
+ *     address string is covered by the filters
+ */
+export async function load(source) {
+    source = await source;
+    const { instance } = typeof Response !== 'undefined' && source instanceof Response
+        ? await WebAssembly.instantiateStreaming(source)
+        : await WebAssembly.instantiate(source);
+    const { check_v4, check_v6 } = instance.exports;
+    return function ipCheck(ip) {
+        if (!ip.includes(':')) {
+            return check_v4(ipv4ToInt(ip)) !== 0;
+        }
+        const hextets = ipv6ToHextets(ip);
+        const half = (start) => hextets
+            .slice(start, start + 4)
+            .reduce((value, hextet) => (value << 16n) | BigInt(hextet), 0n);
+        return check_v6(half(0), half(4)) !== 0;
+    };
+}
+
+function ipv4ToInt(ip) {
+    const parts = ip.split('.');
+    if (parts.length !== 4) {
+        throw new Error('Invalid IPv4 address format');
+    }
+    return parts.reduce((value, part) => {
+        const num = parseInt(part, 10);
+        if (isNaN(num) || num < 0 || num > 255) {
+            throw new Error(`Invalid IPv4 octet: ${part}`);
+        }
+        return (value << 8) | num;
+    }, 0);
+}
+
+function ipv6ToHextets(ip) {
+    // Remove IPv6 zone index if present
+    const zoneIndex = ip.indexOf('%');
+    if (zoneIndex !== -1) {
+        ip = ip.substring(0, zoneIndex);
+    }
+
+    // Expand :: notation
+    const doubleColonIndex = ip.indexOf('::');
+    if (doubleColonIndex !== -1) {
+        const head = ip.substring(0, doubleColonIndex);
+        const tail = ip.substring(doubleColonIndex + 2);
+        const before = head ? head.split(':') : [];
+        const after = tail ? tail.split(':') : [];
+        const missing = 8 - (before.length + after.length);
+        const middle = Array(missing).fill('0');
+        ip = [...before, ...middle, ...after].join(':');
+    }
+
+    const parts = ip.split(':');
+    if (parts.length !== 8) {
+        throw new Error('Invalid IPv6 address format');
+    }
+    return parts.map((part) => {
+        const num = parseInt(part, 16);
+        if (isNaN(num) || num < 0 || num > 65535) {
+            throw new Error(`Invalid IPv6 hextet: ${part}`);
+        }
+        return num;
+    });
+}
@@ -0,0 +1,48 @@
+//! The error type of fallible library operations.
+
+use std::io;
+
+use ipnet::AddrParseError;
+
+use crate::{artifact, proto};
+
+/// Errors returned by the library.
+///
+/// Encoding, loading and parsing helpers return this type, so callers can
+/// match on the cause rather than inspect a message.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum IpCheckError {
+    /// A value is not a network.
+    #[error("invalid network {value:?}: {source}")]
+    Parse {
+        value: String,
+        #[source]
+        source: AddrParseError,
+    },
+    /// A `start-end` range ends before it starts, or in the other family.
+    #[error("invalid range {0:?}: it must end at or after its start, in the same family")]
+    Range(String),
+    /// An operation that needs at least one network got an empty range.
+    #[error("the range is empty")]
+    EmptyRange,
+    /// A node array is malformed.
+    #[error("malformed node array")]
+    InvalidNodes,
+    /// A node index does not fit the target encoding.
+    #[error("node index {0} does not fit in 32 bits")]
+    IndexOverflow(usize),
+    /// An artifact is malformed.
+    #[error(transparent)]
+    Artifact(#[from] artifact::Error),
+    /// A protobuf filter is malformed.
+    #[error(transparent)]
+    Proto(#[from] proto::Error),
+    /// Reading or writing failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Rendering generated code failed.
+    #[cfg(feature = "codegen")]
+    #[error(transparent)]
+    Render(#[from] handlebars::RenderError),
+}
@@ -0,0 +1,335 @@
+//! Conversion between an [`IpTrieNode`] and its flat node-array form.
+//!
+//! The flat form stores two indices per node: the index of the zero child
+//! followed by the index of the one child. Index `0` (the root) is never a
+//! child, so it doubles as "no child". A node whose two slots are both `0`
+//! is a leaf, i.e. a complete network.
+//!
+//! In particular a root-only array, `[0, 0]`, is the whole address family
+//! (`0.0.0.0/0` or `::/0`): the root itself is a leaf, so every address
+//! matches before a single bit is inspected. An empty range has no trie
+//! and hence no node array at all.
+//!
+//! A lookup walks the array from the root, following the bits of the
+//! address from the most significant one: the address matches if it
+//! reaches a leaf, and does not if it reaches a missing child first.
+//!
+//! [`encode`] and [`decode`] convert between an [`IpRange`] and its node
+//! array, the empty array standing for the empty range, for other tools and
+//! bindings to produce and consume the arrays of the generated checkers and
+//! of `--target json`. The layout is that of [`FORMAT_VERSION`], which only
+//! changes with it. [`decode`] accepts any well-formed array, whatever the
+//! order of its nodes, as long as every child comes after its parent; the
+//! arrays of [`encode`] number both children of a node in turn, the one
+//! child first, and then those of the zero child's subtree before those of
+//! the one child's.
+//!
+//! The tries of value lookups, built by [`valued_nodes`], share the layout,
+//! along with the value of each node: the networks are the nodes with a
+//! value, leaves or not, as networks of different values neither merge nor
+//! swallow the networks they cover.
+//!
+//! [`valued_nodes`]: fn.valued_nodes.html
+//! [`encode`]: fn.encode.html
+//! [`decode`]: fn.decode.html
+//! [`FORMAT_VERSION`]: constant.FORMAT_VERSION.html
+//! [`IpRange`]: ../struct.IpRange.html
+//! [`IpTrieNode`]: ../struct.IpTrieNode.html
+
+use std::collections::BTreeMap;
+
+use crate::{IpCheckError, IpNet, IpRange, IpTrieNode};
+
+/// Version of the node-array layout described above.
+///
+/// Bumped whenever the layout changes incompatibly. Emitted artifacts record
+/// it so that consumers refuse data they do not understand rather than
+/// misreading it.
+pub const FORMAT_VERSION: u32 = 1;
+
+// No network is longer than an IPv6 address.
+const MAX_HEIGHT: u32 = 128;
+
+/// Flattens `trie` into a node array.
+///
+/// Children are always assigned a larger index than their parent.
+pub fn trie_to_nodes(trie: Box<IpTrieNode>) -> Vec<usize> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![(trie.as_ref(), nodes.len())];
+    nodes.extend([0, 0]); // Push root node's left and right indices initially
+
+    while let Some((node, idx)) = stack.pop() {
+        let base_idx = idx * 2;
+
+        // Process right child first so it gets lower index
+        if let Some(right) = &node.children[1] {
+            let right_idx = nodes.len() / 2;
+            nodes.extend([0, 0]);
+            stack.push((right.as_ref(), right_idx));
+            nodes[base_idx + 1] = right_idx;
+        }
+
+        // Process left child
+        if let Some(left) = &node.children[0] {
+            let left_idx = nodes.len() / 2;
+            nodes.extend([0, 0]);
+            stack.push((left.as_ref(), left_idx));
+            nodes[base_idx] = left_idx;
+        }
+    }
+
+    nodes
+}
+
+/// The node array of `range`, empty if `range` is.
+pub fn encode<N: IpNet>(range: &IpRange<N>) -> Vec<usize> {
+    let trie = range.clone().into_trie().into_boxed_node();
+    trie.map(trie_to_nodes).unwrap_or_default()
+}
+
+/// The networks of the node array `nodes`, as produced by [`encode`]: none
+/// if it is empty.
+///
+/// Fails with [`IpCheckError::InvalidNodes`] if `nodes` is not empty and
+/// [`nodes_to_trie`] rejects it, or if its trie is deeper than the longest
+/// prefix of `N`, 32 bits for IPv4.
+///
+/// [`encode`]: fn.encode.html
+/// [`nodes_to_trie`]: fn.nodes_to_trie.html
+/// [`IpCheckError::InvalidNodes`]: ../enum.IpCheckError.html#variant.InvalidNodes
+pub fn decode<N: IpNet>(nodes: &[usize]) -> Result<IpRange<N>, IpCheckError> {
+    if nodes.is_empty() {
+        return Ok(IpRange::new());
+    }
+    let trie =
+        trie_within(nodes, u32::from(N::MAX_PREFIX_LEN)).ok_or(IpCheckError::InvalidNodes)?;
+    let mut range = IpRange::from(trie);
+    range.simplify();
+    Ok(range)
+}
+
+/// Builds the trie of `networks`, each with the index of its value, into a
+/// node array, along with the value of each node: `0` for a node without
+/// one, else the index of the value of its network plus one. A lookup
+/// returns the value of the last node with one on the path of the address.
+///
+/// A network given twice keeps its later value. Both arrays are empty if
+/// `networks` is.
+pub fn valued_nodes<N: IpNet>(networks: &[(N, usize)]) -> (Vec<usize>, Vec<usize>) {
+    if networks.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+    let (mut nodes, mut values) = (vec![0, 0], vec![0]);
+    for (network, value) in networks {
+        let mut node = 0;
+        for bit in network.prefix_bits() {
+            let slot = node * 2 + bit as usize;
+            if nodes[slot] == 0 {
+                nodes[slot] = values.len();
+                nodes.extend([0, 0]);
+                values.push(0);
+            }
+            node = nodes[slot];
+        }
+        values[node] = value + 1;
+    }
+    (nodes, values)
+}
+
+/// Rebuilds a trie from a node array produced by [`trie_to_nodes`].
+///
+/// Returns `None` if `nodes` is not a well-formed node array: it is empty
+/// or of odd length, a child index does not point past its parent, a
+/// node is referenced more or less than once, or the trie is deeper than
+/// the longest IPv6 prefix.
+///
+/// [`trie_to_nodes`]: fn.trie_to_nodes.html
+pub fn nodes_to_trie(nodes: &[usize]) -> Option<Box<IpTrieNode>> {
+    trie_within(nodes, MAX_HEIGHT)
+}
+
+/// The trie of `nodes`, as by [`nodes_to_trie`], if it is at most
+/// `max_height` deep.
+fn trie_within(nodes: &[usize], max_height: u32) -> Option<Box<IpTrieNode>> {
+    if nodes.is_empty() || !nodes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    // Subtrees built so far, keyed by index, along with their height.
+    let mut cache = BTreeMap::new();
+    let node_count = nodes.len() / 2;
+
+    for i in (0..node_count).rev() {
+        let mut children = [None, None];
+        let mut height = 0;
+        for (bit, child) in children.iter_mut().enumerate() {
+            let child_idx = nodes[i * 2 + bit];
+            if child_idx == 0 {
+                continue;
+            }
+            if child_idx <= i {
+                return None;
+            }
+            // Fails if the child is out of range or was already claimed.
+            let (node, child_height) = cache.remove(&child_idx)?;
+            *child = Some(node);
+            height = height.max(child_height + 1);
+        }
+        if height > max_height {
+            return None;
+        }
+        cache.insert(i, (Box::new(IpTrieNode { children }), height));
+    }
+
+    // Anything left besides the root was never referenced.
+    if cache.len() != 1 {
+        return None;
+    }
+    cache.remove(&0).map(|(root, _)| root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipnet::{Ipv4Net, Ipv6Net};
+
+    fn roundtrip<N>(networks: &[&str]) -> (IpRange<N>, IpRange<N>)
+    where
+        N: IpNet + std::str::FromStr,
+        <N as std::str::FromStr>::Err: std::fmt::Debug,
+    {
+        let original: IpRange<N> = networks.iter().map(|s| s.parse().unwrap()).collect();
+        let trie = original.clone().into_trie().into_boxed_node().unwrap();
+        let nodes = trie_to_nodes(trie);
+
+        let mut reconstructed = IpRange::<N>::from(nodes_to_trie(&nodes).unwrap());
+        reconstructed.simplify();
+        (original, reconstructed)
+    }
+
+    #[test]
+    fn roundtrip_ipv4() {
+        let (original, reconstructed) = roundtrip::<Ipv4Net>(&["192.168.0.0/24", "10.0.0.0/8"]);
+        assert_eq!(original, reconstructed);
+    }
+
+    #[test]
+    fn roundtrip_single_ip() {
+        let (original, reconstructed) = roundtrip::<Ipv4Net>(&["192.168.1.1/32"]);
+        assert_eq!(original, reconstructed);
+    }
+
+    #[test]
+    fn roundtrip_multiple_ranges() {
+        let (original, reconstructed) =
+            roundtrip::<Ipv4Net>(&["192.168.0.0/16", "10.0.0.0/8", "172.16.0.0/12"]);
+        assert_eq!(original, reconstructed);
+    }
+
+    #[test]
+    fn roundtrip_ipv6() {
+        let (original, reconstructed) = roundtrip::<Ipv6Net>(&["2001:db8::/32", "fe80::/10"]);
+        assert_eq!(original, reconstructed);
+    }
+
+    #[test]
+    fn whole_family_is_root_leaf() {
+        for networks in [
+            &["0.0.0.0/0"][..],
+            &["10.0.0.0/8", "0.0.0.0/0", "1.2.3.4/32"],
+        ] {
+            let range: IpRange<Ipv4Net> = networks.iter().map(|s| s.parse().unwrap()).collect();
+            let nodes = trie_to_nodes(range.into_trie().into_boxed_node().unwrap());
+            assert_eq!(nodes, [0, 0]);
+        }
+        let range: IpRange<Ipv6Net> = ["::/0"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(
+            trie_to_nodes(range.into_trie().into_boxed_node().unwrap()),
+            [0, 0]
+        );
+
+        let (original, reconstructed) = roundtrip::<Ipv4Net>(&["0.0.0.0/0"]);
+        assert_eq!(original, reconstructed);
+        assert!(reconstructed.contains(&"255.255.255.255/32".parse::<Ipv4Net>().unwrap()));
+        let (original, reconstructed) = roundtrip::<Ipv6Net>(&["::/0"]);
+        assert_eq!(original, reconstructed);
+        assert!(reconstructed.contains(&"::/0".parse::<Ipv6Net>().unwrap()));
+    }
+
+    #[test]
+    fn reject_malformed_nodes() {
+        assert!(nodes_to_trie(&[]).is_none());
+        assert!(nodes_to_trie(&[0, 0, 0]).is_none());
+        // Child index out of range.
+        assert!(nodes_to_trie(&[2, 0, 0, 0]).is_none());
+        // Child pointing back at its parent.
+        assert!(nodes_to_trie(&[1, 0, 1, 0]).is_none());
+        // Node referenced twice.
+        assert!(nodes_to_trie(&[1, 1, 0, 0]).is_none());
+        // Unreachable node.
+        assert!(nodes_to_trie(&[0, 1, 0, 0, 0, 0]).is_none());
+        // Deeper than any IPv6 prefix.
+        let mut chain: Vec<usize> = (1..=129).flat_map(|i| [i, 0]).collect();
+        chain.extend([0, 0]);
+        assert!(nodes_to_trie(&chain).is_none());
+        chain.drain(chain.len() - 4..);
+        chain.extend([0, 0]);
+        assert!(nodes_to_trie(&chain).is_some());
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let range: IpRange<Ipv4Net> = ["0.0.0.0/2", "192.0.0.0/2"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        // The children of the root first, then the zero child's subtree.
+        let nodes = encode(&range);
+        assert_eq!(nodes, [2, 1, 0, 4, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(decode::<Ipv4Net>(&nodes).unwrap(), range);
+        // Any order of the nodes decodes, children after their parents.
+        assert_eq!(
+            decode::<Ipv4Net>(&[1, 2, 3, 0, 0, 4, 0, 0, 0, 0]).unwrap(),
+            range
+        );
+
+        assert!(encode(&IpRange::<Ipv6Net>::new()).is_empty());
+        assert_eq!(decode::<Ipv6Net>(&[]).unwrap(), IpRange::new());
+        assert!(matches!(
+            decode::<Ipv6Net>(&[0, 0, 0]),
+            Err(IpCheckError::InvalidNodes)
+        ));
+
+        // A chain deeper than any IPv4 prefix is an IPv6 one at most.
+        let deep = |depth: usize| {
+            let mut chain: Vec<usize> = (1..=depth).flat_map(|i| [i, 0]).collect();
+            chain.extend([0, 0]);
+            chain
+        };
+        assert!(matches!(
+            decode::<Ipv4Net>(&deep(40)),
+            Err(IpCheckError::InvalidNodes)
+        ));
+        assert!(matches!(
+            decode::<Ipv4Net>(&deep(33)),
+            Err(IpCheckError::InvalidNodes)
+        ));
+        assert_eq!(
+            decode::<Ipv4Net>(&deep(32)).unwrap(),
+            ["0.0.0.0/32"].iter().map(|s| s.parse().unwrap()).collect()
+        );
+        assert_eq!(decode::<Ipv6Net>(&deep(40)).unwrap().iter().count(), 1);
+    }
+
+    #[test]
+    fn valued_tries() {
+        let net = |s: &str| s.parse::<Ipv4Net>().unwrap();
+        // 128.0.0.0/1 of value 0 covering 192.0.0.0/2 of value 1.
+        let (nodes, values) = valued_nodes(&[(net("128.0.0.0/1"), 0), (net("192.0.0.0/2"), 1)]);
+        assert_eq!(nodes, [0, 1, 0, 2, 0, 0]);
+        assert_eq!(values, [0, 1, 2]);
+        let (nodes, values) = valued_nodes(&[(net("0.0.0.0/0"), 3), (net("0.0.0.0/0"), 4)]);
+        assert_eq!((nodes, values), (vec![0, 0], vec![5]));
+        assert_eq!(valued_nodes::<Ipv4Net>(&[]), (Vec::new(), Vec::new()));
+    }
+}
@@ -15,11 +15,11 @@
 //! Here is a simple example:
 //!
 //! ```
-//! extern crate iprange;
+//! extern crate ipcheck_rs;
 //! extern crate ipnet;
 //!
 //! use std::net::Ipv4Addr;
-//! use iprange::IpRange;
+//! use ipcheck_rs::IpRange;
 //! use ipnet::Ipv4Net;
 //!
 //! fn main() {
@@ -33,6 +33,20 @@
 //! }
 //! ```
 //!
+//! # Cargo features
+//!
+//! Without features, the crate is the lookup core, with no dependencies
+//! beyond `ipnet`, `thiserror` and `crc32fast`: embedding it for lookups does not pull
+//! in the code generator. The rest is opt-in:
+//!
+//! * `codegen`: the `codegen` module, generating the TypeScript checker
+//!   in-process, and the `backend` API for other code generation targets;
+//! * `ipcheck`: the `ipcheck` CLI, with its CSV loader and templates;
+//! * `serve`, `grpc`, `dns`, `nats`, `redis`, `sqlite` and friends: the
+//!   lookup services and integrations of the CLI;
+//! * `serde`, `wasm`, `capi` and `test-support`: serialization, bindings
+//!   and test helpers for the core.
+//!
 //! [`IpRange`]: struct.IpRange.html
 //! [`IpNet`]: trait.IpNet.html
 //! [`Ipv4Net`]: https://docs.rs/ipnet/1.0.0/ipnet/struct.Ipv4Net.html
@@ -46,6 +60,25 @@ use std::fmt;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+pub use error::IpCheckError;
+
+pub mod artifact;
+#[cfg(feature = "codegen")]
+pub mod backend;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+mod error;
+pub mod flat;
+pub mod proto;
+pub mod source;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// A set of networks that supports various operations:
 ///
@@ -61,7 +94,7 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 ///
 /// ```
 ///
-/// use iprange::IpRange;
+/// use ipcheck_rs::IpRange;
 /// use ipnet::Ipv4Net;
 ///
 /// fn main() {
@@ -111,10 +144,10 @@ impl<N: IpNet> IpRange<N> {
     /// explicitly. For example:
     ///
     /// ```
-    /// extern crate iprange;
+    /// extern crate ipcheck_rs;
     /// extern crate ipnet;
     ///
-    /// use iprange::IpRange;
+    /// use ipcheck_rs::IpRange;
     /// use ipnet::Ipv4Net;
     ///
     /// fn main() {
@@ -144,10 +177,10 @@ impl<N: IpNet> IpRange<N> {
     /// For example:
     ///
     /// ```
-    /// extern crate iprange;
+    /// extern crate ipcheck_rs;
     /// extern crate ipnet;
     ///
-    /// use iprange::IpRange;
+    /// use ipcheck_rs::IpRange;
     /// use ipnet::Ipv4Net;
     ///
     /// fn main() {
@@ -168,7 +201,7 @@ impl<N: IpNet> IpRange<N> {
     /// ```
     /// # extern crate ipnet;
     /// #
-    /// # use iprange::IpRange;
+    /// # use ipcheck_rs::IpRange;
     /// # use ipnet::Ipv4Net;
     /// let mut ip_range = IpRange::new();
     /// let network: Ipv4Net = "1.0.1.0/24".parse().unwrap();
@@ -180,13 +213,17 @@ impl<N: IpNet> IpRange<N> {
         self.trie.root.is_none()
     }
 
-    /// Simplify `self` by combining networks. For example:
+    /// Simplify `self` by combining networks.
+    ///
+    /// A simplified `IpRange` only depends on the addresses it covers, not on
+    /// the networks it was built from or the order they were added in.
+    /// For example:
     ///
     /// ```
-    /// extern crate iprange;
+    /// extern crate ipcheck_rs;
     /// extern crate ipnet;
     ///
-    /// use iprange::IpRange;
+    /// use ipcheck_rs::IpRange;
     /// use ipnet::Ipv4Net;
     ///
     /// fn main() {
@@ -212,7 +249,7 @@ impl<N: IpNet> IpRange<N> {
     ///
     /// The returned `IpRange` is simplified.
     pub fn merge(&self, other: &IpRange<N>) -> Self {
-        self.into_iter().chain(other.into_iter()).collect()
+        self.into_iter().chain(other).collect()
     }
 
     /// Returns a new `IpRange` which contains all networks
@@ -253,11 +290,52 @@ impl<N: IpNet> IpRange<N> {
     }
 
     /// Returns the iterator to `&self`.
-    pub fn iter(&self) -> IpRangeIter<N> {
+    pub fn iter(&self) -> IpRangeIter<'_, N> {
         self.into_iter()
     }
 }
 
+impl IpRange<Ipv4Net> {
+    /// Add every address from `start` to `end`, inclusive, as the fewest
+    /// networks covering exactly them, e.g. `192.0.2.0` to `192.0.2.130`
+    /// as `192.0.2.0/25`, `192.0.2.128/31` and `192.0.2.130/32`.
+    ///
+    /// Returns `&mut self` in order to enable method chaining. Nothing is
+    /// added if `start` is after `end`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate ipnet;
+    /// #
+    /// # use ipcheck_rs::IpRange;
+    /// # use ipnet::Ipv4Net;
+    /// let mut ip_range: IpRange<Ipv4Net> = IpRange::new();
+    /// ip_range.add_range("1.2.3.0".parse().unwrap(), "1.2.3.255".parse().unwrap());
+    /// let networks: Vec<Ipv4Net> = ip_range.iter().collect();
+    /// assert_eq!(networks, ["1.2.3.0/24".parse().unwrap()]);
+    /// ```
+    pub fn add_range(&mut self, start: Ipv4Addr, end: Ipv4Addr) -> &mut Self {
+        for network in ipnet::Ipv4Subnets::new(start, end, 0) {
+            self.add(network);
+        }
+        self
+    }
+}
+
+impl IpRange<Ipv6Net> {
+    /// Add every address from `start` to `end`, inclusive, as the fewest
+    /// networks covering exactly them.
+    ///
+    /// Returns `&mut self` in order to enable method chaining. Nothing is
+    /// added if `start` is after `end`.
+    pub fn add_range(&mut self, start: Ipv6Addr, end: Ipv6Addr) -> &mut Self {
+        for network in ipnet::Ipv6Subnets::new(start, end, 0) {
+            self.add(network);
+        }
+        self
+    }
+}
+
 impl<N> Default for IpRange<N>
 where
     N: IpNet + ToNetwork<N> + Clone,
@@ -313,6 +391,31 @@ impl<N: IpNet> From<Box<IpTrieNode>> for IpRange<N> {
     }
 }
 
+/// Parses a list of networks separated by commas and/or whitespace, such as
+/// `"10.0.0.0/8, 192.168.0.0/16"`.
+///
+/// Fails with [`IpCheckError::Parse`] naming the first invalid network.
+///
+/// [`IpCheckError::Parse`]: enum.IpCheckError.html#variant.Parse
+impl<N> FromStr for IpRange<N>
+where
+    N: IpNet + FromStr<Err = ipnet::AddrParseError>,
+{
+    type Err = IpCheckError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value.parse().map_err(|source| IpCheckError::Parse {
+                    value: value.to_string(),
+                    source,
+                })
+            })
+            .collect()
+    }
+}
+
 #[cfg(feature = "serde")]
 impl<N: IpNet> serde::Serialize for IpRange<N> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -349,6 +452,9 @@ where
     ///`I` is an iterator to the prefix bits of the network.
     type I: Iterator<Item = bool>;
 
+    /// The length of the longest prefix: the number of bits of an address.
+    const MAX_PREFIX_LEN: u8;
+
     /// Returns the iterator to the prefix bits of the network.
     fn prefix_bits(&self) -> Self::I;
 
@@ -462,7 +568,7 @@ where
     }
 
     pub fn into_boxed_node(self) -> Option<Box<IpTrieNode>> {
-        self.root.map(|node| Box::new(node))
+        self.root.map(Box::new)
     }
 
     fn insert(&mut self, network: N) {
@@ -562,11 +668,21 @@ where
 
 /// Node of the inner radix trie.
 #[derive(Clone, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct IpTrieNode {
     pub children: [Option<Box<IpTrieNode>>; 2],
 }
 
+impl Default for IpTrieNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IpTrieNode {
     pub fn new() -> IpTrieNode {
         IpTrieNode {
@@ -675,6 +791,7 @@ const MSO_U32: u32 = 1 << 31; // Most significant one for u32
 impl IpNet for Ipv4Net {
     type S = Ipv4TraverseState;
     type I = Ipv4PrefixBitIterator;
+    const MAX_PREFIX_LEN: u8 = 32;
 
     #[inline]
     fn prefix_bits(&self) -> Self::I {
@@ -764,7 +881,7 @@ impl TraverseState for Ipv4TraverseState {
 
     #[inline]
     fn build(&self) -> Self::Net {
-        Ipv4Net::new(self.prefix.into(), self.prefix_len as u8).unwrap()
+        Ipv4Net::new(self.prefix.into(), self.prefix_len).unwrap()
     }
 }
 
@@ -793,6 +910,7 @@ impl Iterator for Ipv4PrefixBitIterator {
 impl IpNet for Ipv6Net {
     type S = Ipv6TraverseState;
     type I = Ipv6PrefixBitIterator;
+    const MAX_PREFIX_LEN: u8 = 128;
 
     #[inline]
     fn prefix_bits(&self) -> Self::I {
@@ -888,7 +1006,7 @@ impl TraverseState for Ipv6TraverseState {
 
     #[inline]
     fn build(&self) -> Self::Net {
-        Ipv6Net::new(self.prefix.into(), self.prefix_len as u8).unwrap()
+        Ipv6Net::new(self.prefix.into(), self.prefix_len).unwrap()
     }
 }
 
@@ -944,6 +1062,36 @@ mod tests {
         assert_eq!(Some(network), ip_range.get_network(24, "192.168.5.0"));
     }
 
+    #[test]
+    fn add_address_ranges() {
+        let networks = |range: &IpRange<Ipv4Net>| -> Vec<String> {
+            range.iter().map(|network| network.to_string()).collect()
+        };
+        let mut ip_range: IpRange<Ipv4Net> = IpRange::new();
+        ip_range.add_range("192.0.2.0".parse().unwrap(), "192.0.2.130".parse().unwrap());
+        assert_eq!(
+            networks(&ip_range),
+            ["192.0.2.0/25", "192.0.2.128/31", "192.0.2.130/32"]
+        );
+
+        let mut ip_range: IpRange<Ipv4Net> = IpRange::new();
+        ip_range.add_range(Ipv4Addr::new(0, 0, 0, 0), Ipv4Addr::BROADCAST);
+        assert_eq!(networks(&ip_range), ["0.0.0.0/0"]);
+        let mut ip_range: IpRange<Ipv4Net> = IpRange::new();
+        ip_range.add_range("10.0.0.2".parse().unwrap(), "10.0.0.1".parse().unwrap());
+        assert!(ip_range.is_empty());
+
+        let mut ip_range: IpRange<Ipv6Net> = IpRange::new();
+        ip_range.add_range(
+            "2001:db8::".parse().unwrap(),
+            "2001:db8::ffff:ffff:ffff:ffff".parse().unwrap(),
+        );
+        assert_eq!(
+            ip_range.iter().collect::<Vec<_>>(),
+            ["2001:db8::/64".parse().unwrap()]
+        );
+    }
+
     #[test]
     fn add_multiple_networks_disjoint() {
         let mut ip_range = IpRange::new();
@@ -1809,6 +1957,58 @@ mod tests {
         assert!(ip_range.contains_network("0.0.0.0/0"));
     }
 
+    #[test]
+    fn parse_range_from_str() {
+        let range: IpRange<Ipv4Net> = "10.0.0.0/8, 192.168.0.0/16\n172.16.0.0/12,"
+            .parse()
+            .unwrap();
+        let expected: IpRange<Ipv4Net> = ["10.0.0.0/8", "192.168.0.0/16", "172.16.0.0/12"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(range, expected);
+        assert!("".parse::<IpRange<Ipv6Net>>().unwrap().is_empty());
+
+        match "10.0.0.0/8, 10.0.0.0/33".parse::<IpRange<Ipv4Net>>() {
+            Err(IpCheckError::Parse { value, .. }) => assert_eq!(value, "10.0.0.0/33"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn insertion_order_independent() {
+        let networks: Vec<Ipv4Net> = [
+            "10.0.0.0/8",
+            "10.1.0.0/16",
+            "192.168.0.0/24",
+            "192.168.1.0/24",
+            "172.16.0.0/12",
+            "172.16.5.5/32",
+            "0.0.0.0/1",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let expected: IpRange<Ipv4Net> = networks.iter().cloned().collect();
+
+        let mut shuffled = networks.clone();
+        for _ in 0..networks.len() {
+            shuffled.rotate_left(1);
+            let forward: IpRange<Ipv4Net> = shuffled.iter().cloned().collect();
+            let backward: IpRange<Ipv4Net> = shuffled.iter().rev().cloned().collect();
+            assert_eq!(expected.trie, forward.trie);
+            assert_eq!(expected.trie, backward.trie);
+        }
+
+        // Covering the same addresses with different networks is no different.
+        let split: IpRange<Ipv4Net> = ["0.0.0.0/2", "64.0.0.0/2", "128.0.0.0/1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let whole: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(split.trie, whole.trie);
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn serialize_ipv4_as_binary() {
@@ -0,0 +1,299 @@
+//! The IPv4 and IPv6 node arrays as a protobuf message, for shipping a
+//! build over gRPC, Kafka or any other channel carrying protobuf.
+//!
+//! The message is `ipcheck.v1.Filter` of `proto/filter.proto`:
+//!
+//! ```proto
+//! message Filter {
+//!   uint32 format_version = 1;
+//!   repeated uint32 nodes_v4 = 2;
+//!   repeated uint32 nodes_v6 = 3;
+//! }
+//! ```
+//!
+//! with the node arrays of [`flat`], so consumers with generated protobuf
+//! code rebuild the tries the way [`flat::decode`] does. [`encode`]
+//! writes the repeated fields packed, as proto3 does by default, and
+//! [`decode`] also reads them unpacked, as protobuf parsers must, skipping
+//! the fields it does not know.
+//!
+//! As in an [`artifact`], the format version is [`FORMAT_VERSION`], and
+//! [`decode`] rejects any other version, including a message without one.
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! use ipcheck_rs::{proto, IpRange};
+//! use ipnet::{Ipv4Net, Ipv6Net};
+//!
+//! let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+//! let v6: IpRange<Ipv6Net> = IpRange::new();
+//!
+//! let bytes = proto::encode(&v4, &v6).unwrap();
+//! assert_eq!(proto::decode(&bytes).unwrap(), (v4, v6));
+//! ```
+//!
+//! [`artifact`]: ../artifact/index.html
+//! [`flat`]: ../flat/index.html
+//! [`flat::decode`]: ../flat/fn.decode.html
+//! [`encode`]: fn.encode.html
+//! [`decode`]: fn.decode.html
+//! [`FORMAT_VERSION`]: ../flat/constant.FORMAT_VERSION.html
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::flat::{self, trie_to_nodes, FORMAT_VERSION};
+use crate::{IpCheckError, IpNet, IpRange};
+
+const FIELD_FORMAT_VERSION: u64 = 1;
+const FIELD_NODES_V4: u64 = 2;
+const FIELD_NODES_V6: u64 = 3;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Ways in which a `Filter` message can be malformed.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The data ends inside a field.
+    #[error("protobuf filter is truncated")]
+    Truncated,
+    /// The data is not a protobuf message of the `Filter` schema.
+    #[error("not a protobuf filter")]
+    Malformed,
+    /// The message has another format version, or none.
+    #[error("protobuf filter format version {found} is not supported (expected {supported})")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// A node array is not well formed.
+    #[error("protobuf filter contains a malformed node array")]
+    InvalidNodes,
+}
+
+/// Encodes `v4` and `v6` into a `Filter` message.
+///
+/// Fails with [`IpCheckError::IndexOverflow`] if a trie has more nodes than
+/// 32-bit indices can address.
+///
+/// [`IpCheckError::IndexOverflow`]: ../enum.IpCheckError.html#variant.IndexOverflow
+pub fn encode(v4: &IpRange<Ipv4Net>, v6: &IpRange<Ipv6Net>) -> Result<Vec<u8>, IpCheckError> {
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, FIELD_FORMAT_VERSION << 3 | WIRE_VARINT);
+    write_varint(&mut bytes, FORMAT_VERSION.into());
+    write_nodes(&mut bytes, FIELD_NODES_V4, v4)?;
+    write_nodes(&mut bytes, FIELD_NODES_V6, v6)?;
+    Ok(bytes)
+}
+
+/// Decodes a `Filter` message, as [`encode`] or any protobuf encoder
+/// writes it.
+///
+/// [`encode`]: fn.encode.html
+pub fn decode(mut bytes: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), Error> {
+    let mut version = 0;
+    let mut nodes_v4 = Vec::new();
+    let mut nodes_v6 = Vec::new();
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes)?;
+        let (field, wire) = (key >> 3, key & 7);
+        match (field, wire) {
+            (0, _) => return Err(Error::Malformed),
+            (FIELD_FORMAT_VERSION, WIRE_VARINT) => version = read_u32(&mut bytes)?,
+            (FIELD_NODES_V4, WIRE_VARINT) => nodes_v4.push(read_u32(&mut bytes)? as usize),
+            (FIELD_NODES_V6, WIRE_VARINT) => nodes_v6.push(read_u32(&mut bytes)? as usize),
+            (FIELD_NODES_V4, WIRE_LEN) => read_packed(&mut bytes, &mut nodes_v4)?,
+            (FIELD_NODES_V6, WIRE_LEN) => read_packed(&mut bytes, &mut nodes_v6)?,
+            (FIELD_FORMAT_VERSION..=FIELD_NODES_V6, _) => return Err(Error::Malformed),
+            (_, WIRE_VARINT) => {
+                read_varint(&mut bytes)?;
+            }
+            (_, WIRE_FIXED64) => skip(&mut bytes, 8)?,
+            (_, WIRE_LEN) => {
+                let len = read_len(&mut bytes)?;
+                skip(&mut bytes, len)?;
+            }
+            (_, WIRE_FIXED32) => skip(&mut bytes, 4)?,
+            // Groups are deprecated, and never part of a proto3 message.
+            _ => return Err(Error::Malformed),
+        }
+    }
+    if version != FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    Ok((
+        flat::decode(&nodes_v4).map_err(|_| Error::InvalidNodes)?,
+        flat::decode(&nodes_v6).map_err(|_| Error::InvalidNodes)?,
+    ))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_nodes<N: IpNet>(
+    out: &mut Vec<u8>,
+    field: u64,
+    range: &IpRange<N>,
+) -> Result<(), IpCheckError> {
+    let nodes = match range.clone().into_trie().into_boxed_node() {
+        Some(trie) => trie_to_nodes(trie),
+        None => return Ok(()),
+    };
+    let mut packed = Vec::new();
+    for node in nodes {
+        let node = u32::try_from(node).map_err(|_| IpCheckError::IndexOverflow(node))?;
+        write_varint(&mut packed, node.into());
+    }
+    write_varint(out, field << 3 | WIRE_LEN);
+    write_varint(out, packed.len() as u64);
+    out.extend_from_slice(&packed);
+    Ok(())
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for (i, &byte) in input.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte < 0x80 {
+            *input = &input[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err(if input.len() < 10 {
+        Error::Truncated
+    } else {
+        Error::Malformed
+    })
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, Error> {
+    u32::try_from(read_varint(input)?).map_err(|_| Error::Malformed)
+}
+
+fn read_len(input: &mut &[u8]) -> Result<usize, Error> {
+    usize::try_from(read_varint(input)?).map_err(|_| Error::Malformed)
+}
+
+fn skip(input: &mut &[u8], len: usize) -> Result<(), Error> {
+    if input.len() < len {
+        return Err(Error::Truncated);
+    }
+    *input = &input[len..];
+    Ok(())
+}
+
+fn read_packed(input: &mut &[u8], nodes: &mut Vec<usize>) -> Result<(), Error> {
+    let len = read_len(input)?;
+    if input.len() < len {
+        return Err(Error::Truncated);
+    }
+    let (mut packed, rest) = input.split_at(len);
+    *input = rest;
+    while !packed.is_empty() {
+        // A varint cut off by the end of the field is malformed, not
+        // truncated, as the field length says where it ends.
+        let node = read_u32(&mut packed).map_err(|_| Error::Malformed)?;
+        nodes.push(node as usize);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges() -> (IpRange<Ipv4Net>, IpRange<Ipv6Net>) {
+        let v4 = ["10.0.0.0/8", "172.16.0.0/16", "192.168.1.0/24"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let v6 = ["2001:db8::/32", "fe80::/10"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        (v4, v6)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (v4, v6) = ranges();
+        let bytes = encode(&v4, &v6).unwrap();
+        assert_eq!(decode(&bytes).unwrap(), (v4.clone(), v6));
+
+        // An empty family has no field at all, as proto3 omits them.
+        let bytes = encode(&v4, &IpRange::new()).unwrap();
+        assert_eq!(decode(&bytes).unwrap(), (v4, IpRange::new()));
+        let bytes = encode(&IpRange::new(), &IpRange::new()).unwrap();
+        assert_eq!(bytes, [0x08, 0x01]);
+
+        let whole: IpRange<Ipv4Net> = ["0.0.0.0/0"].iter().map(|s| s.parse().unwrap()).collect();
+        let bytes = encode(&whole, &IpRange::new()).unwrap();
+        assert_eq!(bytes, [0x08, 0x01, 0x12, 0x02, 0x00, 0x00]);
+        assert_eq!(decode(&bytes).unwrap().0, whole);
+    }
+
+    #[test]
+    fn decode_unpacked_and_unknown_fields() {
+        // A whole IPv4 family, its nodes unpacked, after an unknown string
+        // field 9 and before an unknown fixed32 field 10.
+        let bytes = [
+            0x4a, 0x02, b'h', b'i', 0x10, 0x00, 0x08, 0x01, 0x10, 0x00, 0x55, 1, 2, 3, 4,
+        ];
+        let (v4, v6) = decode(&bytes).unwrap();
+        assert_eq!(v4.iter().next().unwrap().to_string(), "0.0.0.0/0");
+        assert!(v6.is_empty());
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        assert_eq!(
+            decode(&[0x08, 0x02]),
+            Err(Error::UnsupportedVersion {
+                found: 2,
+                supported: FORMAT_VERSION
+            })
+        );
+        // A message without a version is version 0 in proto3.
+        assert_eq!(
+            decode(&[]),
+            Err(Error::UnsupportedVersion {
+                found: 0,
+                supported: FORMAT_VERSION
+            })
+        );
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let (v4, v6) = ranges();
+        let bytes = encode(&v4, &v6).unwrap();
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), Err(Error::Truncated));
+        assert_eq!(decode(&[0x08]), Err(Error::Truncated));
+        // The version as a string.
+        assert_eq!(decode(&[0x0a, 0x01, 0x01]), Err(Error::Malformed));
+        // A start group.
+        assert_eq!(decode(&[0x08, 0x01, 0x5b]), Err(Error::Malformed));
+        // An odd number of node array entries.
+        assert_eq!(
+            decode(&[0x08, 0x01, 0x12, 0x02, 0x00, 0x00, 0x10, 0x00]),
+            Err(Error::InvalidNodes)
+        );
+        // An IPv4 node array chaining 40 levels deep, packed.
+        let mut chain: Vec<u8> = (1..=40).flat_map(|i| [i, 0]).collect();
+        chain.extend([0, 0]);
+        let mut deep = vec![0x08, 0x01, 0x12, chain.len() as u8];
+        deep.extend(chain);
+        assert_eq!(decode(&deep), Err(Error::InvalidNodes));
+        assert!(IpCheckError::from(Error::Malformed)
+            .to_string()
+            .contains("protobuf"));
+    }
+}
@@ -0,0 +1,825 @@
+//! Pluggable parsers for feed formats.
+//!
+//! A [`SourceParser`] turns the contents of a feed into IPv4 and IPv6
+//! ranges, or into the [`Entry`]s they are read from, one per network as
+//! written, for readers that check and count them one by one. Parsers are kept in a [`Registry`], which picks one by name or
+//! by sniffing the start of a feed. The registry returned by [`registry`]
+//! is the one `ipcheck` reads its inputs with, so a proprietary format only
+//! needs a parser registered with [`register`] at startup rather than a
+//! fork of the loader:
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! use std::path::Path;
+//!
+//! use ipcheck_rs::source::{self, SourceParser};
+//! use ipcheck_rs::{IpCheckError, IpRange};
+//! use ipnet::{Ipv4Net, Ipv6Net};
+//!
+//! /// `block <cidr>` lines.
+//! struct Blocks;
+//!
+//! impl SourceParser for Blocks {
+//!     fn name(&self) -> &'static str {
+//!         "blocks"
+//!     }
+//!
+//!     fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+//!         head.starts_with(b"block ")
+//!     }
+//!
+//!     fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+//!         let text = String::from_utf8_lossy(input);
+//!         let values = text.lines().filter_map(|line| line.strip_prefix("block "));
+//!         source::parse_networks(values)
+//!     }
+//! }
+//!
+//! source::register(Blocks);
+//! let registry = source::registry();
+//! let parser = registry.sniff(Path::new("feed"), b"block 10.0.0.0/8\n").unwrap();
+//! let (v4, _) = parser.parse(b"block 10.0.0.0/8\n").unwrap();
+//! assert_eq!(v4.iter().count(), 1);
+//! ```
+//!
+//! These formats are built in: `lines`, one network per line with `#`
+//! comments, as in FireHOL's `.netset` and `.ipset` files; `nftables`, the
+//! elements of the address sets in an `nft list ruleset` dump;
+//! `iptables-save`, the sources and destinations of the rules in an
+//! `iptables-save` dump; `ipset`, the members of the address sets in an
+//! `ipset save` dump; `spamhaus`, Spamhaus's DROP, EDROP and DROPv6 lists;
+//! and `artifact`, the binary [`artifact`](crate::artifact).
+
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
+
+use ipnet::{IpNet as AnyNet, Ipv4Net, Ipv6Net};
+
+use crate::{artifact, IpCheckError, IpRange};
+
+/// A feed format.
+pub trait SourceParser: Send + Sync {
+    /// The name the format is selected by, e.g. `lines`.
+    fn name(&self) -> &'static str;
+
+    /// Whether the feed at `path`, which starts with `head`, looks like
+    /// this format. `head` holds at most the first [`SNIFF_LEN`] bytes.
+    ///
+    /// [`SNIFF_LEN`]: constant.SNIFF_LEN.html
+    fn sniff(&self, path: &Path, head: &[u8]) -> bool;
+
+    /// Parses a whole feed into its IPv4 and IPv6 networks.
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError>;
+
+    /// The entries of a whole feed, in order, before they are parsed as
+    /// networks. By default, the networks of [`parse`], without lines.
+    ///
+    /// [`parse`]: #tymethod.parse
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let (v4, v6) = self.parse(input)?;
+        let networks = v4.iter().map(|network| network.to_string());
+        let networks = networks.chain(v6.iter().map(|network| network.to_string()));
+        Ok(networks
+            .map(|network| Entry {
+                network,
+                line: None,
+                value: None,
+            })
+            .collect())
+    }
+}
+
+/// One network of a feed as written: a network, an address or a
+/// `start-end` range of addresses, with what the feed says about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The network, trimmed.
+    pub network: String,
+    /// The line it is on, counting from 1, if the feed has lines.
+    pub line: Option<u64>,
+    /// The value the feed gives the network, if any, such as the SBL id of
+    /// a Spamhaus listing, for lookups returning it.
+    pub value: Option<String>,
+}
+
+impl Entry {
+    fn at(line: usize, network: &str) -> Entry {
+        Entry {
+            network: network.trim().to_string(),
+            line: Some(line as u64 + 1),
+            value: None,
+        }
+    }
+}
+
+/// The most bytes passed to [`SourceParser::sniff`].
+///
+/// [`SourceParser::sniff`]: trait.SourceParser.html#tymethod.sniff
+pub const SNIFF_LEN: usize = 512;
+
+/// A set of parsers, looked up by name or by sniffing.
+#[derive(Clone)]
+pub struct Registry {
+    parsers: Vec<Arc<dyn SourceParser>>,
+}
+
+impl Registry {
+    /// A registry without any parsers.
+    pub fn new() -> Registry {
+        Registry {
+            parsers: Vec::new(),
+        }
+    }
+
+    /// A registry of the built-in parsers.
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(Lines);
+        registry.register(Nftables);
+        registry.register(IptablesSave);
+        registry.register(Ipset);
+        registry.register(Spamhaus);
+        registry.register(Artifact);
+        registry
+    }
+
+    /// Adds `parser`, replacing any parser of the same name. Parsers
+    /// registered later are sniffed first.
+    pub fn register<P: SourceParser + 'static>(&mut self, parser: P) {
+        self.parsers.retain(|known| known.name() != parser.name());
+        self.parsers.push(Arc::new(parser));
+    }
+
+    /// The parser named `name`.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn SourceParser>> {
+        self.parsers
+            .iter()
+            .find(|parser| parser.name() == name)
+            .cloned()
+    }
+
+    /// The most recently registered parser that recognizes the feed at
+    /// `path` starting with `head`.
+    pub fn sniff(&self, path: &Path, head: &[u8]) -> Option<Arc<dyn SourceParser>> {
+        let head = &head[..head.len().min(SNIFF_LEN)];
+        self.parsers
+            .iter()
+            .rev()
+            .find(|parser| parser.sniff(path, head))
+            .cloned()
+    }
+
+    /// The names of the registered parsers, in registration order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.parsers.iter().map(|parser| parser.name()).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::with_builtins()
+    }
+}
+
+fn global() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::with_builtins()))
+}
+
+/// Adds `parser` to the process-wide registry, which starts out with the
+/// built-in parsers.
+pub fn register<P: SourceParser + 'static>(parser: P) {
+    global().write().unwrap().register(parser);
+}
+
+/// The process-wide registry.
+pub fn registry() -> RwLockReadGuard<'static, Registry> {
+    global().read().unwrap()
+}
+
+/// Collects networks written as strings, such as `10.0.0.0/8`, a bare
+/// address or a `start-end` range of addresses, into simplified ranges; a
+/// helper for parsers.
+///
+/// Fails with [`IpCheckError::Parse`] naming the first invalid network, or
+/// [`IpCheckError::Range`] the first range that ends before it starts.
+///
+/// [`IpCheckError::Parse`]: ../enum.IpCheckError.html#variant.Parse
+/// [`IpCheckError::Range`]: ../enum.IpCheckError.html#variant.Range
+pub fn parse_networks<'a, I>(
+    values: I,
+) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut v4 = IpRange::new();
+    let mut v6 = IpRange::new();
+    for value in values {
+        let value = value.trim();
+        let network = match value.parse::<AnyNet>() {
+            Ok(network) => network,
+            Err(err) => match value.parse::<IpAddr>() {
+                Ok(addr) => AnyNet::from(addr),
+                Err(_) => match add_range(&mut v4, &mut v6, value) {
+                    Some(added) => {
+                        added?;
+                        continue;
+                    }
+                    None => {
+                        return Err(IpCheckError::Parse {
+                            value: value.to_string(),
+                            source: err,
+                        })
+                    }
+                },
+            },
+        };
+        match network.trunc() {
+            AnyNet::V4(network) => {
+                v4.add(network);
+            }
+            AnyNet::V6(network) => {
+                v6.add(network);
+            }
+        }
+    }
+    v4.simplify();
+    v6.simplify();
+    Ok((v4, v6))
+}
+
+/// Collects the networks of `entries` as [`parse_networks`] does.
+///
+/// [`parse_networks`]: fn.parse_networks.html
+pub fn parse_entries(
+    entries: &[Entry],
+) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+    parse_networks(entries.iter().map(|entry| entry.network.as_str()))
+}
+
+/// Adds `value` if it is a `start-end` range of addresses; `None` if it is
+/// not one.
+fn add_range(
+    v4: &mut IpRange<Ipv4Net>,
+    v6: &mut IpRange<Ipv6Net>,
+    value: &str,
+) -> Option<Result<(), IpCheckError>> {
+    let (start, end) = value.split_once('-')?;
+    let start: IpAddr = start.trim().parse().ok()?;
+    let end: IpAddr = end.trim().parse().ok()?;
+    match (start, end) {
+        (IpAddr::V4(start), IpAddr::V4(end)) if start <= end => {
+            v4.add_range(start, end);
+        }
+        (IpAddr::V6(start), IpAddr::V6(end)) if start <= end => {
+            v6.add_range(start, end);
+        }
+        _ => return Some(Err(IpCheckError::Range(value.to_string()))),
+    }
+    Some(Ok(()))
+}
+
+/// One network or address per line; blank lines and `#` comments, whole
+/// line or trailing, are skipped.
+struct Lines;
+
+impl SourceParser for Lines {
+    fn name(&self) -> &'static str {
+        "lines"
+    }
+
+    fn sniff(&self, path: &Path, _head: &[u8]) -> bool {
+        matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("netset" | "ipset" | "txt" | "list")
+        )
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        parse_entries(&self.entries(input)?)
+    }
+
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let text = String::from_utf8_lossy(input);
+        Ok(text
+            .lines()
+            .enumerate()
+            .map(|(line, text)| (line, text.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, network)| !network.is_empty())
+            .map(|(line, network)| Entry::at(line, network))
+            .collect())
+    }
+}
+
+/// The elements of the address sets, those of `type ipv4_addr` or
+/// `ipv6_addr` or of a `typeof` an `ip` or `ip6` address, in nftables
+/// rulesets such as `nft list ruleset` prints. Only the element itself is
+/// read, not its timeout, comment or mapped value.
+struct Nftables;
+
+impl SourceParser for Nftables {
+    fn name(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn sniff(&self, path: &Path, head: &[u8]) -> bool {
+        path.extension().is_some_and(|ext| ext == "nft")
+            || head.trim_ascii_start().starts_with(b"table ")
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        parse_entries(&self.entries(input)?)
+    }
+
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let text = String::from_utf8_lossy(input);
+        let (lines, tokens): (Vec<usize>, Vec<&str>) = nft_tokens(&text).into_iter().unzip();
+        let mut entries = Vec::new();
+        let mut addresses = false;
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                "set" | "map" => addresses = false,
+                "type" => {
+                    let single = tokens.get(i + 2) != Some(&".");
+                    addresses =
+                        matches!(tokens.get(i + 1), Some(&("ipv4_addr" | "ipv6_addr"))) && single;
+                }
+                "typeof" => {
+                    let single = tokens.get(i + 3) != Some(&".");
+                    addresses = matches!(tokens.get(i + 1), Some(&("ip" | "ip6"))) && single;
+                }
+                "elements" if tokens.get(i + 1..i + 3) == Some(&["=", "{"]) => {
+                    i += 3;
+                    // The first token of each element is the element.
+                    let mut first = true;
+                    while let Some(&token) = tokens.get(i) {
+                        match token {
+                            "}" => break,
+                            "," => first = true,
+                            _ if first => {
+                                if addresses {
+                                    entries.push(Entry::at(lines[i], token));
+                                }
+                                first = false;
+                            }
+                            _ => {}
+                        }
+                        i += 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Ok(entries)
+    }
+}
+
+/// The words, quoted strings and `{`, `}`, `,`, `=` and `;` of nftables
+/// syntax, skipping `#` comments, each with the index of its line.
+fn nft_tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    let mut line = 0;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        line += rest[..start].matches('\n').count();
+        rest = &rest[start..];
+        let len = match rest.as_bytes()[0] {
+            b'#' => {
+                rest = rest.find('\n').map_or("", |end| &rest[end..]);
+                continue;
+            }
+            b'"' => rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+            b'{' | b'}' | b',' | b'=' | b';' => 1,
+            _ => rest
+                .find(|c: char| c.is_whitespace() || "{},=;\"#".contains(c))
+                .unwrap_or(rest.len()),
+        };
+        tokens.push((line, &rest[..len]));
+        line += rest[..len].matches('\n').count();
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// The networks the rules of an `iptables-save` or `ip6tables-save` dump
+/// match by source or destination, `-s`, `-d`, or with `-m iprange`,
+/// `--src-range` and `--dst-range`, whatever the rules do with them.
+/// Negated matches, `! -s`, are skipped.
+struct IptablesSave;
+
+impl SourceParser for IptablesSave {
+    fn name(&self) -> &'static str {
+        "iptables-save"
+    }
+
+    fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+        head.starts_with(b"# Generated by iptables")
+            || head.starts_with(b"# Generated by ip6tables")
+            || head.starts_with(b"*filter\n")
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        parse_entries(&self.entries(input)?)
+    }
+
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let text = String::from_utf8_lossy(input);
+        let mut entries = Vec::new();
+        let rules = text.lines().enumerate();
+        for (line, rule) in rules.filter(|(_, rule)| rule.starts_with("-A ")) {
+            let mut negated = false;
+            let mut words = rule.split_whitespace();
+            while let Some(word) = words.next() {
+                match word {
+                    "!" => {
+                        negated = true;
+                        continue;
+                    }
+                    "-s" | "--source" | "-d" | "--destination" | "--src-range" | "--dst-range" => {
+                        // A match lists one network or range, or several
+                        // separated by commas.
+                        let networks = words.next().unwrap_or("");
+                        if !negated {
+                            let networks = networks.split(',');
+                            entries.extend(networks.map(|network| Entry::at(line, network)));
+                        }
+                    }
+                    _ => {}
+                }
+                negated = false;
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// The members of the sets of addresses or networks, `hash:ip`,
+/// `hash:net` and `bitmap:ip`, in an `ipset save` dump. Members of the
+/// other types, such as `hash:ip,port`, are not networks and are skipped.
+struct Ipset;
+
+impl SourceParser for Ipset {
+    fn name(&self) -> &'static str {
+        "ipset"
+    }
+
+    fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+        head.starts_with(b"create ")
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        parse_entries(&self.entries(input)?)
+    }
+
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let text = String::from_utf8_lossy(input);
+        let mut sets = Vec::new();
+        let mut entries = Vec::new();
+        for (line, text) in text.lines().enumerate() {
+            let mut words = text.split_whitespace();
+            match (words.next(), words.next(), words.next()) {
+                (Some("create"), Some(set), Some("hash:ip" | "hash:net" | "bitmap:ip")) => {
+                    sets.push(set)
+                }
+                (Some("add"), Some(set), Some(member)) if sets.contains(&set) => {
+                    entries.push(Entry::at(line, member))
+                }
+                _ => {}
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Spamhaus's DROP, EDROP and DROPv6 lists: one network per line, each
+/// followed by `; SBL<id>`, below a header of `;` comments. The SBL id is
+/// kept as the value of the network.
+struct Spamhaus;
+
+impl SourceParser for Spamhaus {
+    fn name(&self) -> &'static str {
+        "spamhaus"
+    }
+
+    fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+        head.starts_with(b"; Spamhaus")
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        parse_entries(&self.entries(input)?)
+    }
+
+    fn entries(&self, input: &[u8]) -> Result<Vec<Entry>, IpCheckError> {
+        let text = String::from_utf8_lossy(input);
+        Ok(text
+            .lines()
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let (network, id) = text.split_once(';').unwrap_or((text, ""));
+                if network.trim().is_empty() {
+                    return None;
+                }
+                let id = id.trim();
+                Some(Entry {
+                    value: (!id.is_empty()).then(|| id.to_string()),
+                    ..Entry::at(line, network)
+                })
+            })
+            .collect())
+    }
+}
+
+/// The binary artifact.
+struct Artifact;
+
+impl SourceParser for Artifact {
+    fn name(&self) -> &'static str {
+        "artifact"
+    }
+
+    fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+        head.starts_with(b"IPCK")
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+        Ok(artifact::load(input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str, &'static [u8]);
+
+    impl SourceParser for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn sniff(&self, _path: &Path, head: &[u8]) -> bool {
+            head.starts_with(self.1)
+        }
+
+        fn parse(
+            &self,
+            _input: &[u8],
+        ) -> Result<(IpRange<Ipv4Net>, IpRange<Ipv6Net>), IpCheckError> {
+            Ok((IpRange::new(), IpRange::new()))
+        }
+    }
+
+    #[test]
+    fn lookup_and_sniffing() {
+        let mut registry = Registry::with_builtins();
+        assert_eq!(
+            registry.names(),
+            [
+                "lines",
+                "nftables",
+                "iptables-save",
+                "ipset",
+                "spamhaus",
+                "artifact"
+            ]
+        );
+        let bytes = artifact::write(&IpRange::new(), &IpRange::new()).unwrap();
+        let sniffed = |registry: &Registry, path: &str, head: &[u8]| {
+            registry
+                .sniff(Path::new(path), head)
+                .map(|parser| parser.name())
+        };
+        assert_eq!(sniffed(&registry, "feed.bin", &bytes), Some("artifact"));
+        assert_eq!(
+            sniffed(&registry, "firehol_level1.netset", b"# list"),
+            Some("lines")
+        );
+        assert_eq!(sniffed(&registry, "v4.csv", b"network\n"), None);
+
+        // Later registrations take precedence, and replace their namesakes.
+        registry.register(Named("custom", b"IPCK"));
+        assert_eq!(sniffed(&registry, "feed.bin", &bytes), Some("custom"));
+        registry.register(Named("custom", b"{"));
+        assert_eq!(
+            registry.names(),
+            [
+                "lines",
+                "nftables",
+                "iptables-save",
+                "ipset",
+                "spamhaus",
+                "artifact",
+                "custom"
+            ]
+        );
+        assert_eq!(sniffed(&registry, "feed.bin", &bytes), Some("artifact"));
+        assert!(registry.get("custom").is_some());
+        assert!(Registry::new().get("lines").is_none());
+    }
+
+    #[test]
+    fn parse_lines() {
+        let (v4, v6) = Lines
+            .parse(b"# FireHOL\n10.0.0.0/8\n\n10.1.2.3 # inside\n192.0.2.7\n2001:db8::1/32\n")
+            .unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(v4, ["10.0.0.0/8", "192.0.2.7/32"]);
+        assert_eq!(v6.iter().next().unwrap().to_string(), "2001:db8::/32");
+
+        match Lines.parse(b"10.0.0.0/8\nnot-a-network\n") {
+            Err(IpCheckError::Parse { value, .. }) => assert_eq!(value, "not-a-network"),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn parse_networks_with_ranges() {
+        let (v4, _) = parse_networks(["10.0.0.0/8", "192.0.2.0 - 192.0.2.4"]).unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(v4, ["10.0.0.0/8", "192.0.2.0/30", "192.0.2.4/32"]);
+
+        for range in ["192.0.2.9-192.0.2.1", "192.0.2.0-2001:db8::"] {
+            match parse_networks([range]) {
+                Err(IpCheckError::Range(value)) => assert_eq!(value, range),
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        }
+        assert!(matches!(
+            parse_networks(["192.0.2.0-nope"]),
+            Err(IpCheckError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_nftables_sets() {
+        let ruleset = br#"# nft list ruleset
+table inet filter {
+	set blocklist {
+		type ipv4_addr
+		flags interval
+		elements = { 10.0.0.0/8, 192.0.2.1 timeout 1h expires 59m comment "a, b }",
+			     198.51.100.10-198.51.100.11 }
+	}
+
+	set blocklist6 {
+		typeof ip6 saddr
+		elements = { 2001:db8::/32 }
+	}
+
+	set ports {
+		type inet_service
+		elements = { 22, 80 }
+	}
+
+	set pairs {
+		type ipv4_addr . inet_service
+		elements = { 203.0.113.1 . 22 }
+	}
+
+	map verdicts {
+		type ipv4_addr : verdict
+		elements = { 203.0.113.7 : drop }
+	}
+}
+"#;
+        assert!(Nftables.sniff(Path::new("ruleset"), b"\ntable inet filter {"));
+        assert!(Nftables.sniff(Path::new("sets.nft"), b"# sets"));
+
+        let (v4, v6) = Nftables.parse(ruleset).unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(
+            v4,
+            [
+                "10.0.0.0/8",
+                "198.51.100.10/31",
+                "192.0.2.1/32",
+                "203.0.113.7/32"
+            ]
+        );
+        assert_eq!(v6.iter().next().unwrap().to_string(), "2001:db8::/32");
+
+        let entries: Vec<(Option<u64>, String)> = Nftables
+            .entries(ruleset)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.line, entry.network))
+            .collect();
+        assert_eq!(
+            entries[..4],
+            [
+                (Some(6), "10.0.0.0/8".to_string()),
+                (Some(6), "192.0.2.1".to_string()),
+                (Some(7), "198.51.100.10-198.51.100.11".to_string()),
+                (Some(12), "2001:db8::/32".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_iptables_save_rules() {
+        let dump = b"# Generated by iptables-save v1.8.9 on Mon Jan  1 00:00:00 2024
+*filter
+:INPUT ACCEPT [0:0]
+-A INPUT -s 10.0.0.0/8 -j DROP
+-A INPUT -d 192.0.2.0/24 -p tcp -m tcp --dport 22 -j REJECT
+-A INPUT -s 198.51.100.1/32,198.51.100.2/32 -j DROP
+-A INPUT ! -s 172.16.0.0/12 -j ACCEPT
+-A FORWARD -m iprange --src-range 203.0.113.0-203.0.113.3 -j DROP
+COMMIT
+";
+        let parser = Registry::with_builtins()
+            .sniff(Path::new("rules.v4"), dump)
+            .unwrap();
+        assert_eq!(parser.name(), "iptables-save");
+        let (v4, _) = parser.parse(dump).unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(
+            v4,
+            [
+                "10.0.0.0/8",
+                "192.0.2.0/24",
+                "203.0.113.0/30",
+                "198.51.100.1/32",
+                "198.51.100.2/32"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ipset_members() {
+        let dump = b"create blocklist hash:net family inet hashsize 1024 maxelem 65536
+add blocklist 10.0.0.0/8
+add blocklist 192.0.2.1 timeout 300
+create blocklist6 hash:ip family inet6
+add blocklist6 2001:db8::1
+create services hash:ip,port family inet
+add services 192.0.2.9,tcp:80
+";
+        let parser = Registry::with_builtins()
+            .sniff(Path::new("ipset.save"), dump)
+            .unwrap();
+        assert_eq!(parser.name(), "ipset");
+        let (v4, v6) = parser.parse(dump).unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(v4, ["10.0.0.0/8", "192.0.2.1/32"]);
+        assert_eq!(v6.iter().next().unwrap().to_string(), "2001:db8::1/128");
+        assert_eq!(
+            parser.entries(dump).unwrap()[1],
+            Entry {
+                network: "192.0.2.1".to_string(),
+                line: Some(3),
+                value: None,
+            }
+        );
+
+        // Parsers without entries of their own list their networks.
+        let v4 = ["10.0.0.0/8".parse().unwrap()].into_iter().collect();
+        let bytes = artifact::write(&v4, &IpRange::new()).unwrap();
+        assert_eq!(
+            Artifact.entries(&bytes).unwrap(),
+            [Entry {
+                network: "10.0.0.0/8".to_string(),
+                line: None,
+                value: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_spamhaus_drop() {
+        let drop = b"; Spamhaus DROP List 2024/01/01 - (c) 2024 The Spamhaus Project SLU
+; https://www.spamhaus.org/drop/drop.txt
+; Last-Modified: Mon, 01 Jan 2024 00:00:00 GMT
+1.10.16.0/20 ; SBL256894
+1.19.0.0/16 ; SBL434604
+";
+        let parser = Registry::with_builtins()
+            .sniff(Path::new("drop.txt"), drop)
+            .unwrap();
+        assert_eq!(parser.name(), "spamhaus");
+        let (v4, _) = parser.parse(drop).unwrap();
+        let v4: Vec<String> = v4.iter().map(|net| net.to_string()).collect();
+        assert_eq!(v4, ["1.19.0.0/16", "1.10.16.0/20"]);
+        let entries = parser.entries(drop).unwrap();
+        assert_eq!(
+            entries[0],
+            Entry {
+                network: "1.10.16.0/20".to_string(),
+                line: Some(4),
+                value: Some("SBL256894".to_string()),
+            }
+        );
+        assert_eq!(entries[1].value.as_deref(), Some("SBL434604"));
+        let unlisted = Spamhaus.entries(b"192.0.2.0/24\n").unwrap();
+        assert_eq!(unlisted[0].value, None);
+
+        let (_, v6) = Spamhaus
+            .parse(b"; Spamhaus DROPv6 List\n2001:678:738::/48 ; SBL598494\n")
+            .unwrap();
+        assert_eq!(v6.iter().next().unwrap().to_string(), "2001:678:738::/48");
+    }
+}
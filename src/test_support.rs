@@ -0,0 +1,160 @@
+//! Property-testing support, enabled by the `test-support` feature.
+//!
+//! Provides [proptest] strategies generating random networks and
+//! [`IpRange`]s, plus checks for the invariants every representation of a
+//! range must preserve. Downstream crates (and new codegen targets) can use
+//! them to test their own conversions:
+//!
+//! ```
+//! # extern crate ipcheck_rs;
+//! # extern crate ipnet;
+//! # extern crate proptest;
+//! use ipcheck_rs::test_support::{check_flat_roundtrip, ip_range};
+//! use ipnet::Ipv4Net;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(range in ip_range::<Ipv4Net>(0..32))| {
+//!     check_flat_roundtrip(&range)?;
+//! });
+//! ```
+//!
+//! [proptest]: https://docs.rs/proptest
+//! [`IpRange`]: ../struct.IpRange.html
+
+use ipnet::{Ipv4Net, Ipv6Net};
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use crate::flat::{nodes_to_trie, trie_to_nodes};
+use crate::{IpNet, IpRange};
+
+/// Networks that proptest knows how to generate.
+pub trait ArbitraryNet: IpNet + 'static {
+    /// Returns a strategy generating truncated networks of any prefix length.
+    fn arbitrary_net() -> BoxedStrategy<Self>;
+}
+
+impl ArbitraryNet for Ipv4Net {
+    fn arbitrary_net() -> BoxedStrategy<Self> {
+        (any::<u32>(), 0..=32u8)
+            .prop_map(|(addr, len)| Ipv4Net::new(addr.into(), len).unwrap().trunc())
+            .boxed()
+    }
+}
+
+impl ArbitraryNet for Ipv6Net {
+    fn arbitrary_net() -> BoxedStrategy<Self> {
+        (any::<u128>(), 0..=128u8)
+            .prop_map(|(addr, len)| Ipv6Net::new(addr.into(), len).unwrap().trunc())
+            .boxed()
+    }
+}
+
+/// Returns a strategy generating a single network.
+pub fn network<N: ArbitraryNet>() -> BoxedStrategy<N> {
+    N::arbitrary_net()
+}
+
+/// Returns a strategy generating lists of networks, with `size` elements.
+pub fn networks<N: ArbitraryNet>(size: impl Into<SizeRange>) -> impl Strategy<Value = Vec<N>> {
+    vec(network::<N>(), size)
+}
+
+/// Returns a strategy generating simplified `IpRange`s built from `size`
+/// random networks.
+pub fn ip_range<N: ArbitraryNet>(size: impl Into<SizeRange>) -> impl Strategy<Value = IpRange<N>> {
+    networks::<N>(size).prop_map(|networks| networks.into_iter().collect())
+}
+
+/// Checks that `range` survives range → trie → nodes → trie → range.
+pub fn check_flat_roundtrip<N: IpNet>(range: &IpRange<N>) -> Result<(), TestCaseError> {
+    let trie = match range.clone().into_trie().into_boxed_node() {
+        Some(trie) => trie,
+        None => {
+            prop_assert!(range.is_empty());
+            return Ok(());
+        }
+    };
+
+    let nodes = trie_to_nodes(trie);
+    let decoded = nodes_to_trie(&nodes);
+    prop_assert!(decoded.is_some(), "encoded nodes failed to decode");
+
+    let mut decoded = IpRange::from(decoded.unwrap());
+    decoded.simplify();
+    prop_assert_eq!(range, &decoded);
+    Ok(())
+}
+
+/// Checks that collecting `networks` in `shuffled` order, which must be a
+/// permutation of `networks`, builds exactly the same trie and node array.
+pub fn check_order_independent<N: IpNet>(
+    networks: &[N],
+    shuffled: &[N],
+) -> Result<(), TestCaseError> {
+    let expected: IpRange<N> = networks.iter().cloned().collect();
+    let actual: IpRange<N> = shuffled.iter().cloned().collect();
+    prop_assert_eq!(&expected, &actual);
+
+    let nodes = |range: IpRange<N>| range.into_trie().into_boxed_node().map(trie_to_nodes);
+    prop_assert_eq!(nodes(expected), nodes(actual));
+    Ok(())
+}
+
+/// Checks that `range` contains every network in `networks`, and that every
+/// network it yields is contained in some network of `networks`.
+pub fn check_covers<N: IpNet>(range: &IpRange<N>, networks: &[N]) -> Result<(), TestCaseError> {
+    for network in networks {
+        prop_assert!(range.contains(network), "{:?} missing from range", network);
+    }
+
+    let expected: IpRange<N> = networks.iter().cloned().collect();
+    for network in range {
+        prop_assert!(
+            expected.contains(&network),
+            "{:?} not in the source networks",
+            network
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn flat_roundtrip_ipv4(range in ip_range::<Ipv4Net>(0..64)) {
+            check_flat_roundtrip(&range)?;
+        }
+
+        #[test]
+        fn flat_roundtrip_ipv6(range in ip_range::<Ipv6Net>(0..64)) {
+            check_flat_roundtrip(&range)?;
+        }
+
+        #[test]
+        fn order_independent_ipv4(
+            (networks, shuffled) in networks::<Ipv4Net>(0..64)
+                .prop_flat_map(|v| (Just(v.clone()), Just(v).prop_shuffle()))
+        ) {
+            check_order_independent(&networks, &shuffled)?;
+        }
+
+        #[test]
+        fn order_independent_ipv6(
+            (networks, shuffled) in networks::<Ipv6Net>(0..64)
+                .prop_flat_map(|v| (Just(v.clone()), Just(v).prop_shuffle()))
+        ) {
+            check_order_independent(&networks, &shuffled)?;
+        }
+
+        #[test]
+        fn collect_covers_ipv4(networks in networks::<Ipv4Net>(0..64)) {
+            let range: IpRange<Ipv4Net> = networks.iter().cloned().collect();
+            check_covers(&range, &networks)?;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! WebAssembly bindings for answering lookups from a binary artifact.
+//!
+//! This is an alternative runtime to the generated TypeScript for filters
+//! too large to ship as source: the page or edge worker fetches an
+//! artifact written by [`artifact::write`] and loads it into a [`Filter`].
+//!
+//! ```text
+//! cargo rustc --lib --release --target wasm32-unknown-unknown \
+//!     --crate-type cdylib --features wasm
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/ipcheck_rs.wasm
+//! ```
+//!
+//! ```js
+//! import init, { Filter } from "./pkg/ipcheck_rs.js";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await (await fetch("filter.bin")).arrayBuffer());
+//! const filter = new Filter(bytes);
+//! filter.contains("10.1.2.3"); // true
+//! filter.lookup("10.1.2.3"); // "10.0.0.0/8"
+//! ```
+//!
+//! [`artifact::write`]: ../artifact/fn.write.html
+//! [`Filter`]: struct.Filter.html
+
+use std::net::IpAddr;
+
+use ipnet::{Ipv4Net, Ipv6Net};
+use wasm_bindgen::prelude::*;
+
+use crate::{artifact, IpRange};
+
+/// The IPv4 and IPv6 networks of an artifact.
+#[wasm_bindgen]
+pub struct Filter {
+    v4: IpRange<Ipv4Net>,
+    v6: IpRange<Ipv6Net>,
+}
+
+#[wasm_bindgen]
+impl Filter {
+    /// Loads the bytes of a binary artifact.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<Filter, JsError> {
+        Ok(Filter::load(bytes)?)
+    }
+
+    /// Returns whether any network contains `ip`.
+    pub fn contains(&self, ip: &str) -> Result<bool, JsError> {
+        Ok(self.lookup(ip)?.is_some())
+    }
+
+    /// Returns the network containing `ip`, or `undefined`.
+    pub fn lookup(&self, ip: &str) -> Result<Option<String>, JsError> {
+        let ip = ip
+            .trim()
+            .parse()
+            .map_err(|_| JsError::new(&format!("{:?} is not an IP address", ip)))?;
+        Ok(self.supernet(ip))
+    }
+}
+
+impl Filter {
+    fn load(bytes: &[u8]) -> Result<Filter, artifact::Error> {
+        let (v4, v6) = artifact::load(bytes)?;
+        Ok(Filter { v4, v6 })
+    }
+
+    fn supernet(&self, ip: IpAddr) -> Option<String> {
+        match ip {
+            IpAddr::V4(addr) => self.v4.supernet(&addr).map(|n| n.to_string()),
+            IpAddr::V6(addr) => self.v6.supernet(&addr).map(|n| n.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `JsError` needs a JavaScript host, so only the loading and the lookup
+    // themselves are tested natively.
+    #[test]
+    fn reject_deep_artifacts() {
+        // A trap would abort the page instead of rejecting the artifact.
+        let deep = artifact::with_nodes(&artifact::deep_v4_chain(), &[]);
+        assert!(matches!(
+            Filter::load(&deep),
+            Err(artifact::Error::InvalidNodes)
+        ));
+    }
+
+    #[test]
+    fn supernet_of_loaded_artifact() {
+        let v4: IpRange<Ipv4Net> = ["10.0.0.0/8"].iter().map(|s| s.parse().unwrap()).collect();
+        let v6: IpRange<Ipv6Net> = ["2001:db8::/32"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let bytes = artifact::write(&v4, &v6).unwrap();
+        let filter = Filter::load(&bytes).unwrap();
+
+        let supernet = |ip: &str| filter.supernet(ip.parse().unwrap());
+        assert_eq!(supernet("10.9.8.7").as_deref(), Some("10.0.0.0/8"));
+        assert_eq!(supernet("2001:db8::1").as_deref(), Some("2001:db8::/32"));
+        assert_eq!(supernet("8.8.8.8"), None);
+    }
+}